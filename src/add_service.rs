@@ -6,28 +6,134 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::config::create_owned_dir;
-use crate::helpers::download_and_extract_release;
+use crate::config::{create_owned_dir, create_private_dir};
+use crate::dry_run::is_dry_run;
+use crate::helpers::{
+    download_and_extract_release, extract_local_release, get_file_checksum,
+    validate_rewards_address,
+};
+use crate::node_registry::{Node, NodeRegistry, NodeStatus, NodeType};
+use crate::output::report;
 use crate::service::{ServiceConfig, ServiceControl};
+use crate::timeout::{has_timed_out, within_timeout, TimedOut};
 use color_eyre::{eyre::eyre, Help, Result};
 use colored::Colorize;
 use libp2p::Multiaddr;
-use sn_protocol::node_registry::{Node, NodeRegistry, NodeStatus};
 use sn_releases::{ReleaseType, SafeReleaseRepositoryInterface};
+use std::collections::HashSet;
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A port for a single service, or a range of ports to be given to a number of services in turn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PortRange {
+    Single(u16),
+    /// The first and last ports, inclusive.
+    Range(u16, u16),
+}
+
+impl PortRange {
+    pub fn len(&self) -> u16 {
+        match self {
+            PortRange::Single(_) => 1,
+            PortRange::Range(first, last) => last - first + 1,
+        }
+    }
+
+    /// A range always has at least one port, since the last port can't come before the first.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Get the port for the service at the given position among those being added.
+    pub fn get(&self, index: u16) -> u16 {
+        match self {
+            PortRange::Single(port) => *port,
+            PortRange::Range(first, _) => first + index,
+        }
+    }
+
+    pub fn ports(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_port = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|_| format!("'{port}' is not a valid port"))
+        };
+        match s.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_port(first)?, parse_port(last)?);
+                if first > last {
+                    return Err(format!(
+                        "The range {first}-{last} should start with the lower port"
+                    ));
+                }
+                Ok(PortRange::Range(first, last))
+            }
+            None => Ok(PortRange::Single(parse_port(s)?)),
+        }
+    }
+}
+
+impl std::fmt::Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PortRange::Single(port) => write!(f, "{port}"),
+            PortRange::Range(first, last) => write!(f, "{first}-{last}"),
+        }
+    }
+}
 
 pub struct AddServiceOptions {
+    pub auto_restart: Option<u64>,
+    pub auto_restart_reset_period: u64,
+    /// The checksum the archive that is downloaded, or the local binary or archive, must have.
+    pub checksum: Option<String>,
     pub count: Option<u16>,
+    /// Environment variables set for `safenode` by the service definition.
+    pub env_variables: Vec<(String, String)>,
+    /// Arguments passed to `safenode` as they are, after those set by the manager.
+    pub extra_args: Vec<String>,
     pub genesis: bool,
+    pub home_network: bool,
+    /// The number of milliseconds to wait between adding each service.
+    pub interval: u64,
+    pub keep_alive: bool,
     pub local: bool,
+    pub log_targets: Option<String>,
+    pub max_memory: Option<u64>,
+    pub node_ip: Option<IpAddr>,
+    pub open_firewall: bool,
+    pub owner: Option<String>,
+    /// A local binary or archive, used instead of downloading a release.
+    pub path: Option<PathBuf>,
     pub peers: Vec<Multiaddr>,
-    pub port: Option<u16>,
-    pub rpc_port: Option<u16>,
+    /// A range of ports is given to the new services in turn.
+    pub port: Option<PortRange>,
+    /// The addresses are assigned to the new services in turn.
+    pub rewards_addresses: Vec<String>,
+    /// The address the RPC service listens on, rather than the loopback address.
+    pub rpc_address: Option<IpAddr>,
+    pub rpc_port: Option<PortRange>,
     pub safenode_dir_path: PathBuf,
     pub service_data_dir_path: PathBuf,
     pub service_log_dir_path: PathBuf,
+    pub throttle_interval: Option<u64>,
+    pub upnp: bool,
     pub url: Option<String>,
     pub user: String,
+    /// Each service gets a user of its own, named after the service, rather than running as
+    /// `user`. Its data and log directories are only accessible to that user.
+    pub user_per_node: bool,
     pub version: Option<String>,
 }
 
@@ -56,39 +162,98 @@ pub async fn add(
         }
     }
 
-    if install_options.count.is_some()
-        && (install_options.port.is_some() || install_options.rpc_port.is_some())
+    // Without a count, a range of ports decides how many services are added.
+    let count = install_options
+        .count
+        .or(install_options.port.map(|p| p.len()))
+        .or(install_options.rpc_port.map(|p| p.len()))
+        .unwrap_or(1);
+    for range in [install_options.port, install_options.rpc_port]
+        .into_iter()
+        .flatten()
     {
-        let count = install_options.count.unwrap();
-        if count > 1 {
-            return Err(eyre!(
-                "Custom ports can only be used when adding a single service"
-            ));
+        match range {
+            PortRange::Single(_) if count > 1 => {
+                return Err(
+                    eyre!("Custom ports can only be used when adding a single service")
+                        .suggestion("Use a range of ports, e.g., 12000-12009, for more than one"),
+                );
+            }
+            PortRange::Range(..) if range.len() != count => {
+                return Err(eyre!(
+                    "The range {range} has {} ports, but {count} services are being added",
+                    range.len()
+                ));
+            }
+            _ => {}
         }
     }
 
-    if install_options.port.is_some() {
-        let port = install_options.port.unwrap();
-        if !service_control.is_port_free(port) {
-            return Err(eyre!("Port {port} is already in use")
+    for rewards_address in install_options.rewards_addresses.iter() {
+        validate_rewards_address(rewards_address)?;
+    }
+
+    // The ports of services that aren't running aren't bound, so the host can't be relied on to
+    // say whether they're free.
+    let mut allocated_ports = node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+        .flat_map(|n| [n.port, n.rpc_port])
+        .collect::<HashSet<u16>>();
+    let custom_ports = [install_options.port, install_options.rpc_port]
+        .into_iter()
+        .flatten()
+        .flat_map(|range| range.ports().collect::<Vec<u16>>())
+        .collect::<Vec<u16>>();
+    for (i, port) in custom_ports.iter().enumerate() {
+        if allocated_ports.contains(port) || custom_ports[..i].contains(port) {
+            return Err(eyre!("Port {port} is already allocated to another service")
                 .suggestion("Please try again with an available port"));
         }
     }
-    if install_options.rpc_port.is_some() {
-        let rpc_port = install_options.rpc_port.unwrap();
-        if !service_control.is_port_free(rpc_port) {
-            return Err(eyre!("Port {rpc_port} is already in use")
+    for port in custom_ports.iter() {
+        if !service_control.is_port_free(*port) {
+            return Err(eyre!("Port {port} is already in use")
                 .suggestion("Please try again with an available port"));
         }
     }
-
-    let (safenode_download_path, version) = download_and_extract_release(
-        ReleaseType::Safenode,
-        install_options.url,
-        install_options.version,
-        &*release_repo,
-    )
-    .await?;
+    allocated_ports.extend(custom_ports);
+
+    let is_local_release = install_options.path.is_some();
+    let (safenode_download_path, version) = match install_options.path {
+        // A local binary is still extracted for a dry run, since it only goes in a temporary
+        // directory, and that's the way to find out its version.
+        Some(path) => {
+            extract_local_release(&path, install_options.checksum.as_deref(), &*release_repo)?
+        }
+        None if is_dry_run() => {
+            let version = install_options
+                .version
+                .clone()
+                .unwrap_or_else(|| "latest".to_string());
+            match &install_options.url {
+                Some(url) => report!("Would download safenode from {url}"),
+                None => report!("Would download version {version} of safenode"),
+            }
+            let file_name = if cfg!(windows) {
+                "safenode.exe"
+            } else {
+                "safenode"
+            };
+            (PathBuf::from(file_name), version)
+        }
+        None => {
+            within_timeout(download_and_extract_release(
+                ReleaseType::Safenode,
+                install_options.url,
+                install_options.version,
+                install_options.checksum,
+                &*release_repo,
+            ))
+            .await??
+        }
+    };
     let safenode_file_name = safenode_download_path
         .file_name()
         .ok_or_else(|| eyre!("Could not get filename from the safenode download path"))?
@@ -97,21 +262,36 @@ pub async fn add(
 
     let mut added_service_data = vec![];
     let current_node_count = node_registry.nodes.len() as u16;
-    let target_node_count = current_node_count + install_options.count.unwrap_or(1);
+    let target_node_count = current_node_count + count;
     let mut node_number = current_node_count + 1;
     while node_number <= target_node_count {
-        let node_port = if let Some(port) = install_options.port {
-            port
+        // The services that have been added are kept, so the registry can still be saved.
+        if has_timed_out() {
+            break;
+        }
+        let index = node_number - current_node_count - 1;
+        if index > 0 && install_options.interval > 0 {
+            report!("Waiting for {} milliseconds...", install_options.interval);
+            tokio::time::sleep(Duration::from_millis(install_options.interval)).await;
+        }
+        let node_port = if let Some(range) = install_options.port {
+            range.get(index)
         } else {
-            service_control.get_available_port()?
+            get_unallocated_port(service_control, &mut allocated_ports)?
         };
-        let rpc_port = if let Some(port) = install_options.rpc_port {
-            port
+        let rpc_port = if let Some(range) = install_options.rpc_port {
+            range.get(index)
         } else {
-            service_control.get_available_port()?
+            get_unallocated_port(service_control, &mut allocated_ports)?
         };
 
         let service_name = format!("safenode{node_number}");
+        let rewards_address = if install_options.rewards_addresses.is_empty() {
+            None
+        } else {
+            let index = index as usize % install_options.rewards_addresses.len();
+            Some(install_options.rewards_addresses[index].clone())
+        };
         let service_data_dir_path = install_options
             .service_data_dir_path
             .join(service_name.clone());
@@ -119,27 +299,72 @@ pub async fn add(
         let service_log_dir_path = install_options
             .service_log_dir_path
             .join(service_name.clone());
+        let service_user = if install_options.user_per_node {
+            service_control.create_service_user(&service_name)?;
+            service_name.clone()
+        } else {
+            install_options.user.clone()
+        };
 
-        create_owned_dir(service_data_dir_path.clone(), &install_options.user)?;
-        create_owned_dir(service_log_dir_path.clone(), &install_options.user)?;
-
-        std::fs::copy(
-            safenode_download_path.clone(),
-            service_safenode_path.clone(),
-        )?;
+        let safenode_checksum = if is_dry_run() {
+            report!(
+                "Would create {} and {}, owned by {service_user}",
+                service_data_dir_path.to_string_lossy(),
+                service_log_dir_path.to_string_lossy(),
+            );
+            report!(
+                "Would copy safenode to {}",
+                service_safenode_path.to_string_lossy()
+            );
+            None
+        } else {
+            // A user of its own is only worth having if the other services can't read the node's
+            // keys.
+            let create_dir = if install_options.user_per_node {
+                create_private_dir
+            } else {
+                create_owned_dir
+            };
+            create_dir(service_data_dir_path.clone(), &service_user)?;
+            create_dir(service_log_dir_path.clone(), &service_user)?;
+
+            std::fs::copy(
+                safenode_download_path.clone(),
+                service_safenode_path.clone(),
+            )?;
+            Some(get_file_checksum(&service_safenode_path)?)
+        };
 
         service_control.install(ServiceConfig {
             local: install_options.local,
             data_dir_path: service_data_dir_path.clone(),
+            env_variables: install_options.env_variables.clone(),
+            extra_args: install_options.extra_args.clone(),
             genesis: install_options.genesis,
+            home_network: install_options.home_network,
+            auto_restart: install_options.auto_restart,
+            auto_restart_reset_period: install_options.auto_restart_reset_period,
+            keep_alive: install_options.keep_alive,
             log_dir_path: service_log_dir_path.clone(),
+            log_targets: install_options.log_targets.clone(),
+            max_memory: install_options.max_memory,
             name: service_name.clone(),
+            node_ip: install_options.node_ip,
             peers: install_options.peers.clone(),
             node_port,
+            node_type: NodeType::Safenode,
+            owner: install_options.owner.clone(),
+            rewards_address: rewards_address.clone(),
+            rpc_address: install_options.rpc_address,
             rpc_port,
             safenode_path: service_safenode_path.clone(),
-            service_user: install_options.user.clone(),
+            service_user: service_user.clone(),
+            throttle_interval: install_options.throttle_interval,
+            upnp: install_options.upnp,
         })?;
+        if install_options.open_firewall {
+            service_control.add_firewall_rule(&service_name, node_port)?;
+        }
 
         added_service_data.push((
             service_name.clone(),
@@ -148,15 +373,19 @@ pub async fn add(
             service_log_dir_path.to_string_lossy().into_owned(),
             node_port,
             rpc_port,
+            rewards_address.clone(),
         ));
 
         node_registry.nodes.push(Node {
             genesis: install_options.genesis,
+            node_type: NodeType::Safenode,
             service_name,
-            user: install_options.user.clone(),
+            user: service_user,
             number: node_number,
             port: node_port,
             rpc_port,
+            rpc_address: install_options.rpc_address,
+            node_ip: install_options.node_ip,
             version: version.clone(),
             status: NodeStatus::Added,
             pid: None,
@@ -165,28 +394,115 @@ pub async fn add(
             data_dir_path: Some(service_data_dir_path.clone()),
             safenode_path: Some(service_safenode_path),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: install_options.local,
+            peers: install_options.peers.clone(),
+            home_network: install_options.home_network,
+            upnp: install_options.upnp,
+            extra_args: install_options.extra_args.clone(),
+            env_variables: install_options.env_variables.clone(),
+            rewards_address,
+            owner: install_options.owner.clone(),
+            firewall_rule_added: install_options.open_firewall,
+            keep_alive: install_options.keep_alive,
+            throttle_interval: install_options.throttle_interval,
+            log_targets: install_options.log_targets.clone(),
+            max_memory: install_options.max_memory,
+            auto_restart: install_options.auto_restart,
+            auto_restart_reset_period: install_options.auto_restart_reset_period,
+            safenode_checksum,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         });
+        if count > 1 {
+            report!(
+                "[{}/{count}] Added {}",
+                index + 1,
+                added_service_data[index as usize].0
+            );
+        }
 
         node_number += 1;
     }
 
-    std::fs::remove_file(safenode_download_path)?;
-
-    println!("Services Added:");
+    // Nothing is downloaded for a dry run, so there's only a file to remove for a local release.
+    if !is_dry_run() || is_local_release {
+        std::fs::remove_file(safenode_download_path)?;
+    }
+    if is_dry_run() {
+        report!("Services that would be added:");
+    } else {
+        report!("Services Added:");
+    }
     for install in added_service_data.iter() {
-        println!(" {} {}", "✓".green(), install.0);
-        println!("    - Safenode path: {}", install.1);
-        println!("    - Data path: {}", install.2);
-        println!("    - Log path: {}", install.3);
-        println!("    - Service port: {}", install.4);
-        println!("    - RPC port: {}", install.5);
+        report!(" {} {}", "✓".green(), install.0);
+        report!("    - Safenode path: {}", install.1);
+        report!("    - Data path: {}", install.2);
+        report!("    - Log path: {}", install.3);
+        report!("    - Service port: {}", install.4);
+        report!("    - RPC port: {}", install.5);
+        if let Some(rewards_address) = &install.6 {
+            report!("    - Rewards address: {rewards_address}");
+        }
     }
 
-    println!("[!] Note: newly added services have not been started");
+    report!("[!] Note: newly added services have not been started");
+
+    if node_number <= target_node_count {
+        return Err(TimedOut {
+            completed: added_service_data.into_iter().map(|s| s.0).collect(),
+            incomplete: (node_number..=target_node_count)
+                .map(|n| format!("safenode{n}"))
+                .collect(),
+        }
+        .into());
+    }
 
     Ok(())
 }
 
+/// Get the address of the registry's genesis node, for the nodes added after it to bootstrap from.
+///
+/// `None` is returned if there is no genesis node. Its address is only known once it has been
+/// started and has reported its peer ID.
+pub fn get_genesis_peer(node_registry: &NodeRegistry) -> Result<Option<Multiaddr>> {
+    let Some(genesis_node) = node_registry
+        .nodes
+        .iter()
+        .find(|n| n.genesis && n.status != NodeStatus::Removed)
+    else {
+        return Ok(None);
+    };
+    let peer = genesis_node.get_multiaddr().ok_or_else(|| {
+        eyre!(
+            "The genesis node, {}, has not reported its peer ID",
+            genesis_node.service_name
+        )
+        .suggestion("Start the genesis node before adding the other nodes")
+    })?;
+    Ok(Some(peer))
+}
+
+/// Get a free port that isn't allocated to another service, and mark it as allocated.
+fn get_unallocated_port(
+    service_control: &dyn ServiceControl,
+    allocated_ports: &mut HashSet<u16>,
+) -> Result<u16> {
+    loop {
+        let port = service_control.get_available_port()?;
+        if allocated_ports.insert(port) {
+            return Ok(port);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -202,6 +518,7 @@ mod tests {
         ArchiveType, Platform, ProgressCallback, ReleaseType, Result as SnReleaseResult,
         SafeReleaseRepositoryInterface,
     };
+    use sn_transfers::{bls::SecretKey, MainPubkey};
     use std::path::Path;
 
     #[cfg(not(target_os = "windows"))]
@@ -252,6 +569,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
         let latest_version = "0.96.4";
         let temp_dir = assert_fs::TempDir::new()?;
@@ -319,16 +640,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: true,
                 genesis: true,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode1".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode1")
                     .join(SAFENODE_FILE_NAME),
                 node_port: 8080,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: 8081,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode1"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode1"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -337,16 +673,35 @@ mod tests {
         add(
             AddServiceOptions {
                 local: true,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: true,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
                 port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
                 rpc_port: None,
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -390,11 +745,14 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![Node {
                 genesis: true,
+                node_type: NodeType::Safenode,
                 service_name: "safenode1".to_string(),
                 user: "safe".to_string(),
                 number: 1,
                 port: 8080,
                 rpc_port: 8081,
+                rpc_address: None,
+                node_ip: None,
                 version: latest_version.to_string(),
                 status: NodeStatus::Added,
                 pid: None,
@@ -405,8 +763,38 @@ mod tests {
                     "/var/safenode-manager/services/safenode1/safenode",
                 )),
                 connected_peers: None,
+                records_stored: None,
+                resource_usage: None,
+                listen_addr: None,
+                local: false,
+                peers: vec![],
+                home_network: false,
+                upnp: false,
+                extra_args: vec![],
+                env_variables: vec![],
+                rewards_address: None,
+                owner: None,
+                firewall_rule_added: false,
+                keep_alive: true,
+                throttle_interval: None,
+                log_targets: None,
+                max_memory: None,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                safenode_checksum: None,
+                previous_version: None,
+                maintenance: false,
+                restart_schedule: None,
+                last_stop_method: None,
+                exit_info: None,
+                watchdog_restarts: 0,
+                last_failure: None,
             }],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
 
         let temp_dir = assert_fs::TempDir::new()?;
@@ -423,16 +811,35 @@ mod tests {
         let result = add(
             AddServiceOptions {
                 local: true,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: true,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
-                port: Some(custom_port),
-                rpc_port: Some(custom_rpc_port),
+                port: Some(PortRange::Single(custom_port)),
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: Some(PortRange::Single(custom_rpc_port)),
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -457,6 +864,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
 
         let temp_dir = assert_fs::TempDir::new()?;
@@ -473,16 +884,35 @@ mod tests {
         let result = add(
             AddServiceOptions {
                 local: true,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: true,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: Some(3),
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
-                port: Some(custom_port),
-                rpc_port: Some(custom_rpc_port),
+                port: Some(PortRange::Single(custom_port)),
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: Some(PortRange::Single(custom_rpc_port)),
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -508,6 +938,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
 
         let latest_version = "0.96.4";
@@ -577,16 +1011,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: false,
                 genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode1".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode1")
                     .join(SAFENODE_FILE_NAME),
                 node_port: 8080,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: 8081,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode1"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode1"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -610,16 +1059,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: false,
                 genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode2".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode2")
                     .join(SAFENODE_FILE_NAME),
                 node_port: 8082,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: 8083,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode2"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode2"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -643,16 +1107,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: false,
                 genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode3".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode3")
                     .join(SAFENODE_FILE_NAME),
                 node_port: 8084,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: 8085,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode3"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode3"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -661,16 +1140,35 @@ mod tests {
         add(
             AddServiceOptions {
                 local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: Some(3),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
                 port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
                 rpc_port: None,
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -738,6 +1236,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
 
         let specific_version = "0.95.0";
@@ -800,16 +1302,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: false,
                 genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode1".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode1")
                     .join(SAFENODE_FILE_NAME),
                 node_port: 8080,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: 8081,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode1"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode1"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -818,16 +1335,35 @@ mod tests {
         add(
             AddServiceOptions {
                 local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
                 port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
                 rpc_port: None,
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: Some(specific_version.to_string()),
             },
             &mut node_registry,
@@ -866,11 +1402,14 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![Node {
                 genesis: true,
+                node_type: NodeType::Safenode,
                 service_name: "safenode1".to_string(),
                 user: "safe".to_string(),
                 number: 1,
                 port: 8080,
                 rpc_port: 8081,
+                rpc_address: None,
+                node_ip: None,
                 version: latest_version.to_string(),
                 status: NodeStatus::Added,
                 pid: None,
@@ -881,8 +1420,38 @@ mod tests {
                     "/var/safenode-manager/services/safenode1/safenode",
                 )),
                 connected_peers: None,
+                records_stored: None,
+                resource_usage: None,
+                listen_addr: None,
+                local: false,
+                peers: vec![],
+                home_network: false,
+                upnp: false,
+                extra_args: vec![],
+                env_variables: vec![],
+                rewards_address: None,
+                owner: None,
+                firewall_rule_added: false,
+                keep_alive: true,
+                throttle_interval: None,
+                log_targets: None,
+                max_memory: None,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                safenode_checksum: None,
+                previous_version: None,
+                maintenance: false,
+                restart_schedule: None,
+                last_stop_method: None,
+                exit_info: None,
+                watchdog_restarts: 0,
+                last_failure: None,
             }],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
         let temp_dir = assert_fs::TempDir::new()?;
         let node_data_dir = temp_dir.child("safenode1");
@@ -949,16 +1518,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: false,
                 genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode2".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode2")
                     .join(SAFENODE_FILE_NAME),
                 node_port: 8082,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: 8083,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode2"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode2"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -967,16 +1551,35 @@ mod tests {
         add(
             AddServiceOptions {
                 local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
                 port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
                 rpc_port: None,
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -1005,6 +1608,215 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn add_new_node_should_not_allocate_the_ports_of_an_existing_service() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_release_repo = MockSafeReleaseRepository::new();
+
+        let latest_version = "0.96.4";
+        let mut node_registry = NodeRegistry {
+            save_path: PathBuf::new(),
+            nodes: vec![Node {
+                genesis: true,
+                node_type: NodeType::Safenode,
+                service_name: "safenode1".to_string(),
+                user: "safe".to_string(),
+                number: 1,
+                port: 8080,
+                rpc_port: 8081,
+                rpc_address: None,
+                node_ip: None,
+                version: latest_version.to_string(),
+                status: NodeStatus::Added,
+                pid: None,
+                peer_id: None,
+                log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+                data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+                safenode_path: Some(PathBuf::from(
+                    "/var/safenode-manager/services/safenode1/safenode",
+                )),
+                connected_peers: None,
+                records_stored: None,
+                resource_usage: None,
+                listen_addr: None,
+                local: false,
+                peers: vec![],
+                home_network: false,
+                upnp: false,
+                extra_args: vec![],
+                env_variables: vec![],
+                rewards_address: None,
+                owner: None,
+                firewall_rule_added: false,
+                keep_alive: true,
+                throttle_interval: None,
+                log_targets: None,
+                max_memory: None,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                safenode_checksum: None,
+                previous_version: None,
+                maintenance: false,
+                restart_schedule: None,
+                last_stop_method: None,
+                exit_info: None,
+                watchdog_restarts: 0,
+                last_failure: None,
+            }],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+        let temp_dir = assert_fs::TempDir::new()?;
+        let node_data_dir = temp_dir.child("safenode1");
+        node_data_dir.create_dir_all()?;
+        let node_logs_dir = temp_dir.child("logs");
+        node_logs_dir.create_dir_all()?;
+        let safenode_download_path = temp_dir.child(SAFENODE_FILE_NAME);
+        safenode_download_path.write_binary(b"fake safenode bin")?;
+
+        let mut seq = Sequence::new();
+        mock_release_repo
+            .expect_get_latest_version()
+            .times(1)
+            .returning(|_| Ok(latest_version.to_string()))
+            .in_sequence(&mut seq);
+
+        mock_release_repo
+            .expect_download_release_from_s3()
+            .with(
+                eq(&ReleaseType::Safenode),
+                eq(latest_version),
+                always(), // Varies per platform
+                eq(&ArchiveType::TarGz),
+                always(), // Temporary directory which doesn't really matter
+                always(), // Callback for progress bar which also doesn't matter
+            )
+            .times(1)
+            .returning(move |_, _, _, _, _, _| {
+                Ok(PathBuf::from(format!(
+                    "/tmp/safenode-{}-x86_64-unknown-linux-musl.tar.gz",
+                    latest_version
+                )))
+            })
+            .in_sequence(&mut seq);
+
+        let safenode_download_path_clone = safenode_download_path.to_path_buf().clone();
+        mock_release_repo
+            .expect_extract_release_archive()
+            .with(
+                eq(PathBuf::from(format!(
+                    "/tmp/safenode-{}-x86_64-unknown-linux-musl.tar.gz",
+                    latest_version
+                ))),
+                always(),
+            )
+            .times(1)
+            .returning(move |_, _| Ok(safenode_download_path_clone.clone()))
+            .in_sequence(&mut seq);
+
+        // The first node isn't running, so its ports are free on the host.
+        mock_service_control
+            .expect_get_available_port()
+            .times(1)
+            .returning(|| Ok(8081))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_get_available_port()
+            .times(1)
+            .returning(|| Ok(8082))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_get_available_port()
+            .times(1)
+            .returning(|| Ok(8083))
+            .in_sequence(&mut seq);
+
+        mock_service_control
+            .expect_install()
+            .times(1)
+            .with(eq(ServiceConfig {
+                local: false,
+                genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
+                name: "safenode2".to_string(),
+                node_ip: None,
+                safenode_path: node_data_dir
+                    .to_path_buf()
+                    .join("safenode2")
+                    .join(SAFENODE_FILE_NAME),
+                node_port: 8082,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
+                rpc_port: 8083,
+                service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
+                log_dir_path: node_logs_dir.to_path_buf().join("safenode2"),
+                log_targets: None,
+                max_memory: None,
+                data_dir_path: node_data_dir.to_path_buf().join("safenode2"),
+                env_variables: vec![],
+                extra_args: vec![],
+                peers: vec![],
+            }))
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+
+        add(
+            AddServiceOptions {
+                local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
+                genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
+                count: None,
+                env_variables: vec![],
+                extra_args: vec![],
+                peers: vec![],
+                port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: None,
+                safenode_dir_path: temp_dir.to_path_buf(),
+                service_data_dir_path: node_data_dir.to_path_buf(),
+                service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
+                url: None,
+                user: get_username(),
+                user_per_node: false,
+                version: None,
+            },
+            &mut node_registry,
+            &mock_service_control,
+            Box::new(mock_release_repo),
+        )
+        .await?;
+
+        assert_eq!(node_registry.nodes.len(), 2);
+        assert_eq!(node_registry.nodes[1].port, 8082);
+        assert_eq!(node_registry.nodes[1].rpc_port, 8083);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn add_should_add_a_service_with_safenode_from_a_url() -> Result<()> {
         let mut mock_service_control = MockServiceControl::new();
@@ -1016,6 +1828,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
         let temp_dir = assert_fs::TempDir::new()?;
         let node_data_dir = temp_dir.child("data");
@@ -1072,16 +1888,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: false,
                 genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode1".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode1")
                     .join(SAFENODE_FILE_NAME),
                 node_port: 8080,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: 8081,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode1"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode1"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -1090,16 +1921,35 @@ mod tests {
         add(
             AddServiceOptions {
                 local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
                 port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
                 rpc_port: None,
                 url: Some(url.to_string()),
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -1141,6 +1991,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
         let latest_version = "0.96.4";
         let temp_dir = assert_fs::TempDir::new()?;
@@ -1213,16 +2067,31 @@ mod tests {
             .with(eq(ServiceConfig {
                 local: false,
                 genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
                 name: "safenode1".to_string(),
+                node_ip: None,
                 safenode_path: node_data_dir
                     .to_path_buf()
                     .join("safenode1")
                     .join(SAFENODE_FILE_NAME),
                 node_port: custom_port,
+                node_type: NodeType::Safenode,
+                owner: None,
+                rewards_address: None,
+                rpc_address: None,
                 rpc_port: custom_rpc_port,
                 service_user: get_username(),
+                throttle_interval: None,
+                upnp: false,
                 log_dir_path: node_logs_dir.to_path_buf().join("safenode1"),
+                log_targets: None,
+                max_memory: None,
                 data_dir_path: node_data_dir.to_path_buf().join("safenode1"),
+                env_variables: vec![],
+                extra_args: vec![],
                 peers: vec![],
             }))
             .returning(|_| Ok(()))
@@ -1231,16 +2100,35 @@ mod tests {
         add(
             AddServiceOptions {
                 local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
-                port: Some(custom_port),
-                rpc_port: Some(custom_rpc_port),
+                port: Some(PortRange::Single(custom_port)),
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: Some(PortRange::Single(custom_rpc_port)),
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -1281,6 +2169,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
         let temp_dir = assert_fs::TempDir::new()?;
         let node_data_dir = temp_dir.child("data");
@@ -1300,16 +2192,35 @@ mod tests {
         let result = add(
             AddServiceOptions {
                 local: true,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
-                port: Some(custom_port),
-                rpc_port: Some(custom_rpc_port),
+                port: Some(PortRange::Single(custom_port)),
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: Some(PortRange::Single(custom_rpc_port)),
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -1339,6 +2250,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
         let temp_dir = assert_fs::TempDir::new()?;
         let node_data_dir = temp_dir.child("data");
@@ -1366,16 +2281,35 @@ mod tests {
         let result = add(
             AddServiceOptions {
                 local: true,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: None,
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
-                port: Some(custom_port),
-                rpc_port: Some(custom_rpc_port),
+                port: Some(PortRange::Single(custom_port)),
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: Some(PortRange::Single(custom_rpc_port)),
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -1404,6 +2338,10 @@ mod tests {
             save_path: PathBuf::new(),
             nodes: vec![],
             faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
         };
         let temp_dir = assert_fs::TempDir::new()?;
         let node_data_dir = temp_dir.child("data");
@@ -1417,16 +2355,35 @@ mod tests {
         let result = add(
             AddServiceOptions {
                 local: true,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
                 genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
                 count: Some(3),
+                env_variables: vec![],
+                extra_args: vec![],
                 safenode_dir_path: temp_dir.to_path_buf(),
                 service_data_dir_path: node_data_dir.to_path_buf(),
                 service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
                 peers: vec![],
-                port: Some(custom_port),
-                rpc_port: Some(custom_rpc_port),
+                port: Some(PortRange::Single(custom_port)),
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: Some(PortRange::Single(custom_rpc_port)),
                 url: None,
                 user: get_username(),
+                user_per_node: false,
                 version: None,
             },
             &mut node_registry,
@@ -1447,4 +2404,620 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn add_node_should_return_error_if_the_rewards_address_is_invalid() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_release_repo = MockSafeReleaseRepository::new();
+
+        let mut node_registry = NodeRegistry {
+            save_path: PathBuf::new(),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+        let temp_dir = assert_fs::TempDir::new()?;
+        let node_data_dir = temp_dir.child("data");
+        node_data_dir.create_dir_all()?;
+        let node_logs_dir = temp_dir.child("logs");
+        node_logs_dir.create_dir_all()?;
+
+        mock_release_repo.expect_download_release_from_s3().times(0);
+        mock_service_control.expect_install().times(0);
+
+        let result = add(
+            AddServiceOptions {
+                local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
+                genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
+                count: Some(3),
+                env_variables: vec![],
+                extra_args: vec![],
+                safenode_dir_path: temp_dir.to_path_buf(),
+                service_data_dir_path: node_data_dir.to_path_buf(),
+                service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
+                peers: vec![],
+                port: None,
+                rewards_addresses: vec!["not-an-address".to_string()],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: None,
+                url: None,
+                user: get_username(),
+                user_per_node: false,
+                version: None,
+            },
+            &mut node_registry,
+            &mock_service_control,
+            Box::new(mock_release_repo),
+        )
+        .await;
+
+        match result {
+            Ok(_) => panic!("This test should result in an error"),
+            Err(e) => {
+                assert_eq!(
+                    "The rewards address 'not-an-address' is not valid",
+                    e.to_string()
+                )
+            }
+        }
+        assert!(node_registry.nodes.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_node_should_assign_rewards_addresses_to_services_in_turn() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_release_repo = MockSafeReleaseRepository::new();
+
+        let mut node_registry = NodeRegistry {
+            save_path: PathBuf::new(),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let latest_version = "0.96.4";
+        let temp_dir = assert_fs::TempDir::new()?;
+        let node_data_dir = temp_dir.child("data");
+        node_data_dir.create_dir_all()?;
+        let node_logs_dir = temp_dir.child("logs");
+        node_logs_dir.create_dir_all()?;
+        let safenode_download_path = temp_dir.child(SAFENODE_FILE_NAME);
+        safenode_download_path.write_binary(b"fake safenode bin")?;
+
+        let address_a = MainPubkey::new(SecretKey::random().public_key()).to_hex();
+        let address_b = MainPubkey::new(SecretKey::random().public_key()).to_hex();
+
+        mock_release_repo
+            .expect_get_latest_version()
+            .times(1)
+            .returning(|_| Ok(latest_version.to_string()));
+        mock_release_repo
+            .expect_download_release_from_s3()
+            .times(1)
+            .returning(move |_, _, _, _, _, _| {
+                Ok(PathBuf::from(&format!(
+                    "/tmp/safenode-{}-x86_64-unknown-linux-musl.tar.gz",
+                    latest_version
+                )))
+            });
+        let safenode_download_path_clone = safenode_download_path.to_path_buf().clone();
+        mock_release_repo
+            .expect_extract_release_archive()
+            .times(1)
+            .returning(move |_, _| Ok(safenode_download_path_clone.clone()));
+
+        let next_port = std::sync::atomic::AtomicU16::new(8080);
+        mock_service_control
+            .expect_get_available_port()
+            .times(6)
+            .returning(move || Ok(next_port.fetch_add(1, std::sync::atomic::Ordering::SeqCst)));
+        let mut seq = Sequence::new();
+        for (name, address) in [
+            ("safenode1", address_a.clone()),
+            ("safenode2", address_b.clone()),
+            ("safenode3", address_a.clone()),
+        ] {
+            mock_service_control
+                .expect_install()
+                .times(1)
+                .withf(move |config| {
+                    config.name == name && config.rewards_address == Some(address.clone())
+                })
+                .returning(|_| Ok(()))
+                .in_sequence(&mut seq);
+        }
+
+        add(
+            AddServiceOptions {
+                local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
+                genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
+                count: Some(3),
+                env_variables: vec![],
+                extra_args: vec![],
+                peers: vec![],
+                port: None,
+                rewards_addresses: vec![address_a.clone(), address_b.clone()],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: None,
+                safenode_dir_path: temp_dir.to_path_buf(),
+                service_data_dir_path: node_data_dir.to_path_buf(),
+                service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
+                url: None,
+                user: get_username(),
+                user_per_node: false,
+                version: None,
+            },
+            &mut node_registry,
+            &mock_service_control,
+            Box::new(mock_release_repo),
+        )
+        .await?;
+
+        assert_eq!(node_registry.nodes.len(), 3);
+        assert_eq!(
+            node_registry.nodes[0].rewards_address,
+            Some(address_a.clone())
+        );
+        assert_eq!(node_registry.nodes[1].rewards_address, Some(address_b));
+        assert_eq!(node_registry.nodes[2].rewards_address, Some(address_a));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_node_should_assign_a_range_of_ports_to_services_in_turn() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_release_repo = MockSafeReleaseRepository::new();
+
+        let mut node_registry = NodeRegistry {
+            save_path: PathBuf::new(),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let latest_version = "0.96.4";
+        let temp_dir = assert_fs::TempDir::new()?;
+        let node_data_dir = temp_dir.child("data");
+        node_data_dir.create_dir_all()?;
+        let node_logs_dir = temp_dir.child("logs");
+        node_logs_dir.create_dir_all()?;
+        let safenode_download_path = temp_dir.child(SAFENODE_FILE_NAME);
+        safenode_download_path.write_binary(b"fake safenode bin")?;
+        let node_ip = IpAddr::from([192, 168, 1, 10]);
+
+        mock_release_repo
+            .expect_get_latest_version()
+            .times(1)
+            .returning(|_| Ok(latest_version.to_string()));
+        mock_release_repo
+            .expect_download_release_from_s3()
+            .times(1)
+            .returning(move |_, _, _, _, _, _| {
+                Ok(PathBuf::from(&format!(
+                    "/tmp/safenode-{}-x86_64-unknown-linux-musl.tar.gz",
+                    latest_version
+                )))
+            });
+        let safenode_download_path_clone = safenode_download_path.to_path_buf().clone();
+        mock_release_repo
+            .expect_extract_release_archive()
+            .times(1)
+            .returning(move |_, _| Ok(safenode_download_path_clone.clone()));
+
+        mock_service_control
+            .expect_is_port_free()
+            .times(3)
+            .returning(|_| true);
+        let next_port = std::sync::atomic::AtomicU16::new(13000);
+        mock_service_control
+            .expect_get_available_port()
+            .times(3)
+            .returning(move || Ok(next_port.fetch_add(1, std::sync::atomic::Ordering::SeqCst)));
+        let mut seq = Sequence::new();
+        for (name, port) in [
+            ("safenode1", 12000),
+            ("safenode2", 12001),
+            ("safenode3", 12002),
+        ] {
+            mock_service_control
+                .expect_install()
+                .times(1)
+                .withf(move |config| {
+                    config.name == name
+                        && config.node_port == port
+                        && config.node_ip == Some(node_ip)
+                })
+                .returning(|_| Ok(()))
+                .in_sequence(&mut seq);
+        }
+
+        add(
+            AddServiceOptions {
+                local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: Some(node_ip),
+                open_firewall: false,
+                genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
+                count: None,
+                env_variables: vec![],
+                extra_args: vec![],
+                peers: vec![],
+                port: Some(PortRange::from_str("12000-12002").map_err(|e| eyre!(e))?),
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: None,
+                safenode_dir_path: temp_dir.to_path_buf(),
+                service_data_dir_path: node_data_dir.to_path_buf(),
+                service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
+                url: None,
+                user: get_username(),
+                user_per_node: false,
+                version: None,
+            },
+            &mut node_registry,
+            &mock_service_control,
+            Box::new(mock_release_repo),
+        )
+        .await?;
+
+        assert_eq!(node_registry.nodes.len(), 3);
+        assert_eq!(node_registry.nodes[2].port, 12002);
+        assert_eq!(node_registry.nodes[2].rpc_port, 13002);
+        assert_eq!(node_registry.nodes[2].node_ip, Some(node_ip));
+
+        Ok(())
+    }
+
+    #[test]
+    fn port_range_should_parse_a_single_port_or_a_range() {
+        assert_eq!(PortRange::from_str("12000"), Ok(PortRange::Single(12000)));
+        assert_eq!(
+            PortRange::from_str("12000-12009"),
+            Ok(PortRange::Range(12000, 12009))
+        );
+        assert_eq!(PortRange::Range(12000, 12009).len(), 10);
+        assert!(PortRange::from_str("12009-12000").is_err());
+        assert!(PortRange::from_str("12000-").is_err());
+    }
+
+    #[tokio::test]
+    async fn add_node_should_add_a_firewall_rule_for_each_service() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_release_repo = MockSafeReleaseRepository::new();
+
+        let mut node_registry = NodeRegistry {
+            save_path: PathBuf::new(),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let latest_version = "0.96.4";
+        let temp_dir = assert_fs::TempDir::new()?;
+        let node_data_dir = temp_dir.child("data");
+        node_data_dir.create_dir_all()?;
+        let node_logs_dir = temp_dir.child("logs");
+        node_logs_dir.create_dir_all()?;
+        let safenode_download_path = temp_dir.child(SAFENODE_FILE_NAME);
+        safenode_download_path.write_binary(b"fake safenode bin")?;
+
+        mock_release_repo
+            .expect_get_latest_version()
+            .times(1)
+            .returning(|_| Ok(latest_version.to_string()));
+        mock_release_repo
+            .expect_download_release_from_s3()
+            .times(1)
+            .returning(move |_, _, _, _, _, _| {
+                Ok(PathBuf::from(&format!(
+                    "/tmp/safenode-{}-x86_64-unknown-linux-musl.tar.gz",
+                    latest_version
+                )))
+            });
+        let safenode_download_path_clone = safenode_download_path.to_path_buf().clone();
+        mock_release_repo
+            .expect_extract_release_archive()
+            .times(1)
+            .returning(move |_, _| Ok(safenode_download_path_clone.clone()));
+
+        let mut seq = Sequence::new();
+        for (name, port) in [("safenode1", 8080), ("safenode2", 8082)] {
+            mock_service_control
+                .expect_get_available_port()
+                .times(1)
+                .returning(move || Ok(port))
+                .in_sequence(&mut seq);
+            mock_service_control
+                .expect_get_available_port()
+                .times(1)
+                .returning(move || Ok(port + 1))
+                .in_sequence(&mut seq);
+            mock_service_control
+                .expect_install()
+                .times(1)
+                .withf(move |config| config.name == name)
+                .returning(|_| Ok(()))
+                .in_sequence(&mut seq);
+            mock_service_control
+                .expect_add_firewall_rule()
+                .with(eq(name), eq(port))
+                .times(1)
+                .returning(|_, _| Ok(()))
+                .in_sequence(&mut seq);
+        }
+
+        add(
+            AddServiceOptions {
+                local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: true,
+                genesis: false,
+                home_network: false,
+                interval: 0,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                keep_alive: true,
+                count: Some(2),
+                env_variables: vec![],
+                extra_args: vec![],
+                peers: vec![],
+                port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                owner: None,
+                path: None,
+                rpc_port: None,
+                safenode_dir_path: temp_dir.to_path_buf(),
+                service_data_dir_path: node_data_dir.to_path_buf(),
+                service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
+                url: None,
+                user: get_username(),
+                user_per_node: false,
+                version: None,
+            },
+            &mut node_registry,
+            &mock_service_control,
+            Box::new(mock_release_repo),
+        )
+        .await?;
+
+        assert_eq!(node_registry.nodes.len(), 2);
+        assert!(node_registry.nodes.iter().all(|n| n.firewall_rule_added));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn add_should_use_a_local_binary_and_record_the_version_it_reports() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut mock_service_control = MockServiceControl::new();
+        // The release repository is not used, since nothing is downloaded.
+        let mock_release_repo = MockSafeReleaseRepository::new();
+
+        let mut node_registry = NodeRegistry {
+            save_path: PathBuf::new(),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+        let temp_dir = assert_fs::TempDir::new()?;
+        let node_data_dir = temp_dir.child("data");
+        node_data_dir.create_dir_all()?;
+        let node_logs_dir = temp_dir.child("logs");
+        node_logs_dir.create_dir_all()?;
+        let local_safenode = temp_dir.child("bin").child(SAFENODE_FILE_NAME);
+        local_safenode.write_str("#!/bin/sh\necho \"safenode cli 0.98.1\"\n")?;
+        std::fs::set_permissions(
+            local_safenode.path(),
+            std::fs::Permissions::from_mode(0o755),
+        )?;
+
+        let mut seq = Sequence::new();
+        mock_service_control
+            .expect_get_available_port()
+            .times(1)
+            .returning(|| Ok(8080))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_get_available_port()
+            .times(1)
+            .returning(|| Ok(8081))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_install()
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+
+        add(
+            AddServiceOptions {
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                checksum: None,
+                count: None,
+                env_variables: vec![],
+                extra_args: vec![],
+                genesis: false,
+                home_network: false,
+                interval: 0,
+                keep_alive: true,
+                local: false,
+                log_targets: None,
+                max_memory: None,
+                node_ip: None,
+                open_firewall: false,
+                owner: None,
+                path: Some(local_safenode.to_path_buf()),
+                peers: vec![],
+                port: None,
+                rewards_addresses: vec![],
+                rpc_address: None,
+                rpc_port: None,
+                safenode_dir_path: temp_dir.to_path_buf(),
+                service_data_dir_path: node_data_dir.to_path_buf(),
+                service_log_dir_path: node_logs_dir.to_path_buf(),
+                throttle_interval: None,
+                upnp: false,
+                url: None,
+                user: get_username(),
+                user_per_node: false,
+                version: None,
+            },
+            &mut node_registry,
+            &mock_service_control,
+            Box::new(mock_release_repo),
+        )
+        .await?;
+
+        // The binary is copied, so the original is left where it was.
+        local_safenode.assert(predicate::path::is_file());
+        node_data_dir
+            .child("safenode1")
+            .child(SAFENODE_FILE_NAME)
+            .assert(predicate::path::is_file());
+        assert_eq!(node_registry.nodes.len(), 1);
+        assert_eq!(node_registry.nodes[0].version, "0.98.1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_genesis_peer_should_need_the_genesis_node_to_have_reported_its_peer_id() -> Result<()> {
+        let mut node_registry = NodeRegistry {
+            save_path: PathBuf::new(),
+            nodes: vec![Node {
+                genesis: true,
+                node_type: NodeType::Safenode,
+                service_name: "safenode1".to_string(),
+                user: "safe".to_string(),
+                number: 1,
+                port: 8080,
+                rpc_port: 8081,
+                rpc_address: None,
+                node_ip: None,
+                version: "0.98.1".to_string(),
+                status: NodeStatus::Added,
+                pid: None,
+                peer_id: None,
+                log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+                data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+                safenode_path: Some(PathBuf::from(
+                    "/var/safenode-manager/services/safenode1/safenode",
+                )),
+                connected_peers: None,
+                records_stored: None,
+                resource_usage: None,
+                listen_addr: None,
+                local: false,
+                peers: vec![],
+                home_network: false,
+                upnp: false,
+                extra_args: vec![],
+                env_variables: vec![],
+                rewards_address: None,
+                owner: None,
+                firewall_rule_added: false,
+                keep_alive: true,
+                throttle_interval: None,
+                log_targets: None,
+                max_memory: None,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                safenode_checksum: None,
+                previous_version: None,
+                maintenance: false,
+                restart_schedule: None,
+                last_stop_method: None,
+                exit_info: None,
+                watchdog_restarts: 0,
+                last_failure: None,
+            }],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let result = get_genesis_peer(&node_registry);
+        assert!(result.is_err_and(
+            |e| e.to_string() == "The genesis node, safenode1, has not reported its peer ID"
+        ));
+
+        let peer_id = libp2p_identity::PeerId::random();
+        node_registry.nodes[0].peer_id = Some(peer_id);
+        node_registry.nodes[0].status = NodeStatus::Running;
+        let genesis_peer = get_genesis_peer(&node_registry)?.unwrap();
+        assert!(genesis_peer
+            .to_string()
+            .ends_with(&format!("/p2p/{peer_id}")));
+
+        node_registry.nodes[0].status = NodeStatus::Removed;
+        assert_eq!(get_genesis_peer(&node_registry)?, None);
+        Ok(())
+    }
 }
@@ -0,0 +1,323 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{Node, NodeStatus};
+use crate::service::ServiceControl;
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sn_node_rpc_client::{RpcActions, RpcClient};
+use std::collections::BTreeMap;
+use std::path::Path;
+use sysinfo::{DiskExt, Pid, ProcessExt, System, SystemExt};
+
+/// The limits a node is checked against.
+///
+/// Any threshold that isn't set isn't checked.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertThresholds {
+    #[serde(default)]
+    pub min_connected_peers: Option<usize>,
+    /// The most memory the node's process can use, in megabytes.
+    #[serde(default)]
+    pub max_memory: Option<u64>,
+    /// The fullest the disk with the node's data directory can be, as a percentage.
+    #[serde(default)]
+    pub max_disk_percent: Option<f64>,
+    #[serde(default)]
+    pub max_restarts_per_hour: Option<u32>,
+}
+
+impl AlertThresholds {
+    /// Combine these thresholds with those of a particular node, which take precedence.
+    pub fn with_overrides(&self, overrides: Option<&AlertThresholds>) -> AlertThresholds {
+        match overrides {
+            Some(overrides) => AlertThresholds {
+                min_connected_peers: overrides.min_connected_peers.or(self.min_connected_peers),
+                max_memory: overrides.max_memory.or(self.max_memory),
+                max_disk_percent: overrides.max_disk_percent.or(self.max_disk_percent),
+                max_restarts_per_hour: overrides
+                    .max_restarts_per_hour
+                    .or(self.max_restarts_per_hour),
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+/// The thresholds the nodes are checked against, and the webhook that's notified when one is
+/// breached.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertSettings {
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// The thresholds for every node.
+    #[serde(default)]
+    pub thresholds: AlertThresholds,
+    /// Thresholds for particular nodes, by service name.
+    #[serde(default)]
+    pub nodes: BTreeMap<String, AlertThresholds>,
+}
+
+impl AlertSettings {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Get the thresholds that apply to a node.
+    pub fn get_thresholds(&self, service_name: &str) -> AlertThresholds {
+        self.thresholds.with_overrides(self.nodes.get(service_name))
+    }
+}
+
+/// A count of a node's restarts, as reported by the service manager at a point in time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RestartSample {
+    pub timestamp: DateTime<Utc>,
+    pub restart_count: u32,
+}
+
+/// The restart counts taken by previous checks, which the number of restarts in the last hour is
+/// worked out from.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RestartHistory {
+    pub samples: BTreeMap<String, Vec<RestartSample>>,
+}
+
+impl RestartHistory {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Record a node's restart count and get the number of restarts in the hour before it.
+    ///
+    /// The count is compared with the oldest sample from within the last hour. Samples older than
+    /// that are discarded, apart from the newest of them, which stands in for the count an hour ago
+    /// when the checks are less often than hourly.
+    pub fn record(&mut self, service_name: &str, restart_count: u32, now: DateTime<Utc>) -> u32 {
+        let samples = self.samples.entry(service_name.to_string()).or_default();
+        let hour_ago = now - Duration::hours(1);
+        let older = samples.iter().filter(|s| s.timestamp < hour_ago).count();
+        samples.drain(..older.saturating_sub(1));
+        let baseline = samples
+            .first()
+            .map(|s| s.restart_count)
+            .unwrap_or(restart_count);
+        samples.push(RestartSample {
+            timestamp: now,
+            restart_count,
+        });
+        // The count goes back to zero if the service is reinstalled.
+        restart_count.saturating_sub(baseline)
+    }
+}
+
+/// What was measured for a node during a check. Anything that couldn't be measured is empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeMeasurements {
+    pub connected_peers: Option<usize>,
+    pub memory: Option<u64>,
+    pub disk_percent: Option<f64>,
+    pub restarts_in_last_hour: Option<u32>,
+}
+
+/// Measure a running node for the check.
+///
+/// The restart count is recorded in the history, so the restarts in the last hour can be worked
+/// out on later checks.
+pub async fn measure_node(
+    node: &Node,
+    service_control: &dyn ServiceControl,
+    history: &mut RestartHistory,
+    now: DateTime<Utc>,
+) -> NodeMeasurements {
+    let mut measurements = NodeMeasurements::default();
+    if node.status != NodeStatus::Running {
+        return measurements;
+    }
+
+    let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+    if let Ok(info) = rpc_client.network_info().await {
+        measurements.connected_peers = Some(info.connected_peers.len());
+    }
+
+    let mut system = System::new();
+    if let Some(pid) = node.pid {
+        system.refresh_process(Pid::from(pid as usize));
+        measurements.memory = system
+            .process(Pid::from(pid as usize))
+            .map(|process| process.memory() / (1024 * 1024));
+    }
+    if let Some(data_dir_path) = &node.data_dir_path {
+        system.refresh_disks_list();
+        system.refresh_disks();
+        // The disk the path is on is the one with the longest mount point that contains it.
+        measurements.disk_percent = system
+            .disks()
+            .iter()
+            .filter(|d| data_dir_path.starts_with(d.mount_point()) && d.total_space() > 0)
+            .max_by_key(|d| d.mount_point().as_os_str().len())
+            .map(|d| {
+                let used = d.total_space() - d.available_space();
+                used as f64 * 100.0 / d.total_space() as f64
+            });
+    }
+
+    if let Ok(exit_info) = service_control.get_exit_info(&node.service_name) {
+        if let Some(restart_count) = exit_info.restart_count {
+            measurements.restarts_in_last_hour =
+                Some(history.record(&node.service_name, restart_count, now));
+        }
+    }
+    measurements
+}
+
+/// Get a description of each threshold the node has breached.
+pub fn check_thresholds(
+    thresholds: &AlertThresholds,
+    measurements: &NodeMeasurements,
+) -> Vec<String> {
+    let mut breaches = Vec::new();
+    if let (Some(min), Some(peers)) = (thresholds.min_connected_peers, measurements.connected_peers)
+    {
+        if peers < min {
+            breaches.push(format!(
+                "{peers} connected peers is below the minimum of {min}"
+            ));
+        }
+    }
+    if let (Some(max), Some(memory)) = (thresholds.max_memory, measurements.memory) {
+        if memory > max {
+            breaches.push(format!(
+                "{memory}MB of memory is above the maximum of {max}MB"
+            ));
+        }
+    }
+    if let (Some(max), Some(percent)) = (thresholds.max_disk_percent, measurements.disk_percent) {
+        if percent > max {
+            breaches.push(format!(
+                "the disk is {percent:.1}% full, above the maximum of {max}%"
+            ));
+        }
+    }
+    if let (Some(max), Some(restarts)) = (
+        thresholds.max_restarts_per_hour,
+        measurements.restarts_in_last_hour,
+    ) {
+        if restarts > max {
+            breaches.push(format!(
+                "{restarts} restarts in the last hour is above the maximum of {max}"
+            ));
+        }
+    }
+    breaches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_thresholds_should_apply_the_overrides_for_a_node() {
+        let mut settings = AlertSettings {
+            webhook: None,
+            thresholds: AlertThresholds {
+                min_connected_peers: Some(10),
+                max_memory: Some(1024),
+                max_disk_percent: None,
+                max_restarts_per_hour: Some(3),
+            },
+            nodes: BTreeMap::new(),
+        };
+        settings.nodes.insert(
+            "safenode2".to_string(),
+            AlertThresholds {
+                max_memory: Some(2048),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(settings.get_thresholds("safenode1"), settings.thresholds);
+        let thresholds = settings.get_thresholds("safenode2");
+        assert_eq!(thresholds.max_memory, Some(2048));
+        assert_eq!(thresholds.min_connected_peers, Some(10));
+        assert_eq!(thresholds.max_restarts_per_hour, Some(3));
+    }
+
+    #[test]
+    fn check_thresholds_should_report_each_breach() {
+        let thresholds = AlertThresholds {
+            min_connected_peers: Some(10),
+            max_memory: Some(1024),
+            max_disk_percent: Some(90.0),
+            max_restarts_per_hour: Some(3),
+        };
+        let breaches = check_thresholds(
+            &thresholds,
+            &NodeMeasurements {
+                connected_peers: Some(4),
+                memory: Some(512),
+                disk_percent: Some(95.0),
+                restarts_in_last_hour: None,
+            },
+        );
+        assert_eq!(
+            breaches,
+            vec![
+                "4 connected peers is below the minimum of 10".to_string(),
+                "the disk is 95.0% full, above the maximum of 90%".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn restart_history_should_count_the_restarts_in_the_last_hour() {
+        let start = DateTime::parse_from_rfc3339("2024-02-05T04:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut history = RestartHistory::default();
+        assert_eq!(history.record("safenode1", 2, start), 0);
+        assert_eq!(
+            history.record("safenode1", 4, start + Duration::minutes(30)),
+            2
+        );
+        // The first sample is now more than an hour old, but it's the newest of those.
+        assert_eq!(
+            history.record("safenode1", 5, start + Duration::minutes(70)),
+            3
+        );
+        assert_eq!(
+            history.record("safenode1", 5, start + Duration::minutes(100)),
+            1
+        );
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config::create_owned_dir;
+use crate::control::get_service_config;
+use crate::helpers::{find_process_by_exe, get_file_checksum};
+use crate::node_registry::{Node, NodeRegistry, NodeStatus, NodeType};
+use crate::output::report;
+use crate::service::ServiceControl;
+use color_eyre::{eyre::eyre, Help, Result};
+use colored::Colorize;
+use libp2p::Multiaddr;
+use std::path::PathBuf;
+
+pub const AUDITOR_SERVICE_NAME: &str = "sn_auditor";
+
+pub struct AuditorOptions {
+    pub auditor_download_path: PathBuf,
+    pub extra_args: Vec<String>,
+    pub peers: Vec<Multiaddr>,
+    pub service_data_dir_path: PathBuf,
+    pub service_log_dir_path: PathBuf,
+    pub user: String,
+    pub version: String,
+}
+
+/// Install the auditor as a service.
+///
+/// The auditor is recorded in the registry in the same form as a node, with its own type, so it's
+/// installed, started and stopped by the same code. It's kept apart from the nodes, though, since
+/// it has no ports and doesn't earn anything.
+pub fn install_auditor(
+    options: AuditorOptions,
+    node_registry: &mut NodeRegistry,
+    service_control: &dyn ServiceControl,
+) -> Result<()> {
+    if node_registry
+        .auditor
+        .as_ref()
+        .is_some_and(|auditor| auditor.status != NodeStatus::Removed)
+    {
+        return Err(eyre!("The auditor has already been installed")
+            .suggestion("Remove it first to install it again"));
+    }
+
+    let data_dir_path = options.service_data_dir_path.join(AUDITOR_SERVICE_NAME);
+    let log_dir_path = options.service_log_dir_path.join(AUDITOR_SERVICE_NAME);
+    create_owned_dir(data_dir_path.clone(), &options.user)?;
+    create_owned_dir(log_dir_path.clone(), &options.user)?;
+    let auditor_path = data_dir_path.join(
+        options
+            .auditor_download_path
+            .file_name()
+            .ok_or_else(|| eyre!("Could not get the file name of the auditor binary"))?,
+    );
+    std::fs::copy(&options.auditor_download_path, &auditor_path)?;
+
+    let auditor = Node {
+        genesis: false,
+        node_type: NodeType::Auditor,
+        version: options.version,
+        service_name: AUDITOR_SERVICE_NAME.to_string(),
+        user: options.user,
+        number: 0,
+        port: 0,
+        rpc_port: 0,
+        rpc_address: None,
+        node_ip: None,
+        status: NodeStatus::Added,
+        pid: None,
+        peer_id: None,
+        data_dir_path: Some(data_dir_path),
+        log_dir_path: Some(log_dir_path),
+        safenode_checksum: Some(get_file_checksum(&auditor_path)?),
+        safenode_path: Some(auditor_path),
+        connected_peers: None,
+        records_stored: None,
+        resource_usage: None,
+        listen_addr: None,
+        local: false,
+        peers: options.peers,
+        home_network: false,
+        upnp: false,
+        extra_args: options.extra_args,
+        env_variables: vec![],
+        rewards_address: None,
+        owner: None,
+        firewall_rule_added: false,
+        keep_alive: true,
+        throttle_interval: None,
+        log_targets: None,
+        max_memory: None,
+        auto_restart: None,
+        auto_restart_reset_period: 86400,
+        previous_version: None,
+        maintenance: false,
+        restart_schedule: None,
+        last_stop_method: None,
+        exit_info: None,
+        watchdog_restarts: 0,
+        last_failure: None,
+    };
+    service_control.install(get_service_config(&auditor)?)?;
+    report!(
+        "{} Installed the auditor, at version {}",
+        "✓".green(),
+        auditor.version
+    );
+    node_registry.auditor = Some(auditor);
+    Ok(())
+}
+
+/// Start the auditor service.
+///
+/// The auditor has no RPC service to report its process ID, so its process is found by the path
+/// of its binary once it has had a little time to start.
+pub async fn start_auditor(auditor: &mut Node, service_control: &dyn ServiceControl) -> Result<()> {
+    match auditor.status {
+        NodeStatus::Removed => return Err(eyre!("The auditor has been removed")),
+        NodeStatus::Running
+            if auditor
+                .pid
+                .is_some_and(|pid| service_control.is_service_process_running(pid)) =>
+        {
+            report!("The auditor is already running");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    report!("Attempting to start the auditor...");
+    service_control.start(&auditor.service_name)?;
+    service_control.wait(3).await;
+    let auditor_path = auditor
+        .safenode_path
+        .as_ref()
+        .ok_or_else(|| eyre!("The binary path should be set for an installed auditor"))?;
+    let pid = find_process_by_exe(auditor_path).ok_or_else(|| {
+        eyre!("The auditor did not start").suggestion(format!(
+            "Check the logs in {}",
+            auditor
+                .log_dir_path
+                .as_ref()
+                .map_or("-".to_string(), |p| p.to_string_lossy().to_string())
+        ))
+    })?;
+    auditor.pid = Some(pid);
+    auditor.status = NodeStatus::Running;
+    report!("{} Started the auditor, with PID {pid}", "✓".green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::{make_node_args, MockServiceControl};
+    use assert_fs::prelude::*;
+    use std::ffi::OsString;
+
+    #[test]
+    fn install_auditor_should_give_the_service_its_peers_and_log_directory() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let auditor_download_path = temp_dir.child("download/sn_auditor");
+        auditor_download_path.write_binary(b"fake auditor bin")?;
+        let log_dir_path = temp_dir.child("logs/sn_auditor").to_path_buf();
+        let peer: Multiaddr =
+            "/ip4/127.0.0.1/udp/12000/quic-v1/p2p/12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR"
+                .parse()?;
+
+        let mut mock_service_control = MockServiceControl::new();
+        let expected_args = vec![
+            OsString::from("--log-output-dest"),
+            OsString::from(log_dir_path.to_string_lossy().to_string()),
+            OsString::from("--peer"),
+            OsString::from(peer.to_string()),
+        ];
+        mock_service_control
+            .expect_install()
+            .withf(move |config| {
+                config.name == AUDITOR_SERVICE_NAME && make_node_args(config) == expected_args
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut node_registry = NodeRegistry {
+            save_path: temp_dir.child("node_registry.json").to_path_buf(),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+        install_auditor(
+            AuditorOptions {
+                auditor_download_path: auditor_download_path.to_path_buf(),
+                extra_args: vec![],
+                peers: vec![peer],
+                service_data_dir_path: temp_dir.child("services").to_path_buf(),
+                service_log_dir_path: temp_dir.child("logs").to_path_buf(),
+                user: std::env::var("USER").unwrap_or_default(),
+                version: "0.1.0".to_string(),
+            },
+            &mut node_registry,
+            &mock_service_control,
+        )?;
+
+        let auditor = node_registry.auditor.as_ref().unwrap();
+        assert_eq!(auditor.node_type, NodeType::Auditor);
+        assert_eq!(auditor.status, NodeStatus::Added);
+        temp_dir
+            .child("services/sn_auditor/sn_auditor")
+            .assert(predicates::path::is_file());
+        assert!(node_registry.nodes.is_empty());
+
+        let result = install_auditor(
+            AuditorOptions {
+                auditor_download_path: auditor_download_path.to_path_buf(),
+                extra_args: vec![],
+                peers: vec![],
+                service_data_dir_path: temp_dir.child("services").to_path_buf(),
+                service_log_dir_path: temp_dir.child("logs").to_path_buf(),
+                user: std::env::var("USER").unwrap_or_default(),
+                version: "0.1.0".to_string(),
+            },
+            &mut node_registry,
+            &mock_service_control,
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{Node, NodeRegistry, NodeStatus};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use sysinfo::{DiskExt, Pid, ProcessExt, System, SystemExt};
+
+const MB: u64 = 1024 * 1024;
+
+/// The limits the number of nodes on the host is scaled within.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutoscalePolicy {
+    pub min_nodes: usize,
+    pub max_nodes: usize,
+    /// The fullest the disk with the nodes' data can be, as a percentage.
+    #[serde(default)]
+    pub target_disk_percent: Option<f64>,
+    /// The memory that must be left available on the host, in megabytes.
+    #[serde(default)]
+    pub memory_headroom: Option<u64>,
+}
+
+impl AutoscalePolicy {
+    /// Load the policy, or `None` if autoscaling hasn't been set up.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&contents)?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// The resources in use on the host, and the average footprint of a node. All sizes are in
+/// megabytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostUsage {
+    pub disk_total: u64,
+    pub disk_used: u64,
+    pub available_memory: u64,
+    pub node_disk: u64,
+    pub node_memory: u64,
+}
+
+impl HostUsage {
+    pub fn disk_percent(&self) -> f64 {
+        if self.disk_total == 0 {
+            return 0.0;
+        }
+        self.disk_used as f64 * 100.0 / self.disk_total as f64
+    }
+}
+
+/// What the autoscaler will do with the number of nodes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScalingDecision {
+    Add(usize),
+    Remove(usize),
+    /// Leave the nodes as they are, for the given reason.
+    Hold(String),
+}
+
+/// Decide whether to change the number of nodes.
+///
+/// The bounds of the policy are brought into effect straight away. Otherwise a single node is
+/// added or removed, so the footprint of the nodes has time to settle before the next decision.
+/// A node is only added if there is room for another with the average footprint of the existing
+/// nodes.
+pub fn plan_scaling(
+    policy: &AutoscalePolicy,
+    usage: &HostUsage,
+    node_count: usize,
+) -> ScalingDecision {
+    if node_count < policy.min_nodes {
+        return ScalingDecision::Add(policy.min_nodes - node_count);
+    }
+    if node_count > policy.max_nodes {
+        return ScalingDecision::Remove(node_count - policy.max_nodes);
+    }
+    if policy.target_disk_percent.is_none() && policy.memory_headroom.is_none() {
+        return ScalingDecision::Hold("the policy has no resource targets".to_string());
+    }
+
+    let disk_over = policy
+        .target_disk_percent
+        .is_some_and(|target| usage.disk_percent() > target);
+    let memory_over = policy
+        .memory_headroom
+        .is_some_and(|headroom| usage.available_memory < headroom);
+    if disk_over || memory_over {
+        if node_count <= policy.min_nodes {
+            return ScalingDecision::Hold(
+                "the host is over its targets, but the minimum number of nodes are running"
+                    .to_string(),
+            );
+        }
+        return ScalingDecision::Remove(1);
+    }
+
+    if node_count >= policy.max_nodes {
+        return ScalingDecision::Hold("the maximum number of nodes are running".to_string());
+    }
+    let disk_fits = match policy.target_disk_percent {
+        Some(target) => {
+            let projected = HostUsage {
+                disk_used: usage.disk_used + usage.node_disk,
+                ..usage.clone()
+            };
+            projected.disk_percent() <= target
+        }
+        None => true,
+    };
+    let memory_fits = match policy.memory_headroom {
+        Some(headroom) => usage.available_memory >= headroom + usage.node_memory,
+        None => true,
+    };
+    if disk_fits && memory_fits {
+        ScalingDecision::Add(1)
+    } else {
+        ScalingDecision::Hold("there is no room for another node".to_string())
+    }
+}
+
+/// Measure the resources in use on the host.
+///
+/// The disk is the one with the data directory of the most recently added node, or the given
+/// default directory if there are no nodes.
+pub fn measure_host(node_registry: &NodeRegistry, default_data_dir_path: &Path) -> HostUsage {
+    let nodes = node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+        .collect::<Vec<&Node>>();
+    let data_dir_path = nodes
+        .last()
+        .and_then(|n| n.data_dir_path.as_deref())
+        .unwrap_or(default_data_dir_path);
+
+    let mut system = System::new();
+    system.refresh_memory();
+    system.refresh_disks_list();
+    system.refresh_disks();
+    let mut usage = HostUsage {
+        available_memory: system.available_memory() / MB,
+        ..Default::default()
+    };
+    // The disk the path is on is the one with the longest mount point that contains it.
+    if let Some(disk) = system
+        .disks()
+        .iter()
+        .filter(|d| data_dir_path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+    {
+        usage.disk_total = disk.total_space() / MB;
+        usage.disk_used = (disk.total_space() - disk.available_space()) / MB;
+    }
+
+    let disk_sizes = nodes
+        .iter()
+        .filter_map(|n| n.data_dir_path.as_deref())
+        .map(get_dir_size)
+        .collect::<Vec<u64>>();
+    usage.node_disk = average(&disk_sizes) / MB;
+
+    let memory_sizes = nodes
+        .iter()
+        .filter(|n| n.status == NodeStatus::Running)
+        .filter_map(|n| n.pid)
+        .filter_map(|pid| {
+            system.refresh_process(Pid::from(pid as usize));
+            system
+                .process(Pid::from(pid as usize))
+                .map(|process| process.memory())
+        })
+        .collect::<Vec<u64>>();
+    usage.node_memory = average(&memory_sizes) / MB;
+    usage
+}
+
+fn average(values: &[u64]) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.iter().sum::<u64>() / values.len() as u64
+}
+
+/// Get the total size of the files in a directory and its subdirectories.
+fn get_dir_size(path: &Path) -> u64 {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => get_dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_policy() -> AutoscalePolicy {
+        AutoscalePolicy {
+            min_nodes: 2,
+            max_nodes: 10,
+            target_disk_percent: Some(80.0),
+            memory_headroom: Some(1024),
+        }
+    }
+
+    fn make_usage(disk_used: u64, available_memory: u64) -> HostUsage {
+        HostUsage {
+            disk_total: 100_000,
+            disk_used,
+            available_memory,
+            node_disk: 5_000,
+            node_memory: 500,
+        }
+    }
+
+    #[test]
+    fn plan_scaling_should_bring_the_bounds_into_effect() {
+        let policy = make_policy();
+        assert_eq!(
+            plan_scaling(&policy, &make_usage(90_000, 0), 0),
+            ScalingDecision::Add(2)
+        );
+        assert_eq!(
+            plan_scaling(&policy, &make_usage(0, 10_000), 13),
+            ScalingDecision::Remove(3)
+        );
+    }
+
+    #[test]
+    fn plan_scaling_should_add_a_node_when_there_is_room_for_another() {
+        let policy = make_policy();
+        assert_eq!(
+            plan_scaling(&policy, &make_usage(50_000, 4_000), 5),
+            ScalingDecision::Add(1)
+        );
+        // Another node would take the disk over its target.
+        assert!(matches!(
+            plan_scaling(&policy, &make_usage(76_000, 4_000), 5),
+            ScalingDecision::Hold(_)
+        ));
+        // Another node would use up the memory headroom.
+        assert!(matches!(
+            plan_scaling(&policy, &make_usage(50_000, 1_200), 5),
+            ScalingDecision::Hold(_)
+        ));
+        assert!(matches!(
+            plan_scaling(&policy, &make_usage(50_000, 4_000), 10),
+            ScalingDecision::Hold(_)
+        ));
+    }
+
+    #[test]
+    fn plan_scaling_should_remove_a_node_when_the_host_is_over_its_targets() {
+        let policy = make_policy();
+        assert_eq!(
+            plan_scaling(&policy, &make_usage(85_000, 4_000), 5),
+            ScalingDecision::Remove(1)
+        );
+        assert_eq!(
+            plan_scaling(&policy, &make_usage(50_000, 800), 5),
+            ScalingDecision::Remove(1)
+        );
+        assert!(matches!(
+            plan_scaling(&policy, &make_usage(85_000, 4_000), 2),
+            ScalingDecision::Hold(_)
+        ));
+    }
+}
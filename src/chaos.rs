@@ -0,0 +1,195 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::helpers::parse_duration;
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use color_eyre::{eyre::eyre, Help, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::time::Duration;
+
+/// How often faults are injected, e.g., `1/10m` for one fault every ten minutes on average.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaultRate {
+    pub count: u32,
+    pub period: Duration,
+}
+
+impl FaultRate {
+    /// The average time between faults.
+    pub fn mean_interval(&self) -> Duration {
+        self.period / self.count
+    }
+}
+
+pub fn parse_fault_rate(value: &str) -> Result<FaultRate> {
+    let (count, period) = value.split_once('/').ok_or_else(|| {
+        eyre!("The rate '{value}' is not in the form <count>/<period>")
+            .suggestion("For example, use '1/10m' for one fault every ten minutes")
+    })?;
+    let count: u32 = count
+        .trim()
+        .parse()
+        .map_err(|_| eyre!("The rate '{value}' must start with a number of faults"))?;
+    if count == 0 {
+        return Err(eyre!("The rate '{value}' must have at least one fault"));
+    }
+    Ok(FaultRate {
+        count,
+        period: parse_duration(period)?,
+    })
+}
+
+/// A fault that can be injected into a node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    /// Kill the node's process without stopping its service, so the service manager sees a crash.
+    Kill,
+    /// Stop and start the node's service.
+    Restart,
+    /// Stop the node's service. It will be started again at the end of the run.
+    Stop,
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Fault::Kill => write!(f, "kill"),
+            Fault::Restart => write!(f, "restart"),
+            Fault::Stop => write!(f, "stop"),
+        }
+    }
+}
+
+impl Fault {
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        *[Fault::Kill, Fault::Restart, Fault::Stop]
+            .choose(rng)
+            .unwrap_or(&Fault::Restart)
+    }
+}
+
+/// Pick a node to inject a fault into.
+///
+/// Only running nodes that aren't in maintenance mode are picked, and none are picked if that
+/// would leave `min_running` or fewer nodes running.
+pub fn pick_target<R: Rng>(
+    node_registry: &NodeRegistry,
+    min_running: usize,
+    rng: &mut R,
+) -> Option<String> {
+    let running = node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status == NodeStatus::Running)
+        .collect::<Vec<_>>();
+    if running.len() <= min_running {
+        return None;
+    }
+    let candidates = running
+        .into_iter()
+        .filter(|n| !n.maintenance)
+        .collect::<Vec<_>>();
+    candidates.choose(rng).map(|n| n.service_name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::{Node, NodeType};
+    use std::path::PathBuf;
+
+    fn make_node(service_name: &str, status: NodeStatus, maintenance: bool) -> Node {
+        Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: service_name.to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status,
+            pid: None,
+            peer_id: None,
+            data_dir_path: None,
+            log_dir_path: None,
+            safenode_path: None,
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }
+    }
+
+    #[test]
+    fn parse_fault_rate_should_parse_a_count_and_a_period() -> Result<()> {
+        let rate = parse_fault_rate("1/10m")?;
+        assert_eq!(rate.count, 1);
+        assert_eq!(rate.mean_interval(), Duration::from_secs(600));
+        assert_eq!(
+            parse_fault_rate("6/1h")?.mean_interval(),
+            Duration::from_secs(600)
+        );
+        assert!(parse_fault_rate("10m").is_err());
+        assert!(parse_fault_rate("0/10m").is_err());
+        assert!(parse_fault_rate("one/10m").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pick_target_should_only_pick_running_nodes_outside_maintenance() {
+        let node_registry = NodeRegistry {
+            save_path: PathBuf::from("/tmp/node_registry.json"),
+            nodes: vec![
+                make_node("safenode1", NodeStatus::Running, true),
+                make_node("safenode2", NodeStatus::Stopped, false),
+                make_node("safenode3", NodeStatus::Running, false),
+            ],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            assert_eq!(
+                pick_target(&node_registry, 0, &mut rng),
+                Some("safenode3".to_string())
+            );
+        }
+        assert_eq!(pick_target(&node_registry, 2, &mut rng), None);
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::alerts::{
+    check_thresholds, measure_node, AlertSettings, AlertThresholds, RestartHistory,
+};
+use crate::cmd::is_running_as_root;
+use crate::config::*;
+use crate::node_registry::NodeRegistry;
+use crate::notify::{notify_alerts, AlertNotification};
+use crate::service::get_service_control;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+
+/// Check each service against its alert thresholds, and notify the webhook of any breaches.
+pub async fn check() -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The alerts check command must run as the root user"));
+    }
+
+    let settings = AlertSettings::load(&get_alert_settings_path()?)?;
+    let history_path = get_restart_history_path()?;
+    let mut history = RestartHistory::load(&history_path)?;
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let service_control = get_service_control(&node_registry);
+    let now = chrono::Utc::now();
+
+    let mut notifications = Vec::new();
+    for node in node_registry.nodes.iter().filter(|n| !n.maintenance) {
+        let measurements = measure_node(node, &*service_control, &mut history, now).await;
+        let breaches =
+            check_thresholds(&settings.get_thresholds(&node.service_name), &measurements);
+        if breaches.is_empty() {
+            continue;
+        }
+        for breach in breaches.iter() {
+            println!("{} {}: {breach}", "⚠".yellow(), node.service_name);
+        }
+        notifications.push(AlertNotification::new(&node.service_name, breaches));
+    }
+    history.save(&history_path)?;
+
+    if notifications.is_empty() {
+        println!("{} No alert thresholds were breached", "✓".green());
+    } else if let Some(webhook_url) = &settings.webhook {
+        notify_alerts(webhook_url, &notifications).await;
+    }
+
+    Ok(())
+}
+
+/// Set the alert thresholds, for every service or for the one named, and the webhook.
+pub fn set(
+    max_disk_percent: Option<f64>,
+    max_memory: Option<u64>,
+    max_restarts_per_hour: Option<u32>,
+    min_connected_peers: Option<usize>,
+    service_name: Option<String>,
+    webhook: Option<String>,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The alerts set command must run as the root user"));
+    }
+
+    let path = get_alert_settings_path()?;
+    let mut settings = AlertSettings::load(&path)?;
+    let changes = AlertThresholds {
+        min_connected_peers,
+        max_memory,
+        max_disk_percent,
+        max_restarts_per_hour,
+    };
+    match &service_name {
+        Some(service_name) => {
+            let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+            if !node_registry
+                .nodes
+                .iter()
+                .any(|n| &n.service_name == service_name)
+            {
+                return Err(eyre!("No service named '{service_name}'"));
+            }
+            let thresholds = settings.nodes.entry(service_name.clone()).or_default();
+            *thresholds = thresholds.with_overrides(Some(&changes));
+        }
+        None => settings.thresholds = settings.thresholds.with_overrides(Some(&changes)),
+    }
+    if webhook.is_some() {
+        settings.webhook = webhook;
+    }
+    settings.save(&path)?;
+
+    match service_name {
+        Some(service_name) => {
+            println!("{} Alert thresholds set for {service_name}", "✓".green())
+        }
+        None => println!("{} Alert thresholds set", "✓".green()),
+    }
+    Ok(())
+}
+
+/// Print the alert thresholds and webhook.
+pub fn status() -> Result<()> {
+    let settings = AlertSettings::load(&get_alert_settings_path()?)?;
+    println!("{}", serde_json::to_string_pretty(&settings)?);
+    Ok(())
+}
@@ -0,0 +1,139 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::auditor::{install_auditor, start_auditor, AuditorOptions};
+use crate::cmd::{get_current_username, is_running_as_root};
+use crate::config::*;
+use crate::control;
+use crate::helpers::{
+    download_and_extract_auditor, extract_local_release, get_auditor_archive_url,
+    get_release_checksum,
+};
+use crate::node_registry::{Node, NodeRegistry, NodeStatus};
+use crate::service::{NodeServiceManager, ServiceControl};
+use color_eyre::{eyre::eyre, Help, Result};
+use sn_peers_acquisition::{get_peers_from_args, PeersArgs};
+use sn_releases::SafeReleaseRepositoryInterface;
+use std::path::PathBuf;
+
+/// Install the auditor as a service, from a version or a local file.
+#[allow(clippy::too_many_arguments)]
+pub async fn install(
+    checksum: Option<String>,
+    data_dir_path: Option<PathBuf>,
+    extra_args: Vec<String>,
+    log_dir_path: Option<PathBuf>,
+    path: Option<PathBuf>,
+    peers: PeersArgs,
+    skip_checksum: bool,
+    user: Option<String>,
+    version: Option<String>,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!(
+            "The auditor install command must run as the root user"
+        ));
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let service_manager = NodeServiceManager {};
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    let (auditor_download_path, version) = match (path, version) {
+        (Some(path), _) => extract_local_release(&path, checksum.as_deref(), &*release_repo)?,
+        (None, Some(version)) => {
+            let checksum = match checksum {
+                Some(checksum) => Some(checksum),
+                None => {
+                    get_release_checksum(&get_auditor_archive_url(&version)?, skip_checksum).await?
+                }
+            };
+            let auditor_download_path =
+                download_and_extract_auditor(&version, checksum, &*release_repo).await?;
+            (auditor_download_path, version)
+        }
+        (None, None) => return Err(eyre!("Either a version or a path must be supplied")),
+    };
+
+    let service_user = if is_user_mode() {
+        get_current_username()?
+    } else {
+        let service_user = user.unwrap_or("safe".to_string());
+        service_manager.create_service_user(&service_user)?;
+        service_user
+    };
+    let service_data_dir_path = get_service_data_dir_path(data_dir_path, &service_user)?;
+    let service_log_dir_path = get_service_log_dir_path(log_dir_path, &service_user)?;
+    let peers = get_peers_from_args(peers).await?;
+    install_auditor(
+        AuditorOptions {
+            auditor_download_path,
+            extra_args,
+            peers,
+            service_data_dir_path,
+            service_log_dir_path,
+            user: service_user,
+            version,
+        },
+        &mut node_registry,
+        &service_manager,
+    )?;
+    node_registry.save(registry_lock)?;
+    Ok(())
+}
+
+/// Remove the auditor service.
+pub async fn remove(keep_directories: bool) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!(
+            "The auditor remove command must run as the root user"
+        ));
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let auditor = get_auditor(&mut node_registry)?;
+    let result = control::remove(auditor, &NodeServiceManager {}, keep_directories).await;
+    node_registry.save(registry_lock)?;
+    result
+}
+
+/// Start the auditor service.
+pub async fn start() -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The auditor start command must run as the root user"));
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let auditor = get_auditor(&mut node_registry)?;
+    let result = start_auditor(auditor, &NodeServiceManager {}).await;
+    node_registry.save(registry_lock)?;
+    result
+}
+
+/// Stop the auditor service.
+pub async fn stop() -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The auditor stop command must run as the root user"));
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let auditor = get_auditor(&mut node_registry)?;
+    let result = control::stop(auditor, &NodeServiceManager {}).await;
+    node_registry.save(registry_lock)?;
+    result
+}
+
+/// Get the auditor from the registry, if it's installed.
+fn get_auditor(node_registry: &mut NodeRegistry) -> Result<&mut Node> {
+    node_registry
+        .auditor
+        .as_mut()
+        .filter(|auditor| auditor.status != NodeStatus::Removed)
+        .ok_or_else(|| {
+            eyre!("The auditor is not installed").suggestion("Install it with auditor install")
+        })
+}
@@ -0,0 +1,162 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::autoscale::{measure_host, plan_scaling, AutoscalePolicy, ScalingDecision};
+use crate::cmd::{is_running_as_root, start_node};
+use crate::config::*;
+use crate::control::{remove, stop, DEFAULT_STARTUP_TIMEOUT};
+use crate::dry_run::is_dry_run;
+use crate::event_log::{write_event, EventLevel};
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use crate::service::get_service_control;
+use color_eyre::{eyre::eyre, Help, Result};
+use colored::Colorize;
+use std::future::Future;
+
+/// Remove the autoscale policy.
+pub fn clear() -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!(
+            "The autoscale clear command must run as the root user"
+        ));
+    }
+    let path = get_autoscale_policy_path()?;
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    println!("{} Autoscale policy removed", "✓".green());
+    Ok(())
+}
+
+/// Add or remove services to keep the host within the autoscale policy.
+///
+/// The services are added by running the add command with `run_command`, with the settings of the
+/// last service, and then started.
+pub async fn run<R, F>(run_command: R) -> Result<()>
+where
+    R: Fn(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The autoscale run command must run as the root user"));
+    }
+    let policy = AutoscalePolicy::load(&get_autoscale_policy_path()?)?.ok_or_else(|| {
+        eyre!("There is no autoscale policy").suggestion("Use the autoscale set command to set one")
+    })?;
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let usage = measure_host(&node_registry, &get_default_service_data_dir_path()?);
+    let node_count = node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+        .count();
+    println!(
+        "{node_count} node(s), disk {:.1}% full, {}MB of memory available, \
+         {}MB of disk and {}MB of memory per node",
+        usage.disk_percent(),
+        usage.available_memory,
+        usage.node_disk,
+        usage.node_memory
+    );
+
+    match plan_scaling(&policy, &usage, node_count) {
+        ScalingDecision::Add(count) => {
+            println!("{count} node(s) to be added");
+            if is_dry_run() {
+                return Ok(());
+            }
+            let like = node_registry
+                .nodes
+                .iter()
+                .rev()
+                .find(|n| n.status != NodeStatus::Removed)
+                .map(|n| n.service_name.clone())
+                .ok_or_else(|| {
+                    eyre!("There is no service to copy the settings of")
+                        .suggestion("Use the add command to add the first service")
+                })?;
+            let previous_node_count = node_registry.nodes.len();
+            // The add command locks the registry itself.
+            drop(registry_lock);
+            run_command(vec![
+                "safenode-manager".to_string(),
+                "add".to_string(),
+                "--like".to_string(),
+                like,
+                "--count".to_string(),
+                count.to_string(),
+            ])
+            .await?;
+
+            let (mut node_registry, registry_lock) =
+                NodeRegistry::load_locked(&get_node_registry_path()?)?;
+            let service_control = get_service_control(&node_registry);
+            for node in node_registry.nodes[previous_node_count..].iter_mut() {
+                start_node(node, &*service_control, DEFAULT_STARTUP_TIMEOUT).await?;
+            }
+            node_registry.save(registry_lock)?;
+        }
+        ScalingDecision::Remove(count) => {
+            println!("{count} node(s) to be removed");
+            if is_dry_run() {
+                return Ok(());
+            }
+            node_registry.snapshot("autoscale")?;
+            let service_control = get_service_control(&node_registry);
+            let nodes = node_registry
+                .nodes
+                .iter_mut()
+                .rev()
+                .filter(|n| n.status != NodeStatus::Removed && !n.maintenance)
+                .take(count);
+            for node in nodes {
+                if node.status == NodeStatus::Running {
+                    stop(node, &*service_control).await?;
+                }
+                remove(node, &*service_control, false).await?;
+                write_event(
+                    EventLevel::Information,
+                    &format!("Autoscale removed {}", node.service_name),
+                );
+            }
+            node_registry.save(registry_lock)?;
+        }
+        ScalingDecision::Hold(reason) => {
+            println!("{} No change: {reason}", "✓".green());
+        }
+    }
+
+    Ok(())
+}
+
+/// Set the autoscale policy.
+pub fn set(
+    max_nodes: usize,
+    memory_headroom: Option<u64>,
+    min_nodes: usize,
+    target_disk_percent: Option<f64>,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The autoscale set command must run as the root user"));
+    }
+    if min_nodes > max_nodes {
+        return Err(eyre!(
+            "The minimum number of nodes cannot be more than the maximum"
+        ));
+    }
+    let policy = AutoscalePolicy {
+        min_nodes,
+        max_nodes,
+        target_disk_percent,
+        memory_headroom,
+    };
+    policy.save(&get_autoscale_policy_path()?)?;
+    println!("{} Autoscale policy set", "✓".green());
+    Ok(())
+}
@@ -0,0 +1,111 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::chaos::{pick_target, Fault, FaultRate};
+use crate::cmd::{is_running_as_root, start_node};
+use crate::config::*;
+use crate::control::{stop, DEFAULT_STARTUP_TIMEOUT};
+use crate::event_log::{write_event, EventLevel};
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use crate::service::get_service_control;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use rand::Rng;
+use std::time::Duration;
+
+/// Inject faults into the running services at random for a period, to check how the network
+/// copes.
+///
+/// The services that were stopped are started again at the end.
+pub async fn run(duration: Duration, kill_rate: FaultRate, min_running: usize) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The chaos command must run as the root user"));
+    }
+
+    println!("=================================================");
+    println!("                 Safenode Chaos                  ");
+    println!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let service_control = get_service_control(&node_registry);
+    let mut rng = rand::thread_rng();
+    let mut stopped = Vec::new();
+    let end = std::time::Instant::now() + duration;
+    let max_interval = kill_rate.mean_interval().as_millis() as u64 * 2;
+    loop {
+        let delay = Duration::from_millis(rng.gen_range(0..=max_interval));
+        if std::time::Instant::now() + delay >= end {
+            break;
+        }
+        tokio::time::sleep(delay).await;
+
+        let service_name = match pick_target(&node_registry, min_running, &mut rng) {
+            Some(service_name) => service_name,
+            None => {
+                println!("No fault injected because only {min_running} node(s) are running");
+                continue;
+            }
+        };
+        let node = node_registry
+            .nodes
+            .iter_mut()
+            .find(|n| n.service_name == service_name)
+            .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+        let fault = Fault::random(&mut rng);
+        let result = match fault {
+            Fault::Kill => match node.pid {
+                Some(pid) => service_control.kill_process(pid),
+                None => Err(eyre!("The node has no PID")),
+            },
+            Fault::Restart => match stop(node, &*service_control).await {
+                Ok(()) => start_node(node, &*service_control, DEFAULT_STARTUP_TIMEOUT).await,
+                Err(e) => Err(e),
+            },
+            Fault::Stop => {
+                let result = stop(node, &*service_control).await;
+                if result.is_ok() {
+                    stopped.push(service_name.clone());
+                }
+                result
+            }
+        };
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        match result {
+            Ok(()) => {
+                println!("[{timestamp}] Injected {fault} into {service_name}");
+                write_event(
+                    EventLevel::Information,
+                    &format!("Chaos: injected {fault} into {service_name}"),
+                );
+            }
+            Err(e) => {
+                println!("[{timestamp}] Failed to inject {fault} into {service_name}: {e}");
+                write_event(
+                    EventLevel::Error,
+                    &format!("Chaos: failed to inject {fault} into {service_name}: {e}"),
+                );
+            }
+        }
+        node_registry.save_locked(&registry_lock)?;
+    }
+
+    for service_name in stopped {
+        let node = node_registry
+            .nodes
+            .iter_mut()
+            .find(|n| n.service_name == service_name)
+            .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+        if node.status != NodeStatus::Running {
+            start_node(node, &*service_control, DEFAULT_STARTUP_TIMEOUT).await?;
+        }
+    }
+    node_registry.save(registry_lock)?;
+
+    println!("{} The chaos run has finished", "✓".green());
+    Ok(())
+}
@@ -0,0 +1,458 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::is_running_as_root;
+use crate::config::*;
+use crate::control::{
+    check_responsive, get_recycle_issues, recycle, refresh_node_registry, watchdog_restart,
+};
+use crate::daemon::{
+    get_command_args, get_daemon_token, serve, serve_metrics, DaemonOptions, DaemonRequest,
+    DaemonResponse,
+};
+use crate::earnings::{record_earnings, EarningsHistory};
+use crate::event_log::{write_event, EventLevel};
+use crate::helpers::format_duration;
+use crate::metrics::{collect_metrics, render_metrics};
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use crate::notify::{notify_alerts, notify_earnings, AlertNotification};
+use crate::service::get_service_control;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use sn_node_rpc_client::{RpcActions, RpcClient};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Run the daemon, which serves the API for managing the services and runs the scheduled tasks,
+/// until the process is stopped.
+///
+/// The operations requested through the API and the automatic upgrades are carried out by running
+/// the same commands as the command line, with `run_command`, so they behave in the same way.
+#[allow(clippy::too_many_arguments)]
+pub async fn run<R, F>(
+    address: SocketAddr,
+    auto_upgrade_interval: Option<Duration>,
+    auto_upgrade_delay: Duration,
+    earnings_interval: Option<Duration>,
+    watchdog_interval: Option<Duration>,
+    recycle_shunned_after: Option<Duration>,
+    metrics_port: Option<u16>,
+    notify_webhook: Option<String>,
+    run_command: R,
+) -> Result<()>
+where
+    R: Fn(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The daemon command must run as the root user"));
+    }
+
+    println!("=================================================");
+    println!("             Safenode Manager Daemon             ");
+    println!("=================================================");
+
+    let token_path = get_daemon_token_path()?;
+    let token = get_daemon_token(&token_path)?;
+    println!("The token for the API is in {}", token_path.display());
+    let mut schedules = Vec::new();
+    let mut tasks = Vec::new();
+    if let Some(interval) = auto_upgrade_interval {
+        println!(
+            "Checking for a new version of safenode every {}",
+            format_duration(interval)
+        );
+        schedules.push(interval);
+        tasks.push(ScheduledTask::AutoUpgrade);
+    }
+    if let Some(interval) = earnings_interval {
+        println!(
+            "Recording the balance of the services every {}",
+            format_duration(interval)
+        );
+        schedules.push(interval);
+        tasks.push(ScheduledTask::RecordEarnings);
+    }
+    if let Some(interval) = watchdog_interval {
+        println!(
+            "Checking the services respond every {}",
+            format_duration(interval)
+        );
+        schedules.push(interval);
+        tasks.push(ScheduledTask::Watchdog);
+    }
+    if let Some(period) = recycle_shunned_after {
+        println!(
+            "Recycling services that look shunned over {}",
+            format_duration(period)
+        );
+    }
+    let api = serve(
+        address,
+        &token,
+        |request| handle_request(request, &run_command),
+        &schedules,
+        |index| {
+            run_scheduled_task(
+                tasks[index],
+                auto_upgrade_delay,
+                recycle_shunned_after,
+                notify_webhook.as_deref(),
+                &run_command,
+            )
+        },
+    );
+    let metrics = async {
+        match metrics_port {
+            Some(port) => serve_metrics(SocketAddr::new(address.ip(), port), get_metrics).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = api => result,
+        result = metrics => result,
+    }
+}
+
+/// Handle a request to the daemon by running the command for the operation.
+async fn handle_request<R, F>(request: DaemonRequest, run_command: &R) -> DaemonResponse
+where
+    R: Fn(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => get_status().await,
+        ("POST", "/start") | ("POST", "/stop") | ("POST", "/upgrade") | ("POST", "/restart") => {
+            let options = if request.body.is_empty() {
+                DaemonOptions::default()
+            } else {
+                match serde_json::from_slice::<DaemonOptions>(&request.body) {
+                    Ok(options) => options,
+                    Err(e) => {
+                        return DaemonResponse::error(400, &format!("The body is not valid: {e}"))
+                    }
+                }
+            };
+            run_operation(&request.path[1..], &options, run_command).await
+        }
+        _ => return DaemonResponse::error(404, "There is no such operation"),
+    };
+    match result {
+        Ok(body) => DaemonResponse::ok(body),
+        Err(e) => DaemonResponse::error(500, &e.to_string()),
+    }
+}
+
+async fn get_status() -> Result<String> {
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let service_control = get_service_control(&node_registry);
+    refresh_node_registry(&mut node_registry, &*service_control, true).await;
+    node_registry.save(registry_lock)?;
+    Ok(serde_json::to_string(&node_registry.nodes)?)
+}
+
+async fn get_metrics() -> Result<String> {
+    let mut node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let service_control = get_service_control(&node_registry);
+    let metrics = collect_metrics(&mut node_registry, &*service_control).await;
+    Ok(render_metrics(&metrics))
+}
+
+async fn run_operation<R, F>(
+    operation: &str,
+    options: &DaemonOptions,
+    run_command: &R,
+) -> Result<String>
+where
+    R: Fn(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    run_command(get_command_args(operation, options)).await?;
+    get_status().await
+}
+
+/// The tasks the daemon runs on a schedule.
+#[derive(Clone, Copy)]
+enum ScheduledTask {
+    AutoUpgrade,
+    RecordEarnings,
+    Watchdog,
+}
+
+async fn run_scheduled_task<R, F>(
+    task: ScheduledTask,
+    auto_upgrade_delay: Duration,
+    recycle_period: Option<Duration>,
+    notify_webhook: Option<&str>,
+    run_command: &R,
+) where
+    R: Fn(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    match task {
+        ScheduledTask::AutoUpgrade => auto_upgrade(auto_upgrade_delay, run_command).await,
+        ScheduledTask::RecordEarnings => {
+            if let Err(e) = record_scheduled_earnings(notify_webhook).await {
+                println!("{} Failed to record the earnings: {e}", "✕".red());
+            }
+        }
+        ScheduledTask::Watchdog => {
+            if let Err(e) = watchdog(recycle_period, notify_webhook).await {
+                println!("{} The watchdog check failed: {e}", "✕".red());
+            }
+        }
+    }
+}
+
+/// Record the balance of each node in the earnings history, on behalf of the daemon.
+///
+/// With a webhook, a notification is sent for each node whose balance increased.
+async fn record_scheduled_earnings(notify_webhook: Option<&str>) -> Result<()> {
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let mut earnings_history = EarningsHistory::load(&get_earnings_history_path()?)?;
+    let records = record_earnings(&node_registry, &mut earnings_history).await?;
+    earnings_history.save()?;
+    if let Some(webhook_url) = notify_webhook {
+        notify_earnings(webhook_url, &records).await;
+    }
+    Ok(())
+}
+
+/// Restart the services that should be running but aren't responding.
+///
+/// The registry is saved after each restart, so a failure part of the way through doesn't lose
+/// the record of the restarts that were made. With a webhook, an alert is sent for each node that
+/// was restarted, or that couldn't be, once all of them have been checked.
+///
+/// With a recycle period, the nodes that are responding are then checked for signs of being
+/// shunned, and those that look it are restarted with a new peer ID.
+pub async fn watchdog(
+    recycle_period: Option<Duration>,
+    notify_webhook: Option<&str>,
+) -> Result<()> {
+    let Some((mut node_registry, registry_lock)) =
+        NodeRegistry::try_load_locked(&get_node_registry_path()?)?
+    else {
+        println!(
+            "{} The registry is in use by another command, so the watchdog will check again next time",
+            "⚠".yellow()
+        );
+        return Ok(());
+    };
+    let service_control = get_service_control(&node_registry);
+    let mut alerts = Vec::new();
+    for index in 0..node_registry.nodes.len() {
+        let node = &mut node_registry.nodes[index];
+        if node.status != NodeStatus::Running || node.maintenance || node.local {
+            continue;
+        }
+        let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+        let Some(reason) = check_responsive(node, &*service_control, &rpc_client).await else {
+            continue;
+        };
+        println!(
+            "{} {} is not responding: {reason}",
+            "⚠".yellow(),
+            node.service_name
+        );
+        let message = format!("The watchdog restarted {}: {reason}", node.service_name);
+        match watchdog_restart(
+            node,
+            &*service_control,
+            &rpc_client,
+            reason.clone(),
+            chrono::Utc::now(),
+        )
+        .await
+        {
+            Ok(()) => {
+                write_event(EventLevel::Information, &message);
+                alerts.push(AlertNotification::new(
+                    &node.service_name,
+                    vec![format!("restarted by the watchdog: {reason}")],
+                ));
+            }
+            Err(e) => {
+                println!("{} Failed to restart {}: {e}", "✕".red(), node.service_name);
+                write_event(
+                    EventLevel::Error,
+                    &format!("The watchdog failed to restart {}: {e}", node.service_name),
+                );
+                alerts.push(AlertNotification::new(
+                    &node.service_name,
+                    vec![format!("the watchdog failed to restart it: {e}")],
+                ));
+            }
+        }
+        node_registry.save_locked(&registry_lock)?;
+    }
+    if let Some(period) = recycle_period {
+        let earnings_history = EarningsHistory::load(&get_earnings_history_path()?)?;
+        let now = chrono::Utc::now();
+        for index in 0..node_registry.nodes.len() {
+            let node = &mut node_registry.nodes[index];
+            if node.status != NodeStatus::Running || node.maintenance || node.local {
+                continue;
+            }
+            let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+            let Ok(node_info) = rpc_client.node_info().await else {
+                continue;
+            };
+            if let Ok(info) = rpc_client.network_info().await {
+                node.connected_peers = Some(info.connected_peers);
+            }
+            let issues =
+                get_recycle_issues(node, node_info.uptime, Some(&earnings_history), period, now);
+            if issues.is_empty() {
+                continue;
+            }
+            let breaches = issues.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+            println!(
+                "{} {} looks shunned: {}",
+                "⚠".yellow(),
+                node.service_name,
+                breaches.join("; ")
+            );
+            match recycle(node, &*service_control, &rpc_client, &issues, now).await {
+                Ok(()) => {
+                    write_event(
+                        EventLevel::Information,
+                        &format!(
+                            "The watchdog recycled {} with a new peer ID: {}",
+                            node.service_name,
+                            breaches.join("; ")
+                        ),
+                    );
+                    alerts.push(AlertNotification::new(
+                        &node.service_name,
+                        vec![format!(
+                            "recycled by the watchdog with a new peer ID: {}",
+                            breaches.join("; ")
+                        )],
+                    ));
+                }
+                Err(e) => {
+                    println!("{} Failed to recycle {}: {e}", "✕".red(), node.service_name);
+                    write_event(
+                        EventLevel::Error,
+                        &format!("The watchdog failed to recycle {}: {e}", node.service_name),
+                    );
+                    alerts.push(AlertNotification::new(
+                        &node.service_name,
+                        vec![format!("the watchdog failed to recycle it: {e}")],
+                    ));
+                }
+            }
+            node_registry.save_locked(&registry_lock)?;
+        }
+    }
+    if let Some(webhook_url) = notify_webhook {
+        notify_alerts(webhook_url, &alerts).await;
+    }
+    Ok(())
+}
+
+/// Upgrade the services to the latest version, if there is a new one.
+///
+/// The upgrade command is run with `run_command`, waiting for the delay between each service. This
+/// runs in the daemon, so a failure is reported and the daemon keeps going.
+pub async fn auto_upgrade<R, F>(delay: Duration, run_command: &R)
+where
+    R: Fn(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    let args = vec![
+        "safenode-manager".to_string(),
+        "upgrade".to_string(),
+        "--interval".to_string(),
+        delay.as_millis().to_string(),
+    ];
+    if let Err(e) = run_command(args).await {
+        println!("{} The automatic upgrade failed: {e}", "✕".red());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[tokio::test]
+    async fn auto_upgrade_should_run_the_upgrade_command_with_the_delay_between_services() {
+        let commands = RefCell::new(Vec::new());
+        let run_command = |args: Vec<String>| {
+            commands.borrow_mut().push(args);
+            async { Ok(()) }
+        };
+
+        auto_upgrade(Duration::from_secs(30), &run_command).await;
+
+        assert_eq!(
+            commands.into_inner(),
+            vec![vec!["safenode-manager", "upgrade", "--interval", "30000"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_request_should_return_the_error_if_the_command_fails() {
+        let commands = RefCell::new(Vec::new());
+        let run_command = |args: Vec<String>| {
+            commands.borrow_mut().push(args);
+            async { Err(eyre!("The stop command must run as the root user")) }
+        };
+        let request = DaemonRequest {
+            method: "POST".to_string(),
+            path: "/stop".to_string(),
+            body: br#"{"service_name": "safenode1"}"#.to_vec(),
+            ..Default::default()
+        };
+
+        let response = handle_request(request, &run_command).await;
+
+        assert_eq!(response.status, 500);
+        assert_eq!(
+            response.body,
+            r#"{"error":"The stop command must run as the root user"}"#
+        );
+        assert_eq!(
+            commands.into_inner(),
+            vec![vec![
+                "safenode-manager",
+                "stop",
+                "--service-name",
+                "safenode1"
+            ]]
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_request_should_not_run_a_command_for_an_unknown_operation_or_a_bad_body() {
+        let commands = RefCell::new(Vec::new());
+        let run_command = |args: Vec<String>| {
+            commands.borrow_mut().push(args);
+            async { Ok(()) }
+        };
+
+        let request = DaemonRequest {
+            method: "POST".to_string(),
+            path: "/remove".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(handle_request(request, &run_command).await.status, 404);
+        let request = DaemonRequest {
+            method: "POST".to_string(),
+            path: "/start".to_string(),
+            body: b"{".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(handle_request(request, &run_command).await.status, 400);
+
+        assert!(commands.into_inner().is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::is_running_as_root;
+use crate::config::*;
+use crate::diagnostics::{create_bundle, upload_bundle};
+use crate::doctor;
+use crate::node_registry::NodeRegistry;
+use crate::service::NodeServiceManager;
+use color_eyre::Result;
+use colored::Colorize;
+use sn_releases::SafeReleaseRepositoryInterface;
+use std::path::PathBuf;
+
+/// Write a bundle of information for debugging problems with the services, and upload it if
+/// there's a URL.
+pub async fn collect(out: Option<PathBuf>, upload: Option<String>) -> Result<()> {
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let out = out.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "safenode-manager-diagnostics-{}.tar.gz",
+            chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+        ))
+    });
+    create_bundle(&node_registry, &out)?;
+    println!(
+        "{} Wrote the diagnostics bundle to {}",
+        "✓".green(),
+        out.to_string_lossy()
+    );
+
+    if let Some(url) = upload {
+        println!("Uploading the diagnostics bundle...");
+        upload_bundle(&out, &url).await?;
+        println!("{} Uploaded the diagnostics bundle", "✓".green());
+    }
+
+    Ok(())
+}
+
+/// Check the host can run and manage safenode services.
+pub async fn doctor() -> Result<()> {
+    println!("=================================================");
+    println!("                 Safenode Doctor                 ");
+    println!("=================================================");
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    doctor::doctor(
+        &get_node_registry_path()?,
+        &NodeServiceManager {},
+        &*release_repo,
+        is_running_as_root() || is_user_mode(),
+    )
+    .await
+}
@@ -0,0 +1,82 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::is_running_as_root;
+use crate::config::*;
+use crate::earnings::{
+    daily_earnings, daily_earnings_to_csv, print_report, record_earnings, EarningsHistory,
+    ExportFormat,
+};
+use crate::node_registry::NodeRegistry;
+use crate::notify::notify_earnings;
+use crate::output::is_json_output;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Export the earnings history with a row per service, per day, to a file or to stdout.
+pub fn export(format: ExportFormat, out: Option<PathBuf>) -> Result<()> {
+    let earnings_history = EarningsHistory::load(&get_earnings_history_path()?)?;
+    let rows = daily_earnings(&earnings_history);
+    let exported = match format {
+        ExportFormat::Csv => daily_earnings_to_csv(&rows),
+        ExportFormat::Json => serde_json::to_string(&rows)?,
+    };
+    if let Some(out) = out {
+        std::fs::write(&out, exported)?;
+        println!(
+            "{} Exported {} rows to {}",
+            "✓".green(),
+            rows.len(),
+            out.to_string_lossy()
+        );
+    } else {
+        print!("{exported}");
+    }
+
+    Ok(())
+}
+
+/// Record the current balance of each service in the earnings history.
+///
+/// With a webhook, a notification is sent for each node whose balance increased.
+pub async fn record(notify_webhook: Option<String>) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!(
+            "The earnings record command must run as the root user"
+        ));
+    }
+
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let mut earnings_history = EarningsHistory::load(&get_earnings_history_path()?)?;
+    let records = record_earnings(&node_registry, &mut earnings_history).await?;
+    earnings_history.save()?;
+
+    if let Some(webhook_url) = notify_webhook {
+        notify_earnings(&webhook_url, &records).await;
+    }
+
+    Ok(())
+}
+
+/// Print the earnings of each service over the period up to now.
+pub fn report(csv: bool, since: Duration) -> Result<()> {
+    let earnings_history = EarningsHistory::load(&get_earnings_history_path()?)?;
+    let since = chrono::Utc::now() - chrono::Duration::from_std(since)?;
+    let report = earnings_history.report(since);
+    if is_json_output() {
+        println!("{}", serde_json::to_string(&report)?);
+    } else if csv {
+        print!("{}", report.to_csv());
+    } else {
+        print_report(&report);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::get_bin_path;
+use crate::local::{kill_network, run_faucet, run_network, LocalNetworkOptions};
+use crate::node_registry::{get_local_node_registry_path, NodeRegistry};
+use crate::service::NodeServiceManager;
+use color_eyre::{eyre::eyre, Help, Result};
+use sn_peers_acquisition::{get_peers_from_args, PeersArgs};
+use sn_releases::{ReleaseType, SafeReleaseRepositoryInterface};
+use std::path::PathBuf;
+
+/// Run a faucet for a local network.
+pub async fn faucet(
+    path: Option<PathBuf>,
+    peers: PeersArgs,
+    version: Option<String>,
+) -> Result<()> {
+    println!("=================================================");
+    println!("                 Running Faucet                  ");
+    println!("=================================================");
+
+    let local_node_reg_path = &get_local_node_registry_path()?;
+    let (mut local_node_registry, local_registry_lock) =
+        NodeRegistry::load_locked(local_node_reg_path)?;
+    if !local_node_registry.nodes.is_empty() {
+        return Err(eyre!("A local network is already running")
+            .suggestion("Use the kill command to destroy the network then try again"));
+    }
+
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    let faucet_path = get_bin_path(path, ReleaseType::Faucet, version, &*release_repo).await?;
+
+    let peers = get_peers_from_args(peers).await?;
+    run_faucet(&mut local_node_registry, faucet_path, peers[0].clone()).await?;
+
+    local_node_registry.save(local_registry_lock)?;
+
+    Ok(())
+}
+
+/// Add nodes to an existing local network.
+pub async fn join(
+    count: u16,
+    faucet_path: Option<PathBuf>,
+    faucet_version: Option<String>,
+    node_path: Option<PathBuf>,
+    node_version: Option<String>,
+    peers: PeersArgs,
+) -> Result<()> {
+    println!("=================================================");
+    println!("             Joining Local Network               ");
+    println!("=================================================");
+
+    let local_node_reg_path = &get_local_node_registry_path()?;
+    let (mut local_node_registry, local_registry_lock) =
+        NodeRegistry::load_locked(local_node_reg_path)?;
+
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    let faucet_path = get_bin_path(
+        faucet_path,
+        ReleaseType::Faucet,
+        faucet_version,
+        &*release_repo,
+    )
+    .await?;
+    let node_path = get_bin_path(
+        node_path,
+        ReleaseType::Safenode,
+        node_version,
+        &*release_repo,
+    )
+    .await?;
+
+    // If no peers are obtained we will attempt to join the existing local network, if one
+    // is running.
+    let peers = match get_peers_from_args(peers).await {
+        Ok(peers) => Some(peers),
+        Err(e) => match e {
+            sn_peers_acquisition::error::Error::PeersNotObtained => None,
+            _ => return Err(e.into()),
+        },
+    };
+    let options = LocalNetworkOptions {
+        faucet_bin_path: faucet_path,
+        join: true,
+        node_count: count,
+        peers,
+        safenode_bin_path: node_path,
+        skip_validation: true,
+    };
+    run_network(
+        &mut local_node_registry,
+        &local_registry_lock,
+        &NodeServiceManager {},
+        options,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Kill the running local network.
+pub fn kill(keep_directories: bool) -> Result<()> {
+    let local_reg_path = &get_local_node_registry_path()?;
+    let (local_node_registry, _local_registry_lock) = NodeRegistry::load_locked(local_reg_path)?;
+    if local_node_registry.nodes.is_empty() {
+        println!("No local network is currently running");
+    } else {
+        println!("=================================================");
+        println!("             Killing Local Network               ");
+        println!("=================================================");
+        kill_network(&local_node_registry, keep_directories)?;
+        std::fs::remove_file(local_reg_path)?;
+    }
+    Ok(())
+}
+
+/// Run a local network, with a faucet and `count` nodes.
+pub async fn run(
+    count: u16,
+    faucet_path: Option<PathBuf>,
+    faucet_version: Option<String>,
+    node_path: Option<PathBuf>,
+    node_version: Option<String>,
+) -> Result<()> {
+    let local_node_reg_path = &get_local_node_registry_path()?;
+    let (mut local_node_registry, local_registry_lock) =
+        NodeRegistry::load_locked(local_node_reg_path)?;
+    if !local_node_registry.nodes.is_empty() {
+        return Err(eyre!("A local network is already running")
+            .suggestion("Use the kill command to destroy the network then try again"));
+    }
+
+    println!("=================================================");
+    println!("             Launching Local Network             ");
+    println!("=================================================");
+
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    let faucet_path = get_bin_path(
+        faucet_path,
+        ReleaseType::Faucet,
+        faucet_version,
+        &*release_repo,
+    )
+    .await?;
+    let node_path = get_bin_path(
+        node_path,
+        ReleaseType::Safenode,
+        node_version,
+        &*release_repo,
+    )
+    .await?;
+
+    let options = LocalNetworkOptions {
+        faucet_bin_path: faucet_path,
+        join: false,
+        node_count: count,
+        peers: None,
+        safenode_bin_path: node_path,
+        skip_validation: true,
+    };
+    run_network(
+        &mut local_node_registry,
+        &local_registry_lock,
+        &NodeServiceManager {},
+        options,
+    )
+    .await?;
+
+    local_node_registry.save(local_registry_lock)?;
+
+    Ok(())
+}
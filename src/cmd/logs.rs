@@ -0,0 +1,129 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config::*;
+use crate::event_log::read_events;
+use crate::log_shipping::{get_host_name, ship_lines, LogDestination, LogLabels, BUFFER_LIMIT};
+use crate::logs::{print_logs, LogFilter, LogLevel, LogTail};
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use color_eyre::{eyre::eyre, Help, Result};
+use colored::Colorize;
+use regex::Regex;
+use std::time::Duration;
+
+/// Send the logs of the services to Loki or Elasticsearch as they are written, until the process
+/// is stopped.
+pub async fn ship(
+    elasticsearch: Option<String>,
+    index: String,
+    interval: u64,
+    loki: Option<String>,
+) -> Result<()> {
+    let destination = match (loki, elasticsearch) {
+        (Some(url), _) => LogDestination::Loki { url },
+        (None, Some(url)) => LogDestination::Elasticsearch { url, index },
+        (None, None) => return Err(eyre!("Either --loki or --elasticsearch is required")),
+    };
+
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let host = get_host_name();
+    let mut labels = Vec::new();
+    let mut tails = Vec::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        if let Some(log_dir_path) = &node.log_dir_path {
+            let mut tail = LogTail::new(log_dir_path);
+            // Only the lines written from now on are sent.
+            tail.read_last(tails.len(), 0, &LogFilter::default())?;
+            tails.push(tail);
+            labels.push(LogLabels::new(&host, node));
+        }
+    }
+    println!("Shipping the logs of {} service(s)", tails.len());
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+    let mut buffer = Vec::new();
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        for (source, tail) in tails.iter_mut().enumerate() {
+            buffer.extend(tail.read_new(source)?);
+        }
+        if buffer.len() > BUFFER_LIMIT {
+            let dropped = buffer.len() - BUFFER_LIMIT;
+            buffer.drain(..dropped);
+            println!(
+                "{} Dropped {dropped} lines that could not be sent",
+                "⚠".yellow()
+            );
+        }
+        if buffer.is_empty() {
+            continue;
+        }
+        match ship_lines(&client, &destination, &labels, &buffer).await {
+            Ok(()) => buffer.clear(),
+            Err(e) => println!(
+                "{} Failed to send {} lines: {e}",
+                "⚠".yellow(),
+                buffer.len()
+            ),
+        }
+    }
+}
+
+/// Print the logs of the services, or the events written by the node manager.
+#[allow(clippy::too_many_arguments)]
+pub async fn show(
+    all: bool,
+    count: usize,
+    events: bool,
+    follow: bool,
+    grep: Option<Regex>,
+    level: Option<LogLevel>,
+    merge: bool,
+    service_name: Option<String>,
+    since: Option<Duration>,
+) -> Result<()> {
+    if events {
+        print!("{}", read_events(count)?);
+        return Ok(());
+    }
+
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let nodes = if let Some(ref name) = service_name {
+        vec![node_registry
+            .nodes
+            .iter()
+            .find(|n| n.service_name == *name)
+            .ok_or_else(|| eyre!("No service named '{name}'"))?]
+    } else if all {
+        node_registry
+            .nodes
+            .iter()
+            .filter(|n| n.status != NodeStatus::Removed)
+            .collect()
+    } else {
+        return Err(eyre!("No logs were selected").suggestion(
+            "Use --service-name or --all for the logs of the services, or --events for \
+            the events of the node manager",
+        ));
+    };
+    let filter = LogFilter {
+        pattern: grep,
+        level,
+        since: match since {
+            Some(since) => Some(chrono::Utc::now() - chrono::Duration::from_std(since)?),
+            None => None,
+        },
+    };
+    print_logs(&nodes, count, merge, follow, &filter).await
+}
@@ -0,0 +1,128 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::{get_current_username, is_running_as_root};
+use crate::config::*;
+use crate::container::make_compose_file;
+use crate::migration;
+use crate::migration::{import_nodes, read_export, ImportOptions};
+use crate::node_registry::NodeRegistry;
+use crate::output::report;
+use crate::service::get_service_control;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use sn_releases::SafeReleaseRepositoryInterface;
+use std::path::PathBuf;
+
+/// Write a Compose file with a container for each service, to a file or to stdout.
+pub fn export_compose(out: Option<PathBuf>) -> Result<()> {
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let compose = make_compose_file(&node_registry)?;
+    if let Some(out) = out {
+        std::fs::write(&out, compose)?;
+        println!(
+            "{} Exported the services to {}",
+            "✓".green(),
+            out.to_string_lossy()
+        );
+    } else {
+        print!("{compose}");
+    }
+
+    Ok(())
+}
+
+/// Export the services in the registry to a file, which can be imported on another host.
+pub fn export_registry(include_data: bool, out: PathBuf) -> Result<()> {
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let count = migration::export_registry(&node_registry, &out, include_data)?;
+    println!(
+        "{} Exported {count} service(s) to {}",
+        "✓".green(),
+        out.to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Import the services from an export of another host's registry.
+///
+/// The imported services aren't started.
+pub async fn import(
+    path: PathBuf,
+    data_dir_path: Option<PathBuf>,
+    log_dir_path: Option<PathBuf>,
+    skip_checksum: bool,
+    user: Option<String>,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The import command must run as the root user"));
+    }
+
+    report!("=================================================");
+    report!("             Import Safenode Services            ");
+    report!("=================================================");
+
+    let nodes = read_export(&path)?;
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("import")?;
+    let service_control = get_service_control(&node_registry);
+
+    // The directories the services are under belong to the user running the command, and
+    // each node's directories to the user it runs as.
+    let current_user = get_current_username()?;
+    let user = if is_user_mode() {
+        Some(current_user.clone())
+    } else {
+        user
+    };
+    let service_data_dir_path = get_service_data_dir_path(data_dir_path, &current_user)?;
+    let service_log_dir_path = get_service_log_dir_path(log_dir_path, &current_user)?;
+    if !is_user_mode() {
+        let mut service_users = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            // A node with a user of its own gets a new one, for the name it's imported as.
+            let service_user = match &user {
+                Some(user) => user.clone(),
+                None if node.user == node.service_name => {
+                    format!("safenode{}", node_registry.nodes.len() + i + 1)
+                }
+                None => node.user.clone(),
+            };
+            if !service_users.contains(&service_user) {
+                service_control.create_service_user(&service_user)?;
+                service_users.push(service_user);
+            }
+        }
+    }
+
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    let result = import_nodes(
+        &path,
+        nodes,
+        &ImportOptions {
+            service_data_dir_path,
+            service_log_dir_path,
+            skip_checksum,
+            user,
+        },
+        &mut node_registry,
+        &*service_control,
+        &*release_repo,
+    )
+    .await;
+    node_registry.save(registry_lock)?;
+    for (exported_name, service_name) in result? {
+        if exported_name == service_name {
+            report!("{} Imported {service_name}", "✓".green());
+        } else {
+            report!("{} Imported {exported_name} as {service_name}", "✓".green());
+        }
+    }
+    report!("[!] Note: the imported services have not been started");
+    Ok(())
+}
@@ -0,0 +1,187 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+pub mod alerts;
+pub mod auditor;
+pub mod autoscale;
+pub mod chaos;
+pub mod daemon;
+pub mod diagnostics;
+pub mod earnings;
+pub mod local;
+pub mod logs;
+pub mod migration;
+pub mod node;
+pub mod restart_schedule;
+pub mod setup;
+pub mod telemetry;
+
+use crate::control::start_with_timeout;
+use crate::event_log::{write_event, EventLevel};
+use crate::helpers::download_and_extract_release;
+use crate::node_registry::Node;
+use crate::output::report;
+use crate::service::ServiceControl;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use sn_node_rpc_client::RpcClient;
+use sn_releases::{ReleaseType, SafeReleaseRepositoryInterface};
+use std::path::PathBuf;
+
+/// Start a node service and record the outcome in the event log.
+pub(crate) async fn start_node(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    startup_timeout: u64,
+) -> Result<()> {
+    let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+    let service_name = node.service_name.clone();
+    match start_with_timeout(node, service_control, &rpc_client, startup_timeout).await {
+        Ok(()) => {
+            write_event(EventLevel::Information, &format!("Started {service_name}"));
+            Ok(())
+        }
+        Err(e) => {
+            write_event(
+                EventLevel::Error,
+                &format!("Failed to start {service_name}: {e}"),
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Report the outcome of an operation for each of the nodes, in a table, once it has been
+/// attempted for all of them.
+///
+/// The results are those of `Progress::run_concurrently`, for the named services. It's an error if
+/// the operation failed for any of them, after they have all been reported.
+pub(crate) fn check_failures(
+    operation: &str,
+    service_names: Vec<String>,
+    results: Vec<Option<Result<()>>>,
+) -> Result<()> {
+    let mut failed_count = 0;
+    report!("Summary:");
+    report!("{:<18} Result", "Service Name");
+    for (service_name, result) in service_names.into_iter().zip(results) {
+        match result {
+            Some(Ok(())) => report!("{service_name:<18} {}", "✓ succeeded".green()),
+            Some(Err(e)) => {
+                report!("{service_name:<18} {} {e}", "✕ failed:".red());
+                failed_count += 1;
+            }
+            None => report!("{service_name:<18} - not attempted"),
+        }
+    }
+    if failed_count > 0 {
+        return Err(eyre!("Failed to {operation} {failed_count} service(s)"));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn get_current_username() -> Result<String> {
+    users::get_current_username()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| eyre!("Could not obtain the name of the current user"))
+}
+
+#[cfg(windows)]
+pub(crate) fn get_current_username() -> Result<String> {
+    std::env::var("USERNAME").map_err(|_| eyre!("Could not obtain the name of the current user"))
+}
+
+#[cfg(unix)]
+pub(crate) fn is_running_as_root() -> bool {
+    users::get_effective_uid() == 0
+}
+
+/// On Windows, being a member of the Administrators group is not enough, because with UAC the
+/// process only gets an administrative token if it was launched with "Run as administrator". We
+/// therefore check whether the token of the current process is elevated.
+#[cfg(windows)]
+pub(crate) fn is_running_as_root() -> bool {
+    use std::ffi::c_void;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let mut token: HANDLE = 0;
+    // SAFETY: the pseudo handle from `GetCurrentProcess` does not need to be closed, and the
+    // token handle is only used if it was successfully opened.
+    unsafe {
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+        let mut returned_length = 0u32;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut c_void,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned_length,
+        );
+        CloseHandle(token);
+        result != 0 && elevation.TokenIsElevated != 0
+    }
+}
+
+pub(crate) async fn get_bin_path(
+    path_option: Option<PathBuf>,
+    release_type: ReleaseType,
+    version: Option<String>,
+    release_repo: &dyn SafeReleaseRepositoryInterface,
+) -> Result<PathBuf> {
+    if let Some(path) = path_option {
+        Ok(path)
+    } else {
+        let (download_path, _) =
+            download_and_extract_release(release_type, None, version, None, release_repo).await?;
+        Ok(download_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_failures_should_fail_after_reporting_every_node_if_any_failed() {
+        let service_names = vec![
+            "safenode1".to_string(),
+            "safenode2".to_string(),
+            "safenode3".to_string(),
+            "safenode4".to_string(),
+        ];
+        let results = vec![
+            Some(Ok(())),
+            Some(Err(eyre!("The service did not start"))),
+            None,
+            Some(Err(eyre!("The service did not start"))),
+        ];
+
+        let result = check_failures("start", service_names, results);
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to start 2 service(s)"
+        );
+    }
+
+    #[test]
+    fn check_failures_should_succeed_if_no_node_failed() {
+        let service_names = vec!["safenode1".to_string(), "safenode2".to_string()];
+        let results = vec![Some(Ok(())), None];
+
+        assert!(check_failures("stop", service_names, results).is_ok());
+    }
+}
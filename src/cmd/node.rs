@@ -0,0 +1,1546 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::add_service;
+use crate::add_service::{get_genesis_peer, AddServiceOptions, PortRange};
+use crate::cmd::{check_failures, get_current_username, is_running_as_root, start_node};
+use crate::config::*;
+use crate::config_file::ConfigFile;
+use crate::container::ServiceBackend;
+use crate::control;
+use crate::control::{stop_with_grace_period, NodeEdit, UpgradeResult, VerifyResult};
+use crate::dry_run::is_dry_run;
+use crate::earnings::{get_balances, print_balances, EarningsHistory};
+use crate::event_log::{write_event, EventLevel};
+use crate::helpers::{
+    download_and_extract_release, extract_local_release, get_bin_version, get_file_checksum,
+    get_process_exe, get_release_archive_url, get_release_checksum, merge_env_variables,
+    read_rewards_addresses,
+};
+use crate::host::get_host_defaults;
+use crate::node_registry::{
+    get_local_node_registry_path, get_snapshots_dir, Node, NodeRegistry, NodeStatus,
+    RegistrySnapshot,
+};
+use crate::output::{is_json_output, print_nodes_json, report};
+use crate::ports::{check_ports, get_port_entries, is_port_in_use, print_ports};
+use crate::selector::NodeSelector;
+use crate::service::{get_service_control, NodeServiceManager, ServiceControl};
+use crate::setup::ask_yes_no;
+use crate::timeout::{is_timeout, within_timeout, Progress};
+use color_eyre::{eyre::eyre, Help, Result};
+use colored::Colorize;
+use semver::Version;
+use service_manager::ServiceManagerKind;
+use sn_node_rpc_client::RpcClient;
+use sn_peers_acquisition::{get_peers_from_args, PeersArgs};
+use sn_releases::{ReleaseType, SafeReleaseRepositoryInterface};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Add one or more safenode services, with the settings of an existing one if `like` names one.
+///
+/// The settings that aren't given are taken from the configuration file, and the checksum of the
+/// release is looked up before it's downloaded.
+#[allow(clippy::too_many_arguments)]
+pub async fn add(
+    auto_restart: Option<u64>,
+    auto_restart_reset_period: u64,
+    backend: Option<ServiceBackend>,
+    checksum: Option<String>,
+    count: Option<u16>,
+    data_dir_path: Option<PathBuf>,
+    env_variables: Vec<(String, String)>,
+    extra_args: Vec<String>,
+    home_network: bool,
+    interval: u64,
+    image: Option<String>,
+    like: Option<String>,
+    local: bool,
+    log_dir_path: Option<PathBuf>,
+    max_memory: Option<u64>,
+    no_keep_alive: bool,
+    node_ip: Option<IpAddr>,
+    open_firewall: bool,
+    owner: Option<String>,
+    path: Option<PathBuf>,
+    peers: PeersArgs,
+    port: Option<PortRange>,
+    rewards_address: Vec<String>,
+    rewards_address_file: Option<PathBuf>,
+    rpc_address: Option<IpAddr>,
+    rpc_port: Option<PortRange>,
+    skip_checksum: bool,
+    throttle_interval: Option<u64>,
+    upnp: bool,
+    url: Option<String>,
+    user: Option<String>,
+    user_per_node: bool,
+    version: Option<String>,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The add command must run as the root user"));
+    }
+    // The ports from the configuration files are only used along with its count, since
+    // they would likely not fit the count given on the command line.
+    let config = ConfigFile::load(&get_config_file_paths())?.add;
+    let (count, port, rpc_port) = if count.is_none() && port.is_none() && rpc_port.is_none() {
+        (config.count, config.port, config.rpc_port)
+    } else {
+        (count, port, rpc_port)
+    };
+
+    if url.is_some() && version.is_some() {
+        return Err(
+            eyre!("The url and version arguments are mutually exclusive")
+                .suggestion("Please try again specifying either url or version, but not both."),
+        );
+    }
+    if open_firewall && !cfg!(windows) {
+        return Err(eyre!("The open-firewall argument only applies on Windows"));
+    }
+    if auto_restart.is_some() && !cfg!(windows) {
+        return Err(eyre!("The auto-restart argument only applies on Windows"));
+    }
+    if max_memory.is_some() && !cfg!(target_os = "linux") {
+        return Err(eyre!("The max-memory argument only applies on Linux"));
+    }
+    if matches!(
+        backend,
+        Some(ServiceBackend::Docker) | Some(ServiceBackend::Podman)
+    ) && !cfg!(target_os = "linux")
+    {
+        return Err(eyre!("Containers are only supported on Linux"));
+    }
+    if backend == Some(ServiceBackend::SystemdTemplate)
+        && !matches!(
+            ServiceManagerKind::native(),
+            Ok(ServiceManagerKind::Systemd)
+        )
+    {
+        return Err(eyre!("The systemd-template backend requires systemd"));
+    }
+    if image.is_some()
+        && matches!(
+            backend,
+            Some(ServiceBackend::Native) | Some(ServiceBackend::SystemdTemplate)
+        )
+    {
+        return Err(eyre!("The image argument only applies to containers"));
+    }
+
+    report!("=================================================");
+    report!("              Add Safenode Services              ");
+    report!("=================================================");
+    let service_count = count
+        .or(port.map(|p| p.len()))
+        .or(rpc_port.map(|p| p.len()))
+        .unwrap_or(1);
+    report!("{service_count} service(s) to be added");
+
+    let host_defaults = get_host_defaults();
+    if host_defaults.is_constrained() {
+        report!(
+            "Low-memory ARM host detected: nodes will use reduced logging and a {}MB \
+             memory limit",
+            max_memory.or(host_defaults.max_memory).unwrap_or_default()
+        );
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("add")?;
+    if let Some(backend) = backend {
+        let container = backend.get_container(image);
+        let systemd_template = backend == ServiceBackend::SystemdTemplate;
+        if (container != node_registry.container
+            || systemd_template != node_registry.systemd_template)
+            && node_registry
+                .nodes
+                .iter()
+                .any(|n| n.status != NodeStatus::Removed)
+        {
+            return Err(
+                eyre!("The existing services use a different backend or image")
+                    .suggestion("All the services in the registry must use the same backend"),
+            );
+        }
+        node_registry.container = container;
+        node_registry.systemd_template = systemd_template;
+    }
+    let like_node = match like {
+        Some(ref name) => Some(
+            node_registry
+                .nodes
+                .iter()
+                .find(|n| n.service_name == *name)
+                .cloned()
+                .ok_or_else(|| eyre!("No service named '{name}'"))?,
+        ),
+        None => None,
+    };
+
+    let mut rewards_addresses = rewards_address;
+    if let Some(path) = rewards_address_file {
+        rewards_addresses.extend(read_rewards_addresses(&path)?);
+    }
+
+    // Nodes that were added with a user of their own are run as the user they're named
+    // after, and one added like them should be too.
+    let user_per_node = user_per_node
+        || (user.is_none() && like_node.as_ref().is_some_and(|n| n.user == n.service_name));
+    if user_per_node && is_user_mode() {
+        return Err(eyre!(
+            "The services can't have users of their own when they run as the current user"
+        ));
+    }
+
+    let service_manager = get_service_control(&node_registry);
+    // With a user for each service, the directories they're under belong to the user
+    // running the command.
+    let service_user = if is_user_mode() || user_per_node {
+        get_current_username()?
+    } else {
+        let service_user = user
+            .or(like_node.as_ref().map(|n| n.user.clone()))
+            .unwrap_or("safe".to_string());
+        service_manager.create_service_user(&service_user)?;
+        service_user
+    };
+
+    // The data and log directories of a node are named after its service, underneath the
+    // prefix it was added with.
+    let like_dir_prefix = |path: &Option<PathBuf>| {
+        path.as_ref()
+            .and_then(|p| p.parent())
+            .map(|p| p.to_path_buf())
+    };
+    let data_dir_path = data_dir_path
+        .or(like_node
+            .as_ref()
+            .and_then(|n| like_dir_prefix(&n.data_dir_path)))
+        .or(config.data_dir_path.clone());
+    let log_dir_path = log_dir_path
+        .or(like_node
+            .as_ref()
+            .and_then(|n| like_dir_prefix(&n.log_dir_path)))
+        .or(config.log_dir_path.clone());
+    let service_data_dir_path = get_service_data_dir_path(data_dir_path, &service_user)?;
+    let service_log_dir_path = get_service_log_dir_path(log_dir_path, &service_user)?;
+
+    let mut peers = peers;
+    if !peers.first && peers.peers.is_empty() && like_node.is_none() {
+        peers.peers = config.peers.clone();
+    }
+    let genesis = peers.first;
+    // On a new network, the nodes bootstrap from the genesis node if no peers are given.
+    let mut peers = if genesis || !peers.peers.is_empty() {
+        get_peers_from_args(peers).await?
+    } else {
+        match get_genesis_peer(&node_registry)? {
+            Some(genesis_peer) => {
+                report!("Using the genesis node at {genesis_peer} as the peer");
+                vec![genesis_peer]
+            }
+            None => get_peers_from_args(peers).await?,
+        }
+    };
+    let mut auto_restart = auto_restart;
+    let mut auto_restart_reset_period = auto_restart_reset_period;
+    let mut env_variables = env_variables;
+    let mut extra_args = extra_args;
+    let mut home_network = home_network;
+    let mut keep_alive = !no_keep_alive;
+    let mut local = local;
+    let mut log_targets = host_defaults.log_targets;
+    let mut max_memory = max_memory;
+    let mut node_ip = node_ip;
+    let mut open_firewall = open_firewall;
+    let mut owner = owner;
+    let mut rpc_address = rpc_address;
+    let mut throttle_interval = throttle_interval;
+    let mut upnp = upnp;
+    let mut version = version;
+    if let Some(like_node) = like_node {
+        report!("Using the settings of {}", like_node.service_name);
+        if auto_restart.is_none() {
+            auto_restart = like_node.auto_restart;
+            auto_restart_reset_period = like_node.auto_restart_reset_period;
+        }
+        // The variables that were given take precedence over those of the existing node.
+        let mut like_env_variables = like_node.env_variables;
+        merge_env_variables(&mut like_env_variables, env_variables);
+        env_variables = like_env_variables;
+        if extra_args.is_empty() {
+            extra_args = like_node.extra_args;
+        }
+        home_network = home_network || like_node.home_network;
+        keep_alive = keep_alive && like_node.keep_alive;
+        local = local || like_node.local;
+        log_targets = like_node.log_targets.or(log_targets);
+        max_memory = max_memory.or(like_node.max_memory);
+        node_ip = node_ip.or(like_node.node_ip);
+        open_firewall = open_firewall || like_node.firewall_rule_added;
+        owner = owner.or(like_node.owner);
+        if peers.is_empty() {
+            peers = like_node.peers;
+        }
+        if rewards_addresses.is_empty() {
+            rewards_addresses.extend(like_node.rewards_address);
+        }
+        rpc_address = rpc_address.or(like_node.rpc_address);
+        throttle_interval = throttle_interval.or(like_node.throttle_interval);
+        upnp = upnp || like_node.upnp;
+        if path.is_none() && url.is_none() && version.is_none() {
+            version = Some(like_node.version);
+        }
+    }
+    let env_variables = config.get_env_variables(env_variables);
+    if path.is_none() && url.is_none() && version.is_none() {
+        version = config.version;
+    }
+
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    // The checksum is looked up before anything is downloaded, so the download can be
+    // checked against it.
+    let mut checksum = checksum;
+    if checksum.is_none() && path.is_none() {
+        let archive_url = match &url {
+            Some(url) => url.clone(),
+            None => {
+                if version.is_none() {
+                    report!("Retrieving latest version for safenode...");
+                    version = Some(
+                        release_repo
+                            .get_latest_version(&ReleaseType::Safenode)
+                            .await?,
+                    );
+                }
+                get_release_archive_url(
+                    &ReleaseType::Safenode,
+                    version.as_deref().unwrap_or_default(),
+                )?
+            }
+        };
+        checksum = get_release_checksum(&archive_url, skip_checksum).await?;
+    }
+    let previous_node_count = node_registry.nodes.len();
+    if let Some(recommended_max_count) = host_defaults.recommended_max_count {
+        if previous_node_count + service_count as usize > recommended_max_count as usize {
+            report!(
+                "[!] Warning: this host is only recommended to run \
+                 {recommended_max_count} node(s)"
+            );
+        }
+    }
+
+    let result = add_service::add(
+        AddServiceOptions {
+            auto_restart,
+            auto_restart_reset_period,
+            checksum,
+            local,
+            log_targets,
+            max_memory: max_memory.or(host_defaults.max_memory),
+            node_ip,
+            genesis,
+            home_network,
+            interval,
+            count,
+            env_variables,
+            extra_args,
+            keep_alive,
+            open_firewall,
+            owner,
+            path,
+            peers,
+            port,
+            rewards_addresses,
+            rpc_address,
+            rpc_port,
+            safenode_dir_path: service_data_dir_path.clone(),
+            service_data_dir_path,
+            service_log_dir_path,
+            throttle_interval,
+            upnp,
+            url,
+            user: service_user,
+            user_per_node,
+            version,
+        },
+        &mut node_registry,
+        &*service_manager,
+        release_repo,
+    )
+    .await;
+    match result {
+        Ok(()) => write_event(
+            EventLevel::Information,
+            &format!(
+                "Added {}",
+                node_registry.nodes[previous_node_count..]
+                    .iter()
+                    .map(|n| n.service_name.clone())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        ),
+        Err(e) => {
+            write_event(EventLevel::Error, &format!("Failed to add services: {e}"));
+            // The services added before the timeout are kept.
+            if is_timeout(&e) {
+                node_registry.save(registry_lock)?;
+            }
+            return Err(e);
+        }
+    }
+
+    node_registry.save(registry_lock)?;
+    if is_json_output() {
+        let service_names = node_registry.nodes[previous_node_count..]
+            .iter()
+            .map(|n| n.service_name.clone())
+            .collect::<Vec<String>>();
+        print_nodes_json(&node_registry, &service_names)?;
+    }
+
+    Ok(())
+}
+
+/// Print the balance of the wallet of each of the selected services, and the total.
+pub fn balance(selector: NodeSelector) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The balance command must run as the root user"));
+    }
+
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let report = match selector.select(&node_registry)? {
+        Some(indexes) => get_balances(indexes.into_iter().map(|i| &node_registry.nodes[i]))?,
+        None => get_balances(node_registry.nodes.iter())?,
+    };
+    if is_json_output() {
+        println!("{}", serde_json::to_string(&report)?);
+    } else {
+        print_balances(&report);
+    }
+    Ok(())
+}
+
+/// Change the settings of the selected services and reinstall them with the new ones.
+#[allow(clippy::too_many_arguments)]
+pub async fn edit(
+    auto_restart: Option<u64>,
+    auto_restart_reset_period: Option<u64>,
+    env_variables: Vec<(String, String)>,
+    keep_alive: Option<bool>,
+    log_targets: Option<String>,
+    max_memory: Option<u64>,
+    port: Option<u16>,
+    rpc_address: Option<IpAddr>,
+    rpc_port: Option<u16>,
+    selector: NodeSelector,
+    throttle_interval: Option<u64>,
+    unset_env_variables: Vec<String>,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The node command must run as the root user"));
+    }
+
+    println!("=================================================");
+    println!("              Edit Safenode Service              ");
+    println!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("node edit")?;
+    let service_control = get_service_control(&node_registry);
+    let nodes = selector.select_mut(&mut node_registry)?.ok_or_else(|| {
+        eyre!("A service must be specified to edit")
+            .suggestion("Use either the --peer-id or --service-name argument")
+    })?;
+    if nodes.len() > 1 && (port.is_some() || rpc_port.is_some()) {
+        return Err(eyre!(
+            "The ports can only be changed for one service at a time"
+        ));
+    }
+
+    for node in nodes {
+        let rpc_client = RpcClient::new(
+            &Node {
+                rpc_address: rpc_address.or(node.rpc_address),
+                rpc_port: rpc_port.unwrap_or(node.rpc_port),
+                ..node.clone()
+            }
+            .get_rpc_endpoint(),
+        );
+        control::edit(
+            node,
+            NodeEdit {
+                auto_restart,
+                auto_restart_reset_period,
+                env_variables: env_variables.clone(),
+                keep_alive,
+                log_targets: log_targets.clone(),
+                max_memory,
+                port,
+                rpc_address,
+                rpc_port,
+                throttle_interval,
+                unset_env_variables: unset_env_variables.clone(),
+            },
+            &*service_control,
+            &rpc_client,
+        )
+        .await?;
+    }
+
+    node_registry.save(registry_lock)?;
+
+    Ok(())
+}
+
+/// Put the selected services in maintenance mode, or take them out of it.
+///
+/// Without a selection, this applies to every service.
+pub fn maintenance(enable: bool, selector: NodeSelector) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The maintenance command must run as the root user"));
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("maintenance")?;
+    let nodes = match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => nodes,
+        None => node_registry
+            .nodes
+            .iter_mut()
+            .filter(|n| n.status != NodeStatus::Removed)
+            .collect::<Vec<&mut Node>>(),
+    };
+
+    for node in nodes {
+        node.maintenance = enable;
+        if enable {
+            println!(
+                "{} {} is in maintenance mode",
+                "✓".green(),
+                node.service_name
+            );
+        } else {
+            println!(
+                "{} {} is out of maintenance mode",
+                "✓".green(),
+                node.service_name
+            );
+        }
+    }
+
+    node_registry.save(registry_lock)?;
+
+    Ok(())
+}
+
+/// Print the paths the node manager uses on this machine, and those of each service.
+pub fn paths() -> Result<()> {
+    let node_registry_path = get_node_registry_path()?;
+    println!("Node registry: {}", node_registry_path.to_string_lossy());
+    println!(
+        "Registry snapshots: {}",
+        get_snapshots_dir(&node_registry_path).to_string_lossy()
+    );
+    println!(
+        "Earnings history: {}",
+        get_earnings_history_path()?.to_string_lossy()
+    );
+    println!(
+        "Service data: {}",
+        get_default_service_data_dir_path()?.to_string_lossy()
+    );
+    println!(
+        "Service logs: {}",
+        get_default_service_log_dir_path()?.to_string_lossy()
+    );
+    println!(
+        "Local network registry: {}",
+        get_local_node_registry_path()?.to_string_lossy()
+    );
+    println!("Downloads: {}", std::env::temp_dir().to_string_lossy());
+
+    let node_registry = NodeRegistry::load(&node_registry_path)?;
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        println!();
+        println!("{}:", node.service_name);
+        for (name, path) in [
+            ("Data", &node.data_dir_path),
+            ("Logs", &node.log_dir_path),
+            ("Binary", &node.safenode_path),
+        ] {
+            println!(
+                "  {name}: {}",
+                path.as_ref()
+                    .map_or("-".to_string(), |p| p.to_string_lossy().to_string())
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Print every port allocated to the services, and whether anything is bound to it.
+pub fn ports() -> Result<()> {
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let mut entries = get_port_entries(&node_registry);
+    check_ports(&mut entries, is_port_in_use);
+    if is_json_output() {
+        println!("{}", serde_json::to_string(&entries)?);
+    } else {
+        print_ports(&entries);
+    }
+    Ok(())
+}
+
+/// Remove the selected services, or every service with `all`.
+pub async fn remove(all: bool, selector: NodeSelector, keep_directories: bool) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The remove command must run as the root user"));
+    }
+    if selector.is_empty() && !all {
+        return Err(eyre!("Either a peer ID or a service name must be supplied")
+            .suggestion("Use the --all flag to remove every service"));
+    }
+
+    report!("=================================================");
+    report!("           Remove Safenode Services              ");
+    report!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("remove")?;
+    let service_control = get_service_control(&node_registry);
+    let mut progress = Progress::default();
+    let nodes = match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => nodes,
+        None => node_registry
+            .nodes
+            .iter_mut()
+            .filter(|n| n.status != NodeStatus::Removed)
+            .collect::<Vec<&mut Node>>(),
+    };
+    for node in nodes {
+        let service_name = node.service_name.clone();
+        progress
+            .run(
+                &service_name,
+                control::remove(node, &*service_control, keep_directories),
+            )
+            .await
+            .transpose()?;
+    }
+
+    node_registry.save(registry_lock)?;
+
+    if is_json_output() {
+        print_nodes_json(&node_registry, progress.completed())?;
+    }
+    progress.finish()
+}
+
+/// Rename a service, along with its directories and its entries in the earnings history.
+pub async fn rename(service_name: String, to: String) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The node command must run as the root user"));
+    }
+
+    println!("=================================================");
+    println!("             Rename Safenode Service             ");
+    println!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("node rename")?;
+    let service_control = get_service_control(&node_registry);
+    if node_registry.nodes.iter().any(|n| n.service_name == to) {
+        return Err(eyre!("A service named '{to}' already exists"));
+    }
+    let node = node_registry
+        .nodes
+        .iter_mut()
+        .find(|x| x.service_name == service_name)
+        .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+
+    let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+    control::rename(node, &to, &*service_control, &rpc_client).await?;
+    node_registry.save(registry_lock)?;
+
+    let mut earnings_history = EarningsHistory::load(&get_earnings_history_path()?)?;
+    earnings_history.rename_service(&service_name, &to);
+    earnings_history.save()?;
+
+    Ok(())
+}
+
+/// Remove every service and delete their data and log directories.
+///
+/// Unless `force` is used, this asks for confirmation first.
+pub async fn reset(force: bool) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The reset command must run as the root user"));
+    }
+    if !force
+        && !is_dry_run()
+        && !ask_yes_no(
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout(),
+            "This will remove every service and delete all node data. Continue?",
+            false,
+        )?
+    {
+        return Err(eyre!("The reset was cancelled"));
+    }
+
+    report!("=================================================");
+    report!("             Reset Safenode Services             ");
+    report!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("reset")?;
+    let service_control = get_service_control(&node_registry);
+    let result = control::reset(&mut node_registry, &*service_control).await;
+    node_registry.save(registry_lock)?;
+    result?;
+
+    if !is_dry_run() {
+        report!("{} All services were removed", "✓".green());
+    }
+    Ok(())
+}
+
+/// Restart the selected services, or every service that isn't in maintenance mode.
+///
+/// The restarts are all attempted, and it's an error if any of them failed.
+pub async fn restart(
+    retain_peer_id: bool,
+    selector: NodeSelector,
+    startup_timeout: u64,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The restart command must run as the root user"));
+    }
+
+    report!("=================================================");
+    report!("            Restart Safenode Services            ");
+    report!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let service_control = get_service_control(&node_registry);
+    let nodes = match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => nodes,
+        None => node_registry
+            .nodes
+            .iter_mut()
+            .filter(|n| n.status != NodeStatus::Removed && !n.maintenance)
+            .collect::<Vec<&mut Node>>(),
+    };
+    let mut service_names = Vec::new();
+    let mut results = Vec::new();
+    let mut progress = Progress::default();
+    for node in nodes {
+        let service_name = node.service_name.clone();
+        let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+        let result = progress
+            .run(
+                &service_name,
+                control::restart(
+                    node,
+                    &*service_control,
+                    &rpc_client,
+                    retain_peer_id,
+                    startup_timeout,
+                ),
+            )
+            .await;
+        match &result {
+            Some(Ok(())) => {
+                write_event(
+                    EventLevel::Information,
+                    &format!("Restarted {service_name}"),
+                );
+            }
+            Some(Err(e)) => {
+                write_event(
+                    EventLevel::Error,
+                    &format!("Failed to restart {service_name}: {e}"),
+                );
+            }
+            None => {}
+        }
+        NodeRegistry::save_node(&registry_lock, node)?;
+        service_names.push(service_name);
+        results.push(result);
+    }
+
+    node_registry.save_nodes(registry_lock)?;
+
+    if is_json_output() {
+        print_nodes_json(&node_registry, progress.completed())?;
+    }
+    check_failures("restart", service_names, results)?;
+    progress.finish()
+}
+
+/// Roll the selected services back to the versions they ran before they were last upgraded.
+pub async fn rollback(selector: NodeSelector) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The rollback command must run as the root user"));
+    }
+
+    report!("=================================================");
+    report!("           Roll Back Safenode Services           ");
+    report!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("rollback")?;
+    let service_control = get_service_control(&node_registry);
+    let nodes = match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => nodes,
+        None => node_registry
+            .nodes
+            .iter_mut()
+            .filter(|n| n.status != NodeStatus::Removed && n.previous_version.is_some())
+            .collect::<Vec<&mut Node>>(),
+    };
+
+    let mut rollback_summary = Vec::new();
+    let mut progress = Progress::default();
+    for node in nodes {
+        let service_name = node.service_name.clone();
+        let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+        if let Some(result) = progress
+            .run(
+                &service_name,
+                control::rollback(node, &*service_control, &rpc_client),
+            )
+            .await
+        {
+            rollback_summary.push((service_name, result));
+        }
+    }
+
+    node_registry.save(registry_lock)?;
+
+    report!("Rollback summary:");
+    for (service_name, result) in rollback_summary {
+        match result {
+            Ok((from_version, to_version)) => {
+                report!(
+                    "{} {service_name} rolled back from {from_version} to {to_version}",
+                    "✓".green()
+                );
+            }
+            Err(e) => {
+                report!("{} {service_name} was not rolled back: {e}", "✕".red());
+            }
+        }
+    }
+
+    if is_json_output() {
+        print_nodes_json(&node_registry, progress.completed())?;
+    }
+    progress.finish()
+}
+
+/// Set the owner of the selected services, or of every service without a selection.
+pub async fn set_owner(owner: String, selector: NodeSelector) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The node command must run as the root user"));
+    }
+
+    println!("=================================================");
+    println!("               Set Safenode Owner                ");
+    println!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("node set-owner")?;
+    let service_control = get_service_control(&node_registry);
+    match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => {
+            for node in nodes {
+                let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+                control::set_owner(node, &owner, &*service_control, &rpc_client).await?;
+            }
+        }
+        None => {
+            for node in node_registry
+                .nodes
+                .iter_mut()
+                .filter(|n| n.status != NodeStatus::Removed)
+            {
+                let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+                control::set_owner(node, &owner, &*service_control, &rpc_client).await?;
+            }
+        }
+    }
+
+    node_registry.save(registry_lock)?;
+
+    Ok(())
+}
+
+/// Set the rewards address of the selected services, or of every service without a selection.
+pub async fn set_rewards_address(address: String, selector: NodeSelector) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The node command must run as the root user"));
+    }
+
+    println!("=================================================");
+    println!("          Set Safenode Rewards Address           ");
+    println!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("node set-rewards-address")?;
+    let service_control = get_service_control(&node_registry);
+    match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => {
+            for node in nodes {
+                let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+                control::set_rewards_address(node, &address, &*service_control, &rpc_client)
+                    .await?;
+            }
+        }
+        None => {
+            for node in node_registry
+                .nodes
+                .iter_mut()
+                .filter(|n| n.status != NodeStatus::Removed)
+            {
+                let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+                control::set_rewards_address(node, &address, &*service_control, &rpc_client)
+                    .await?;
+            }
+        }
+    }
+
+    node_registry.save(registry_lock)?;
+
+    Ok(())
+}
+
+/// Start the selected services, or every service, up to `concurrency` of them at once.
+///
+/// The starts are all attempted, and it's an error if any of them failed.
+pub async fn start(
+    concurrency: u16,
+    interval: u64,
+    selector: NodeSelector,
+    startup_timeout: u64,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The start command must run as the root user"));
+    }
+
+    report!("=================================================");
+    report!("             Start Safenode Services             ");
+    report!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let service_control = get_service_control(&node_registry);
+    let service_control = &*service_control;
+    let lock = &registry_lock;
+    let nodes = match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => nodes,
+        None => node_registry.nodes.iter_mut().collect::<Vec<&mut Node>>(),
+    };
+    let mut started_any = false;
+    let steps = nodes
+        .into_iter()
+        .map(|node| {
+            let service_name = node.service_name.clone();
+            let needs_start =
+                node.status != NodeStatus::Running && node.status != NodeStatus::Removed;
+            let wait_first = interval > 0 && started_any && needs_start;
+            started_any = started_any || needs_start;
+            let step = move || async move {
+                if wait_first {
+                    report!("Waiting for {interval} milliseconds...");
+                    tokio::time::sleep(Duration::from_millis(interval)).await;
+                }
+                let result = start_node(node, service_control, startup_timeout).await;
+                NodeRegistry::save_node(lock, node)?;
+                result
+            };
+            (service_name, step)
+        })
+        .collect::<Vec<_>>();
+    let service_names = steps
+        .iter()
+        .map(|(service_name, _)| service_name.clone())
+        .collect::<Vec<String>>();
+    let mut progress = Progress::default();
+    let results = progress
+        .run_concurrently(steps, usize::from(concurrency))
+        .await;
+
+    // The nodes were saved as they were done, and this saves anything else the
+    // operation changed about them, without overwriting the rest of the registry.
+    node_registry.save_nodes(registry_lock)?;
+
+    if is_json_output() {
+        print_nodes_json(&node_registry, progress.completed())?;
+    }
+    check_failures("start", service_names, results)?;
+    progress.finish()
+}
+
+/// Print the status of the services, the auditor and the local network.
+///
+/// The statuses are refreshed and saved to the registry, unless another command is changing it.
+pub async fn status(details: bool, fail: bool, health_period: Duration) -> Result<()> {
+    let json = is_json_output();
+    // The refreshed statuses are saved if no other command is changing the registry, but
+    // the status is never held up waiting for one, e.g., for a long upgrade to finish.
+    let node_registry_path = get_node_registry_path()?;
+    let (mut node_registry, registry_lock) =
+        match NodeRegistry::try_load_locked(&node_registry_path)? {
+            Some((node_registry, registry_lock)) => (node_registry, Some(registry_lock)),
+            None => (NodeRegistry::load(&node_registry_path)?, None),
+        };
+    let service_control = get_service_control(&node_registry);
+    if !node_registry.nodes.is_empty() {
+        let earnings_history = EarningsHistory::load(&get_earnings_history_path()?)?;
+        if !json {
+            println!("=================================================");
+            println!("                Safenode Services                ");
+            println!("=================================================");
+        }
+        control::status(
+            &mut node_registry,
+            &*service_control,
+            details,
+            json,
+            fail,
+            Some(&earnings_history),
+            health_period,
+        )
+        .await?;
+        if let Some(registry_lock) = registry_lock {
+            node_registry.save(registry_lock)?;
+        }
+    }
+    if let Some(auditor) = node_registry
+        .auditor
+        .as_ref()
+        .filter(|auditor| auditor.status != NodeStatus::Removed)
+    {
+        if !json {
+            let auditor_status = match auditor.pid {
+                Some(pid) if service_control.is_service_process_running(pid) => {
+                    format!("{} (PID {pid})", "RUNNING".green())
+                }
+                _ => "STOPPED".red().to_string(),
+            };
+            println!("Auditor: {auditor_status} (version {})", auditor.version);
+        }
+    }
+
+    let (mut local_node_registry, local_registry_lock) =
+        NodeRegistry::load_locked(&get_local_node_registry_path()?)?;
+    if !local_node_registry.nodes.is_empty() || local_node_registry.faucet_pid.is_some() {
+        if !json {
+            println!("=================================================");
+            println!("                Local Network                    ");
+            println!("=================================================");
+        }
+        if !local_node_registry.nodes.is_empty() {
+            control::status(
+                &mut local_node_registry,
+                &NodeServiceManager {},
+                details,
+                json,
+                fail,
+                None,
+                health_period,
+            )
+            .await?;
+        }
+        if let Some(pid) = local_node_registry.faucet_pid {
+            if !json {
+                let service_control = NodeServiceManager {};
+                let faucet_status = if service_control.is_service_process_running(pid) {
+                    "RUNNING".green()
+                } else {
+                    "STOPPED".red()
+                };
+                println!("Faucet: {faucet_status} (PID {pid})");
+            }
+        }
+        local_node_registry.save(local_registry_lock)?;
+    }
+
+    Ok(())
+}
+
+/// Stop the selected services, or every service, up to `concurrency` of them at once.
+///
+/// The stops are all attempted, and it's an error if any of them failed.
+pub async fn stop(
+    concurrency: u16,
+    grace_period: u64,
+    interval: u64,
+    selector: NodeSelector,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The stop command must run as the root user"));
+    }
+
+    report!("=================================================");
+    report!("              Stop Safenode Services             ");
+    report!("=================================================");
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let service_control = get_service_control(&node_registry);
+    let service_control = &*service_control;
+    let lock = &registry_lock;
+    // The interval only applies between the running services, when stopping all of them.
+    let (nodes, interval) = match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => (nodes, 0),
+        None => (
+            node_registry.nodes.iter_mut().collect::<Vec<&mut Node>>(),
+            interval,
+        ),
+    };
+    let mut stopped_any = false;
+    let steps = nodes
+        .into_iter()
+        .map(|node| {
+            let service_name = node.service_name.clone();
+            let wait_first = interval > 0 && stopped_any;
+            if node.status == NodeStatus::Running {
+                stopped_any = true;
+            }
+            let step = move || async move {
+                if wait_first && node.status == NodeStatus::Running {
+                    report!("Waiting for {interval} milliseconds...");
+                    tokio::time::sleep(Duration::from_millis(interval)).await;
+                }
+                let result = stop_with_grace_period(node, service_control, grace_period).await;
+                NodeRegistry::save_node(lock, node)?;
+                result
+            };
+            (service_name, step)
+        })
+        .collect::<Vec<_>>();
+    let service_names = steps
+        .iter()
+        .map(|(service_name, _)| service_name.clone())
+        .collect::<Vec<String>>();
+    let mut progress = Progress::default();
+    let results = progress
+        .run_concurrently(steps, usize::from(concurrency))
+        .await;
+
+    node_registry.save_nodes(registry_lock)?;
+
+    if is_json_output() {
+        print_nodes_json(&node_registry, progress.completed())?;
+    }
+    check_failures("stop", service_names, results)?;
+    progress.finish()
+}
+
+/// Undo the most recent operation that changed the services, from its snapshot of the registry.
+pub fn undo() -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The undo command must run as the root user"));
+    }
+
+    let node_registry_path = get_node_registry_path()?;
+    let (snapshot_path, mut snapshot) = RegistrySnapshot::load_latest(&node_registry_path)?
+        .ok_or_else(|| eyre!("There is no operation to undo"))?;
+    if snapshot.undone {
+        return Err(eyre!(
+            "The most recent operation, {}, has already been undone",
+            snapshot.operation
+        )
+        .suggestion("Only the most recent operation can be undone"));
+    }
+
+    println!(
+        "Undoing {} from {}...",
+        snapshot.operation,
+        snapshot.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    let (node_registry, registry_lock) = NodeRegistry::load_locked(&node_registry_path)?;
+    let service_control = get_service_control(&node_registry);
+    let restored = control::undo(&node_registry, &snapshot.registry, &*service_control)?;
+    restored.save(registry_lock)?;
+
+    snapshot.undone = true;
+    std::fs::write(snapshot_path, serde_json::to_string(&snapshot)?)?;
+    println!("{} Undid {}", "✓".green(), snapshot.operation);
+
+    Ok(())
+}
+
+/// Upgrade the selected services to a version, or to the latest one.
+///
+/// Without a selection, every service that isn't in maintenance mode is upgraded.
+pub async fn upgrade(
+    checksum: Option<String>,
+    force: bool,
+    interval: u64,
+    path: Option<PathBuf>,
+    selector: NodeSelector,
+    skip_checksum: bool,
+    version: Option<String>,
+) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The upgrade command must run as the root user"));
+    }
+    if force && path.is_none() && version.is_none() {
+        return Err(eyre!("The force argument requires a version or a path"));
+    }
+
+    report!("=================================================");
+    report!("           Upgrade Safenode Services             ");
+    report!("=================================================");
+
+    let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+    let local_release = path
+        .map(|path| extract_local_release(&path, checksum.as_deref(), &*release_repo))
+        .transpose()?;
+    let target_version = match (&local_release, version) {
+        (Some((_, version)), _) => Version::parse(version)?,
+        (None, Some(version)) => Version::parse(&version)?,
+        (None, None) => {
+            report!("Retrieving latest version of safenode...");
+            let latest_version = release_repo
+                .get_latest_version(&ReleaseType::Safenode)
+                .await
+                .map(|v| Version::parse(&v).unwrap())?;
+            report!("Latest version is {latest_version}");
+            latest_version
+        }
+    };
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("upgrade")?;
+    let service_control = get_service_control(&node_registry);
+    let needs_upgrade = |node: &Node| {
+        let current_version = Version::parse(&node.version).unwrap();
+        current_version < target_version || (force && current_version > target_version)
+    };
+    let any_nodes_need_upgraded = node_registry.nodes.iter().any(needs_upgrade);
+
+    if !any_nodes_need_upgraded {
+        report!("{} All nodes are at version {target_version}", "✓".green());
+        if is_json_output() {
+            print_nodes_json(&node_registry, &[])?;
+        }
+        return Ok(());
+    }
+
+    let safenode_download_path = match local_release {
+        Some((path, _)) => path,
+        None if is_dry_run() => {
+            report!("Would download version {target_version} of safenode");
+            PathBuf::new()
+        }
+        None => {
+            let checksum = match checksum {
+                Some(checksum) => Some(checksum),
+                None => {
+                    get_release_checksum(
+                        &get_release_archive_url(
+                            &ReleaseType::Safenode,
+                            &target_version.to_string(),
+                        )?,
+                        skip_checksum,
+                    )
+                    .await?
+                }
+            };
+            within_timeout(download_and_extract_release(
+                ReleaseType::Safenode,
+                None,
+                Some(target_version.to_string()),
+                checksum,
+                &*release_repo,
+            ))
+            .await??
+            .0
+        }
+    };
+
+    // Without a selection, every node is upgraded, apart from those in maintenance mode.
+    let (indexes, skip_maintenance) = match selector.select(&node_registry)? {
+        Some(indexes) => (indexes, false),
+        None => ((0..node_registry.nodes.len()).collect::<Vec<usize>>(), true),
+    };
+    let mut upgrade_summary = Vec::new();
+    let mut progress = Progress::with_total(indexes.len());
+    let mut upgraded_any = false;
+    for index in indexes {
+        let node = &mut node_registry.nodes[index];
+        if skip_maintenance && node.maintenance {
+            upgrade_summary.push((
+                node.service_name.clone(),
+                UpgradeResult::Skipped("it is in maintenance mode".to_string()),
+            ));
+            continue;
+        }
+        if upgraded_any && interval > 0 && needs_upgrade(node) {
+            report!("Waiting for {interval} milliseconds...");
+            tokio::time::sleep(Duration::from_millis(interval)).await;
+        }
+        let service_name = node.service_name.clone();
+        let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+        let result = progress
+            .run(
+                &service_name,
+                control::upgrade(
+                    node,
+                    &safenode_download_path,
+                    &target_version,
+                    &*service_control,
+                    &rpc_client,
+                    force,
+                ),
+            )
+            .await;
+
+        match result {
+            Some(Ok(upgrade_result)) => {
+                if let UpgradeResult::Upgraded(_, _) = upgrade_result {
+                    upgraded_any = true;
+                }
+                upgrade_summary.push((service_name, upgrade_result));
+            }
+            Some(Err(e)) => {
+                upgrade_summary.push((service_name, UpgradeResult::Error(format!("Error: {}", e))));
+            }
+            None => {}
+        }
+        // Each node is recorded as it's upgraded, so the registry isn't left behind the
+        // services if the command is interrupted.
+        node_registry.save_locked(&registry_lock)?;
+    }
+
+    node_registry.save(registry_lock)?;
+
+    if is_dry_run() {
+        report!("Upgrade summary, for a dry run:");
+    } else {
+        report!("Upgrade summary:");
+    }
+    let mut failed_count = 0;
+    for (service_name, upgrade_result) in upgrade_summary {
+        match upgrade_result {
+            UpgradeResult::NotRequired => {
+                report!("- {service_name} was already at version {target_version}");
+            }
+            UpgradeResult::Skipped(reason) => {
+                report!("- {service_name} was skipped because {reason}");
+            }
+            UpgradeResult::Upgraded(previous_version, new_version) => {
+                report!(
+                    "{} {service_name} upgraded from {previous_version} to {new_version}",
+                    "✓".green()
+                );
+            }
+            UpgradeResult::Error(msg) => {
+                report!("{} {service_name} was not upgraded: {}", "✕".red(), msg);
+                failed_count += 1;
+            }
+        }
+    }
+
+    if is_json_output() {
+        print_nodes_json(&node_registry, progress.completed())?;
+    }
+    if failed_count > 0 {
+        return Err(eyre!("Failed to upgrade {failed_count} service(s)"));
+    }
+    progress.finish()
+}
+
+/// Check the binaries of the selected services against the checksums recorded for them, or with
+/// `release`, against those of their releases.
+pub async fn verify(release: bool, selector: NodeSelector) -> Result<()> {
+    println!("=================================================");
+    println!("             Verify Safenode Services            ");
+    println!("=================================================");
+
+    let node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
+    let nodes = match selector.select(&node_registry)? {
+        Some(indexes) => indexes
+            .into_iter()
+            .map(|i| &node_registry.nodes[i])
+            .collect::<Vec<&Node>>(),
+        None => node_registry
+            .nodes
+            .iter()
+            .filter(|n| n.status != NodeStatus::Removed)
+            .collect::<Vec<&Node>>(),
+    };
+
+    let mut release_checksums: HashMap<String, String> = HashMap::new();
+    if release {
+        let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
+        for node in nodes.iter() {
+            if release_checksums.contains_key(&node.version) {
+                continue;
+            }
+            let (safenode_download_path, _) = download_and_extract_release(
+                ReleaseType::Safenode,
+                None,
+                Some(node.version.clone()),
+                None,
+                &*release_repo,
+            )
+            .await?;
+            release_checksums.insert(
+                node.version.clone(),
+                get_file_checksum(&safenode_download_path)?,
+            );
+            std::fs::remove_file(safenode_download_path)?;
+        }
+    }
+
+    let mut failed = 0;
+    for node in nodes {
+        let release_checksum = release_checksums.get(&node.version).map(|c| c.as_str());
+        match control::verify(node, release_checksum)? {
+            VerifyResult::Verified => {
+                println!("{} {} is unmodified", "✓".green(), node.service_name);
+            }
+            VerifyResult::Modified { expected, actual } => {
+                failed += 1;
+                println!(
+                    "{} {} has been modified: expected checksum {expected}, found {actual}",
+                    "✕".red(),
+                    node.service_name
+                );
+            }
+            VerifyResult::Missing => {
+                failed += 1;
+                println!("{} {} is missing its binary", "✕".red(), node.service_name);
+            }
+            VerifyResult::NotRecorded => {
+                println!(
+                    "{} {} has no recorded checksum; use --release to verify it",
+                    "⚠".yellow(),
+                    node.service_name
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(eyre!("{failed} service(s) failed verification")
+            .suggestion("Remove the affected services and add them again to reinstall safenode"));
+    }
+    Ok(())
+}
+
+/// Print the version of the node manager, and with `details`, the versions of the nodes.
+pub fn version(details: bool) -> Result<()> {
+    println!("safenode-manager {}", env!("CARGO_PKG_VERSION"));
+    if !details {
+        return Ok(());
+    }
+
+    let mut mismatches = 0;
+    for (title, registry_path) in [
+        ("Services", get_node_registry_path()?),
+        ("Local network", get_local_node_registry_path()?),
+    ] {
+        let node_registry = NodeRegistry::load(&registry_path)?;
+        let nodes = node_registry
+            .nodes
+            .iter()
+            .filter(|n| n.status != NodeStatus::Removed)
+            .collect::<Vec<&Node>>();
+        if nodes.is_empty() && node_registry.faucet_pid.is_none() {
+            continue;
+        }
+
+        println!();
+        println!("{title}:");
+        println!("{:<18} {:<10} {:<10}", "Name", "Registry", "Binary");
+        for node in nodes.iter() {
+            let bin_version = node
+                .safenode_path
+                .as_ref()
+                .ok_or_else(|| eyre!("no binary path"))
+                .and_then(|path| get_bin_version(path));
+            match bin_version {
+                Ok(bin_version) if bin_version == node.version => {
+                    println!(
+                        "{:<18} {:<10} {:<10}",
+                        node.service_name, node.version, bin_version
+                    );
+                }
+                Ok(bin_version) => {
+                    mismatches += 1;
+                    println!(
+                        "{:<18} {:<10} {:<10} {}",
+                        node.service_name,
+                        node.version,
+                        bin_version,
+                        "⚠ mismatch".yellow()
+                    );
+                }
+                Err(e) => {
+                    mismatches += 1;
+                    println!(
+                        "{:<18} {:<10} {:<10} {}",
+                        node.service_name,
+                        node.version,
+                        "-",
+                        format!("✕ {e}").red()
+                    );
+                }
+            }
+        }
+
+        let mut versions = nodes
+            .iter()
+            .map(|n| n.version.as_str())
+            .collect::<Vec<&str>>();
+        versions.sort();
+        versions.dedup();
+        if versions.len() > 1 {
+            println!(
+                "{} The nodes are running {} different versions: {}",
+                "⚠".yellow(),
+                versions.len(),
+                versions.join(", ")
+            );
+        }
+
+        if let Some(faucet_pid) = node_registry.faucet_pid {
+            match get_process_exe(faucet_pid).map(|path| get_bin_version(&path)) {
+                Some(Ok(version)) => println!("Faucet: {version}"),
+                Some(Err(e)) => println!("Faucet: {}", format!("✕ {e}").red()),
+                None => println!("Faucet: not running"),
+            }
+        }
+    }
+
+    if mismatches > 0 {
+        println!();
+        println!(
+            "{} {mismatches} node(s) have a binary that does not match the registry",
+            "⚠".yellow()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::{is_running_as_root, start_node};
+use crate::config::*;
+use crate::control::{stop, DEFAULT_STARTUP_TIMEOUT};
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use crate::schedule::{get_nodes_due_for_restart, CronSchedule};
+use crate::selector::NodeSelector;
+use crate::service::get_service_control;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use rand::Rng;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Remove the restart schedule of the selected services, or the one for every service.
+pub fn clear(selector: NodeSelector) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!(
+            "The restart-schedule clear command must run as the root user"
+        ));
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("restart-schedule clear")?;
+    match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => {
+            for node in nodes {
+                node.restart_schedule = None;
+                println!(
+                    "{} Removed the restart schedule for {}",
+                    "✓".green(),
+                    node.service_name
+                );
+            }
+        }
+        None => {
+            node_registry.restart_schedule = None;
+            println!("{} Removed the restart schedule for all nodes", "✓".green());
+        }
+    }
+    node_registry.save(registry_lock)?;
+
+    Ok(())
+}
+
+/// Restart the services that are scheduled to restart in the current minute.
+///
+/// A service isn't restarted if that would leave `min_running` or fewer running.
+pub async fn run(jitter: u64, min_running: usize) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!(
+            "The restart-schedule run command must run as the root user"
+        ));
+    }
+
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    let due = get_nodes_due_for_restart(&node_registry, &chrono::Local::now())?;
+    let service_control = get_service_control(&node_registry);
+    for service_name in due {
+        if jitter > 0 {
+            let delay = rand::thread_rng().gen_range(0..=jitter);
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+        }
+
+        let running = node_registry
+            .nodes
+            .iter()
+            .filter(|n| {
+                n.status == NodeStatus::Running
+                    && n.pid
+                        .is_some_and(|pid| service_control.is_service_process_running(pid))
+            })
+            .count();
+        if running <= min_running {
+            println!(
+                "{} {service_name} was not restarted because only {running} nodes are running",
+                "⚠".yellow()
+            );
+            continue;
+        }
+
+        let node = node_registry
+            .nodes
+            .iter_mut()
+            .find(|n| n.service_name == service_name)
+            .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+        if let Err(e) = stop(node, &*service_control).await {
+            println!("{} {service_name} was not restarted: {e}", "✕".red());
+            continue;
+        }
+        if let Err(e) = start_node(node, &*service_control, DEFAULT_STARTUP_TIMEOUT).await {
+            println!("{} {service_name} failed to start again: {e}", "✕".red());
+        }
+        node_registry.save_locked(&registry_lock)?;
+    }
+
+    Ok(())
+}
+
+/// Set the restart schedule of the selected services, or the one for every service.
+pub fn set(expression: String, selector: NodeSelector) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!(
+            "The restart-schedule set command must run as the root user"
+        ));
+    }
+
+    CronSchedule::from_str(&expression)?;
+    let (mut node_registry, registry_lock) = NodeRegistry::load_locked(&get_node_registry_path()?)?;
+    node_registry.snapshot("restart-schedule set")?;
+    match selector.select_mut(&mut node_registry)? {
+        Some(nodes) => {
+            for node in nodes {
+                node.restart_schedule = Some(expression.clone());
+                println!(
+                    "{} {} will restart on the schedule '{expression}'",
+                    "✓".green(),
+                    node.service_name
+                );
+            }
+        }
+        None => {
+            node_registry.restart_schedule = Some(expression.clone());
+            println!(
+                "{} Nodes without their own schedule will restart on the schedule '{expression}'",
+                "✓".green()
+            );
+        }
+    }
+    node_registry.save(registry_lock)?;
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::{is_running_as_root, start_node};
+use crate::config::*;
+use crate::control::DEFAULT_STARTUP_TIMEOUT;
+use crate::host::get_host_defaults;
+use crate::node_registry::NodeRegistry;
+use crate::service::get_service_control;
+use crate::setup::{ask_setup_questions, get_add_commands};
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use std::future::Future;
+
+/// Set up services by asking a few questions, then adding them by running the add command with
+/// `run_command`.
+pub async fn run<R, F>(run_command: R) -> Result<()>
+where
+    R: Fn(Vec<String>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The setup command must run as the root user"));
+    }
+
+    println!("=================================================");
+    println!("              Set Up Safenode Services           ");
+    println!("=================================================");
+
+    let host_defaults = get_host_defaults();
+    let answers = ask_setup_questions(
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+        host_defaults.recommended_max_count.unwrap_or(1),
+    )?;
+
+    let previous_node_count = NodeRegistry::load(&get_node_registry_path()?)?.nodes.len();
+    for args in get_add_commands(&answers) {
+        run_command(args).await?;
+    }
+
+    if answers.start {
+        let (mut node_registry, registry_lock) =
+            NodeRegistry::load_locked(&get_node_registry_path()?)?;
+        let service_control = get_service_control(&node_registry);
+        for node in node_registry.nodes[previous_node_count..].iter_mut() {
+            start_node(node, &*service_control, DEFAULT_STARTUP_TIMEOUT).await?;
+        }
+        node_registry.save(registry_lock)?;
+    }
+
+    println!(
+        "{} Setup is complete. Use the upgrade command to keep the nodes up to date.",
+        "✓".green()
+    );
+    Ok(())
+}
@@ -0,0 +1,76 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::cmd::is_running_as_root;
+use crate::config::*;
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use crate::telemetry::{send_event, TelemetryEvent, TelemetrySettings};
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+
+/// Stop sending telemetry.
+pub fn off() -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The telemetry off command must run as the root user"));
+    }
+    let path = get_telemetry_settings_path()?;
+    let mut settings = TelemetrySettings::load(&path)?;
+    settings.enabled = false;
+    settings.save(&path)?;
+    println!("{} Telemetry is off", "✓".green());
+    Ok(())
+}
+
+/// Send telemetry to the endpoint after each command.
+pub fn on(endpoint: String) -> Result<()> {
+    if !is_running_as_root() && !is_user_mode() {
+        return Err(eyre!("The telemetry on command must run as the root user"));
+    }
+    let settings = TelemetrySettings {
+        enabled: true,
+        endpoint: Some(endpoint),
+    };
+    settings.save(&get_telemetry_settings_path()?)?;
+    println!("{} Telemetry is on", "✓".green());
+    Ok(())
+}
+
+/// Print whether telemetry is being sent.
+pub fn status() -> Result<()> {
+    let settings = TelemetrySettings::load(&get_telemetry_settings_path()?)?;
+    match (settings.enabled, settings.endpoint) {
+        (true, Some(endpoint)) => println!("Telemetry is on, sending to {endpoint}"),
+        _ => println!("Telemetry is off"),
+    }
+    Ok(())
+}
+
+/// Send the telemetry for a command, if it has been turned on.
+///
+/// Any failure is ignored, since it shouldn't affect the command.
+pub async fn send(command: &str, result: &Result<()>) {
+    let settings = match get_telemetry_settings_path().and_then(|p| TelemetrySettings::load(&p)) {
+        Ok(settings) => settings,
+        Err(_) => return,
+    };
+    let endpoint = match settings.endpoint {
+        Some(endpoint) if settings.enabled => endpoint,
+        _ => return,
+    };
+    let node_count = get_node_registry_path()
+        .and_then(|p| NodeRegistry::load(&p))
+        .map(|r| {
+            r.nodes
+                .iter()
+                .filter(|n| n.status != NodeStatus::Removed)
+                .count()
+        })
+        .unwrap_or_default();
+    let event = TelemetryEvent::new(command, node_count, result);
+    let _ = send_event(&endpoint, &event).await;
+}
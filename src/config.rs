@@ -6,9 +6,23 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use color_eyre::Result;
+use crate::dry_run::is_dry_run;
+use color_eyre::{eyre::eyre, Result};
 use std::path::{Path, PathBuf};
 
+/// On macOS, services can be installed as launchd agents for the current user, which doesn't
+/// require root. This is used when the node manager is not running as root, so Mac users can run
+/// nodes without sudo. Everything is then kept under the user's home directory.
+#[cfg(target_os = "macos")]
+pub fn is_user_mode() -> bool {
+    users::get_effective_uid() != 0
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn is_user_mode() -> bool {
+    false
+}
+
 #[cfg(unix)]
 pub fn get_node_manager_path() -> Result<PathBuf> {
     // This needs to be a system-wide location rather than a user directory because the `install`
@@ -16,6 +30,14 @@ pub fn get_node_manager_path() -> Result<PathBuf> {
     // other commands, e.g., requesting status, shouldn't require root.
     use std::os::unix::fs::PermissionsExt;
 
+    if is_user_mode() {
+        let path = dirs_next::data_dir()
+            .ok_or_else(|| eyre!("Could not obtain user data directory"))?
+            .join("safenode-manager");
+        std::fs::create_dir_all(&path)?;
+        return Ok(path);
+    }
+
     let path = Path::new("/var/safenode-manager/");
     if !path.exists() {
         std::fs::create_dir_all(path)?;
@@ -34,48 +56,135 @@ pub fn get_node_registry_path() -> Result<PathBuf> {
 }
 
 #[cfg(unix)]
-pub fn get_service_data_dir_path(custom_path: Option<PathBuf>, owner: &str) -> Result<PathBuf> {
-    let path = match custom_path {
-        Some(p) => p,
-        None => PathBuf::from("/var/safenode-manager/services"),
-    };
-    create_owned_dir(path.clone(), owner)?;
-    Ok(path)
+pub fn get_earnings_history_path() -> Result<PathBuf> {
+    let path = get_node_manager_path()?;
+    Ok(path.join("earnings_history.json"))
+}
+
+#[cfg(unix)]
+pub fn get_telemetry_settings_path() -> Result<PathBuf> {
+    let path = get_node_manager_path()?;
+    Ok(path.join("telemetry.json"))
+}
+
+#[cfg(unix)]
+pub fn get_alert_settings_path() -> Result<PathBuf> {
+    let path = get_node_manager_path()?;
+    Ok(path.join("alerts.json"))
+}
+
+#[cfg(unix)]
+pub fn get_autoscale_policy_path() -> Result<PathBuf> {
+    let path = get_node_manager_path()?;
+    Ok(path.join("autoscale.json"))
+}
+
+#[cfg(unix)]
+pub fn get_daemon_token_path() -> Result<PathBuf> {
+    let path = get_node_manager_path()?;
+    Ok(path.join("daemon_token"))
+}
+
+#[cfg(unix)]
+pub fn get_restart_history_path() -> Result<PathBuf> {
+    let path = get_node_manager_path()?;
+    Ok(path.join("restart_history.json"))
 }
 
 #[cfg(windows)]
-pub fn get_service_data_dir_path(custom_path: Option<PathBuf>, _owner: &str) -> Result<PathBuf> {
-    let path = match custom_path {
-        Some(p) => p,
-        None => PathBuf::from("C:\\ProgramData\\safenode\\data"),
-    };
-    std::fs::create_dir_all(&path)?;
-    Ok(path)
+pub fn get_telemetry_settings_path() -> Result<PathBuf> {
+    let path = Path::new("C:\\ProgramData\\safenode-manager");
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path.join("telemetry.json"))
 }
 
+/// Get the paths of the configuration files, in the order they're applied.
+///
+/// The system-wide file is applied first, so the settings in the user's own file take precedence.
+/// Neither file has to exist.
 #[cfg(unix)]
-pub fn get_service_log_dir_path(custom_path: Option<PathBuf>, owner: &str) -> Result<PathBuf> {
+pub fn get_config_file_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("/etc/safenode-manager/config.toml")];
+    paths.extend(
+        dirs_next::config_dir().map(|dir| dir.join("safenode-manager").join("config.toml")),
+    );
+    paths
+}
+
+#[cfg(windows)]
+pub fn get_config_file_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(
+        "C:\\ProgramData\\safenode-manager\\config.toml",
+    )];
+    paths.extend(
+        dirs_next::config_dir().map(|dir| dir.join("safenode-manager").join("config.toml")),
+    );
+    paths
+}
+
+/// Get the directory the data directories of new services go in, unless another is specified.
+///
+/// Each node's copy of the safenode binary is also kept in its data directory.
+#[cfg(unix)]
+pub fn get_default_service_data_dir_path() -> Result<PathBuf> {
+    if is_user_mode() {
+        return Ok(dirs_next::data_dir()
+            .ok_or_else(|| eyre!("Could not obtain user data directory"))?
+            .join("safenode")
+            .join("services"));
+    }
+    Ok(PathBuf::from("/var/safenode-manager/services"))
+}
+
+#[cfg(windows)]
+pub fn get_default_service_data_dir_path() -> Result<PathBuf> {
+    Ok(PathBuf::from("C:\\ProgramData\\safenode\\data"))
+}
+
+/// Get the directory the log directories of new services go in, unless another is specified.
+#[cfg(unix)]
+pub fn get_default_service_log_dir_path() -> Result<PathBuf> {
+    if is_user_mode() {
+        return Ok(dirs_next::home_dir()
+            .ok_or_else(|| eyre!("Could not obtain user home directory"))?
+            .join("Library")
+            .join("Logs")
+            .join("safenode"));
+    }
+    Ok(PathBuf::from("/var/log/safenode"))
+}
+
+#[cfg(windows)]
+pub fn get_default_service_log_dir_path() -> Result<PathBuf> {
+    Ok(PathBuf::from("C:\\ProgramData\\safenode\\logs"))
+}
+
+pub fn get_service_data_dir_path(custom_path: Option<PathBuf>, owner: &str) -> Result<PathBuf> {
     let path = match custom_path {
         Some(p) => p,
-        None => PathBuf::from("/var/log/safenode"),
+        None => get_default_service_data_dir_path()?,
     };
-    create_owned_dir(path.clone(), owner)?;
+    if !is_dry_run() {
+        create_owned_dir(path.clone(), owner)?;
+    }
     Ok(path)
 }
 
-#[cfg(windows)]
-pub fn get_service_log_dir_path(custom_path: Option<PathBuf>, _owner: &str) -> Result<PathBuf> {
+pub fn get_service_log_dir_path(custom_path: Option<PathBuf>, owner: &str) -> Result<PathBuf> {
     let path = match custom_path {
         Some(p) => p,
-        None => PathBuf::from("C:\\ProgramData\\safenode\\logs"),
+        None => get_default_service_log_dir_path()?,
     };
-    std::fs::create_dir_all(&path)?;
+    if !is_dry_run() {
+        create_owned_dir(path.clone(), owner)?;
+    }
     Ok(path)
 }
 
 #[cfg(unix)]
 pub fn create_owned_dir(path: PathBuf, owner: &str) -> Result<()> {
-    use color_eyre::eyre::eyre;
     use nix::unistd::{chown, Gid, Uid};
     use std::os::unix::fs::PermissionsExt;
     use users::get_user_by_name;
@@ -84,6 +193,12 @@ pub fn create_owned_dir(path: PathBuf, owner: &str) -> Result<()> {
     let permissions = std::fs::Permissions::from_mode(0o755);
     std::fs::set_permissions(&path, permissions)?;
 
+    // The directory will already be owned by the current user, who will also be running the
+    // services.
+    if is_user_mode() {
+        return Ok(());
+    }
+
     let user = get_user_by_name(owner).ok_or_else(|| eyre!("User '{owner}' does not exist"))?;
     let uid = Uid::from_raw(user.uid());
     let gid = Gid::from_raw(user.primary_group_id());
@@ -91,9 +206,87 @@ pub fn create_owned_dir(path: PathBuf, owner: &str) -> Result<()> {
     Ok(())
 }
 
+/// Create a directory owned by a user, which no other user can read.
+#[cfg(unix)]
+pub fn create_private_dir(path: PathBuf, owner: &str) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    create_owned_dir(path.clone(), owner)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o700))?;
+    Ok(())
+}
+
+/// The services all run as the same account on Windows, so the directory is created in the same
+/// way as any other.
+#[cfg(windows)]
+pub fn create_private_dir(path: PathBuf, owner: &str) -> Result<()> {
+    create_owned_dir(path, owner)
+}
+
+/// Create a file only its owner can read, with the permissions in place before anything is
+/// written to it.
+#[cfg(unix)]
+pub fn create_private_file(path: &Path) -> Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    Ok(std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?)
+}
+
+/// The inherited permissions would let any user read the file, so they're replaced with full
+/// control for `SYSTEM` and `Administrators` only.
+#[cfg(windows)]
+pub fn create_private_file(path: &Path) -> Result<std::fs::File> {
+    use std::process::Command;
+
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    let output = Command::new("icacls")
+        .arg(path)
+        .arg("/inheritance:r")
+        .arg("/grant:r")
+        .arg("*S-1-5-18:F")
+        .arg("*S-1-5-32-544:F")
+        .output()?;
+    if !output.status.success() {
+        drop(file);
+        std::fs::remove_file(path)?;
+        return Err(eyre!(
+            "Failed to set permissions on {}",
+            path.to_string_lossy()
+        ));
+    }
+    Ok(file)
+}
+
+/// On Windows the services run as the `LocalSystem` account, so there is no service user to give
+/// ownership to. By default, any user can create files under `C:\ProgramData`, so the inherited
+/// permissions are replaced with full control for `SYSTEM` and `Administrators`, and read access
+/// for other users. Well-known SIDs are used because the account names are localised.
 #[cfg(windows)]
 pub fn create_owned_dir(path: PathBuf, _owner: &str) -> Result<()> {
+    use std::process::Command;
+
     std::fs::create_dir_all(&path)?;
+    let output = Command::new("icacls")
+        .arg(&path)
+        .arg("/inheritance:r")
+        .arg("/grant:r")
+        .arg("*S-1-5-18:(OI)(CI)F")
+        .arg("*S-1-5-32-544:(OI)(CI)F")
+        .arg("*S-1-5-32-545:(OI)(CI)RX")
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Failed to set permissions on {}",
+            path.to_string_lossy()
+        ));
+    }
     Ok(())
 }
 
@@ -105,3 +298,48 @@ pub fn get_node_registry_path() -> Result<PathBuf> {
     }
     Ok(path.join("node_registry.json"))
 }
+
+#[cfg(windows)]
+pub fn get_earnings_history_path() -> Result<PathBuf> {
+    let path = Path::new("C:\\ProgramData\\safenode-manager");
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path.join("earnings_history.json"))
+}
+
+#[cfg(windows)]
+pub fn get_alert_settings_path() -> Result<PathBuf> {
+    let path = Path::new("C:\\ProgramData\\safenode-manager");
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path.join("alerts.json"))
+}
+
+#[cfg(windows)]
+pub fn get_daemon_token_path() -> Result<PathBuf> {
+    let path = Path::new("C:\\ProgramData\\safenode-manager");
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path.join("daemon_token"))
+}
+
+#[cfg(windows)]
+pub fn get_restart_history_path() -> Result<PathBuf> {
+    let path = Path::new("C:\\ProgramData\\safenode-manager");
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path.join("restart_history.json"))
+}
+
+#[cfg(windows)]
+pub fn get_autoscale_policy_path() -> Result<PathBuf> {
+    let path = Path::new("C:\\ProgramData\\safenode-manager");
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path.join("autoscale.json"))
+}
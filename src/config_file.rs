@@ -0,0 +1,204 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::add_service::PortRange;
+use crate::helpers::merge_env_variables;
+use color_eyre::{eyre::eyre, Result};
+use libp2p::Multiaddr;
+use serde::{de::Error, Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The settings read from the configuration files.
+///
+/// The files are TOML, with a table for each command they supply defaults for:
+///
+/// ```toml
+/// [add]
+/// count = 10
+/// version = "0.105.3"
+/// port = "12000-12009"
+/// data_dir_path = "/mnt/safenode/data"
+/// peers = ["/ip4/10.0.0.1/udp/12000/quic-v1/p2p/12D3KooW..."]
+///
+/// [add.env]
+/// SN_LOG = "all"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub add: AddDefaults,
+}
+
+/// The defaults for the `add` command, which apply to any argument that isn't used.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AddDefaults {
+    pub count: Option<u16>,
+    pub data_dir_path: Option<PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub log_dir_path: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_peers")]
+    pub peers: Vec<Multiaddr>,
+    #[serde(default, deserialize_with = "deserialize_port_range")]
+    pub port: Option<PortRange>,
+    #[serde(default, deserialize_with = "deserialize_port_range")]
+    pub rpc_port: Option<PortRange>,
+    pub version: Option<String>,
+}
+
+impl AddDefaults {
+    /// Get the environment variables, with those given on the command line taking precedence.
+    pub fn get_env_variables(&self, env_variables: Vec<(String, String)>) -> Vec<(String, String)> {
+        let mut variables = self
+            .env
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        merge_env_variables(&mut variables, env_variables);
+        variables
+    }
+
+    /// Apply the settings of a later file over these ones.
+    fn apply(&mut self, other: AddDefaults) {
+        self.count = other.count.or(self.count);
+        self.data_dir_path = other.data_dir_path.or(self.data_dir_path.take());
+        self.env.extend(other.env);
+        self.log_dir_path = other.log_dir_path.or(self.log_dir_path.take());
+        if !other.peers.is_empty() {
+            self.peers = other.peers;
+        }
+        self.port = other.port.or(self.port);
+        self.rpc_port = other.rpc_port.or(self.rpc_port);
+        self.version = other.version.or(self.version.take());
+    }
+}
+
+impl ConfigFile {
+    /// Load the configuration files that exist, in the order they're given.
+    ///
+    /// Each setting is taken from the last file that has it, apart from the environment variables,
+    /// which are combined. An invalid file is an error rather than being skipped, so a mistake in
+    /// it doesn't go unnoticed.
+    pub fn load(paths: &[PathBuf]) -> Result<Self> {
+        let mut config = ConfigFile::default();
+        for path in paths.iter().filter(|p| p.exists()) {
+            config.add.apply(Self::read(path)?.add);
+        }
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| {
+            eyre!(
+                "The configuration file at {} is invalid: {e}",
+                path.display()
+            )
+        })
+    }
+}
+
+/// Ports can be given as a number, or as a string for a range, like they are on the command line.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PortValue {
+    Number(u16),
+    Text(String),
+}
+
+fn deserialize_port_range<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<PortRange>, D::Error> {
+    match Option::<PortValue>::deserialize(deserializer)? {
+        Some(PortValue::Number(port)) => Ok(Some(PortRange::Single(port))),
+        Some(PortValue::Text(text)) => PortRange::from_str(&text)
+            .map(Some)
+            .map_err(D::Error::custom),
+        None => Ok(None),
+    }
+}
+
+fn deserialize_peers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Multiaddr>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|peer| Multiaddr::from_str(peer).map_err(D::Error::custom))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn load_should_take_each_setting_from_the_last_file_that_has_it() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let system_file = temp_dir.child("etc/config.toml");
+        system_file.write_str(
+            r#"
+[add]
+count = 10
+version = "0.105.3"
+port = "12000-12009"
+rpc_port = 13000
+data_dir_path = "/mnt/safenode/data"
+
+[add.env]
+SN_LOG = "all"
+RUST_LOG = "info"
+"#,
+        )?;
+        let user_file = temp_dir.child("home/config.toml");
+        user_file.write_str(
+            r#"
+[add]
+version = "0.106.0"
+peers = ["/ip4/127.0.0.1/udp/12000/quic-v1/p2p/12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR"]
+
+[add.env]
+RUST_LOG = "debug"
+"#,
+        )?;
+
+        let config = ConfigFile::load(&[
+            system_file.to_path_buf(),
+            temp_dir.child("missing/config.toml").to_path_buf(),
+            user_file.to_path_buf(),
+        ])?;
+
+        assert_eq!(config.add.count, Some(10));
+        assert_eq!(config.add.version, Some("0.106.0".to_string()));
+        assert_eq!(config.add.port, Some(PortRange::Range(12000, 12009)));
+        assert_eq!(config.add.rpc_port, Some(PortRange::Single(13000)));
+        assert_eq!(
+            config.add.data_dir_path,
+            Some(PathBuf::from("/mnt/safenode/data"))
+        );
+        assert_eq!(config.add.log_dir_path, None);
+        assert_eq!(config.add.peers.len(), 1);
+        assert_eq!(
+            config
+                .add
+                .get_env_variables(vec![("SN_LOG".to_string(), "none".to_string())]),
+            vec![
+                ("RUST_LOG".to_string(), "debug".to_string()),
+                ("SN_LOG".to_string(), "none".to_string()),
+            ]
+        );
+
+        user_file.write_str("[add]\ncuont = 5\n")?;
+        assert!(ConfigFile::load(&[user_file.to_path_buf()]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config::is_user_mode;
+use crate::control::get_service_config;
+use crate::node_registry::{
+    ContainerBackend, ContainerRuntime, ExitInfo, NodeRegistry, NodeStatus,
+};
+use crate::service::{make_node_args, NodeServiceManager, ServiceConfig, ServiceControl};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use color_eyre::{eyre::eyre, Help, Result};
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+/// The image the node containers are created from.
+///
+/// The safenode binary is statically linked, so it doesn't need anything from the image beyond a
+/// root filesystem. The binary is mounted from the host, which means upgrades and rollbacks work
+/// in the same way they do for native services.
+pub const DEFAULT_CONTAINER_IMAGE: &str = "debian:bookworm-slim";
+
+/// How the node services are run.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ServiceBackend {
+    /// The platform's service manager, e.g., systemd or launchd.
+    Native,
+    Docker,
+    Podman,
+    /// A single templated systemd unit, `safenode@.service`, with a drop-in for each node.
+    SystemdTemplate,
+}
+
+impl ServiceBackend {
+    /// Get the container backend the registry should record, which is empty for native services.
+    pub fn get_container(&self, image: Option<String>) -> Option<ContainerBackend> {
+        let runtime = match self {
+            ServiceBackend::Native | ServiceBackend::SystemdTemplate => return None,
+            ServiceBackend::Docker => ContainerRuntime::Docker,
+            ServiceBackend::Podman => ContainerRuntime::Podman,
+        };
+        Some(ContainerBackend {
+            runtime,
+            image: image.unwrap_or(DEFAULT_CONTAINER_IMAGE.to_string()),
+        })
+    }
+}
+
+/// Runs each node service as a container using Docker or Podman.
+///
+/// The containers use the host's network and process namespaces, so the node's ports, RPC
+/// endpoint and PID are the same as they would be for a native service. Anything that doesn't
+/// involve the service itself, like firewall rules and port checks, is done on the host.
+pub struct ContainerServiceManager {
+    pub backend: ContainerBackend,
+}
+
+impl ContainerServiceManager {
+    fn run(&self, args: &[OsString]) -> Result<String> {
+        let command = self.backend.runtime.command();
+        let output = Command::new(command).args(args).output().map_err(|e| {
+            eyre!("Could not run {command}: {e}")
+                .suggestion(format!("Make sure {command} is installed and on the PATH"))
+        })?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "{command} {} failed: {}",
+                args.first()
+                    .map(|a| a.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+#[async_trait]
+impl ServiceControl for ContainerServiceManager {
+    fn add_firewall_rule(&self, service_name: &str, port: u16) -> Result<()> {
+        NodeServiceManager {}.add_firewall_rule(service_name, port)
+    }
+
+    fn create_service_user(&self, username: &str) -> Result<()> {
+        NodeServiceManager {}.create_service_user(username)
+    }
+
+    fn get_available_port(&self) -> Result<u16> {
+        NodeServiceManager {}.get_available_port()
+    }
+
+    fn get_exit_info(&self, service_name: &str) -> Result<ExitInfo> {
+        let output = self.run(&[
+            OsString::from("inspect"),
+            OsString::from("--format"),
+            OsString::from("{{.State.ExitCode}} {{.State.OOMKilled}} {{.RestartCount}}"),
+            OsString::from(service_name),
+        ])?;
+        Ok(parse_inspect_exit_info(&output))
+    }
+
+    fn install(&self, config: ServiceConfig) -> Result<()> {
+        self.run(&[OsString::from("pull"), OsString::from(&self.backend.image)])?;
+        let (uid, gid) = get_user_ids(&config.service_user)?;
+        self.run(&make_create_args(&config, &self.backend.image, &uid, &gid))?;
+        Ok(())
+    }
+
+    fn is_port_free(&self, port: u16) -> bool {
+        NodeServiceManager {}.is_port_free(port)
+    }
+
+    fn is_service_process_running(&self, pid: u32) -> bool {
+        NodeServiceManager {}.is_service_process_running(pid)
+    }
+
+    fn kill_process(&self, pid: u32) -> Result<()> {
+        NodeServiceManager {}.kill_process(pid)
+    }
+
+    fn remove_firewall_rule(&self, service_name: &str) -> Result<()> {
+        NodeServiceManager {}.remove_firewall_rule(service_name)
+    }
+
+    fn start(&self, service_name: &str) -> Result<()> {
+        self.run(&[OsString::from("start"), OsString::from(service_name)])?;
+        Ok(())
+    }
+
+    fn stop(&self, service_name: &str) -> Result<()> {
+        self.run(&[OsString::from("stop"), OsString::from(service_name)])?;
+        Ok(())
+    }
+
+    fn uninstall(&self, service_name: &str) -> Result<()> {
+        self.run(&[
+            OsString::from("rm"),
+            OsString::from("--force"),
+            OsString::from(service_name),
+        ])?;
+        Ok(())
+    }
+
+    async fn wait(&self, delay: u64) {
+        NodeServiceManager {}.wait(delay).await
+    }
+}
+
+fn get_user_ids(username: &str) -> Result<(String, String)> {
+    let get_id = |flag: &str| -> Result<String> {
+        let mut command = Command::new("id");
+        command.arg(flag);
+        if !is_user_mode() {
+            command.arg(username);
+        }
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(eyre!("Could not get the IDs of the {username} user"));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    Ok((get_id("-u")?, get_id("-g")?))
+}
+
+fn make_create_args(config: &ServiceConfig, image: &str, uid: &str, gid: &str) -> Vec<OsString> {
+    let mount = |path: &Path, options: &str| {
+        OsString::from(format!("{0}:{0}{options}", path.to_string_lossy()))
+    };
+    let mut args = vec![
+        OsString::from("create"),
+        OsString::from("--name"),
+        OsString::from(&config.name),
+        OsString::from("--network"),
+        OsString::from("host"),
+        OsString::from("--pid"),
+        OsString::from("host"),
+        OsString::from("--restart"),
+        OsString::from(if config.keep_alive {
+            "unless-stopped"
+        } else {
+            "no"
+        }),
+        OsString::from("--user"),
+        OsString::from(format!("{uid}:{gid}")),
+        OsString::from("--volume"),
+        mount(&config.data_dir_path, ""),
+        OsString::from("--volume"),
+        mount(&config.log_dir_path, ""),
+    ];
+    // The binary is normally in the data directory, but it could have been put somewhere else.
+    if let Some(bin_dir_path) = config
+        .safenode_path
+        .parent()
+        .filter(|p| !p.starts_with(&config.data_dir_path))
+    {
+        args.push(OsString::from("--volume"));
+        args.push(mount(bin_dir_path, ":ro"));
+    }
+    if let Some(max_memory) = config.max_memory {
+        args.push(OsString::from("--memory"));
+        args.push(OsString::from(format!("{max_memory}m")));
+    }
+    if let Some(log_targets) = &config.log_targets {
+        args.push(OsString::from("--env"));
+        args.push(OsString::from(format!("SN_LOG={log_targets}")));
+    }
+    for (name, value) in &config.env_variables {
+        args.push(OsString::from("--env"));
+        args.push(OsString::from(format!("{name}={value}")));
+    }
+    args.push(OsString::from("--entrypoint"));
+    args.push(OsString::from(&config.safenode_path));
+    args.push(OsString::from(image));
+    args.extend(make_node_args(config));
+    args
+}
+
+/// Render the registry's nodes as a Compose file, with a service for each node.
+///
+/// The services are the same as the containers the container backend would create, so the nodes
+/// can be run elsewhere with the same ports, directories and binaries.
+pub fn make_compose_file(node_registry: &NodeRegistry) -> Result<String> {
+    let image = node_registry
+        .container
+        .as_ref()
+        .map(|c| c.image.clone())
+        .unwrap_or(DEFAULT_CONTAINER_IMAGE.to_string());
+    let mut compose = String::from("services:\n");
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        let (uid, gid) = get_user_ids(&node.user)?;
+        compose.push_str(&make_compose_service(
+            &get_service_config(node)?,
+            &node.version,
+            &image,
+            &uid,
+            &gid,
+        ));
+    }
+    Ok(compose)
+}
+
+fn make_compose_service(
+    config: &ServiceConfig,
+    version: &str,
+    image: &str,
+    uid: &str,
+    gid: &str,
+) -> String {
+    // Quoting every value as a JSON string keeps it valid YAML, whatever it contains.
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    let mut volumes = vec![
+        (&config.data_dir_path as &Path, ""),
+        (&config.log_dir_path, ""),
+    ];
+    if let Some(bin_dir_path) = config
+        .safenode_path
+        .parent()
+        .filter(|p| !p.starts_with(&config.data_dir_path))
+    {
+        volumes.push((bin_dir_path, ":ro"));
+    }
+
+    let mut service = format!("  {}:\n", config.name);
+    service.push_str(&format!("    image: {}\n", quote(image)));
+    service.push_str(&format!("    container_name: {}\n", quote(&config.name)));
+    service.push_str("    network_mode: host\n    pid: host\n");
+    service.push_str(&format!(
+        "    restart: {}\n",
+        if config.keep_alive {
+            "unless-stopped"
+        } else {
+            "\"no\""
+        }
+    ));
+    service.push_str(&format!("    user: {}\n", quote(&format!("{uid}:{gid}"))));
+    service.push_str(&format!(
+        "    labels:\n      safenode.version: {}\n",
+        quote(version)
+    ));
+    service.push_str("    volumes:\n");
+    for (path, options) in volumes {
+        let path = path.to_string_lossy();
+        service.push_str(&format!(
+            "      - {}\n",
+            quote(&format!("{path}:{path}{options}"))
+        ));
+    }
+    let environment = config
+        .log_targets
+        .iter()
+        .map(|log_targets| ("SN_LOG", log_targets.as_str()))
+        .chain(
+            config
+                .env_variables
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+        .collect::<Vec<_>>();
+    if !environment.is_empty() {
+        service.push_str("    environment:\n");
+        for (name, value) in environment {
+            service.push_str(&format!("      {name}: {}\n", quote(value)));
+        }
+    }
+    if let Some(max_memory) = config.max_memory {
+        service.push_str(&format!("    mem_limit: {max_memory}m\n"));
+    }
+    service.push_str(&format!(
+        "    entrypoint: [{}]\n",
+        quote(&config.safenode_path.to_string_lossy())
+    ));
+    service.push_str(&format!(
+        "    command: [{}]\n",
+        make_node_args(config)
+            .iter()
+            .map(|a| quote(&a.to_string_lossy()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    ));
+    service
+}
+
+fn parse_inspect_exit_info(output: &str) -> ExitInfo {
+    let fields = output.split_whitespace().collect::<Vec<&str>>();
+    let exit_code = fields.first().and_then(|f| f.parse::<i32>().ok());
+    ExitInfo {
+        // The runtimes report a process killed by a signal as 128 plus the signal number.
+        exit_code: exit_code.filter(|code| *code <= 128),
+        signal: exit_code.filter(|code| *code > 128).map(|code| code - 128),
+        reason: match fields.get(1) {
+            Some(&"true") => Some("oom-kill".to_string()),
+            _ => None,
+        },
+        restart_count: fields.get(2).and_then(|f| f.parse::<u32>().ok()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::NodeType;
+    use std::path::PathBuf;
+
+    #[test]
+    fn make_create_args_should_share_the_host_network_and_mount_the_node_directories() {
+        let config = ServiceConfig {
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            env_variables: vec![],
+            extra_args: vec![],
+            genesis: false,
+            home_network: false,
+            keep_alive: true,
+            local: false,
+            log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            log_targets: None,
+            max_memory: Some(2048),
+            name: "safenode1".to_string(),
+            node_ip: None,
+            node_port: 8080,
+            node_type: NodeType::Safenode,
+            owner: None,
+            peers: vec![],
+            rewards_address: None,
+            rpc_address: None,
+            rpc_port: 8081,
+            safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+            service_user: "safe".to_string(),
+            throttle_interval: None,
+            upnp: false,
+        };
+
+        let args = make_create_args(&config, DEFAULT_CONTAINER_IMAGE, "1001", "1001")
+            .iter()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        assert_eq!(
+            args,
+            "create --name safenode1 --network host --pid host --restart unless-stopped \
+            --user 1001:1001 \
+            --volume /var/safenode-manager/services/safenode1:/var/safenode-manager/services/safenode1 \
+            --volume /var/log/safenode/safenode1:/var/log/safenode/safenode1 \
+            --memory 2048m \
+            --entrypoint /var/safenode-manager/services/safenode1/safenode debian:bookworm-slim \
+            --port 8080 --rpc 127.0.0.1:8081 --root-dir /var/safenode-manager/services/safenode1 \
+            --log-output-dest /var/log/safenode/safenode1"
+        );
+    }
+
+    #[test]
+    fn make_compose_service_should_match_the_container_the_backend_would_create() {
+        let config = ServiceConfig {
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            env_variables: vec![("RUST_BACKTRACE".to_string(), "1".to_string())],
+            extra_args: vec![],
+            genesis: false,
+            home_network: false,
+            keep_alive: true,
+            local: false,
+            log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            log_targets: Some("sn_networking=debug".to_string()),
+            max_memory: None,
+            name: "safenode1".to_string(),
+            node_ip: None,
+            node_port: 8080,
+            node_type: NodeType::Safenode,
+            owner: None,
+            peers: vec![],
+            rewards_address: None,
+            rpc_address: None,
+            rpc_port: 8081,
+            safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+            service_user: "safe".to_string(),
+            throttle_interval: None,
+            upnp: false,
+        };
+
+        assert_eq!(
+            make_compose_service(&config, "0.98.1", DEFAULT_CONTAINER_IMAGE, "1001", "1001"),
+            r#"  safenode1:
+    image: "debian:bookworm-slim"
+    container_name: "safenode1"
+    network_mode: host
+    pid: host
+    restart: unless-stopped
+    user: "1001:1001"
+    labels:
+      safenode.version: "0.98.1"
+    volumes:
+      - "/var/safenode-manager/services/safenode1:/var/safenode-manager/services/safenode1"
+      - "/var/log/safenode/safenode1:/var/log/safenode/safenode1"
+    environment:
+      SN_LOG: "sn_networking=debug"
+      RUST_BACKTRACE: "1"
+    entrypoint: ["/var/safenode-manager/services/safenode1/safenode"]
+    command: ["--port", "8080", "--rpc", "127.0.0.1:8081", "--root-dir", "/var/safenode-manager/services/safenode1", "--log-output-dest", "/var/log/safenode/safenode1"]
+"#
+        );
+    }
+
+    #[test]
+    fn parse_inspect_exit_info_should_report_a_signal_and_an_oom_kill() {
+        assert_eq!(
+            parse_inspect_exit_info("137 true 3\n"),
+            ExitInfo {
+                exit_code: None,
+                signal: Some(9),
+                reason: Some("oom-kill".to_string()),
+                restart_count: Some(3),
+            }
+        );
+        assert_eq!(
+            parse_inspect_exit_info("1 false 0\n"),
+            ExitInfo {
+                exit_code: Some(1),
+                signal: None,
+                reason: None,
+                restart_count: Some(0),
+            }
+        );
+    }
+}
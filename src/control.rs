@@ -6,24 +6,94 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::service::ServiceControl;
+use crate::dry_run::is_dry_run;
+use crate::earnings::EarningsHistory;
+use crate::helpers::{
+    format_duration, get_dir_size, get_file_checksum, merge_env_variables, validate_rewards_address,
+};
+use crate::node_registry::{
+    Node, NodeFailure, NodeRegistry, NodeStatus, ResourceUsage, StopMethod,
+};
+use crate::output::report;
+use crate::service::{ServiceConfig, ServiceControl};
+use chrono::{DateTime, Utc};
 use color_eyre::{eyre::eyre, Help, Result};
 use colored::Colorize;
 use semver::Version;
 use sn_node_rpc_client::{RpcActions, RpcClient};
-use sn_protocol::node_registry::{Node, NodeRegistry, NodeStatus};
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use sysinfo::{Pid, ProcessExt, System, SystemExt};
 
+const MB: u64 = 1024 * 1024;
+
+/// The number of seconds to wait for a node to exit after stopping its service, before killing it.
+pub const DEFAULT_STOP_GRACE_PERIOD: u64 = 30;
+
+/// The number of seconds to wait for a node that has been started to report its peer ID and the
+/// addresses it's listening on.
+pub const DEFAULT_STARTUP_TIMEOUT: u64 = 60;
+
+#[derive(Debug)]
 pub enum UpgradeResult {
     NotRequired,
+    Skipped(String),
     Upgraded(String, String),
     Error(String),
 }
 
+#[derive(Debug, PartialEq)]
+pub enum VerifyResult {
+    Verified,
+    /// The checksum of the binary doesn't match the expected checksum.
+    Modified {
+        expected: String,
+        actual: String,
+    },
+    Missing,
+    /// There is no checksum to compare against, which is the case for nodes added before
+    /// checksums were recorded.
+    NotRecorded,
+}
+
+/// The settings to change for a node. Anything that is `None` is left as it is.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeEdit {
+    pub auto_restart: Option<u64>,
+    pub auto_restart_reset_period: Option<u64>,
+    /// Variables to set, replacing any existing values.
+    pub env_variables: Vec<(String, String)>,
+    pub keep_alive: Option<bool>,
+    pub log_targets: Option<String>,
+    pub max_memory: Option<u64>,
+    pub port: Option<u16>,
+    pub rpc_address: Option<IpAddr>,
+    pub rpc_port: Option<u16>,
+    pub throttle_interval: Option<u64>,
+    /// The names of the variables to remove.
+    pub unset_env_variables: Vec<String>,
+}
+
 pub async fn start(
     node: &mut Node,
     service_control: &dyn ServiceControl,
     rpc_client: &dyn RpcActions,
+) -> Result<()> {
+    start_with_timeout(node, service_control, rpc_client, DEFAULT_STARTUP_TIMEOUT).await
+}
+
+/// Start a node, waiting up to the timeout for it to report its peer ID and the addresses it's
+/// listening on.
+///
+/// A node that's slow to join the network may not be able to answer straight away, so its RPC
+/// service is polled once a second. If the node still hasn't reported any addresses when the
+/// timeout is reached, it's started without them, as long as its peer ID is known.
+pub async fn start_with_timeout(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+    startup_timeout: u64,
 ) -> Result<()> {
     if let NodeStatus::Running = node.status {
         // The last time we checked the service was running, but it doesn't mean it's actually
@@ -31,50 +101,111 @@ pub async fn start(
         // stopped because of a fault, we will drop to the code below and attempt to start it
         // again.
         if service_control.is_service_process_running(node.pid.unwrap()) {
-            println!("The {} service is already running", node.service_name);
+            report!("The {} service is already running", node.service_name);
             return Ok(());
         }
     }
 
     // At this point the service either hasn't been started for the first time or it has been
     // stopped. If it was stopped, it was either intentional or because it crashed.
-    println!("Attempting to start {}...", node.service_name);
+    report!("Attempting to start {}...", node.service_name);
     service_control.start(&node.service_name)?;
 
     // Give the node a little bit of time to start before initiating the node info query.
-    service_control.wait(3);
-    let node_info = rpc_client.node_info().await?;
+    service_control.wait(3).await;
+    let mut waited = 0;
+    let (node_info, listeners) = loop {
+        let result = async {
+            let node_info = rpc_client.node_info().await?;
+            let network_info = rpc_client.network_info().await?;
+            Ok::<_, color_eyre::Report>((node_info, network_info.listeners))
+        }
+        .await;
+        match result {
+            Ok((node_info, listeners)) if !listeners.is_empty() || waited >= startup_timeout => {
+                break (node_info, listeners);
+            }
+            Err(e) if waited >= startup_timeout => return Err(e),
+            _ => {
+                service_control.wait(1).await;
+                waited += 1;
+            }
+        }
+    };
     node.pid = Some(node_info.pid);
     node.peer_id = Some(node_info.peer_id);
+    node.listen_addr = Some(listeners);
     node.status = NodeStatus::Running;
 
-    println!("{} Started {} service", "✓".green(), node.service_name);
-    println!("  - Peer ID: {}", node_info.peer_id);
-    println!("  - Logs: {}", node_info.log_path.to_string_lossy());
+    report!("{} Started {} service", "✓".green(), node.service_name);
+    report!("  - Peer ID: {}", node_info.peer_id);
+    report!("  - Logs: {}", node_info.log_path.to_string_lossy());
 
     Ok(())
 }
 
 pub async fn stop(node: &mut Node, service_control: &dyn ServiceControl) -> Result<()> {
+    stop_with_grace_period(node, service_control, DEFAULT_STOP_GRACE_PERIOD).await
+}
+
+/// Stop a node, killing its process if it is still running once the grace period has passed.
+///
+/// The service manager is asked to stop the service first, which gives the node the chance to
+/// shut down cleanly. A node that hangs rather than exiting will then be killed, so it doesn't
+/// hold up an operation on many nodes. The method that was needed is recorded on the node.
+pub async fn stop_with_grace_period(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    grace_period: u64,
+) -> Result<()> {
     match node.status {
         NodeStatus::Added => Err(eyre!(
             "Service {} has not been started since it was installed",
             node.service_name
         )),
         NodeStatus::Removed => Err(eyre!("Service {} has been removed", node.service_name)),
+        // The process keeps running, so there's nothing to wait for.
+        NodeStatus::Running if is_dry_run() => {
+            service_control.stop(&node.service_name)?;
+            node.pid = None;
+            node.status = NodeStatus::Stopped;
+            Ok(())
+        }
         NodeStatus::Running => {
             let pid = node.pid.unwrap();
             if service_control.is_service_process_running(pid) {
-                println!("Attempting to stop {}...", node.service_name);
+                report!("Attempting to stop {}...", node.service_name);
                 service_control.stop(&node.service_name)?;
-                println!(
-                    "{} Service {} with PID {} was stopped",
-                    "✓".green(),
-                    node.service_name,
-                    pid
-                );
+
+                let mut waited = 0;
+                let stop_method = loop {
+                    if !service_control.is_service_process_running(pid) {
+                        break StopMethod::Graceful;
+                    }
+                    if waited >= grace_period {
+                        service_control.kill_process(pid)?;
+                        break StopMethod::Killed;
+                    }
+                    service_control.wait(1).await;
+                    waited += 1;
+                };
+                match stop_method {
+                    StopMethod::Graceful => report!(
+                        "{} Service {} with PID {} was stopped",
+                        "✓".green(),
+                        node.service_name,
+                        pid
+                    ),
+                    StopMethod::Killed => report!(
+                        "{} Service {} with PID {} did not stop within {grace_period} seconds and was killed",
+                        "⚠".yellow(),
+                        node.service_name,
+                        pid
+                    ),
+                }
+                node.last_stop_method = Some(stop_method);
             } else {
-                println!(
+                report!(
                     "{} Service {} was already stopped",
                     "✓".green(),
                     node.service_name
@@ -83,10 +214,11 @@ pub async fn stop(node: &mut Node, service_control: &dyn ServiceControl) -> Resu
             node.pid = None;
             node.status = NodeStatus::Stopped;
             node.connected_peers = None;
+            node.records_stored = None;
             Ok(())
         }
         NodeStatus::Stopped => {
-            println!(
+            report!(
                 "{} Service {} was already stopped",
                 "✓".green(),
                 node.service_name
@@ -96,17 +228,85 @@ pub async fn stop(node: &mut Node, service_control: &dyn ServiceControl) -> Resu
     }
 }
 
-pub async fn status(
+/// A sign that a node may have been shunned by the other nodes on the network.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HealthIssue {
+    NoConnectedPeers,
+    NotEarning(Duration),
+}
+
+impl std::fmt::Display for HealthIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HealthIssue::NoConnectedPeers => write!(f, "no connected peers"),
+            HealthIssue::NotEarning(period) => write!(
+                f,
+                "no earnings, payments or new records in the last {}",
+                format_duration(*period)
+            ),
+        }
+    }
+}
+
+/// Check a running node for signs that it has been shunned.
+///
+/// This is a heuristic. A shunned node will usually lose its connections, or remain connected but
+/// stop being given records to store, and hence stop earning. The earnings check relies on the
+/// history built by `earnings record`, so it only applies if that has been running for at least
+/// the length of the period.
+pub fn check_health(
+    node: &Node,
+    earnings_history: Option<&EarningsHistory>,
+    period: Duration,
+    now: DateTime<Utc>,
+) -> Vec<HealthIssue> {
+    let mut issues = Vec::new();
+    if node.status != NodeStatus::Running {
+        return issues;
+    }
+    if let Some(connected_peers) = &node.connected_peers {
+        if connected_peers.is_empty() {
+            issues.push(HealthIssue::NoConnectedPeers);
+        }
+    }
+    if let Some(earnings_history) = earnings_history {
+        let since = chrono::Duration::from_std(period)
+            .ok()
+            .and_then(|period| now.checked_sub_signed(period));
+        if let Some(since) = since {
+            if earnings_history.has_earned_since(&node.service_name, since) == Some(false) {
+                issues.push(HealthIssue::NotEarning(period));
+            }
+        }
+    }
+    issues
+}
+
+/// Bring the state of the nodes in the registry up to date with the services.
+///
+/// With `fetch_details`, the service manager is also asked how each node last exited, and each
+/// running node is asked how many records it holds.
+pub async fn refresh_node_registry(
     node_registry: &mut NodeRegistry,
     service_control: &dyn ServiceControl,
-    detailed_view: bool,
-    output_json: bool,
-    fail: bool,
-) -> Result<()> {
+    fetch_details: bool,
+) {
     // Again confirm that services which are marked running are still actually running.
     // If they aren't we'll mark them as stopped.
     for node in &mut node_registry.nodes {
-        let rpc_client = RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
+        // Asking the service manager how each node last exited is only worth the extra time when
+        // the details are wanted.
+        if fetch_details
+            && !node.local
+            && node.status != NodeStatus::Added
+            && node.status != NodeStatus::Removed
+        {
+            if let Ok(exit_info) = service_control.get_exit_info(&node.service_name) {
+                node.exit_info = Some(exit_info);
+            }
+        }
+
+        let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
         if let NodeStatus::Running = node.status {
             if let Some(pid) = node.pid {
                 // First we can try the PID we have now. If there is still a process running with
@@ -147,8 +347,74 @@ pub async fn status(
                 }
             }
         }
+
+        // Listing the node's records is only worth the extra time when the details are wanted.
+        if fetch_details && node.status == NodeStatus::Running {
+            node.records_stored = rpc_client
+                .record_addresses()
+                .await
+                .ok()
+                .map(|r| r.len() as u64);
+        }
+    }
+
+    if fetch_details {
+        measure_resource_usage(&mut node_registry.nodes);
+    }
+}
+
+/// Measure the disk usage of each node's data directory, and the memory and CPU used by the
+/// process of each running node.
+///
+/// The CPU usage is worked out from two readings of the processes, so there's a short wait in
+/// between, which is skipped if none of the nodes are running.
+fn measure_resource_usage(nodes: &mut [Node]) {
+    let mut system = System::new();
+    let pids = nodes
+        .iter()
+        .filter(|n| n.status == NodeStatus::Running)
+        .filter_map(|n| n.pid)
+        .map(|pid| Pid::from(pid as usize))
+        .collect::<Vec<Pid>>();
+    let mut any_running = false;
+    for pid in &pids {
+        any_running |= system.refresh_process(*pid);
+    }
+    if any_running {
+        std::thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL);
+        for pid in &pids {
+            system.refresh_process(*pid);
+        }
+    }
+
+    for node in nodes.iter_mut().filter(|n| n.status != NodeStatus::Removed) {
+        let process = node
+            .pid
+            .filter(|_| node.status == NodeStatus::Running)
+            .and_then(|pid| system.process(Pid::from(pid as usize)));
+        node.resource_usage = Some(ResourceUsage {
+            disk_bytes: node
+                .data_dir_path
+                .as_ref()
+                .and_then(|path| get_dir_size(path).ok()),
+            memory_bytes: process.map(|p| p.memory()),
+            cpu_percent: process.map(|p| p.cpu_usage()),
+        });
     }
+}
+
+pub async fn status(
+    node_registry: &mut NodeRegistry,
+    service_control: &dyn ServiceControl,
+    detailed_view: bool,
+    output_json: bool,
+    fail: bool,
+    earnings_history: Option<&EarningsHistory>,
+    health_period: Duration,
+) -> Result<()> {
+    refresh_node_registry(node_registry, service_control, detailed_view || output_json).await;
 
+    let now = Utc::now();
     if output_json {
         let json = serde_json::to_string(&node_registry.nodes)?;
         println!("{json}");
@@ -156,68 +422,170 @@ pub async fn status(
         for node in &node_registry.nodes {
             let service_status = format!("{} - {}", node.service_name, format_status(&node.status));
             let banner = "=".repeat(service_status.len());
-            println!("{}", banner);
-            println!("{service_status}");
-            println!("{}", banner);
-            println!("Version: {}", node.version);
-            println!(
+            report!("{}", banner);
+            report!("{service_status}");
+            report!("{}", banner);
+            report!("Version: {}", node.version);
+            report!(
                 "Peer ID: {}",
                 node.peer_id.map_or("-".to_string(), |p| p.to_string())
             );
-            println!("Port: {}", node.port);
-            println!("RPC Port: {}", node.rpc_port);
-            println!(
+            report!("Port: {}", node.port);
+            report!("RPC Port: {}", node.rpc_port);
+            if let Some(rpc_address) = node.rpc_address {
+                report!("RPC Address: {rpc_address}");
+            }
+            report!(
                 "Multiaddr: {}",
                 node.get_multiaddr()
                     .map_or("-".to_string(), |m| m.to_string())
             );
-            println!(
+            report!(
+                "Listen addresses: {}",
+                node.listen_addr
+                    .as_ref()
+                    .map_or("-".to_string(), |addrs| addrs
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "))
+            );
+            report!(
                 "PID: {}",
                 node.pid.map_or("-".to_string(), |p| p.to_string())
             );
-            println!(
+            report!(
                 "Data path: {}",
                 node.data_dir_path
                     .as_ref()
                     .map_or("-".to_string(), |p| p.to_string_lossy().to_string())
             );
-            println!(
+            report!(
                 "Log path: {}",
                 node.log_dir_path
                     .as_ref()
                     .map_or("-".to_string(), |p| p.to_string_lossy().to_string())
             );
-            println!(
+            report!(
                 "Bin path: {}",
                 node.safenode_path
                     .as_ref()
                     .map_or("-".to_string(), |p| p.to_string_lossy().to_string())
             );
-            println!(
+            report!(
+                "Rewards address: {}",
+                node.rewards_address.as_deref().unwrap_or("-")
+            );
+            report!("Owner: {}", node.owner.as_deref().unwrap_or("-"));
+            report!(
+                "Maintenance: {}",
+                if node.maintenance { "on" } else { "off" }
+            );
+            if let Some(exit_info) = &node.exit_info {
+                report!("Last exit: {exit_info}");
+                report!(
+                    "Restarts: {}",
+                    exit_info
+                        .restart_count
+                        .map_or("-".to_string(), |c| c.to_string())
+                );
+            }
+            report!(
+                "Last stop: {}",
+                match node.last_stop_method {
+                    Some(StopMethod::Graceful) => "graceful",
+                    Some(StopMethod::Killed) => "killed after the grace period",
+                    None => "-",
+                }
+            );
+            report!("Watchdog restarts: {}", node.watchdog_restarts);
+            if let Some(failure) = &node.last_failure {
+                report!(
+                    "Last failure: {} ({})",
+                    failure.reason,
+                    failure.time.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+            }
+            report!(
+                "Restart schedule: {}",
+                node.restart_schedule
+                    .as_deref()
+                    .or(node_registry.restart_schedule.as_deref())
+                    .unwrap_or("-")
+            );
+            report!(
                 "Connected peers: {}",
                 node.connected_peers
                     .as_ref()
                     .map_or("-".to_string(), |p| p.len().to_string())
             );
-            println!();
+            report!(
+                "Records stored: {}",
+                node.records_stored
+                    .map_or("-".to_string(), |r| r.to_string())
+            );
+            if let Some(usage) = &node.resource_usage {
+                report!(
+                    "Disk usage: {}",
+                    usage
+                        .disk_bytes
+                        .map_or("-".to_string(), |b| format!("{} MB", b / MB))
+                );
+                report!(
+                    "Memory: {}",
+                    usage
+                        .memory_bytes
+                        .map_or("-".to_string(), |b| format!("{} MB", b / MB))
+                );
+                report!(
+                    "CPU: {}",
+                    usage
+                        .cpu_percent
+                        .map_or("-".to_string(), |c| format!("{c:.1}%"))
+                );
+            }
+            if node.status == NodeStatus::Running {
+                let issues = check_health(node, earnings_history, health_period, now);
+                if issues.is_empty() {
+                    report!("Health: OK");
+                } else {
+                    report!(
+                        "Health: possibly shunned ({})",
+                        issues
+                            .iter()
+                            .map(|i| i.to_string())
+                            .collect::<Vec<String>>()
+                            .join("; ")
+                    );
+                }
+            }
+            report!();
         }
     } else {
-        println!(
+        report!(
             "{:<18} {:<52} {:<7} {:>15}",
-            "Service Name", "Peer ID", "Status", "Connected Peers"
+            "Service Name",
+            "Peer ID",
+            "Status",
+            "Connected Peers"
         );
         let nodes = node_registry
             .nodes
             .iter()
             .filter(|n| n.status != NodeStatus::Removed)
             .collect::<Vec<&Node>>();
+        let mut unhealthy = Vec::new();
         for node in nodes {
+            let issues = check_health(node, earnings_history, health_period, now);
+            if !issues.is_empty() {
+                unhealthy.push((node.service_name.clone(), issues));
+            }
             let peer_id = node.peer_id.map_or("-".to_string(), |p| p.to_string());
             let connected_peers = node
                 .connected_peers
                 .clone()
                 .map_or("-".to_string(), |p| p.len().to_string());
-            println!(
+            report!(
                 "{:<18} {:<52} {:<7} {:>15}",
                 node.service_name,
                 peer_id,
@@ -225,6 +593,21 @@ pub async fn status(
                 connected_peers
             );
         }
+        for node in node_registry
+            .nodes
+            .iter()
+            .filter(|n| n.maintenance && n.status != NodeStatus::Removed)
+        {
+            report!("- {} is in maintenance mode", node.service_name);
+        }
+        for (service_name, issues) in unhealthy {
+            for issue in issues {
+                report!(
+                    "{} {service_name} may have been shunned: {issue}",
+                    "⚠".yellow()
+                );
+            }
+        }
     }
 
     if fail
@@ -265,16 +648,22 @@ pub async fn remove(
     }
 
     service_control.uninstall(&node.service_name)?;
+    if node.firewall_rule_added {
+        service_control.remove_firewall_rule(&node.service_name)?;
+        node.firewall_rule_added = false;
+    }
 
     if !keep_directories {
-        std::fs::remove_dir_all(node.data_dir_path.as_ref().ok_or_else(|| {
-            eyre!("The data directory should be set before the node is removed")
-        })?)?;
-        std::fs::remove_dir_all(
-            node.log_dir_path.as_ref().ok_or_else(|| {
-                eyre!("The log directory should be set before the node is removed")
-            })?,
-        )?;
+        let data_dir_path = node
+            .data_dir_path
+            .as_ref()
+            .ok_or_else(|| eyre!("The data directory should be set before the node is removed"))?;
+        let log_dir_path = node
+            .log_dir_path
+            .as_ref()
+            .ok_or_else(|| eyre!("The log directory should be set before the node is removed"))?;
+        remove_dir(data_dir_path)?;
+        remove_dir(log_dir_path)?;
         node.data_dir_path = None;
         node.log_dir_path = None;
         node.safenode_path = None;
@@ -282,101 +671,871 @@ pub async fn remove(
 
     node.status = NodeStatus::Removed;
 
-    println!("{} Service {} was removed", "✓".green(), node.service_name);
+    if !is_dry_run() {
+        report!("{} Service {} was removed", "✓".green(), node.service_name);
+    }
+
+    Ok(())
+}
+
+/// Delete a directory and everything in it, or for a dry run, report that it would be deleted.
+fn remove_dir(dir_path: &Path) -> Result<()> {
+    if is_dry_run() {
+        report!("Would delete {}", dir_path.to_string_lossy());
+        return Ok(());
+    }
+    std::fs::remove_dir_all(dir_path)?;
+    Ok(())
+}
 
+/// Stop and remove every service, delete the data and log directories of all the nodes, and
+/// clear the nodes from the registry.
+///
+/// The directories of services that were removed while keeping them are deleted too.
+pub async fn reset(
+    node_registry: &mut NodeRegistry,
+    service_control: &dyn ServiceControl,
+) -> Result<()> {
+    for node in node_registry.nodes.iter_mut() {
+        if node.status == NodeStatus::Running {
+            stop(node, service_control).await?;
+        }
+        if node.status != NodeStatus::Removed {
+            remove(node, service_control, true).await?;
+        }
+        for dir_path in [&node.data_dir_path, &node.log_dir_path]
+            .into_iter()
+            .flatten()
+        {
+            if dir_path.exists() {
+                remove_dir(dir_path)?;
+            }
+        }
+    }
+    node_registry.nodes.clear();
     Ok(())
 }
 
+/// Restart a node, stopping it first if it's running.
+///
+/// The node keeps its peer ID unless `retain_peer_id` is false. In that case its data directory
+/// is cleared before it's started again, which removes its secret key, so it joins the network as
+/// a new node. The `safenode` binary, the versions kept for rolling back and the wallet are left
+/// in place, so any balance the node hasn't claimed is kept.
+pub async fn restart(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+    retain_peer_id: bool,
+    startup_timeout: u64,
+) -> Result<()> {
+    match node.status {
+        NodeStatus::Removed => {
+            return Err(eyre!("Service {} has been removed", node.service_name));
+        }
+        NodeStatus::Running => stop(node, service_control).await?,
+        _ => {}
+    }
+
+    if !retain_peer_id {
+        let data_dir_path = node
+            .data_dir_path
+            .as_ref()
+            .ok_or_else(|| eyre!("The data directory should be set for an installed node"))?;
+        let safenode_file_name = node.safenode_path.as_ref().and_then(|p| p.file_name());
+        for entry in std::fs::read_dir(data_dir_path)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            if Some(file_name.as_os_str()) == safenode_file_name
+                || file_name == "versions"
+                || file_name == "wallet"
+            {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(entry.path())?;
+            } else {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        report!(
+            "Cleared the data directory of {}, so it will have a new peer ID",
+            node.service_name
+        );
+        node.peer_id = None;
+        node.connected_peers = None;
+        node.records_stored = None;
+    }
+
+    start_with_timeout(node, service_control, rpc_client, startup_timeout).await
+}
+
+/// Check that a node the registry has as running is alive and answering on its RPC service.
+///
+/// The reason it isn't is returned, or `None` if it's healthy.
+pub async fn check_responsive(
+    node: &Node,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+) -> Option<String> {
+    match node.pid {
+        Some(pid) if service_control.is_service_process_running(pid) => {}
+        Some(pid) => return Some(format!("the process with PID {pid} is not running")),
+        None => return Some("the process ID is not known".to_string()),
+    }
+    match rpc_client.node_info().await {
+        Ok(_) => None,
+        Err(e) => Some(format!("the RPC service did not respond: {e}")),
+    }
+}
+
+/// Restart a node the watchdog found wasn't responding, recording why on the node.
+///
+/// A node whose process is hung rather than gone is killed if it doesn't stop within the grace
+/// period. The node keeps its peer ID.
+pub async fn watchdog_restart(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+    reason: String,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    node.watchdog_restarts += 1;
+    node.last_failure = Some(NodeFailure { reason, time: now });
+    restart(
+        node,
+        service_control,
+        rpc_client,
+        true,
+        DEFAULT_STARTUP_TIMEOUT,
+    )
+    .await
+}
+
+/// Check whether a running node looks shunned enough for the watchdog to recycle it.
+///
+/// This is `check_health`, except a node that hasn't been running for the whole period, going by
+/// the uptime it reports, is left alone. It won't have had the chance to connect and earn yet,
+/// whether it was started by hand or was just recycled.
+pub fn get_recycle_issues(
+    node: &Node,
+    uptime: Duration,
+    earnings_history: Option<&EarningsHistory>,
+    period: Duration,
+    now: DateTime<Utc>,
+) -> Vec<HealthIssue> {
+    if uptime < period {
+        return Vec::new();
+    }
+    check_health(node, earnings_history, period, now)
+}
+
+/// Restart a node that looks shunned with a new peer ID, so it joins the network as a new node.
+///
+/// Only its secret key and record store are removed. The wallet is kept, so any balance it hasn't
+/// claimed isn't lost, and so is everything else in the data directory. The issues are recorded
+/// on the node as the reason for its last failure.
+pub async fn recycle(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+    issues: &[HealthIssue],
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let reason = issues
+        .iter()
+        .map(|issue| issue.to_string())
+        .collect::<Vec<_>>()
+        .join("; ");
+    node.watchdog_restarts += 1;
+    node.last_failure = Some(NodeFailure {
+        reason: format!("possibly shunned: {reason}"),
+        time: now,
+    });
+    if node.status == NodeStatus::Running {
+        stop(node, service_control).await?;
+    }
+
+    let data_dir_path = node
+        .data_dir_path
+        .as_ref()
+        .ok_or_else(|| eyre!("The data directory should be set for an installed node"))?;
+    let secret_key_path = data_dir_path.join("secret-key");
+    if secret_key_path.exists() {
+        std::fs::remove_file(secret_key_path)?;
+    }
+    let record_store_path = data_dir_path.join("record_store");
+    if record_store_path.exists() {
+        std::fs::remove_dir_all(record_store_path)?;
+    }
+    node.peer_id = None;
+    node.connected_peers = None;
+    node.records_stored = None;
+
+    start_with_timeout(node, service_control, rpc_client, DEFAULT_STARTUP_TIMEOUT).await
+}
+
+/// Switch a node to the target version of `safenode`.
+///
+/// The target is usually the latest version. A node at a later version than the target is only
+/// downgraded if `force` is set, since that's only wanted when a new release has gone wrong.
 pub async fn upgrade(
     node: &mut Node,
-    upgraded_safenode_path: &PathBuf,
-    latest_version: &Version,
+    upgraded_safenode_path: &Path,
+    target_version: &Version,
     service_control: &dyn ServiceControl,
     rpc_client: &dyn RpcActions,
+    force: bool,
 ) -> Result<UpgradeResult> {
     let current_version = Version::parse(&node.version)?;
-    if current_version == *latest_version {
+    if current_version == *target_version {
         return Ok(UpgradeResult::NotRequired);
     }
+    if current_version > *target_version && !force {
+        return Ok(UpgradeResult::Skipped(format!(
+            "it is at a later version, {current_version}, and --force is needed to downgrade it"
+        )));
+    }
 
-    stop(node, service_control).await?;
-    std::fs::copy(
+    let safenode_path = node
+        .safenode_path
+        .clone()
+        .ok_or_else(|| eyre!("Unable to obtain safenode path for current node"))?;
+
+    if is_dry_run() {
+        report!(
+            "Would copy version {target_version} of safenode to {}, then restart {} with it",
+            get_version_path(&safenode_path, &target_version.to_string())?.to_string_lossy(),
+            node.service_name
+        );
+        return Ok(UpgradeResult::Upgraded(
+            current_version.to_string(),
+            target_version.to_string(),
+        ));
+    }
+
+    // Nodes that were added before versions were kept side by side have the binary itself at the
+    // safenode path, so it needs to be kept as the current version before it can be switched.
+    if !safenode_path.is_symlink() {
+        stage_version(&safenode_path, &safenode_path, &node.version)?;
+    }
+    let previous_version_path = get_version_path(&safenode_path, &node.version)?;
+    let upgraded_version_path = stage_version(
+        &safenode_path,
         upgraded_safenode_path,
-        node.safenode_path
-            .as_ref()
-            .ok_or_else(|| eyre!("Unable to obtain safenode path for current node"))?,
+        &target_version.to_string(),
     )?;
-    start(node, service_control, rpc_client).await?;
-    node.version = latest_version.to_string();
+
+    stop(node, service_control).await?;
+    activate_version(&safenode_path, &upgraded_version_path)?;
+    if let Err(e) = start(node, service_control, rpc_client).await {
+        activate_version(&safenode_path, &previous_version_path)?;
+        start(node, service_control, rpc_client).await?;
+        return Err(eyre!(
+            "{} did not start at version {target_version}, so it was rolled back to {}: {e}",
+            node.service_name,
+            node.version
+        ));
+    }
+    node.safenode_checksum = Some(get_file_checksum(&safenode_path)?);
+    node.previous_version = Some(node.version.clone());
+    node.version = target_version.to_string();
+    if let Err(e) = prune_versions(
+        &safenode_path,
+        &[&node.version, &current_version.to_string()],
+    ) {
+        report!(
+            "[!] Warning: could not remove the old versions of safenode for {}: {e}",
+            node.service_name
+        );
+    }
 
     Ok(UpgradeResult::Upgraded(
         current_version.to_string(),
-        latest_version.to_string(),
+        target_version.to_string(),
     ))
 }
 
-fn format_status(status: &NodeStatus) -> String {
-    match status {
-        NodeStatus::Running => "RUNNING".green().to_string(),
-        NodeStatus::Stopped => "STOPPED".red().to_string(),
-        NodeStatus::Added => "ADDED".yellow().to_string(),
-        NodeStatus::Removed => "REMOVED".red().to_string(),
+/// Switch a node back to the version it was running before it was last upgraded.
+///
+/// Since the previous binary is kept, this is only a matter of pointing the safenode path back at
+/// it and restarting the node. Returns the versions the node was rolled back from and to.
+pub async fn rollback(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+) -> Result<(String, String)> {
+    let previous_version = node.previous_version.clone().ok_or_else(|| {
+        eyre!(
+            "{} has no previous version to roll back to",
+            node.service_name
+        )
+    })?;
+    let safenode_path = node
+        .safenode_path
+        .clone()
+        .ok_or_else(|| eyre!("Unable to obtain safenode path for current node"))?;
+    let previous_version_path = get_version_path(&safenode_path, &previous_version)?;
+    if !previous_version_path.exists() {
+        return Err(eyre!(
+            "The binary for version {previous_version} of {} is missing",
+            node.service_name
+        ));
+    }
+
+    let was_running = node.status == NodeStatus::Running;
+    if was_running {
+        stop(node, service_control).await?;
+    }
+    activate_version(&safenode_path, &previous_version_path)?;
+    node.safenode_checksum = Some(get_file_checksum(&safenode_path)?);
+    let rolled_back_version = std::mem::replace(&mut node.version, previous_version);
+    node.previous_version = Some(rolled_back_version.clone());
+    if was_running {
+        start(node, service_control, rpc_client).await?;
     }
+
+    Ok((rolled_back_version, node.version.clone()))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::service::MockServiceControl;
-    use assert_fs::prelude::*;
-    use assert_matches::assert_matches;
-    use async_trait::async_trait;
-    use libp2p_identity::PeerId;
-    use mockall::mock;
-    use mockall::predicate::*;
-    use mockall::Sequence;
-    use predicates::prelude::*;
-    use sn_node_rpc_client::{
-        NetworkInfo, NodeInfo, RecordAddress, Result as RpcResult, RpcActions,
-    };
-    use sn_protocol::node_registry::{Node, NodeStatus};
-    use std::path::PathBuf;
-    use std::str::FromStr;
+/// Get the path of the binary for a version, which is kept in a directory for that version
+/// alongside the node's safenode path.
+fn get_version_path(safenode_path: &Path, version: &str) -> Result<PathBuf> {
+    let file_name = safenode_path
+        .file_name()
+        .ok_or_else(|| eyre!("Could not get filename from the safenode path"))?;
+    let dir_path = safenode_path
+        .parent()
+        .ok_or_else(|| eyre!("Could not get the directory of the safenode path"))?;
+    Ok(dir_path.join("versions").join(version).join(file_name))
+}
 
-    mock! {
-        pub RpcClient {}
-        #[async_trait]
-        impl RpcActions for RpcClient {
-            async fn node_info(&self) -> RpcResult<NodeInfo>;
-            async fn network_info(&self) -> RpcResult<NetworkInfo>;
-            async fn record_addresses(&self) -> RpcResult<Vec<RecordAddress>>;
-            async fn gossipsub_subscribe(&self, topic: &str) -> RpcResult<()>;
-            async fn gossipsub_unsubscribe(&self, topic: &str) -> RpcResult<()>;
-            async fn gossipsub_publish(&self, topic: &str, message: &str) -> RpcResult<()>;
-            async fn node_restart(&self, delay_millis: u64) -> RpcResult<()>;
-            async fn node_stop(&self, delay_millis: u64) -> RpcResult<()>;
-            async fn node_update(&self, delay_millis: u64) -> RpcResult<()>;
+/// Copy a binary into the directory for its version, without changing the version the node runs.
+///
+/// The binary is written under a temporary name and then renamed, so a failed copy never leaves
+/// something that could be mistaken for a complete binary.
+fn stage_version(safenode_path: &Path, binary_path: &Path, version: &str) -> Result<PathBuf> {
+    let version_path = get_version_path(safenode_path, version)?;
+    if let Some(parent) = version_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial_path = version_path.with_extension("partial");
+    std::fs::copy(binary_path, &partial_path)?;
+    std::fs::rename(&partial_path, &version_path)?;
+    Ok(version_path)
+}
+
+/// Remove the binaries for every version apart from those to keep.
+///
+/// Only the current and previous versions are needed, to run the node and to roll it back, so
+/// the others would otherwise build up with every upgrade.
+fn prune_versions(safenode_path: &Path, keep: &[&str]) -> Result<()> {
+    let versions_dir_path = safenode_path
+        .parent()
+        .ok_or_else(|| eyre!("Could not get the directory of the safenode path"))?
+        .join("versions");
+    for entry in std::fs::read_dir(versions_dir_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name();
+        if !keep.iter().any(|version| name == **version) {
+            std::fs::remove_dir_all(entry.path())?;
         }
     }
+    Ok(())
+}
 
-    #[tokio::test]
-    async fn start_should_start_a_newly_installed_service() -> Result<()> {
-        let mut mock_service_control = MockServiceControl::new();
-        let mut mock_rpc_client = MockRpcClient::new();
+/// Point the safenode path at the binary for a version.
+///
+/// A new link is created and renamed over the safenode path, which replaces it in one step, so
+/// the path always refers to a complete binary.
+fn activate_version(safenode_path: &Path, version_path: &Path) -> Result<()> {
+    let link_path = safenode_path.with_extension("link");
+    if link_path.is_symlink() {
+        std::fs::remove_file(&link_path)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(version_path, &link_path)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file(version_path, &link_path)?;
+    std::fs::rename(&link_path, safenode_path)?;
+    Ok(())
+}
 
-        mock_service_control
-            .expect_start()
-            .with(eq("Safenode service 1"))
-            .times(1)
-            .returning(|_| Ok(()));
-        mock_service_control
-            .expect_wait()
-            .with(eq(3))
-            .times(1)
-            .returning(|_| ());
-        mock_rpc_client.expect_node_info().times(1).returning(|| {
-            Ok(NodeInfo {
-                pid: 1000,
+/// Check the safenode binary for a node has not been modified since it was installed.
+///
+/// The binary is compared against the checksum recorded in the registry, unless a checksum for the
+/// release of the node's version is supplied.
+pub fn verify(node: &Node, release_checksum: Option<&str>) -> Result<VerifyResult> {
+    let safenode_path = node
+        .safenode_path
+        .as_ref()
+        .ok_or_else(|| eyre!("Unable to obtain safenode path for current node"))?;
+    if !safenode_path.exists() {
+        return Ok(VerifyResult::Missing);
+    }
+    let Some(expected) = release_checksum.or(node.safenode_checksum.as_deref()) else {
+        return Ok(VerifyResult::NotRecorded);
+    };
+    let actual = get_file_checksum(safenode_path)?;
+    if actual != expected {
+        return Ok(VerifyResult::Modified {
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    Ok(VerifyResult::Verified)
+}
+
+/// Change the rewards address used by an existing node.
+///
+/// The address is supplied to safenode as an argument, so the service definition needs to be
+/// regenerated. If the node was running, it will be restarted so that the new address takes
+/// effect; its data directory and peer ID are retained.
+pub async fn set_rewards_address(
+    node: &mut Node,
+    rewards_address: &str,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+) -> Result<()> {
+    if node.status == NodeStatus::Removed {
+        return Err(eyre!("Service {} has been removed", node.service_name));
+    }
+    validate_rewards_address(rewards_address)?;
+
+    let previous_address = node.rewards_address.clone();
+    reconfigure(node, service_control, rpc_client, |node| {
+        node.rewards_address = Some(rewards_address.to_string())
+    })
+    .await?;
+
+    report!(
+        "{} Rewards address for {} changed from {} to {}",
+        "✓".green(),
+        node.service_name,
+        previous_address.unwrap_or("-".to_string()),
+        rewards_address
+    );
+
+    Ok(())
+}
+
+/// Change the owner recorded against an existing node.
+///
+/// Like the rewards address, the owner is supplied to safenode as an argument, so the service is
+/// regenerated and restarted in the same way.
+pub async fn set_owner(
+    node: &mut Node,
+    owner: &str,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+) -> Result<()> {
+    if node.status == NodeStatus::Removed {
+        return Err(eyre!("Service {} has been removed", node.service_name));
+    }
+    if owner.trim().is_empty() {
+        return Err(eyre!("The owner cannot be empty"));
+    }
+
+    let previous_owner = node.owner.clone();
+    reconfigure(node, service_control, rpc_client, |node| {
+        node.owner = Some(owner.to_string())
+    })
+    .await?;
+
+    report!(
+        "{} Owner for {} changed from {} to {}",
+        "✓".green(),
+        node.service_name,
+        previous_owner.unwrap_or("-".to_string()),
+        owner
+    );
+
+    Ok(())
+}
+
+/// Change the settings of an existing node.
+///
+/// The service definition is regenerated with the new settings, and if the node was running, it
+/// will be restarted; its data directory and peer ID are retained. The supplied RPC client should
+/// use the new RPC address and port, if they're being changed.
+pub async fn edit(
+    node: &mut Node,
+    edit: NodeEdit,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+) -> Result<()> {
+    if node.status == NodeStatus::Removed {
+        return Err(eyre!("Service {} has been removed", node.service_name));
+    }
+    if edit == NodeEdit::default() {
+        return Err(eyre!("No settings were supplied to change"));
+    }
+    for port in [edit.port, edit.rpc_port].into_iter().flatten() {
+        if port != node.port && port != node.rpc_port && !service_control.is_port_free(port) {
+            return Err(eyre!("Port {port} is already in use")
+                .suggestion("Please try again with an available port"));
+        }
+    }
+
+    let previous_port = node.port;
+    reconfigure(node, service_control, rpc_client, |node| {
+        if let Some(auto_restart) = edit.auto_restart {
+            node.auto_restart = Some(auto_restart);
+        }
+        if let Some(period) = edit.auto_restart_reset_period {
+            node.auto_restart_reset_period = period;
+        }
+        merge_env_variables(&mut node.env_variables, edit.env_variables);
+        node.env_variables
+            .retain(|(name, _)| !edit.unset_env_variables.contains(name));
+        if let Some(keep_alive) = edit.keep_alive {
+            node.keep_alive = keep_alive;
+        }
+        if let Some(log_targets) = edit.log_targets {
+            node.log_targets = Some(log_targets);
+        }
+        if let Some(max_memory) = edit.max_memory {
+            node.max_memory = Some(max_memory);
+        }
+        if let Some(port) = edit.port {
+            node.port = port;
+        }
+        if let Some(rpc_address) = edit.rpc_address {
+            node.rpc_address = Some(rpc_address);
+        }
+        if let Some(rpc_port) = edit.rpc_port {
+            node.rpc_port = rpc_port;
+        }
+        if let Some(throttle_interval) = edit.throttle_interval {
+            node.throttle_interval = Some(throttle_interval);
+        }
+    })
+    .await?;
+
+    // The firewall rule is for a specific port.
+    if node.firewall_rule_added && node.port != previous_port {
+        service_control.remove_firewall_rule(&node.service_name)?;
+        service_control.add_firewall_rule(&node.service_name, node.port)?;
+    }
+
+    report!("{} Settings for {} changed", "✓".green(), node.service_name);
+
+    Ok(())
+}
+
+/// Apply a change to a node's registry entry and regenerate its service definition.
+///
+/// A running node is stopped before the change and started again afterwards.
+async fn reconfigure(
+    node: &mut Node,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+    change: impl FnOnce(&mut Node),
+) -> Result<()> {
+    let was_running = node.status == NodeStatus::Running;
+    if was_running {
+        stop(node, service_control).await?;
+    }
+
+    change(node);
+    reinstall(node, service_control)?;
+
+    if was_running {
+        start(node, service_control, rpc_client).await?;
+    }
+    Ok(())
+}
+
+/// Give a node a new service name.
+///
+/// The service is recreated under the new name. If the node was running, it will be restarted;
+/// its data directory, log directory and peer ID are retained, so those paths will still have
+/// the old name. The caller is responsible for checking no other node has the new name.
+pub async fn rename(
+    node: &mut Node,
+    new_name: &str,
+    service_control: &dyn ServiceControl,
+    rpc_client: &dyn RpcActions,
+) -> Result<()> {
+    if node.status == NodeStatus::Removed {
+        return Err(eyre!("Service {} has been removed", node.service_name));
+    }
+    let is_valid = new_name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && new_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid {
+        return Err(
+            eyre!("The name '{new_name}' is not a valid service name").suggestion(
+                "Use letters, numbers, hyphens and underscores, starting with a letter or number",
+            ),
+        );
+    }
+
+    let was_running = node.status == NodeStatus::Running;
+    if was_running {
+        stop(node, service_control).await?;
+    }
+
+    let previous_name = node.service_name.clone();
+    service_control.uninstall(&previous_name)?;
+    if node.firewall_rule_added {
+        service_control.remove_firewall_rule(&previous_name)?;
+    }
+    node.service_name = new_name.to_string();
+    service_control.install(get_service_config(node)?)?;
+    if node.firewall_rule_added {
+        service_control.add_firewall_rule(&node.service_name, node.port)?;
+    }
+
+    if was_running {
+        start(node, service_control, rpc_client).await?;
+    }
+
+    report!(
+        "{} Renamed {} to {}",
+        "✓".green(),
+        previous_name,
+        node.service_name
+    );
+
+    Ok(())
+}
+
+/// Reverse the changes an operation made to the services, and return the registry as it was
+/// before the operation.
+///
+/// Services that were added by the operation are uninstalled, and services whose definition was
+/// changed are reinstalled with their previous settings. Neither is safe while the node is running,
+/// so added nodes must be stopped first, and a running node whose settings changed keeps its
+/// current service definition until it is changed again. Removing, renaming, upgrading or rolling
+/// back nodes can't be reversed this way, so those operations are refused.
+///
+/// The state of each node, like whether it is running, is kept from the current registry.
+pub fn undo(
+    current: &NodeRegistry,
+    previous: &NodeRegistry,
+    service_control: &dyn ServiceControl,
+) -> Result<NodeRegistry> {
+    let find_previous = |service_name: &str| {
+        previous
+            .nodes
+            .iter()
+            .find(|n| n.service_name == service_name && n.status != NodeStatus::Removed)
+    };
+
+    for node in current.nodes.iter() {
+        match find_previous(&node.service_name) {
+            Some(_) if node.status == NodeStatus::Removed => {
+                return Err(
+                    eyre!("The removal of {} cannot be undone", node.service_name)
+                        .suggestion("Add a new node to replace it"),
+                );
+            }
+            Some(previous_node) if previous_node.version != node.version => {
+                return Err(eyre!(
+                    "The version change of {} cannot be undone",
+                    node.service_name
+                )
+                .suggestion("Use the rollback command to switch the node's version back"));
+            }
+            None if node.status == NodeStatus::Running => {
+                return Err(eyre!(
+                    "The {} service was added, but it is now running",
+                    node.service_name
+                )
+                .suggestion("Stop the node then try again"));
+            }
+            _ => {}
+        }
+    }
+    for previous_node in previous
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        if !current
+            .nodes
+            .iter()
+            .any(|n| n.service_name == previous_node.service_name)
+        {
+            return Err(eyre!(
+                "The {} service no longer exists, so the operation cannot be undone",
+                previous_node.service_name
+            )
+            .suggestion(
+                "If the service was renamed, use the node rename command to change it back",
+            ));
+        }
+    }
+
+    let mut restored = previous.clone();
+    restored.save_path = current.save_path.clone();
+    for node in current
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        match restored
+            .nodes
+            .iter_mut()
+            .find(|n| n.service_name == node.service_name)
+        {
+            None => {
+                report!("Removing {}, which was added...", node.service_name);
+                service_control.uninstall(&node.service_name)?;
+                if node.firewall_rule_added {
+                    service_control.remove_firewall_rule(&node.service_name)?;
+                }
+            }
+            Some(previous_node) => {
+                previous_node.status = node.status.clone();
+                previous_node.pid = node.pid;
+                previous_node.peer_id = node.peer_id;
+                previous_node.connected_peers = node.connected_peers.clone();
+                previous_node.last_stop_method = node.last_stop_method.clone();
+                previous_node.exit_info = node.exit_info.clone();
+                previous_node.watchdog_restarts = node.watchdog_restarts;
+                previous_node.last_failure = node.last_failure.clone();
+
+                if get_service_config(previous_node)? == get_service_config(node)? {
+                    continue;
+                }
+                if node.status == NodeStatus::Running {
+                    report!(
+                        "{} {} is running, so its service will keep its current settings",
+                        "⚠".yellow(),
+                        node.service_name
+                    );
+                } else {
+                    report!("Restoring the settings of {}...", node.service_name);
+                    reinstall(previous_node, service_control)?;
+                }
+            }
+        }
+    }
+
+    Ok(restored)
+}
+
+/// Regenerate the service definition for a node using what has been retained in the registry.
+///
+/// The service should not be running when this is called.
+fn reinstall(node: &Node, service_control: &dyn ServiceControl) -> Result<()> {
+    service_control.uninstall(&node.service_name)?;
+    service_control.install(get_service_config(node)?)?;
+    Ok(())
+}
+
+pub fn get_service_config(node: &Node) -> Result<ServiceConfig> {
+    Ok(ServiceConfig {
+        auto_restart: node.auto_restart,
+        auto_restart_reset_period: node.auto_restart_reset_period,
+        data_dir_path: node
+            .data_dir_path
+            .clone()
+            .ok_or_else(|| eyre!("The data directory should be set for an installed node"))?,
+        env_variables: node.env_variables.clone(),
+        extra_args: node.extra_args.clone(),
+        genesis: node.genesis,
+        home_network: node.home_network,
+        keep_alive: node.keep_alive,
+        local: node.local,
+        log_dir_path: node
+            .log_dir_path
+            .clone()
+            .ok_or_else(|| eyre!("The log directory should be set for an installed node"))?,
+        log_targets: node.log_targets.clone(),
+        max_memory: node.max_memory,
+        name: node.service_name.clone(),
+        node_ip: node.node_ip,
+        node_port: node.port,
+        node_type: node.node_type.clone(),
+        owner: node.owner.clone(),
+        peers: node.peers.clone(),
+        rewards_address: node.rewards_address.clone(),
+        rpc_address: node.rpc_address,
+        rpc_port: node.rpc_port,
+        safenode_path: node
+            .safenode_path
+            .clone()
+            .ok_or_else(|| eyre!("The safenode path should be set for an installed node"))?,
+        service_user: node.user.clone(),
+        throttle_interval: node.throttle_interval,
+        upnp: node.upnp,
+    })
+}
+
+fn format_status(status: &NodeStatus) -> String {
+    match status {
+        NodeStatus::Running => "RUNNING".green().to_string(),
+        NodeStatus::Stopped => "STOPPED".red().to_string(),
+        NodeStatus::Added => "ADDED".yellow().to_string(),
+        NodeStatus::Removed => "REMOVED".red().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::earnings::NodeSnapshot;
+    use crate::node_registry::{Node, NodeStatus, NodeType};
+    use crate::service::MockServiceControl;
+    use assert_fs::prelude::*;
+    use assert_matches::assert_matches;
+    use async_trait::async_trait;
+    use libp2p::Multiaddr;
+    use libp2p_identity::PeerId;
+    use mockall::mock;
+    use mockall::predicate::*;
+    use mockall::Sequence;
+    use predicates::prelude::*;
+    use sn_node_rpc_client::{
+        NetworkInfo, NodeInfo, RecordAddress, Result as RpcResult, RpcActions,
+    };
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    const REWARDS_ADDRESS: &str =
+        "97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb";
+
+    mock! {
+        pub RpcClient {}
+        #[async_trait]
+        impl RpcActions for RpcClient {
+            async fn node_info(&self) -> RpcResult<NodeInfo>;
+            async fn network_info(&self) -> RpcResult<NetworkInfo>;
+            async fn record_addresses(&self) -> RpcResult<Vec<RecordAddress>>;
+            async fn gossipsub_subscribe(&self, topic: &str) -> RpcResult<()>;
+            async fn gossipsub_unsubscribe(&self, topic: &str) -> RpcResult<()>;
+            async fn gossipsub_publish(&self, topic: &str, message: &str) -> RpcResult<()>;
+            async fn node_restart(&self, delay_millis: u64) -> RpcResult<()>;
+            async fn node_stop(&self, delay_millis: u64) -> RpcResult<()>;
+            async fn node_update(&self, delay_millis: u64) -> RpcResult<()>;
+        }
+    }
+
+    #[tokio::test]
+    async fn start_should_start_a_newly_installed_service() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+
+        mock_service_control
+            .expect_start()
+            .with(eq("Safenode service 1"))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_service_control
+            .expect_wait()
+            .with(eq(3))
+            .times(1)
+            .returning(|_| ());
+        mock_rpc_client.expect_node_info().times(1).returning(|| {
+            Ok(NodeInfo {
+                pid: 1000,
                 peer_id: PeerId::from_str("12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR")?,
                 data_path: PathBuf::from("~/.local/share/safe/service1"),
                 log_path: PathBuf::from("~/.local/share/safe/service1/logs"),
@@ -384,15 +1543,27 @@ mod tests {
                 uptime: std::time::Duration::from_secs(1), // the service was just started
             })
         });
+        mock_rpc_client
+            .expect_network_info()
+            .times(1)
+            .returning(|| {
+                Ok(NetworkInfo {
+                    connected_peers: vec![],
+                    listeners: vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?],
+                })
+            });
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "Safenode service 1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Added,
             pid: None,
             peer_id: None,
@@ -402,6 +1573,32 @@ mod tests {
                 "/var/safenode-manager/services/safenode1/safenode",
             )),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
         start(&mut node, &mock_service_control, &mock_rpc_client).await?;
 
@@ -442,15 +1639,27 @@ mod tests {
                 uptime: std::time::Duration::from_secs(1),
             })
         });
+        mock_rpc_client
+            .expect_network_info()
+            .times(1)
+            .returning(|| {
+                Ok(NetworkInfo {
+                    connected_peers: vec![],
+                    listeners: vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?],
+                })
+            });
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "Safenode service 2".to_string(),
             user: "safe".to_string(),
             number: 2,
             port: 8082,
             rpc_port: 8083,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Stopped,
             pid: Some(1001),
             peer_id: Some(PeerId::from_str(
@@ -462,6 +1671,32 @@ mod tests {
                 "/var/safenode-manager/services/safenode1/safenode",
             )),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
         start(&mut node, &mock_service_control, &mock_rpc_client).await?;
 
@@ -477,6 +1712,120 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn start_should_wait_for_the_node_to_report_its_listen_addresses() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+        let mut seq = Sequence::new();
+
+        mock_service_control
+            .expect_start()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_service_control
+            .expect_wait()
+            .with(eq(3))
+            .times(1)
+            .returning(|_| ());
+        mock_service_control
+            .expect_wait()
+            .with(eq(1))
+            .times(2)
+            .returning(|_| ());
+        // The RPC service isn't available to begin with, and then the node has yet to listen.
+        mock_rpc_client
+            .expect_node_info()
+            .times(1)
+            .returning(|| Err("not a multiaddr".parse::<Multiaddr>().unwrap_err().into()))
+            .in_sequence(&mut seq);
+        for listeners in [vec![], vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?]] {
+            mock_rpc_client
+                .expect_node_info()
+                .times(1)
+                .returning(|| {
+                    Ok(NodeInfo {
+                        pid: 1000,
+                        peer_id: PeerId::from_str(
+                            "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
+                        )?,
+                        data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                        log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                        version: "0.98.1".to_string(),
+                        uptime: std::time::Duration::from_secs(1),
+                    })
+                })
+                .in_sequence(&mut seq);
+            mock_rpc_client
+                .expect_network_info()
+                .times(1)
+                .returning(move || {
+                    Ok(NetworkInfo {
+                        connected_peers: vec![],
+                        listeners: listeners.clone(),
+                    })
+                })
+                .in_sequence(&mut seq);
+        }
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Added,
+            pid: None,
+            peer_id: None,
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+        start_with_timeout(&mut node, &mock_service_control, &mock_rpc_client, 10).await?;
+
+        assert_matches!(node.status, NodeStatus::Running);
+        assert_eq!(
+            node.listen_addr,
+            Some(vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?])
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn start_should_not_attempt_to_start_a_running_service() -> Result<()> {
         let mut mock_service_control = MockServiceControl::new();
@@ -505,12 +1854,15 @@ mod tests {
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "Safenode service 1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Running,
             pid: Some(1000),
             peer_id: Some(PeerId::from_str(
@@ -522,6 +1874,32 @@ mod tests {
                 "/var/safenode-manager/services/safenode1/safenode",
             )),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
         start(&mut node, &mock_service_control, &mock_rpc_client).await?;
 
@@ -556,12 +1934,15 @@ mod tests {
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "Safenode service 1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Running,
             pid: Some(1000),
             peer_id: Some(PeerId::from_str(
@@ -573,6 +1954,32 @@ mod tests {
                 "/var/safenode-manager/services/safenode1/safenode",
             )),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
         start(&mut node, &mock_service_control, &mock_rpc_client).await?;
 
@@ -596,15 +2003,24 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()))
             .in_sequence(&mut seq);
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| false)
+            .in_sequence(&mut seq);
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "Safenode service 1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Running,
             pid: Some(1000),
             peer_id: Some(PeerId::from_str(
@@ -618,6 +2034,32 @@ mod tests {
             connected_peers: Some(vec![PeerId::from_str(
                 "12D3KooWKbV9vUmZQdHmTwrQqHrqAQpM7GUWHJXeK1xLeh2LVpuc",
             )?]),
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
         stop(&mut node, &mock_service_control).await?;
 
@@ -631,31 +2073,165 @@ mod tests {
         );
         assert_matches!(node.status, NodeStatus::Stopped);
         assert_matches!(node.connected_peers, None);
+        assert_eq!(node.last_stop_method, Some(StopMethod::Graceful));
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn stop_should_return_error_for_attempt_to_stop_installed_service() -> Result<()> {
-        let mock_service_control = MockServiceControl::new();
+    async fn stop_with_grace_period_should_kill_a_node_that_does_not_exit() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
 
-        let mut node = Node {
-            genesis: false,
-            version: "0.98.1".to_string(),
-            service_name: "safenode1".to_string(),
-            user: "safe".to_string(),
-            number: 1,
-            port: 8080,
-            rpc_port: 8081,
-            status: NodeStatus::Added,
-            pid: None,
-            peer_id: None,
-            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+        let mut seq = Sequence::new();
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| true)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_stop()
+            .with(eq("Safenode service 1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        for _ in 0..2 {
+            mock_service_control
+                .expect_is_service_process_running()
+                .with(eq(1000))
+                .times(1)
+                .returning(|_| true)
+                .in_sequence(&mut seq);
+            mock_service_control
+                .expect_wait()
+                .with(eq(1))
+                .times(1)
+                .returning(|_| ())
+                .in_sequence(&mut seq);
+        }
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| true)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_kill_process()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "Safenode service 1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: Some(1000),
+            peer_id: Some(PeerId::from_str(
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
+            )?),
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers: Some(vec![PeerId::from_str(
+                "12D3KooWKbV9vUmZQdHmTwrQqHrqAQpM7GUWHJXeK1xLeh2LVpuc",
+            )?]),
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+        stop_with_grace_period(&mut node, &mock_service_control, 2).await?;
+
+        assert_eq!(node.pid, None);
+        assert_matches!(node.status, NodeStatus::Stopped);
+        assert_eq!(node.last_stop_method, Some(StopMethod::Killed));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stop_should_return_error_for_attempt_to_stop_installed_service() -> Result<()> {
+        let mock_service_control = MockServiceControl::new();
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Added,
+            pid: None,
+            peer_id: None,
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
             data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
             safenode_path: Some(PathBuf::from(
                 "/var/safenode-manager/services/safenode1/safenode",
             )),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
 
         let result = stop(&mut node, &mock_service_control).await;
@@ -680,12 +2256,15 @@ mod tests {
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "Safenode service 1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Stopped,
             pid: None,
             peer_id: None,
@@ -695,6 +2274,32 @@ mod tests {
                 "/var/safenode-manager/services/safenode1/safenode",
             )),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
 
         stop(&mut node, &mock_service_control).await?;
@@ -724,12 +2329,15 @@ mod tests {
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "safenode1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Stopped,
             pid: None,
             peer_id: None,
@@ -737,6 +2345,32 @@ mod tests {
             data_dir_path: Some(data_dir.to_path_buf()),
             safenode_path: Some(safenode_bin.to_path_buf()),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
 
         remove(&mut node, &mock_service_control, false).await?;
@@ -763,12 +2397,15 @@ mod tests {
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "safenode1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Running,
             pid: Some(1000),
             peer_id: Some(PeerId::from_str(
@@ -780,6 +2417,32 @@ mod tests {
                 "/var/safenode-manager/services/safenode1/safenode",
             )),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
 
         let result = remove(&mut node, &mock_service_control, false).await;
@@ -811,12 +2474,15 @@ mod tests {
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "safenode1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Running,
             pid: Some(1000),
             peer_id: Some(PeerId::from_str(
@@ -826,6 +2492,32 @@ mod tests {
             data_dir_path: Some(data_dir.to_path_buf()),
             safenode_path: Some(safenode_bin.to_path_buf()),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
 
         let result = remove(&mut node, &mock_service_control, false).await;
@@ -862,12 +2554,15 @@ mod tests {
 
         let mut node = Node {
             genesis: false,
+            node_type: NodeType::Safenode,
             version: "0.98.1".to_string(),
             service_name: "safenode1".to_string(),
             user: "safe".to_string(),
             number: 1,
             port: 8080,
             rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
             status: NodeStatus::Stopped,
             pid: None,
             peer_id: None,
@@ -875,6 +2570,32 @@ mod tests {
             data_dir_path: Some(data_dir.to_path_buf()),
             safenode_path: Some(safenode_bin.to_path_buf()),
             connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
         };
 
         remove(&mut node, &mock_service_control, true).await?;
@@ -888,4 +2609,1666 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn reset_should_remove_every_service_and_delete_their_directories() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let make_node = |number: u16, status: NodeStatus, pid: Option<u32>| {
+            let data_dir = temp_dir.child(format!("safenode{number}-data"));
+            data_dir.create_dir_all().unwrap();
+            let log_dir = temp_dir.child(format!("safenode{number}-logs"));
+            log_dir.create_dir_all().unwrap();
+            Node {
+                genesis: false,
+                node_type: NodeType::Safenode,
+                version: "0.98.1".to_string(),
+                service_name: format!("safenode{number}"),
+                user: "safe".to_string(),
+                number,
+                port: 8080 + number,
+                rpc_port: 9080 + number,
+                rpc_address: None,
+                node_ip: None,
+                status,
+                pid,
+                peer_id: None,
+                log_dir_path: Some(log_dir.to_path_buf()),
+                data_dir_path: Some(data_dir.to_path_buf()),
+                safenode_path: Some(data_dir.child("safenode").to_path_buf()),
+                connected_peers: None,
+                records_stored: None,
+                resource_usage: None,
+                listen_addr: None,
+                local: false,
+                peers: vec![],
+                home_network: false,
+                upnp: false,
+                extra_args: vec![],
+                env_variables: vec![],
+                rewards_address: None,
+                owner: None,
+                firewall_rule_added: false,
+                keep_alive: true,
+                throttle_interval: None,
+                log_targets: None,
+                max_memory: None,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                safenode_checksum: None,
+                previous_version: None,
+                maintenance: false,
+                restart_schedule: None,
+                last_stop_method: None,
+                exit_info: None,
+                watchdog_restarts: 0,
+                last_failure: None,
+            }
+        };
+        let mut node_registry = NodeRegistry {
+            save_path: temp_dir.child("node_registry.json").to_path_buf(),
+            nodes: vec![
+                make_node(1, NodeStatus::Running, Some(1000)),
+                // A service removed with its directories kept.
+                make_node(2, NodeStatus::Removed, None),
+            ],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let mut mock_service_control = MockServiceControl::new();
+        let mut seq = Sequence::new();
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| true)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_stop()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| false)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+
+        reset(&mut node_registry, &mock_service_control).await?;
+
+        assert!(node_registry.nodes.is_empty());
+        for number in 1..=2 {
+            temp_dir
+                .child(format!("safenode{number}-data"))
+                .assert(predicate::path::missing());
+            temp_dir
+                .child(format!("safenode{number}-logs"))
+                .assert(predicate::path::missing());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restart_should_clear_the_data_directory_but_the_wallet_unless_the_peer_id_is_retained(
+    ) -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let data_dir = temp_dir.child("safenode1");
+        data_dir
+            .child("safenode")
+            .write_binary(b"fake safenode bin")?;
+        data_dir
+            .child("versions/0.98.1/safenode")
+            .write_binary(b"fake safenode bin")?;
+        data_dir.child("secret-key").write_binary(b"fake key")?;
+        data_dir
+            .child("record_store/record")
+            .write_binary(b"fake record")?;
+        data_dir
+            .child("wallet/main_secret_key")
+            .write_binary(b"fake wallet key")?;
+
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+        let mut seq = Sequence::new();
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| true)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_stop()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| false)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_start()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_wait()
+            .with(eq(3))
+            .times(1)
+            .returning(|_| ())
+            .in_sequence(&mut seq);
+        mock_rpc_client.expect_node_info().times(1).returning(|| {
+            Ok(NodeInfo {
+                pid: 1001,
+                peer_id: PeerId::from_str("12D3KooWAAqZWsjhdZTX7tniJ7Dwye3nEbp1dx1wE96sbgL51obs")?,
+                data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                version: "0.98.1".to_string(),
+                uptime: std::time::Duration::from_secs(1),
+            })
+        });
+        mock_rpc_client
+            .expect_network_info()
+            .times(1)
+            .returning(|| {
+                Ok(NetworkInfo {
+                    connected_peers: vec![],
+                    listeners: vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?],
+                })
+            });
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: Some(1000),
+            peer_id: Some(PeerId::from_str(
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
+            )?),
+            data_dir_path: Some(data_dir.to_path_buf()),
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            safenode_path: Some(data_dir.child("safenode").to_path_buf()),
+            connected_peers: None,
+            records_stored: Some(10),
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+
+        restart(
+            &mut node,
+            &mock_service_control,
+            &mock_rpc_client,
+            false,
+            DEFAULT_STARTUP_TIMEOUT,
+        )
+        .await?;
+
+        assert_eq!(node.status, NodeStatus::Running);
+        assert_eq!(node.pid, Some(1001));
+        assert_eq!(
+            node.peer_id,
+            Some(PeerId::from_str(
+                "12D3KooWAAqZWsjhdZTX7tniJ7Dwye3nEbp1dx1wE96sbgL51obs"
+            )?)
+        );
+        assert_eq!(node.records_stored, None);
+        data_dir
+            .child("safenode")
+            .assert(predicate::path::is_file());
+        data_dir
+            .child("versions/0.98.1/safenode")
+            .assert(predicate::path::is_file());
+        data_dir
+            .child("secret-key")
+            .assert(predicate::path::missing());
+        data_dir
+            .child("record_store")
+            .assert(predicate::path::missing());
+        data_dir
+            .child("wallet/main_secret_key")
+            .assert(predicate::path::is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn measure_resource_usage_should_measure_the_data_directory_and_the_running_process(
+    ) -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let data_dir = temp_dir.child("safenode1");
+        data_dir.child("secret-key").write_binary(&[0; 32])?;
+        data_dir
+            .child("record_store/record")
+            .write_binary(&[0; 100])?;
+
+        // The test process stands in for the node's process.
+        let mut nodes = vec![Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: Some(std::process::id()),
+            peer_id: None,
+            data_dir_path: Some(data_dir.to_path_buf()),
+            log_dir_path: None,
+            safenode_path: None,
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }];
+        measure_resource_usage(&mut nodes);
+
+        let usage = nodes[0].resource_usage.as_ref().unwrap();
+        assert_eq!(usage.disk_bytes, Some(132));
+        assert!(usage.memory_bytes.is_some_and(|m| m > 0));
+        assert!(usage.cpu_percent.is_some());
+
+        nodes[0].status = NodeStatus::Stopped;
+        measure_resource_usage(&mut nodes);
+        let usage = nodes[0].resource_usage.as_ref().unwrap();
+        assert_eq!(usage.disk_bytes, Some(132));
+        assert_eq!(usage.memory_bytes, None);
+        assert_eq!(usage.cpu_percent, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_rewards_address_should_reinstall_and_restart_a_running_service() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+        let mut seq = Sequence::new();
+
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| true)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_stop()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| false)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_install()
+            .with(eq(ServiceConfig {
+                data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                env_variables: vec![],
+                extra_args: vec![],
+                genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
+                local: false,
+                log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+                log_targets: None,
+                max_memory: None,
+                name: "safenode1".to_string(),
+                node_ip: None,
+                node_port: 8080,
+                node_type: NodeType::Safenode,
+                owner: None,
+                peers: vec![],
+                rewards_address: Some(REWARDS_ADDRESS.to_string()),
+                rpc_address: None,
+                rpc_port: 8081,
+                safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+                service_user: "safe".to_string(),
+                throttle_interval: None,
+                upnp: false,
+            }))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_start()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_wait()
+            .with(eq(3))
+            .times(1)
+            .returning(|_| ())
+            .in_sequence(&mut seq);
+        mock_rpc_client.expect_node_info().times(1).returning(|| {
+            Ok(NodeInfo {
+                pid: 1001,
+                peer_id: PeerId::from_str("12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR")?,
+                data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                version: "0.98.1".to_string(),
+                uptime: std::time::Duration::from_secs(1),
+            })
+        });
+        mock_rpc_client
+            .expect_network_info()
+            .times(1)
+            .returning(|| {
+                Ok(NetworkInfo {
+                    connected_peers: vec![],
+                    listeners: vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?],
+                })
+            });
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: Some(1000),
+            peer_id: Some(PeerId::from_str(
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
+            )?),
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: Some("old-rewards-address".to_string()),
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+
+        set_rewards_address(
+            &mut node,
+            REWARDS_ADDRESS,
+            &mock_service_control,
+            &mock_rpc_client,
+        )
+        .await?;
+
+        assert_eq!(node.rewards_address, Some(REWARDS_ADDRESS.to_string()));
+        assert_eq!(node.pid, Some(1001));
+        assert_matches!(node.status, NodeStatus::Running);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_rewards_address_should_not_start_a_service_that_was_not_running() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_service_control
+            .expect_install()
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_service_control.expect_start().times(0);
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Added,
+            pid: None,
+            peer_id: None,
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+
+        set_rewards_address(
+            &mut node,
+            REWARDS_ADDRESS,
+            &mock_service_control,
+            &mock_rpc_client,
+        )
+        .await?;
+
+        assert_eq!(node.rewards_address, Some(REWARDS_ADDRESS.to_string()));
+        assert_matches!(node.status, NodeStatus::Added);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_rewards_address_should_return_an_error_for_an_invalid_address() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+
+        mock_service_control.expect_stop().times(0);
+        mock_service_control.expect_uninstall().times(0);
+        mock_service_control.expect_install().times(0);
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: Some(1000),
+            peer_id: None,
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: Some(REWARDS_ADDRESS.to_string()),
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+
+        let result = set_rewards_address(
+            &mut node,
+            "not-an-address",
+            &mock_service_control,
+            &mock_rpc_client,
+        )
+        .await;
+
+        match result {
+            Ok(()) => panic!("This test should result in an error"),
+            Err(e) => assert_eq!(
+                "The rewards address 'not-an-address' is not valid",
+                e.to_string()
+            ),
+        }
+        assert_eq!(node.rewards_address, Some(REWARDS_ADDRESS.to_string()));
+        assert_matches!(node.status, NodeStatus::Running);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_owner_should_reinstall_the_service_with_the_new_owner() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+        let mut seq = Sequence::new();
+
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_install()
+            .with(eq(ServiceConfig {
+                data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                env_variables: vec![],
+                extra_args: vec![],
+                genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
+                local: false,
+                log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+                log_targets: None,
+                max_memory: None,
+                name: "safenode1".to_string(),
+                node_ip: None,
+                node_port: 8080,
+                node_type: NodeType::Safenode,
+                owner: Some("alice".to_string()),
+                peers: vec![],
+                rewards_address: Some(REWARDS_ADDRESS.to_string()),
+                rpc_address: None,
+                rpc_port: 8081,
+                safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+                service_user: "safe".to_string(),
+                throttle_interval: None,
+                upnp: false,
+            }))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control.expect_start().times(0);
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Stopped,
+            pid: None,
+            peer_id: None,
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: Some(REWARDS_ADDRESS.to_string()),
+            owner: Some("bob".to_string()),
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+
+        set_owner(&mut node, "alice", &mock_service_control, &mock_rpc_client).await?;
+
+        assert_eq!(node.owner, Some("alice".to_string()));
+        assert_eq!(node.rewards_address, Some(REWARDS_ADDRESS.to_string()));
+        assert_matches!(node.status, NodeStatus::Stopped);
+
+        Ok(())
+    }
+
+    fn running_node(connected_peers: Option<Vec<PeerId>>) -> Result<Node> {
+        Ok(Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: Some(1000),
+            peer_id: Some(PeerId::from_str(
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
+            )?),
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        })
+    }
+
+    #[test]
+    fn check_health_should_flag_a_running_node_with_no_connected_peers() -> Result<()> {
+        let node = running_node(Some(vec![]))?;
+        let issues = check_health(&node, None, Duration::from_secs(86400), Utc::now());
+        assert_eq!(issues, vec![HealthIssue::NoConnectedPeers]);
+
+        let node = running_node(Some(vec![PeerId::random()]))?;
+        let issues = check_health(&node, None, Duration::from_secs(86400), Utc::now());
+        assert!(issues.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn check_health_should_flag_a_running_node_that_has_not_earned_over_the_period() -> Result<()> {
+        let now = Utc::now();
+        let period = Duration::from_secs(86400);
+        let snapshot = || NodeSnapshot {
+            balance: 100,
+            payments: vec![],
+            records_stored: Some(10),
+        };
+        let mut earnings_history = EarningsHistory {
+            save_path: PathBuf::new(),
+            records: vec![],
+        };
+        earnings_history.record("safenode1", snapshot(), now - chrono::Duration::hours(36));
+        earnings_history.record("safenode1", snapshot(), now - chrono::Duration::hours(1));
+
+        let node = running_node(Some(vec![PeerId::random()]))?;
+        let issues = check_health(&node, Some(&earnings_history), period, now);
+        assert_eq!(issues, vec![HealthIssue::NotEarning(period)]);
+        assert_eq!(
+            issues[0].to_string(),
+            "no earnings, payments or new records in the last 1d"
+        );
+
+        let mut node = running_node(Some(vec![]))?;
+        node.status = NodeStatus::Stopped;
+        let issues = check_health(&node, Some(&earnings_history), period, now);
+        assert!(issues.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn get_recycle_issues_should_leave_a_node_that_has_not_been_running_for_the_period(
+    ) -> Result<()> {
+        let now = Utc::now();
+        let period = Duration::from_secs(86400);
+        let node = running_node(Some(vec![]))?;
+
+        assert!(get_recycle_issues(&node, Duration::from_secs(60), None, period, now).is_empty());
+        assert!(
+            get_recycle_issues(&node, Duration::from_secs(86399), None, period, now).is_empty()
+        );
+        assert_eq!(
+            get_recycle_issues(&node, Duration::from_secs(90000), None, period, now),
+            vec![HealthIssue::NoConnectedPeers]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recycle_should_remove_the_secret_key_and_records_but_keep_the_wallet() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let data_dir = temp_dir.child("safenode1");
+        data_dir
+            .child("safenode")
+            .write_binary(b"fake safenode bin")?;
+        data_dir.child("secret-key").write_binary(b"fake key")?;
+        data_dir
+            .child("record_store/record")
+            .write_binary(b"fake record")?;
+        data_dir
+            .child("wallet/main_secret_key")
+            .write_binary(b"fake wallet key")?;
+        data_dir.child("safenode.log").write_str("fake log")?;
+
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+        let mut seq = Sequence::new();
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| true)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_stop()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(1)
+            .returning(|_| false)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_start()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_wait()
+            .with(eq(3))
+            .times(1)
+            .returning(|_| ())
+            .in_sequence(&mut seq);
+        mock_rpc_client.expect_node_info().times(1).returning(|| {
+            Ok(NodeInfo {
+                pid: 1001,
+                peer_id: PeerId::from_str("12D3KooWAAqZWsjhdZTX7tniJ7Dwye3nEbp1dx1wE96sbgL51obs")?,
+                data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                version: "0.98.1".to_string(),
+                uptime: std::time::Duration::from_secs(1),
+            })
+        });
+        mock_rpc_client
+            .expect_network_info()
+            .times(1)
+            .returning(|| {
+                Ok(NetworkInfo {
+                    connected_peers: vec![],
+                    listeners: vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?],
+                })
+            });
+
+        let mut node = running_node(Some(vec![]))?;
+        node.data_dir_path = Some(data_dir.to_path_buf());
+        node.safenode_path = Some(data_dir.child("safenode").to_path_buf());
+        node.records_stored = Some(10);
+        let now = Utc::now();
+
+        recycle(
+            &mut node,
+            &mock_service_control,
+            &mock_rpc_client,
+            &[HealthIssue::NoConnectedPeers],
+            now,
+        )
+        .await?;
+
+        assert_eq!(node.status, NodeStatus::Running);
+        assert_eq!(
+            node.peer_id,
+            Some(PeerId::from_str(
+                "12D3KooWAAqZWsjhdZTX7tniJ7Dwye3nEbp1dx1wE96sbgL51obs"
+            )?)
+        );
+        assert_eq!(node.records_stored, None);
+        assert_eq!(node.watchdog_restarts, 1);
+        assert_eq!(
+            node.last_failure,
+            Some(NodeFailure {
+                reason: "possibly shunned: no connected peers".to_string(),
+                time: now,
+            })
+        );
+        data_dir
+            .child("secret-key")
+            .assert(predicate::path::missing());
+        data_dir
+            .child("record_store")
+            .assert(predicate::path::missing());
+        data_dir
+            .child("wallet/main_secret_key")
+            .assert(predicate::path::is_file());
+        data_dir
+            .child("safenode")
+            .assert(predicate::path::is_file());
+        data_dir
+            .child("safenode.log")
+            .assert(predicate::path::is_file());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_should_delete_the_firewall_rule_for_the_node() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let log_dir = temp_dir.child("safenode1-logs");
+        log_dir.create_dir_all()?;
+        let data_dir = temp_dir.child("safenode1-data");
+        data_dir.create_dir_all()?;
+
+        let mut mock_service_control = MockServiceControl::new();
+        let mut seq = Sequence::new();
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_remove_firewall_rule()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Stopped,
+            pid: None,
+            peer_id: None,
+            log_dir_path: Some(log_dir.to_path_buf()),
+            data_dir_path: Some(data_dir.to_path_buf()),
+            safenode_path: Some(data_dir.child("safenode").to_path_buf()),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: true,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        };
+
+        remove(&mut node, &mock_service_control, true).await?;
+
+        assert!(!node.firewall_rule_added);
+        assert_matches!(node.status, NodeStatus::Removed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn edit_should_reinstall_the_service_with_the_new_settings() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+        let mut seq = Sequence::new();
+
+        mock_service_control
+            .expect_is_port_free()
+            .with(eq(9000))
+            .times(1)
+            .returning(|_| true)
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_install()
+            .with(eq(ServiceConfig {
+                data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                env_variables: vec![("SN_LOG".to_string(), "all".to_string())],
+                extra_args: vec![],
+                genesis: false,
+                home_network: false,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                keep_alive: true,
+                local: false,
+                log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+                log_targets: None,
+                max_memory: Some(512),
+                name: "safenode1".to_string(),
+                node_ip: None,
+                node_port: 9000,
+                node_type: NodeType::Safenode,
+                owner: None,
+                peers: vec![],
+                rewards_address: None,
+                rpc_address: None,
+                rpc_port: 8081,
+                safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+                service_user: "safe".to_string(),
+                throttle_interval: None,
+                upnp: false,
+            }))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_remove_firewall_rule()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_add_firewall_rule()
+            .with(eq("safenode1"), eq(9000))
+            .times(1)
+            .returning(|_, _| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control.expect_start().times(0);
+
+        let mut node = running_node(None)?;
+        node.status = NodeStatus::Stopped;
+        node.pid = None;
+        node.firewall_rule_added = true;
+        node.env_variables = vec![("RUST_BACKTRACE".to_string(), "1".to_string())];
+
+        edit(
+            &mut node,
+            NodeEdit {
+                env_variables: vec![("SN_LOG".to_string(), "all".to_string())],
+                max_memory: Some(512),
+                port: Some(9000),
+                unset_env_variables: vec!["RUST_BACKTRACE".to_string()],
+                ..Default::default()
+            },
+            &mock_service_control,
+            &mock_rpc_client,
+        )
+        .await?;
+
+        assert_eq!(node.port, 9000);
+        assert_eq!(node.max_memory, Some(512));
+        assert_eq!(
+            node.env_variables,
+            vec![("SN_LOG".to_string(), "all".to_string())]
+        );
+        assert_eq!(
+            node.peer_id,
+            Some(PeerId::from_str(
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR"
+            )?)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn edit_should_return_an_error_if_no_settings_are_supplied() -> Result<()> {
+        let mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+
+        let mut node = running_node(None)?;
+        let result = edit(
+            &mut node,
+            NodeEdit::default(),
+            &mock_service_control,
+            &mock_rpc_client,
+        )
+        .await;
+        match result {
+            Ok(()) => panic!("This test should result in an error"),
+            Err(e) => assert_eq!("No settings were supplied to change", e.to_string()),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_should_recreate_the_service_under_the_new_name() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+        let mut seq = Sequence::new();
+
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode3"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_remove_firewall_rule()
+            .with(eq("safenode3"))
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_install()
+            .withf(|config| {
+                config.name == "canary1"
+                    && config.data_dir_path.as_path()
+                        == std::path::Path::new("/var/safenode-manager/services/safenode1")
+            })
+            .times(1)
+            .returning(|_| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control
+            .expect_add_firewall_rule()
+            .with(eq("canary1"), eq(8080))
+            .times(1)
+            .returning(|_, _| Ok(()))
+            .in_sequence(&mut seq);
+        mock_service_control.expect_start().times(0);
+
+        let mut node = running_node(None)?;
+        node.service_name = "safenode3".to_string();
+        node.status = NodeStatus::Stopped;
+        node.pid = None;
+        node.firewall_rule_added = true;
+
+        rename(
+            &mut node,
+            "canary1",
+            &mock_service_control,
+            &mock_rpc_client,
+        )
+        .await?;
+
+        assert_eq!(node.service_name, "canary1");
+        assert!(node.firewall_rule_added);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_should_return_an_error_for_an_invalid_name() -> Result<()> {
+        let mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+
+        let mut node = running_node(None)?;
+        let result = rename(
+            &mut node,
+            "../canary",
+            &mock_service_control,
+            &mock_rpc_client,
+        )
+        .await;
+        match result {
+            Ok(()) => panic!("This test should result in an error"),
+            Err(e) => assert_eq!(
+                "The name '../canary' is not a valid service name",
+                e.to_string()
+            ),
+        }
+        assert_eq!(node.service_name, "safenode1");
+
+        Ok(())
+    }
+
+    fn mock_start(
+        mock_service_control: &mut MockServiceControl,
+        mock_rpc_client: &mut MockRpcClient,
+    ) {
+        mock_service_control
+            .expect_start()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_service_control
+            .expect_wait()
+            .with(eq(3))
+            .times(1)
+            .returning(|_| ());
+        mock_rpc_client.expect_node_info().times(1).returning(|| {
+            Ok(NodeInfo {
+                pid: 1001,
+                peer_id: PeerId::from_str("12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR")?,
+                data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                version: "0.98.2".to_string(),
+                uptime: std::time::Duration::from_secs(1),
+            })
+        });
+        mock_rpc_client
+            .expect_network_info()
+            .times(1)
+            .returning(|| {
+                Ok(NetworkInfo {
+                    connected_peers: vec![],
+                    listeners: vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?],
+                })
+            });
+    }
+
+    #[tokio::test]
+    async fn upgrade_should_switch_the_node_to_the_new_version_and_keep_the_previous_one(
+    ) -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let safenode_bin = temp_dir.child("safenode1/safenode");
+        safenode_bin.write_binary(b"safenode 0.98.1")?;
+        let upgraded_bin = temp_dir.child("download/safenode");
+        upgraded_bin.write_binary(b"safenode 0.98.2")?;
+        let older_bin = temp_dir.child("safenode1/versions/0.98.0/safenode");
+        older_bin.write_binary(b"safenode 0.98.0")?;
+
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+        mock_start(&mut mock_service_control, &mut mock_rpc_client);
+
+        let mut node = running_node(None)?;
+        node.status = NodeStatus::Stopped;
+        node.pid = None;
+        node.safenode_path = Some(safenode_bin.to_path_buf());
+
+        let result = upgrade(
+            &mut node,
+            upgraded_bin.path(),
+            &Version::parse("0.98.2")?,
+            &mock_service_control,
+            &mock_rpc_client,
+            false,
+        )
+        .await?;
+
+        match result {
+            UpgradeResult::Upgraded(from, to) => {
+                assert_eq!(from, "0.98.1");
+                assert_eq!(to, "0.98.2");
+            }
+            _ => panic!("The node should have been upgraded"),
+        }
+        assert_eq!(node.version, "0.98.2");
+        assert_eq!(node.previous_version, Some("0.98.1".to_string()));
+        assert!(safenode_bin.path().is_symlink());
+        assert_eq!(
+            std::fs::read_link(safenode_bin.path())?,
+            temp_dir.child("safenode1/versions/0.98.2/safenode").path()
+        );
+        assert_eq!(std::fs::read(safenode_bin.path())?, b"safenode 0.98.2");
+        temp_dir
+            .child("safenode1/versions/0.98.1/safenode")
+            .assert(predicate::path::is_file());
+        temp_dir
+            .child("safenode1/versions/0.98.0")
+            .assert(predicate::path::missing());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upgrade_should_only_downgrade_a_node_if_forced() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let safenode_bin = temp_dir.child("safenode1/safenode");
+        safenode_bin.write_binary(b"safenode 0.98.2")?;
+        let downgraded_bin = temp_dir.child("download/safenode");
+        downgraded_bin.write_binary(b"safenode 0.98.1")?;
+
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+        mock_start(&mut mock_service_control, &mut mock_rpc_client);
+
+        let mut node = running_node(None)?;
+        node.version = "0.98.2".to_string();
+        node.status = NodeStatus::Stopped;
+        node.pid = None;
+        node.safenode_path = Some(safenode_bin.to_path_buf());
+
+        let result = upgrade(
+            &mut node,
+            downgraded_bin.path(),
+            &Version::parse("0.98.1")?,
+            &mock_service_control,
+            &mock_rpc_client,
+            false,
+        )
+        .await?;
+        assert_matches!(result, UpgradeResult::Skipped(_));
+        assert_eq!(node.version, "0.98.2");
+
+        let result = upgrade(
+            &mut node,
+            downgraded_bin.path(),
+            &Version::parse("0.98.1")?,
+            &mock_service_control,
+            &mock_rpc_client,
+            true,
+        )
+        .await?;
+        assert_matches!(result, UpgradeResult::Upgraded(_, _));
+        assert_eq!(node.version, "0.98.1");
+        assert_eq!(node.previous_version, Some("0.98.2".to_string()));
+        assert_eq!(std::fs::read(safenode_bin.path())?, b"safenode 0.98.1");
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rollback_should_switch_the_node_back_to_the_previous_version() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let previous_bin = temp_dir.child("safenode1/versions/0.98.1/safenode");
+        previous_bin.write_binary(b"safenode 0.98.1")?;
+        let current_bin = temp_dir.child("safenode1/versions/0.98.2/safenode");
+        current_bin.write_binary(b"safenode 0.98.2")?;
+        let safenode_path = temp_dir.child("safenode1/safenode");
+        std::os::unix::fs::symlink(current_bin.path(), safenode_path.path())?;
+
+        let mut mock_service_control = MockServiceControl::new();
+        let mock_rpc_client = MockRpcClient::new();
+        mock_service_control.expect_start().times(0);
+
+        let mut node = running_node(None)?;
+        node.status = NodeStatus::Stopped;
+        node.pid = None;
+        node.safenode_path = Some(safenode_path.to_path_buf());
+        node.version = "0.98.2".to_string();
+        node.previous_version = Some("0.98.1".to_string());
+
+        let (from, to) = rollback(&mut node, &mock_service_control, &mock_rpc_client).await?;
+
+        assert_eq!(from, "0.98.2");
+        assert_eq!(to, "0.98.1");
+        assert_eq!(node.version, "0.98.1");
+        assert_eq!(node.previous_version, Some("0.98.2".to_string()));
+        assert_eq!(std::fs::read(safenode_path.path())?, b"safenode 0.98.1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_should_compare_the_binary_against_the_recorded_checksum() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let safenode_bin = temp_dir.child("safenode");
+        safenode_bin.write_binary(b"abc")?;
+
+        let mut node = running_node(None)?;
+        node.safenode_path = Some(safenode_bin.to_path_buf());
+        assert_eq!(verify(&node, None)?, VerifyResult::NotRecorded);
+
+        node.safenode_checksum =
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string());
+        assert_eq!(verify(&node, None)?, VerifyResult::Verified);
+        assert_eq!(
+            verify(&node, Some("0123"))?,
+            VerifyResult::Modified {
+                expected: "0123".to_string(),
+                actual: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+                    .to_string(),
+            }
+        );
+
+        safenode_bin.write_binary(b"abd")?;
+        assert_matches!(verify(&node, None)?, VerifyResult::Modified { .. });
+
+        std::fs::remove_file(safenode_bin.path())?;
+        assert_eq!(verify(&node, None)?, VerifyResult::Missing);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_should_uninstall_a_service_that_was_added() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        mock_service_control
+            .expect_uninstall()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_service_control.expect_install().times(0);
+
+        let mut node = running_node(None)?;
+        node.status = NodeStatus::Added;
+        node.pid = None;
+        let current = NodeRegistry {
+            save_path: PathBuf::from("/var/safenode-manager/node_registry.json"),
+            nodes: vec![node],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+        let previous = NodeRegistry {
+            save_path: PathBuf::from("/var/safenode-manager/node_registry.json"),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let restored = undo(&current, &previous, &mock_service_control)?;
+
+        assert!(restored.nodes.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn undo_should_refuse_to_undo_a_removal() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        mock_service_control.expect_uninstall().times(0);
+        mock_service_control.expect_install().times(0);
+
+        let mut previous_node = running_node(None)?;
+        previous_node.status = NodeStatus::Stopped;
+        previous_node.pid = None;
+        let mut node = previous_node.clone();
+        node.status = NodeStatus::Removed;
+        let current = NodeRegistry {
+            save_path: PathBuf::from("/var/safenode-manager/node_registry.json"),
+            nodes: vec![node],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+        let previous = NodeRegistry {
+            save_path: PathBuf::from("/var/safenode-manager/node_registry.json"),
+            nodes: vec![previous_node],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let result = undo(&current, &previous, &mock_service_control);
+
+        match result {
+            Ok(_) => panic!("This test should result in an error"),
+            Err(e) => assert_eq!("The removal of safenode1 cannot be undone", e.to_string()),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watchdog_restart_should_record_why_a_node_that_died_was_restarted() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        let mut mock_rpc_client = MockRpcClient::new();
+        mock_service_control
+            .expect_is_service_process_running()
+            .with(eq(1000))
+            .times(2)
+            .returning(|_| false);
+        mock_service_control.expect_stop().times(0);
+        mock_service_control
+            .expect_start()
+            .with(eq("safenode1"))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_service_control
+            .expect_wait()
+            .with(eq(3))
+            .times(1)
+            .returning(|_| ());
+        mock_rpc_client.expect_node_info().times(1).returning(|| {
+            Ok(NodeInfo {
+                pid: 1001,
+                peer_id: PeerId::from_str("12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR")?,
+                data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                version: "0.98.1".to_string(),
+                uptime: std::time::Duration::from_secs(1),
+            })
+        });
+        mock_rpc_client
+            .expect_network_info()
+            .times(1)
+            .returning(|| {
+                Ok(NetworkInfo {
+                    connected_peers: vec![],
+                    listeners: vec!["/ip4/127.0.0.1/udp/8080/quic-v1".parse()?],
+                })
+            });
+
+        let mut node = Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: "safenode1".to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: Some(1000),
+            peer_id: Some(PeerId::from_str(
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
+            )?),
+            data_dir_path: Some(PathBuf::from("/var/safenode-manager/services/safenode1")),
+            log_dir_path: Some(PathBuf::from("/var/log/safenode/safenode1")),
+            safenode_path: Some(PathBuf::from(
+                "/var/safenode-manager/services/safenode1/safenode",
+            )),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 1,
+            last_failure: None,
+        };
+
+        let reason = check_responsive(&node, &mock_service_control, &mock_rpc_client)
+            .await
+            .expect("The node should not be responsive");
+        assert_eq!(reason, "the process with PID 1000 is not running");
+
+        let now = Utc::now();
+        watchdog_restart(
+            &mut node,
+            &mock_service_control,
+            &mock_rpc_client,
+            reason,
+            now,
+        )
+        .await?;
+
+        assert_eq!(node.status, NodeStatus::Running);
+        assert_eq!(node.pid, Some(1001));
+        assert_eq!(node.watchdog_restarts, 2);
+        assert_eq!(
+            node.last_failure,
+            Some(NodeFailure {
+                reason: "the process with PID 1000 is not running".to_string(),
+                time: now,
+            })
+        );
+        Ok(())
+    }
 }
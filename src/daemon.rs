@@ -0,0 +1,483 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config::create_private_file;
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// Requests larger than this are rejected, which is far more than any of the operations need.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const MAX_HEADER_COUNT: usize = 64;
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+/// How long a client has to send its request, or receive the response, before the connection is
+/// dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many requests that have been read can wait to be handled.
+const REQUEST_QUEUE_LENGTH: usize = 16;
+
+/// A request received by the daemon, with the header names in lowercase.
+#[derive(Debug, Default)]
+pub struct DaemonRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct DaemonResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl DaemonResponse {
+    pub fn ok(body: String) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+/// The options for the operations, which pick the nodes in the same way as the arguments for
+/// the commands.
+///
+/// Every field is optional, so an empty body applies the operation to every node.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DaemonOptions {
+    pub peer_ids: Vec<String>,
+    pub service_name: Option<String>,
+    pub version: Option<String>,
+    pub force: bool,
+}
+
+/// Get the arguments for the command that performs an operation.
+///
+/// The daemon runs the same commands as the command line, so the operations behave exactly the
+/// same way.
+pub fn get_command_args(operation: &str, options: &DaemonOptions) -> Vec<String> {
+    let mut args = vec!["safenode-manager".to_string(), operation.to_string()];
+    for peer_id in &options.peer_ids {
+        args.push("--peer-id".to_string());
+        args.push(peer_id.clone());
+    }
+    if let Some(service_name) = &options.service_name {
+        args.push("--service-name".to_string());
+        args.push(service_name.clone());
+    }
+    if operation == "upgrade" {
+        if let Some(version) = &options.version {
+            args.push("--version".to_string());
+            args.push(version.clone());
+        }
+        if options.force {
+            args.push("--force".to_string());
+        }
+    }
+    args
+}
+
+/// Get the token clients use to authenticate with the daemon.
+///
+/// The token is generated the first time the daemon runs, and the file is only readable by its
+/// owner, so only the users who could run the commands themselves can ask the daemon to.
+pub fn get_daemon_token(path: &Path) -> Result<String> {
+    if path.exists() {
+        let token = std::fs::read_to_string(path)?.trim().to_string();
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let mut file = create_private_file(path)?;
+    file.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+/// Check the request has the token.
+///
+/// The digests of the tokens are compared rather than the tokens themselves, so the time the
+/// comparison takes says nothing about how much of a guess was right.
+pub fn is_authorized(request: &DaemonRequest, token: &str) -> bool {
+    request
+        .headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| {
+            Sha256::digest(value.trim().as_bytes()) == Sha256::digest(token.as_bytes())
+        })
+}
+
+/// Serve the API on the address until the process is stopped.
+///
+/// Each connection has a task of its own to read its request and write the response, so a client
+/// that is slow to send its request doesn't hold up the others. The requests are handled one at a
+/// time, though. The operations on the nodes shouldn't overlap, and running them in turn means
+/// they don't have to contend for the registry lock.
+///
+/// Each of the schedules is a period for a task, which is run when the daemon starts and then
+/// once every period, in between the requests. The scheduled task is called with the index of the
+/// schedule that's due.
+pub async fn serve<H, F, T, G>(
+    address: SocketAddr,
+    token: &str,
+    mut handler: H,
+    schedules: &[Duration],
+    mut scheduled_task: T,
+) -> Result<()>
+where
+    H: FnMut(DaemonRequest) -> F,
+    F: Future<Output = DaemonResponse>,
+    T: FnMut(usize) -> G,
+    G: Future<Output = ()>,
+{
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| eyre!("Could not listen on {address}: {e}"))?;
+    println!("Listening on {address}");
+
+    let (sender, mut receiver) = mpsc::channel(REQUEST_QUEUE_LENGTH);
+    let accepting = tokio::spawn(accept_requests(listener, sender));
+    let mut tickers = schedules
+        .iter()
+        .map(|period| {
+            let mut ticker = tokio::time::interval(*period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticker
+        })
+        .collect::<Vec<_>>();
+    loop {
+        let (request, stream, peer_address) = tokio::select! {
+            received = receiver.recv() => match received {
+                Some(received) => received,
+                None => return accepting.await?,
+            },
+            index = next_tick(&mut tickers) => {
+                scheduled_task(index).await;
+                continue;
+            }
+        };
+        let response = if is_authorized(&request, token) {
+            println!("{} {} from {peer_address}", request.method, request.path);
+            handler(request).await
+        } else {
+            DaemonResponse::error(401, "The request did not have a valid token")
+        };
+        tokio::spawn(respond(stream, peer_address, response));
+    }
+}
+
+/// Accept connections and read a request from each of them, in a task per connection, sending
+/// those that are read on to be handled.
+///
+/// A request that can't be read gets its error straight away, and a connection that doesn't send
+/// its request in time is dropped.
+async fn accept_requests(
+    listener: TcpListener,
+    sender: mpsc::Sender<(DaemonRequest, TcpStream, SocketAddr)>,
+) -> Result<()> {
+    loop {
+        let (mut stream, peer_address) = listener.accept().await?;
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+                Ok(Ok(request)) => {
+                    let _ = sender.send((request, stream, peer_address)).await;
+                }
+                Ok(Err(e)) => {
+                    let response = DaemonResponse::error(400, &e.to_string());
+                    respond(stream, peer_address, response).await;
+                }
+                Err(_) => {}
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, peer_address: SocketAddr, response: DaemonResponse) {
+    let result = tokio::time::timeout(REQUEST_TIMEOUT, write_response(&mut stream, &response));
+    match result.await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => println!("Could not send the response to {peer_address}: {e}"),
+        Err(_) => println!("Timed out sending the response to {peer_address}"),
+    }
+}
+
+/// Wait for the next of the tickers to tick, and get its index. Without any, this never returns.
+async fn next_tick(tickers: &mut [Interval]) -> usize {
+    std::future::poll_fn(|cx| {
+        for (index, ticker) in tickers.iter_mut().enumerate() {
+            if ticker.poll_tick(cx).is_ready() {
+                return Poll::Ready(index);
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Serve the metrics on the address, at `/metrics`, until the process is stopped.
+///
+/// Unlike the API, the metrics don't need a token, since they don't allow anything to be changed
+/// and Prometheus expects to be able to scrape them without one.
+pub async fn serve_metrics<M, F>(address: SocketAddr, mut get_metrics: M) -> Result<()>
+where
+    M: FnMut() -> F,
+    F: Future<Output = Result<String>>,
+{
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| eyre!("Could not listen on {address}: {e}"))?;
+    println!("Serving metrics on {address}");
+
+    let (sender, mut receiver) = mpsc::channel(REQUEST_QUEUE_LENGTH);
+    let accepting = tokio::spawn(accept_requests(listener, sender));
+    while let Some((request, stream, peer_address)) = receiver.recv().await {
+        let response = if request.method == "GET" && request.path == "/metrics" {
+            match get_metrics().await {
+                Ok(metrics) => DaemonResponse {
+                    status: 200,
+                    content_type: "text/plain; version=0.0.4",
+                    body: metrics,
+                },
+                Err(e) => DaemonResponse::error(500, &e.to_string()),
+            }
+        } else {
+            DaemonResponse::error(404, "The metrics are at /metrics")
+        };
+        tokio::spawn(respond(stream, peer_address, response));
+    }
+    accepting.await?
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<DaemonRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    read_line(&mut reader, &mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(eyre!("The request line is not valid"));
+    };
+    let mut request = DaemonRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        ..Default::default()
+    };
+
+    loop {
+        read_line(&mut reader, &mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if request.headers.len() == MAX_HEADER_COUNT {
+            return Err(eyre!("The request has too many headers"));
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| eyre!("The header '{header}' is not valid"))?;
+        request
+            .headers
+            .insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    let length = match request.headers.get("content-length") {
+        Some(length) => length
+            .parse::<usize>()
+            .map_err(|_| eyre!("The content length is not valid"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(eyre!("The request body is too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).await?;
+    Ok(request)
+}
+
+/// Read a line of the request, which is an error if it's longer than the limit, so a client
+/// can't have the daemon buffer any amount of data.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut String) -> Result<()> {
+    line.clear();
+    reader.take(MAX_LINE_LENGTH as u64).read_line(line).await?;
+    if line.len() == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        return Err(eyre!("The request has a line that is too long"));
+    }
+    Ok(())
+}
+
+async fn write_response(stream: &mut TcpStream, response: &DaemonResponse) -> Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+    let message = format!(
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    );
+    stream.write_all(message.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_command_args_should_pick_the_nodes_and_only_pass_the_version_to_upgrade() -> Result<()> {
+        let options: DaemonOptions = serde_json::from_str(
+            r#"{"peer_ids": ["12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR"], "version": "0.98.1", "force": true}"#,
+        )?;
+        assert_eq!(
+            get_command_args("upgrade", &options),
+            vec![
+                "safenode-manager",
+                "upgrade",
+                "--peer-id",
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
+                "--version",
+                "0.98.1",
+                "--force"
+            ]
+        );
+        assert_eq!(
+            get_command_args("stop", &options),
+            vec![
+                "safenode-manager",
+                "stop",
+                "--peer-id",
+                "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR"
+            ]
+        );
+
+        let options: DaemonOptions = serde_json::from_str("{}")?;
+        assert_eq!(
+            get_command_args("start", &options),
+            vec!["safenode-manager", "start"]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn get_daemon_token_should_create_a_file_only_the_owner_can_read() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = assert_fs::TempDir::new()?;
+        let path = temp_dir.path().join("daemon_token");
+        let token = get_daemon_token(&path)?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(get_daemon_token(&path)?, token);
+        Ok(())
+    }
+
+    #[test]
+    fn is_authorized_should_only_accept_the_token() {
+        let request = |authorization: &str| DaemonRequest {
+            method: "GET".to_string(),
+            path: "/status".to_string(),
+            headers: HashMap::from([("authorization".to_string(), authorization.to_string())]),
+            body: vec![],
+        };
+        assert!(is_authorized(&request("Bearer token"), "token"));
+        assert!(!is_authorized(&request("Bearer toke"), "token"));
+        assert!(!is_authorized(&request("token"), "token"));
+        assert!(!is_authorized(&DaemonRequest::default(), "token"));
+    }
+
+    #[tokio::test]
+    async fn serve_should_handle_a_request_while_another_client_is_idle() -> Result<()> {
+        let address = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let server = serve(
+            address,
+            "token",
+            |_| async { DaemonResponse::ok("[]".to_string()) },
+            &[],
+            |_| async {},
+        );
+        let client = async {
+            let idle_stream = loop {
+                match TcpStream::connect(address).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            let mut stream = TcpStream::connect(address).await?;
+            stream
+                .write_all(b"GET /status HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n")
+                .await?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await?;
+            drop(idle_stream);
+            Ok::<_, color_eyre::Report>(response)
+        };
+
+        // The idle client would hold up the other for the whole of the request timeout if the
+        // connections were handled one at a time.
+        let response = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                result = server => result.map(|_| String::new()),
+                result = client => result,
+            }
+        })
+        .await??;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("[]"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_line_should_reject_a_line_longer_than_the_limit() -> Result<()> {
+        let mut line = String::new();
+        read_line(&mut "GET /status HTTP/1.1\r\n".as_bytes(), &mut line).await?;
+        assert_eq!(line, "GET /status HTTP/1.1\r\n");
+
+        let long_line = "a".repeat(MAX_LINE_LENGTH * 2);
+        assert!(read_line(&mut long_line.as_bytes(), &mut line)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
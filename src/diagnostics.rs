@@ -0,0 +1,267 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use color_eyre::{eyre::eyre, Result};
+use flate2::{write::GzEncoder, Compression};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use sysinfo::{DiskExt, System, SystemExt};
+
+/// The most of each log file that is included in a bundle, in bytes, taken from the end.
+const LOG_LIMIT: u64 = 1024 * 1024;
+
+/// Write a bundle of the information needed to debug problems with the node services.
+///
+/// The bundle is a gzipped tarball with a summary of the system, the node registry, and the end
+/// of the most recent log file of each node, along with anything the service manager captured
+/// from its standard output and error.
+pub fn create_bundle(node_registry: &NodeRegistry, out_path: &Path) -> Result<()> {
+    let file = std::fs::File::create(out_path)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    append(&mut builder, "system.txt", get_system_summary().as_bytes())?;
+    append(
+        &mut builder,
+        "node_registry.json",
+        serde_json::to_string_pretty(node_registry)?.as_bytes(),
+    )?;
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        let log_dir_path = match &node.log_dir_path {
+            Some(path) => path,
+            None => continue,
+        };
+        for log_path in get_log_files(log_dir_path)? {
+            let file_name = log_path
+                .file_name()
+                .ok_or_else(|| eyre!("The log file should have a name"))?
+                .to_string_lossy()
+                .to_string();
+            append(
+                &mut builder,
+                &format!("logs/{}/{file_name}", node.service_name),
+                &read_tail(&log_path, LOG_LIMIT)?,
+            )?;
+        }
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Upload a bundle to the URL supplied by support, which can be an S3 presigned URL.
+pub async fn upload_bundle(path: &Path, url: &str) -> Result<()> {
+    let response = reqwest::Client::new()
+        .put(url)
+        .header("Content-Type", "application/gzip")
+        .body(std::fs::read(path)?)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(eyre!("The upload failed with status {}", response.status()));
+    }
+    Ok(())
+}
+
+fn append<W: std::io::Write>(builder: &mut tar::Builder<W>, path: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+fn get_system_summary() -> String {
+    let mut system = System::new();
+    system.refresh_memory();
+    system.refresh_disks_list();
+    system.refresh_disks();
+
+    let mut summary = format!(
+        "safenode-manager: {}\nOS: {} {}\nKernel: {}\nArchitecture: {}\nMemory: {} MB total, {} MB available\n",
+        env!("CARGO_PKG_VERSION"),
+        system.name().unwrap_or_else(|| std::env::consts::OS.to_string()),
+        system.os_version().unwrap_or_default(),
+        system.kernel_version().unwrap_or_default(),
+        std::env::consts::ARCH,
+        system.total_memory() / (1024 * 1024),
+        system.available_memory() / (1024 * 1024),
+    );
+    for disk in system.disks() {
+        summary.push_str(&format!(
+            "Disk: {} {} MB free of {} MB\n",
+            disk.mount_point().to_string_lossy(),
+            disk.available_space() / (1024 * 1024),
+            disk.total_space() / (1024 * 1024),
+        ));
+    }
+    summary
+}
+
+/// Get the most recently written log file in the directory, along with any files that capture the
+/// standard output and error of the node.
+fn get_log_files(log_dir_path: &Path) -> Result<Vec<PathBuf>> {
+    if !log_dir_path.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files = Vec::new();
+    let mut latest: Option<(std::time::SystemTime, PathBuf)> = None;
+    for entry in std::fs::read_dir(log_dir_path)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.ends_with(".stdout") || file_name.ends_with(".stderr") {
+            files.push(path);
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        let is_latest = match &latest {
+            Some((time, _)) => modified > *time,
+            None => true,
+        };
+        if is_latest {
+            latest = Some((modified, path));
+        }
+    }
+    if let Some((_, path)) = latest {
+        files.insert(0, path);
+    }
+    Ok(files)
+}
+
+fn read_tail(path: &Path, limit: u64) -> Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    if len > limit {
+        file.seek(SeekFrom::Start(len - limit))?;
+    }
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::{Node, NodeType};
+    use assert_fs::prelude::*;
+    use flate2::read::GzDecoder;
+
+    #[test]
+    fn create_bundle_should_include_the_registry_and_the_latest_logs() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let log_dir = temp_dir.child("logs/safenode1");
+        log_dir.create_dir_all()?;
+        log_dir.child("safenode.log").write_str("node started")?;
+        log_dir.child("safenode.stdout").write_str("panicked")?;
+
+        let node_registry = NodeRegistry {
+            save_path: temp_dir.child("node_registry.json").to_path_buf(),
+            nodes: vec![Node {
+                genesis: false,
+                node_type: NodeType::Safenode,
+                version: "0.98.1".to_string(),
+                service_name: "safenode1".to_string(),
+                user: "safe".to_string(),
+                number: 1,
+                port: 8080,
+                rpc_port: 8081,
+                rpc_address: None,
+                node_ip: None,
+                status: NodeStatus::Stopped,
+                pid: None,
+                peer_id: None,
+                data_dir_path: None,
+                log_dir_path: Some(log_dir.to_path_buf()),
+                safenode_path: None,
+                connected_peers: None,
+                records_stored: None,
+                resource_usage: None,
+                listen_addr: None,
+                local: false,
+                peers: vec![],
+                home_network: false,
+                upnp: false,
+                extra_args: vec![],
+                env_variables: vec![],
+                rewards_address: None,
+                owner: None,
+                firewall_rule_added: false,
+                keep_alive: true,
+                throttle_interval: None,
+                log_targets: None,
+                max_memory: None,
+                auto_restart: None,
+                auto_restart_reset_period: 86400,
+                safenode_checksum: None,
+                previous_version: None,
+                maintenance: false,
+                restart_schedule: None,
+                last_stop_method: None,
+                exit_info: None,
+                watchdog_restarts: 0,
+                last_failure: None,
+            }],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let bundle_path = temp_dir.child("bundle.tar.gz");
+        create_bundle(&node_registry, bundle_path.path())?;
+
+        let mut archive =
+            tar::Archive::new(GzDecoder::new(std::fs::File::open(bundle_path.path())?));
+        let mut entries = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            entries.push((entry.path()?.to_string_lossy().to_string(), contents));
+        }
+
+        let names = entries
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            names,
+            vec![
+                "system.txt",
+                "node_registry.json",
+                "logs/safenode1/safenode.log",
+                "logs/safenode1/safenode.stdout",
+            ]
+        );
+        assert_eq!(entries[2].1, "node started");
+        assert!(entries[1].1.contains("\"service_name\": \"safenode1\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_tail_should_only_read_the_end_of_a_large_file() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let file = temp_dir.child("safenode.log");
+        file.write_str("0123456789")?;
+        assert_eq!(read_tail(file.path(), 4)?, b"6789");
+        assert_eq!(read_tail(file.path(), 100)?, b"0123456789");
+        Ok(())
+    }
+}
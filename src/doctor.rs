@@ -0,0 +1,447 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{Node, NodeRegistry, NodeStatus};
+use crate::service::ServiceControl;
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use service_manager::ServiceManagerKind;
+use sn_releases::{ReleaseType, SafeReleaseRepositoryInterface};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use sysinfo::{DiskExt, System, SystemExt};
+
+/// The URL the server time is read from when checking the clock. It's the bucket safenode is
+/// downloaded from, so if it can't be reached, neither can the releases.
+const TIME_CHECK_URL: &str = "https://sn-node.s3.eu-west-2.amazonaws.com";
+/// The differences between the local clock and the server time, in seconds, at which the clock
+/// is reported.
+const CLOCK_SKEW_WARN: i64 = 30;
+const CLOCK_SKEW_FAIL: i64 = 300;
+/// The amounts of free disk space, in bytes, at which the space is reported.
+const DISK_SPACE_WARN: u64 = 5 * 1024 * 1024 * 1024;
+const DISK_SPACE_FAIL: u64 = 1024 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, message: String) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            message,
+            suggestion: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: String, suggestion: &str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            message,
+            suggestion: Some(suggestion.to_string()),
+        }
+    }
+
+    fn fail(name: &'static str, message: String, suggestion: &str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            message,
+            suggestion: Some(suggestion.to_string()),
+        }
+    }
+}
+
+/// Check the host can run and manage nodes, printing the result of each check.
+///
+/// An error is returned if any of the checks failed, but warnings are only reported.
+pub async fn doctor(
+    registry_path: &Path,
+    service_control: &dyn ServiceControl,
+    release_repo: &dyn SafeReleaseRepositoryInterface,
+    is_privileged: bool,
+) -> Result<()> {
+    let mut results = vec![
+        check_init_system(ServiceManagerKind::native()),
+        check_privileges(is_privileged),
+    ];
+    match NodeRegistry::load(registry_path) {
+        Ok(node_registry) => {
+            results.push(check_registry(&node_registry.nodes));
+            results.push(check_ports(&node_registry.nodes, service_control));
+            results.push(check_disk_space(&get_disk_space_path(
+                registry_path,
+                &node_registry.nodes,
+            )));
+        }
+        Err(e) => results.push(CheckResult::fail(
+            "Node registry",
+            format!("{} could not be read: {e}", registry_path.to_string_lossy()),
+            "Restore the registry from a backup, or move it aside to start again",
+        )),
+    }
+    results.push(check_clock_skew(get_server_time().await, Utc::now()));
+    results.push(check_release_api(release_repo).await);
+
+    for result in results.iter() {
+        let symbol = match result.status {
+            CheckStatus::Pass => "✓".green(),
+            CheckStatus::Warn => "⚠".yellow(),
+            CheckStatus::Fail => "✕".red(),
+        };
+        println!("{symbol} {}: {}", result.name, result.message);
+        if let Some(suggestion) = &result.suggestion {
+            println!("    {suggestion}");
+        }
+    }
+
+    let failed = results
+        .iter()
+        .filter(|r| r.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(eyre!("{failed} check(s) failed"));
+    }
+    Ok(())
+}
+
+fn check_init_system(kind: std::io::Result<ServiceManagerKind>) -> CheckResult {
+    let name = "Init system";
+    match kind {
+        Ok(ServiceManagerKind::Launchd) => CheckResult::pass(name, "launchd".to_string()),
+        Ok(ServiceManagerKind::OpenRc) => CheckResult::pass(name, "OpenRC".to_string()),
+        Ok(ServiceManagerKind::Rcd) => CheckResult::pass(name, "rc.d".to_string()),
+        Ok(ServiceManagerKind::Sc) => {
+            CheckResult::pass(name, "Service Control Manager".to_string())
+        }
+        Ok(ServiceManagerKind::Systemd) => CheckResult::pass(name, "systemd".to_string()),
+        Ok(ServiceManagerKind::WinSw) => CheckResult::pass(name, "WinSW".to_string()),
+        Err(e) => CheckResult::fail(
+            name,
+            format!("no supported init system was found: {e}"),
+            "Services can be managed by systemd, OpenRC, launchd, rc.d or the Windows Service \
+             Control Manager",
+        ),
+    }
+}
+
+fn check_privileges(is_privileged: bool) -> CheckResult {
+    let name = "Privileges";
+    if is_privileged {
+        return CheckResult::pass(name, "services can be managed".to_string());
+    }
+    CheckResult::warn(
+        name,
+        "commands that change services will fail".to_string(),
+        "Run the node manager as root, or as Administrator on Windows",
+    )
+}
+
+/// Check the services in the registry still have their binaries and directories, and that no
+/// two of them use the same port.
+fn check_registry(nodes: &[Node]) -> CheckResult {
+    let name = "Node registry";
+    let mut issues = Vec::new();
+    let mut ports: HashMap<u16, &str> = HashMap::new();
+    for node in nodes {
+        match &node.safenode_path {
+            Some(path) if !path.exists() => issues.push(format!(
+                "the safenode binary for {} is missing",
+                node.service_name
+            )),
+            _ => {}
+        }
+        match &node.data_dir_path {
+            Some(path) if !path.exists() => issues.push(format!(
+                "the data directory for {} is missing",
+                node.service_name
+            )),
+            _ => {}
+        }
+        if let Some(other) = ports.insert(node.port, &node.service_name) {
+            issues.push(format!(
+                "{} and {} are both using port {}",
+                other, node.service_name, node.port
+            ));
+        }
+    }
+
+    if issues.is_empty() {
+        return CheckResult::pass(name, format!("{} service(s) registered", nodes.len()));
+    }
+    CheckResult::warn(
+        name,
+        issues.join("; "),
+        "Remove the affected services and add them again",
+    )
+}
+
+/// Check the ports of the services that aren't running are free, so they'll be able to start.
+fn check_ports(nodes: &[Node], service_control: &dyn ServiceControl) -> CheckResult {
+    let name = "Ports";
+    let in_use = nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Running && n.status != NodeStatus::Removed)
+        .filter(|n| !service_control.is_port_free(n.port))
+        .map(|n| format!("port {} for {} is in use", n.port, n.service_name))
+        .collect::<Vec<String>>();
+    if in_use.is_empty() {
+        return CheckResult::pass(name, "the ports for stopped services are free".to_string());
+    }
+    CheckResult::fail(
+        name,
+        in_use.join("; "),
+        "Stop whatever else is using the port, or remove the service and add it on another port",
+    )
+}
+
+/// Get the path the disk space should be checked for, which is where the nodes store their data.
+fn get_disk_space_path(registry_path: &Path, nodes: &[Node]) -> PathBuf {
+    nodes
+        .iter()
+        .find_map(|n| n.data_dir_path.clone())
+        .or_else(|| registry_path.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| registry_path.to_path_buf())
+}
+
+fn check_disk_space(path: &Path) -> CheckResult {
+    let mut system = System::new();
+    system.refresh_disks_list();
+    system.refresh_disks();
+    // The disk the path is on is the one with the longest mount point that contains it.
+    let available = system
+        .disks()
+        .iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map(|d| d.available_space());
+    match available {
+        Some(available) => evaluate_disk_space(path, available),
+        None => CheckResult::warn(
+            "Disk space",
+            format!("could not find the disk for {}", path.to_string_lossy()),
+            "Check there is enough space for the nodes to store records",
+        ),
+    }
+}
+
+fn evaluate_disk_space(path: &Path, available: u64) -> CheckResult {
+    let name = "Disk space";
+    let message = format!(
+        "{}GB available for {}",
+        available / (1024 * 1024 * 1024),
+        path.to_string_lossy()
+    );
+    if available < DISK_SPACE_FAIL {
+        CheckResult::fail(
+            name,
+            message,
+            "Free some space, or use --data-dir-path to add services on a larger disk",
+        )
+    } else if available < DISK_SPACE_WARN {
+        CheckResult::warn(
+            name,
+            message,
+            "The nodes may run out of space to store records",
+        )
+    } else {
+        CheckResult::pass(name, message)
+    }
+}
+
+/// Read the time from the `Date` header of a response from the release bucket.
+async fn get_server_time() -> Option<DateTime<Utc>> {
+    let response = reqwest::Client::new()
+        .head(TIME_CHECK_URL)
+        .send()
+        .await
+        .ok()?;
+    let date = response
+        .headers()
+        .get(reqwest::header::DATE)?
+        .to_str()
+        .ok()?;
+    DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+fn check_clock_skew(server_time: Option<DateTime<Utc>>, now: DateTime<Utc>) -> CheckResult {
+    let name = "Clock";
+    let Some(server_time) = server_time else {
+        return CheckResult::warn(
+            name,
+            "the time could not be read from the network".to_string(),
+            "Check the host is connected to the internet",
+        );
+    };
+    let skew = (now - server_time).num_seconds().abs();
+    let message = format!("{skew} second(s) from the server time");
+    if skew >= CLOCK_SKEW_FAIL {
+        CheckResult::fail(name, message, "Enable time synchronisation, e.g., with NTP")
+    } else if skew >= CLOCK_SKEW_WARN {
+        CheckResult::warn(name, message, "Enable time synchronisation, e.g., with NTP")
+    } else {
+        CheckResult::pass(name, message)
+    }
+}
+
+async fn check_release_api(release_repo: &dyn SafeReleaseRepositoryInterface) -> CheckResult {
+    let name = "Releases";
+    match release_repo
+        .get_latest_version(&ReleaseType::Safenode)
+        .await
+    {
+        Ok(version) => CheckResult::pass(name, format!("the latest safenode is {version}")),
+        Err(e) => CheckResult::fail(
+            name,
+            format!("the latest version could not be retrieved: {e}"),
+            "Check the host is connected to the internet, or use --url to add services",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::NodeType;
+    use crate::service::MockServiceControl;
+    use assert_fs::prelude::*;
+    use mockall::predicate::*;
+
+    fn added_node(service_name: &str, port: u16, data_dir_path: PathBuf) -> Node {
+        Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: service_name.to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port,
+            rpc_port: port + 1,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Added,
+            pid: None,
+            peer_id: None,
+            log_dir_path: None,
+            data_dir_path: Some(data_dir_path.clone()),
+            safenode_path: Some(data_dir_path.join("safenode")),
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }
+    }
+
+    #[test]
+    fn check_registry_should_report_missing_binaries_and_shared_ports() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let node_data_dir = temp_dir.child("safenode1");
+        node_data_dir.create_dir_all()?;
+        node_data_dir
+            .child("safenode")
+            .write_binary(b"fake safenode bin")?;
+
+        let nodes = vec![added_node("safenode1", 8080, node_data_dir.to_path_buf())];
+        assert_eq!(check_registry(&nodes).status, CheckStatus::Pass);
+
+        let nodes = vec![
+            added_node("safenode1", 8080, node_data_dir.to_path_buf()),
+            added_node("safenode2", 8080, temp_dir.child("safenode2").to_path_buf()),
+        ];
+        let result = check_registry(&nodes);
+        assert_eq!(result.status, CheckStatus::Warn);
+        assert_eq!(
+            result.message,
+            "the safenode binary for safenode2 is missing; the data directory for safenode2 is \
+             missing; safenode1 and safenode2 are both using port 8080"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_ports_should_fail_if_the_port_of_a_stopped_node_is_in_use() {
+        let mut mock_service_control = MockServiceControl::new();
+        mock_service_control
+            .expect_is_port_free()
+            .with(eq(8080))
+            .times(1)
+            .returning(|_| false);
+
+        let nodes = vec![added_node("safenode1", 8080, PathBuf::from("/tmp"))];
+        let result = check_ports(&nodes, &mock_service_control);
+        assert_eq!(result.status, CheckStatus::Fail);
+        assert_eq!(result.message, "port 8080 for safenode1 is in use");
+    }
+
+    #[test]
+    fn evaluate_disk_space_should_warn_and_fail_as_space_runs_out() {
+        let path = Path::new("/var/safenode-manager/services");
+        let gb = 1024 * 1024 * 1024;
+        assert_eq!(evaluate_disk_space(path, 20 * gb).status, CheckStatus::Pass);
+        assert_eq!(evaluate_disk_space(path, 2 * gb).status, CheckStatus::Warn);
+        assert_eq!(evaluate_disk_space(path, gb / 2).status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn check_clock_skew_should_warn_and_fail_as_the_clock_drifts() {
+        let now = Utc::now();
+        assert_eq!(
+            check_clock_skew(Some(now - chrono::Duration::seconds(2)), now).status,
+            CheckStatus::Pass
+        );
+        assert_eq!(
+            check_clock_skew(Some(now + chrono::Duration::seconds(60)), now).status,
+            CheckStatus::Warn
+        );
+        assert_eq!(
+            check_clock_skew(Some(now - chrono::Duration::seconds(600)), now).status,
+            CheckStatus::Fail
+        );
+        assert_eq!(check_clock_skew(None, now).status, CheckStatus::Warn);
+    }
+}
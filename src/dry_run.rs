@@ -0,0 +1,131 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::ExitInfo;
+use crate::report;
+use crate::service::{ServiceConfig, ServiceControl};
+use async_trait::async_trait;
+use color_eyre::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+pub fn set_dry_run() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+/// Whether the command should only report the changes it would make.
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Reports the changes that would be made to the services, rather than making them.
+///
+/// Anything that only inspects the machine, like whether a port is free or a process is running,
+/// is passed through to the real service control, so the commands take the same decisions they
+/// would otherwise.
+pub struct DryRunServiceControl {
+    pub inner: Box<dyn ServiceControl>,
+}
+
+#[async_trait]
+impl ServiceControl for DryRunServiceControl {
+    fn add_firewall_rule(&self, service_name: &str, port: u16) -> Result<()> {
+        report!("Would open port {port} in the firewall for {service_name}");
+        Ok(())
+    }
+
+    fn create_service_user(&self, username: &str) -> Result<()> {
+        report!("Would create the {username} user, if it doesn't already exist");
+        Ok(())
+    }
+
+    fn get_available_port(&self) -> Result<u16> {
+        self.inner.get_available_port()
+    }
+
+    fn get_exit_info(&self, service_name: &str) -> Result<ExitInfo> {
+        self.inner.get_exit_info(service_name)
+    }
+
+    fn install(&self, config: ServiceConfig) -> Result<()> {
+        report!(
+            "Would write the service definition for {}, to run {} as {}",
+            config.name,
+            config.safenode_path.to_string_lossy(),
+            config.service_user
+        );
+        Ok(())
+    }
+
+    fn is_port_free(&self, port: u16) -> bool {
+        self.inner.is_port_free(port)
+    }
+
+    fn is_service_process_running(&self, pid: u32) -> bool {
+        self.inner.is_service_process_running(pid)
+    }
+
+    fn kill_process(&self, pid: u32) -> Result<()> {
+        report!("Would kill process {pid}");
+        Ok(())
+    }
+
+    fn remove_firewall_rule(&self, service_name: &str) -> Result<()> {
+        report!("Would remove the firewall rule for {service_name}");
+        Ok(())
+    }
+
+    fn start(&self, service_name: &str) -> Result<()> {
+        report!("Would start {service_name}");
+        Ok(())
+    }
+
+    fn stop(&self, service_name: &str) -> Result<()> {
+        report!("Would stop {service_name}");
+        Ok(())
+    }
+
+    fn uninstall(&self, service_name: &str) -> Result<()> {
+        report!("Would remove the service definition for {service_name}");
+        Ok(())
+    }
+
+    async fn wait(&self, _delay: u64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::MockServiceControl;
+
+    #[test]
+    fn dry_run_service_control_should_only_pass_through_the_inspections() -> Result<()> {
+        let mut mock_service_control = MockServiceControl::new();
+        mock_service_control
+            .expect_is_port_free()
+            .with(mockall::predicate::eq(8080))
+            .times(1)
+            .returning(|_| true);
+        mock_service_control.expect_install().times(0);
+        mock_service_control.expect_uninstall().times(0);
+        mock_service_control.expect_stop().times(0);
+        mock_service_control.expect_create_service_user().times(0);
+        mock_service_control.expect_remove_firewall_rule().times(0);
+
+        let service_control = DryRunServiceControl {
+            inner: Box::new(mock_service_control),
+        };
+        assert!(service_control.is_port_free(8080));
+        service_control.create_service_user("safe")?;
+        service_control.stop("safenode1")?;
+        service_control.uninstall("safenode1")?;
+        service_control.remove_firewall_rule("safenode1")?;
+        Ok(())
+    }
+}
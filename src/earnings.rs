@@ -0,0 +1,853 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{Node, NodeRegistry, NodeStatus};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use sn_node_rpc_client::{RpcActions, RpcClient};
+use sn_transfers::{LocalWallet, NanoTokens};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The message safenode logs when it accepts a payment for storing a record.
+const PAYMENT_ACCEPTED_LOG_MESSAGE: &str = "Total payment of NanoTokens(";
+
+/// A snapshot of a node's wallet balance at a point in time.
+///
+/// The delta is the change in balance since the previous record for the same node, which is what
+/// the report is built from. For the first record of a node the delta is zero, since we have no
+/// way of knowing what it earned before we started tracking it. The same applies to the payments,
+/// which are those accepted by the node since its previous record.
+///
+/// The number of records stored is only available if the node was running at the time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceRecord {
+    pub service_name: String,
+    pub timestamp: DateTime<Utc>,
+    pub balance: u64,
+    pub delta: i64,
+    #[serde(default)]
+    pub payments_received: u64,
+    #[serde(default)]
+    pub records_stored: Option<u64>,
+}
+
+/// A payment accepted by a node for storing a record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaymentEvent {
+    pub timestamp: DateTime<Utc>,
+    pub amount: u64,
+}
+
+/// The information gathered from a node when its earnings are recorded.
+pub struct NodeSnapshot {
+    pub balance: u64,
+    pub payments: Vec<PaymentEvent>,
+    pub records_stored: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EarningsHistory {
+    pub save_path: PathBuf,
+    pub records: Vec<BalanceRecord>,
+}
+
+impl EarningsHistory {
+    pub fn save(&self) -> Result<()> {
+        let path = Path::new(&self.save_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_string(self)?;
+        let mut file = std::fs::File::create(self.save_path.clone())?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(EarningsHistory {
+                save_path: path.to_path_buf(),
+                records: vec![],
+            });
+        }
+        let mut file = std::fs::File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let history = serde_json::from_str(&contents)?;
+        Ok(history)
+    }
+
+    /// Add a snapshot for a node, calculating the deltas from its previous snapshot.
+    pub fn record(&mut self, service_name: &str, snapshot: NodeSnapshot, timestamp: DateTime<Utc>) {
+        let (delta, payments_received) = match self
+            .records
+            .iter()
+            .rev()
+            .find(|r| r.service_name == service_name)
+        {
+            Some(previous) => (
+                snapshot.balance as i64 - previous.balance as i64,
+                snapshot
+                    .payments
+                    .iter()
+                    .filter(|p| p.timestamp > previous.timestamp && p.timestamp <= timestamp)
+                    .count() as u64,
+            ),
+            None => (0, 0),
+        };
+        self.records.push(BalanceRecord {
+            service_name: service_name.to_string(),
+            timestamp,
+            balance: snapshot.balance,
+            delta,
+            payments_received,
+            records_stored: snapshot.records_stored,
+        });
+    }
+
+    /// Move the history of a node to a new service name, after it has been renamed.
+    pub fn rename_service(&mut self, service_name: &str, new_name: &str) {
+        for record in self
+            .records
+            .iter_mut()
+            .filter(|r| r.service_name == service_name)
+        {
+            record.service_name = new_name.to_string();
+        }
+    }
+
+    /// Determine whether a node has shown any sign of earning since a point in time.
+    ///
+    /// That is, its balance increased, it accepted a payment or the number of records it stores
+    /// went up. If the node wasn't being tracked for the whole period, there isn't enough history
+    /// to say, so `None` is returned.
+    pub fn has_earned_since(&self, service_name: &str, since: DateTime<Utc>) -> Option<bool> {
+        let records = self
+            .records
+            .iter()
+            .filter(|r| r.service_name == service_name)
+            .collect::<Vec<&BalanceRecord>>();
+        if !records.iter().any(|r| r.timestamp <= since) {
+            return None;
+        }
+
+        let (before, after): (Vec<&BalanceRecord>, Vec<&BalanceRecord>) =
+            records.into_iter().partition(|r| r.timestamp <= since);
+        if after.iter().any(|r| r.delta > 0 || r.payments_received > 0) {
+            return Some(true);
+        }
+        let records_stored_before = before.iter().rev().find_map(|r| r.records_stored);
+        let records_stored_after = after.iter().filter_map(|r| r.records_stored).max();
+        Some(matches!(
+            (records_stored_before, records_stored_after),
+            (Some(before), Some(after)) if after > before
+        ))
+    }
+
+    /// Summarise the earnings of each node for records taken at or after `since`.
+    ///
+    /// Only increases in balance count as earnings. A decrease means tokens were moved out of the
+    /// node's wallet, which is not something we want to subtract from what it earned.
+    pub fn report(&self, since: DateTime<Utc>) -> EarningsReport {
+        let mut nodes: Vec<NodeEarnings> = Vec::new();
+        for record in self.records.iter() {
+            let index = match nodes
+                .iter()
+                .position(|n| n.service_name == record.service_name)
+            {
+                Some(index) => index,
+                None => {
+                    nodes.push(NodeEarnings {
+                        service_name: record.service_name.clone(),
+                        earned: 0,
+                        balance: 0,
+                        payments_received: 0,
+                        records_stored: None,
+                    });
+                    nodes.len() - 1
+                }
+            };
+            let node = &mut nodes[index];
+            node.balance = record.balance;
+            if record.records_stored.is_some() {
+                node.records_stored = record.records_stored;
+            }
+            if record.timestamp >= since {
+                if record.delta > 0 {
+                    node.earned += record.delta as u64;
+                }
+                node.payments_received += record.payments_received;
+            }
+        }
+        let total_earned = nodes.iter().map(|n| n.earned).sum();
+        let total_balance = nodes.iter().map(|n| n.balance).sum();
+        let total_payments_received = nodes.iter().map(|n| n.payments_received).sum();
+        let total_records_stored = nodes.iter().filter_map(|n| n.records_stored).sum();
+        EarningsReport {
+            since,
+            nodes,
+            total_earned,
+            total_balance,
+            total_payments_received,
+            total_records_stored,
+        }
+    }
+}
+
+/// The earnings of a node on a single day, in UTC.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DailyEarnings {
+    pub date: NaiveDate,
+    pub service_name: String,
+    pub earned: u64,
+    /// The balance from the last record taken on that day.
+    pub closing_balance: u64,
+    pub payments_received: u64,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NodeEarnings {
+    pub service_name: String,
+    pub earned: u64,
+    pub balance: u64,
+    pub payments_received: u64,
+    /// The number of records the node held the last time it was recorded while running.
+    pub records_stored: Option<u64>,
+}
+
+/// Produce a row per node, per day, for the days on which the node was recorded.
+///
+/// The rows are ordered by date, then by the order in which the nodes were first recorded.
+pub fn daily_earnings(earnings_history: &EarningsHistory) -> Vec<DailyEarnings> {
+    let mut rows: Vec<DailyEarnings> = Vec::new();
+    for record in earnings_history.records.iter() {
+        let date = record.timestamp.date_naive();
+        let index = match rows
+            .iter()
+            .position(|r| r.date == date && r.service_name == record.service_name)
+        {
+            Some(index) => index,
+            None => {
+                rows.push(DailyEarnings {
+                    date,
+                    service_name: record.service_name.clone(),
+                    earned: 0,
+                    closing_balance: 0,
+                    payments_received: 0,
+                });
+                rows.len() - 1
+            }
+        };
+        let row = &mut rows[index];
+        row.closing_balance = record.balance;
+        row.payments_received += record.payments_received;
+        if record.delta > 0 {
+            row.earned += record.delta as u64;
+        }
+    }
+    rows.sort_by_key(|r| r.date);
+    rows
+}
+
+pub fn daily_earnings_to_csv(rows: &[DailyEarnings]) -> String {
+    let mut csv = String::from("date,service_name,earned,closing_balance,payments_received\n");
+    for row in rows.iter() {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            row.date.format("%Y-%m-%d"),
+            row.service_name,
+            NanoTokens::from(row.earned),
+            NanoTokens::from(row.closing_balance),
+            row.payments_received
+        ));
+    }
+    csv
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EarningsReport {
+    pub since: DateTime<Utc>,
+    pub nodes: Vec<NodeEarnings>,
+    pub total_earned: u64,
+    pub total_balance: u64,
+    pub total_payments_received: u64,
+    pub total_records_stored: u64,
+}
+
+impl EarningsReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("service_name,earned,balance,payments_received,records_stored\n");
+        for node in self.nodes.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                node.service_name,
+                NanoTokens::from(node.earned),
+                NanoTokens::from(node.balance),
+                node.payments_received,
+                node.records_stored.map_or(String::new(), |r| r.to_string())
+            ));
+        }
+        csv.push_str(&format!(
+            "total,{},{},{},{}\n",
+            NanoTokens::from(self.total_earned),
+            NanoTokens::from(self.total_balance),
+            self.total_payments_received,
+            self.total_records_stored
+        ));
+        csv
+    }
+}
+
+/// Read the balance of the wallet in the node's data directory.
+///
+/// A node that has never been started will not have a wallet yet, so it has a zero balance. We
+/// don't want to load the wallet in that case, because that would create one.
+pub fn get_node_balance(node: &Node) -> Result<u64> {
+    let data_dir_path = node
+        .data_dir_path
+        .as_ref()
+        .ok_or_else(|| eyre!("The data directory should be set for an installed node"))?;
+    if !data_dir_path.join("wallet").exists() {
+        return Ok(0);
+    }
+    let wallet = LocalWallet::try_load_from(data_dir_path)?;
+    Ok(wallet.balance().as_nano())
+}
+
+/// The current balance of a node's wallet.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NodeBalance {
+    pub service_name: String,
+    pub balance: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BalanceReport {
+    pub nodes: Vec<NodeBalance>,
+    pub total_balance: u64,
+}
+
+/// Read the current balance of each of the nodes that hasn't been removed.
+pub fn get_balances<'a>(nodes: impl IntoIterator<Item = &'a Node>) -> Result<BalanceReport> {
+    let mut balances = Vec::new();
+    for node in nodes
+        .into_iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        balances.push(NodeBalance {
+            service_name: node.service_name.clone(),
+            balance: get_node_balance(node)?,
+        });
+    }
+    let total_balance = balances.iter().map(|b| b.balance).sum();
+    Ok(BalanceReport {
+        nodes: balances,
+        total_balance,
+    })
+}
+
+pub fn print_balances(report: &BalanceReport) {
+    println!("{:<18} {:>20}", "Service Name", "Balance");
+    for node in report.nodes.iter() {
+        println!(
+            "{:<18} {:>20}",
+            node.service_name,
+            NanoTokens::from(node.balance).to_string()
+        );
+    }
+    println!(
+        "{:<18} {:>20}",
+        "Total",
+        NanoTokens::from(report.total_balance).to_string()
+    );
+}
+
+/// Parse the payments accepted by a node from the contents of one of its log files.
+///
+/// The lines we are interested in look like this:
+/// ```text
+/// [2024-01-23T12:00:00.123456Z INFO sn_node::put_validation] Total payment of NanoTokens(10) nanos accepted for record 1234(abcd..)
+/// ```
+pub fn parse_payment_events(log_contents: &str) -> Vec<PaymentEvent> {
+    log_contents
+        .lines()
+        .filter_map(|line| {
+            let amount_start =
+                line.find(PAYMENT_ACCEPTED_LOG_MESSAGE)? + PAYMENT_ACCEPTED_LOG_MESSAGE.len();
+            let amount_len = line[amount_start..].find(')')?;
+            let amount = line[amount_start..amount_start + amount_len].parse().ok()?;
+            let timestamp = line.strip_prefix('[')?.split_whitespace().next()?;
+            let timestamp = DateTime::parse_from_rfc3339(timestamp)
+                .ok()?
+                .with_timezone(&Utc);
+            Some(PaymentEvent { timestamp, amount })
+        })
+        .collect()
+}
+
+/// Read the payments accepted by a node from the log files in its log directory.
+///
+/// Log files that have been compressed on rotation are skipped, so only the payments in the
+/// uncompressed files will be found. This is why the payments are captured each time the earnings
+/// are recorded, rather than when the report is produced.
+pub fn read_payment_events(log_dir_path: &Path) -> Result<Vec<PaymentEvent>> {
+    let mut events = Vec::new();
+    if !log_dir_path.exists() {
+        return Ok(events);
+    }
+    for entry in std::fs::read_dir(log_dir_path)? {
+        let path = entry?.path();
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !file_name.starts_with("safenode.log") || file_name.ends_with(".gz") {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        events.extend(parse_payment_events(&contents));
+    }
+    events.sort_by_key(|e| e.timestamp);
+    Ok(events)
+}
+
+/// Take a snapshot of every node in the registry that hasn't been removed.
+///
+/// This is intended to run periodically, e.g., from a timer, so that a history is built up. A
+/// node whose balance or payments can't be read is reported and skipped, so it doesn't stop the
+/// others from being recorded.
+///
+/// The records taken are returned, so the caller can act on any changes in balance.
+pub async fn record_earnings(
+    node_registry: &NodeRegistry,
+    earnings_history: &mut EarningsHistory,
+) -> Result<Vec<BalanceRecord>> {
+    let now = Utc::now();
+    let mut records = Vec::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        match get_node_balance(node) {
+            Ok(balance) => {
+                let payments = match node.log_dir_path {
+                    Some(ref log_dir_path) => match read_payment_events(log_dir_path) {
+                        Ok(payments) => payments,
+                        Err(e) => {
+                            println!(
+                                "{} {}: failed to read payments: {e}",
+                                "✕".red(),
+                                node.service_name
+                            );
+                            continue;
+                        }
+                    },
+                    None => Vec::new(),
+                };
+                let records_stored = if node.status == NodeStatus::Running {
+                    let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+                    rpc_client
+                        .record_addresses()
+                        .await
+                        .ok()
+                        .map(|r| r.len() as u64)
+                } else {
+                    None
+                };
+                earnings_history.record(
+                    &node.service_name,
+                    NodeSnapshot {
+                        balance,
+                        payments,
+                        records_stored,
+                    },
+                    now,
+                );
+                if let Some(record) = earnings_history.records.last() {
+                    records.push(record.clone());
+                }
+                println!(
+                    "{} {}: {}",
+                    "✓".green(),
+                    node.service_name,
+                    NanoTokens::from(balance)
+                );
+            }
+            Err(e) => {
+                println!(
+                    "{} {}: failed to read balance: {e}",
+                    "✕".red(),
+                    node.service_name
+                );
+            }
+        }
+    }
+    Ok(records)
+}
+
+pub fn print_report(report: &EarningsReport) {
+    println!(
+        "Earnings since {}",
+        report.since.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    println!(
+        "{:<18} {:>20} {:>20} {:>10} {:>10}",
+        "Service Name", "Earned", "Balance", "Payments", "Records"
+    );
+    for node in report.nodes.iter() {
+        println!(
+            "{:<18} {:>20} {:>20} {:>10} {:>10}",
+            node.service_name,
+            NanoTokens::from(node.earned).to_string(),
+            NanoTokens::from(node.balance).to_string(),
+            node.payments_received,
+            node.records_stored
+                .map_or("-".to_string(), |r| r.to_string())
+        );
+    }
+    println!(
+        "{:<18} {:>20} {:>20} {:>10} {:>10}",
+        "Total",
+        NanoTokens::from(report.total_earned).to_string(),
+        NanoTokens::from(report.total_balance).to_string(),
+        report.total_payments_received,
+        report.total_records_stored
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::NodeType;
+    use chrono::{Duration, TimeZone};
+
+    fn make_node(service_name: &str, status: NodeStatus, data_dir_path: &Path) -> Node {
+        Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: service_name.to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status,
+            pid: None,
+            peer_id: None,
+            data_dir_path: Some(data_dir_path.to_path_buf()),
+            log_dir_path: None,
+            safenode_path: None,
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }
+    }
+
+    fn new_history() -> EarningsHistory {
+        EarningsHistory {
+            save_path: PathBuf::new(),
+            records: vec![],
+        }
+    }
+
+    fn snapshot(balance: u64) -> NodeSnapshot {
+        NodeSnapshot {
+            balance,
+            payments: vec![],
+            records_stored: None,
+        }
+    }
+
+    #[test]
+    fn record_should_calculate_delta_from_the_previous_record_for_the_same_node() {
+        let mut history = new_history();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        history.record("safenode1", snapshot(100), start);
+        history.record("safenode2", snapshot(50), start);
+        history.record("safenode1", snapshot(250), start + Duration::hours(1));
+        history.record("safenode2", snapshot(20), start + Duration::hours(1));
+
+        assert_eq!(history.records[0].delta, 0);
+        assert_eq!(history.records[1].delta, 0);
+        assert_eq!(history.records[2].delta, 150);
+        assert_eq!(history.records[3].delta, -30);
+    }
+
+    #[test]
+    fn report_should_only_include_earnings_within_the_window() {
+        let mut history = new_history();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        history.record("safenode1", snapshot(100), start);
+        history.record("safenode1", snapshot(200), start + Duration::days(1));
+        history.record("safenode1", snapshot(150), start + Duration::days(2));
+        history.record("safenode1", snapshot(400), start + Duration::days(3));
+        history.record("safenode2", snapshot(0), start + Duration::days(2));
+        history.record("safenode2", snapshot(10), start + Duration::days(3));
+
+        let report = history.report(start + Duration::days(2));
+
+        assert_eq!(report.nodes.len(), 2);
+        assert_eq!(report.nodes[0].service_name, "safenode1");
+        assert_eq!(report.nodes[0].earned, 250);
+        assert_eq!(report.nodes[0].balance, 400);
+        assert_eq!(report.nodes[1].service_name, "safenode2");
+        assert_eq!(report.nodes[1].earned, 10);
+        assert_eq!(report.nodes[1].balance, 10);
+        assert_eq!(report.total_earned, 260);
+        assert_eq!(report.total_balance, 410);
+    }
+
+    #[test]
+    fn get_balances_should_skip_removed_nodes_and_treat_a_missing_wallet_as_empty() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let nodes = [
+            make_node("safenode1", NodeStatus::Added, temp_dir.path()),
+            make_node("safenode2", NodeStatus::Removed, temp_dir.path()),
+        ];
+        let report = get_balances(nodes.iter())?;
+        assert_eq!(
+            report,
+            BalanceReport {
+                nodes: vec![NodeBalance {
+                    service_name: "safenode1".to_string(),
+                    balance: 0,
+                }],
+                total_balance: 0,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn report_to_csv_should_include_a_row_per_node_and_a_total() {
+        let mut history = new_history();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        history.record("safenode1", snapshot(0), start);
+        history.record(
+            "safenode1",
+            snapshot(1_500_000_000),
+            start + Duration::days(1),
+        );
+
+        let csv = history.report(start).to_csv();
+
+        assert_eq!(
+            csv,
+            "service_name,earned,balance,payments_received,records_stored\n\
+             safenode1,1.500000000,1.500000000,0,\n\
+             total,1.500000000,1.500000000,0,0\n"
+        );
+    }
+
+    #[test]
+    fn parse_payment_events_should_find_accepted_payments() -> Result<()> {
+        let log = "\
+[2024-01-23T12:00:00.000000Z INFO sn_node::put_validation] Validating record payment for 1234(abcd..)
+[2024-01-23T12:00:01.500000Z INFO sn_node::put_validation] Total payment of NanoTokens(10) nanos accepted for record 1234(abcd..)
+[2024-01-23T12:05:00.000000Z INFO sn_node::put_validation] Total payment of NanoTokens(25) nanos accepted for record 5678(ef01..)
+";
+        let events = parse_payment_events(log);
+
+        assert_eq!(
+            events,
+            vec![
+                PaymentEvent {
+                    timestamp: DateTime::parse_from_rfc3339("2024-01-23T12:00:01.500000Z")?
+                        .with_timezone(&Utc),
+                    amount: 10,
+                },
+                PaymentEvent {
+                    timestamp: DateTime::parse_from_rfc3339("2024-01-23T12:05:00.000000Z")?
+                        .with_timezone(&Utc),
+                    amount: 25,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn record_should_count_payments_since_the_previous_record() {
+        let mut history = new_history();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let payments = vec![
+            PaymentEvent {
+                timestamp: start - Duration::minutes(10),
+                amount: 10,
+            },
+            PaymentEvent {
+                timestamp: start + Duration::minutes(10),
+                amount: 10,
+            },
+            PaymentEvent {
+                timestamp: start + Duration::minutes(20),
+                amount: 10,
+            },
+        ];
+
+        history.record("safenode1", snapshot(0), start);
+        history.record(
+            "safenode1",
+            NodeSnapshot {
+                balance: 20,
+                payments,
+                records_stored: Some(15),
+            },
+            start + Duration::hours(1),
+        );
+
+        assert_eq!(history.records[0].payments_received, 0);
+        assert_eq!(history.records[1].payments_received, 2);
+
+        let report = history.report(start);
+        assert_eq!(report.nodes[0].payments_received, 2);
+        assert_eq!(report.nodes[0].records_stored, Some(15));
+        assert_eq!(report.total_records_stored, 15);
+    }
+
+    #[test]
+    fn daily_earnings_should_produce_a_row_per_node_per_day() {
+        let mut history = new_history();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+
+        history.record("safenode1", snapshot(0), start);
+        history.record("safenode2", snapshot(0), start);
+        history.record("safenode1", snapshot(100), start + Duration::hours(6));
+        history.record("safenode1", snapshot(300), start + Duration::days(1));
+        history.record("safenode2", snapshot(50), start + Duration::days(1));
+
+        let rows = daily_earnings(&history);
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].service_name, "safenode1");
+        assert_eq!(rows[0].earned, 100);
+        assert_eq!(rows[0].closing_balance, 100);
+        assert_eq!(rows[1].service_name, "safenode2");
+        assert_eq!(rows[1].earned, 0);
+        assert_eq!(rows[2].service_name, "safenode1");
+        assert_eq!(rows[2].earned, 200);
+        assert_eq!(rows[3].service_name, "safenode2");
+        assert_eq!(rows[3].earned, 50);
+
+        assert_eq!(
+            daily_earnings_to_csv(&rows[2..3]),
+            "date,service_name,earned,closing_balance,payments_received\n\
+             2024-01-02,safenode1,0.000000200,0.000000300,0\n"
+        );
+    }
+
+    #[test]
+    fn has_earned_since_should_need_history_covering_the_whole_period() {
+        let mut history = new_history();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        history.record("safenode1", snapshot(100), start);
+        history.record("safenode1", snapshot(100), start + Duration::hours(12));
+
+        assert_eq!(
+            history.has_earned_since("safenode1", start - Duration::hours(1)),
+            None
+        );
+        assert_eq!(
+            history.has_earned_since("safenode2", start + Duration::hours(1)),
+            None
+        );
+        assert_eq!(
+            history.has_earned_since("safenode1", start + Duration::hours(1)),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn has_earned_since_should_count_balance_payments_or_records_as_earning() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let since = start + Duration::hours(1);
+
+        let mut history = new_history();
+        history.record("safenode1", snapshot(100), start);
+        history.record("safenode1", snapshot(150), start + Duration::hours(12));
+        assert_eq!(history.has_earned_since("safenode1", since), Some(true));
+
+        let mut history = new_history();
+        history.record("safenode1", snapshot(100), start);
+        history.record(
+            "safenode1",
+            NodeSnapshot {
+                balance: 100,
+                payments: vec![PaymentEvent {
+                    timestamp: start + Duration::hours(6),
+                    amount: 10,
+                }],
+                records_stored: None,
+            },
+            start + Duration::hours(12),
+        );
+        assert_eq!(history.has_earned_since("safenode1", since), Some(true));
+
+        let mut history = new_history();
+        history.record(
+            "safenode1",
+            NodeSnapshot {
+                balance: 100,
+                payments: vec![],
+                records_stored: Some(10),
+            },
+            start,
+        );
+        history.record(
+            "safenode1",
+            NodeSnapshot {
+                balance: 100,
+                payments: vec![],
+                records_stored: Some(12),
+            },
+            start + Duration::hours(12),
+        );
+        assert_eq!(history.has_earned_since("safenode1", since), Some(true));
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use color_eyre::Result;
+
+/// The source the node manager's events are written under, in the Application log.
+#[cfg(windows)]
+const EVENT_SOURCE: &str = "safenode-manager";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventLevel {
+    Information,
+    Error,
+}
+
+#[cfg(windows)]
+impl EventLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EventLevel::Information => "INFORMATION",
+            EventLevel::Error => "ERROR",
+        }
+    }
+}
+
+/// Write the result of an operation to the Windows Event Log.
+///
+/// The event source is registered the first time an event is written. Failing to write an event
+/// should not cause the operation itself to fail, so any error is only reported.
+///
+/// On other platforms this does nothing, since the results are already in the service manager's
+/// own logs.
+#[cfg(windows)]
+pub fn write_event(level: EventLevel, message: &str) {
+    use std::process::Command;
+
+    if crate::dry_run::is_dry_run() {
+        return;
+    }
+
+    let result = Command::new("eventcreate")
+        .arg("/L")
+        .arg("APPLICATION")
+        .arg("/SO")
+        .arg(EVENT_SOURCE)
+        .arg("/T")
+        .arg(level.as_str())
+        .arg("/ID")
+        .arg("1")
+        .arg("/D")
+        .arg(message)
+        .output();
+    match result {
+        Ok(output) if output.status.success() => {}
+        _ => println!("Failed to write event to the Windows Event Log"),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn write_event(_level: EventLevel, _message: &str) {}
+
+/// Read the most recent events written by the node manager, newest first.
+#[cfg(windows)]
+pub fn read_events(count: usize) -> Result<String> {
+    use color_eyre::eyre::eyre;
+    use std::process::Command;
+
+    let output = Command::new("wevtutil")
+        .arg("qe")
+        .arg("Application")
+        .arg(format!("/q:*[System[Provider[@Name='{EVENT_SOURCE}']]]"))
+        .arg(format!("/c:{count}"))
+        .arg("/rd:true")
+        .arg("/f:text")
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!("Failed to read events from the Windows Event Log"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(not(windows))]
+pub fn read_events(_count: usize) -> Result<String> {
+    use color_eyre::{eyre::eyre, Help};
+    Err(eyre!("The event log is only available on Windows")
+        .suggestion("On Linux, use journalctl to view the events for the node services"))
+}
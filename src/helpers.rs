@@ -6,33 +6,34 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use color_eyre::Result;
+use crate::host::get_running_platform;
+use crate::output::report;
+use color_eyre::{eyre::eyre, Help, Result};
+use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
-use sn_releases::{get_running_platform, ArchiveType, ReleaseType, SafeReleaseRepositoryInterface};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use sn_releases::{ArchiveType, ReleaseType, SafeReleaseRepositoryInterface};
+use sn_transfers::MainPubkey;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Downloads and extracts a release binary to a temporary location.
+///
+/// If a checksum is given, the downloaded archive must match it, otherwise nothing is extracted.
 pub async fn download_and_extract_release(
     release_type: ReleaseType,
     url: Option<String>,
     version: Option<String>,
+    checksum: Option<String>,
     release_repo: &dyn SafeReleaseRepositoryInterface,
 ) -> Result<(PathBuf, String)> {
-    let pb = Arc::new(ProgressBar::new(0));
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
-        .progress_chars("#>-"));
-    let pb_clone = pb.clone();
-    let callback: Box<dyn Fn(u64, u64) + Send + Sync> = Box::new(move |downloaded, total| {
-        pb_clone.set_length(total);
-        pb_clone.set_position(downloaded);
-    });
+    let (pb, callback) = make_download_progress()?;
 
     let temp_dir_path = create_temp_dir()?;
 
     let (archive_path, version) = if let Some(url) = url {
-        println!("Retrieving {release_type} from {url}");
+        report!("Retrieving {release_type} from {url}");
         let archive_path = release_repo
             .download_release(&url, &temp_dir_path, &callback)
             .await?;
@@ -42,16 +43,20 @@ pub async fn download_and_extract_release(
         let version = if let Some(version) = version {
             version
         } else {
-            println!("Retrieving latest version for {release_type}...");
+            report!("Retrieving latest version for {release_type}...");
             release_repo.get_latest_version(&release_type).await?
         };
 
-        println!("Downloading {release_type} version {version}...");
+        report!("Downloading {release_type} version {version}...");
         let archive_path = release_repo
             .download_release_from_s3(
                 &release_type,
                 &version,
-                &get_running_platform()?,
+                &get_running_platform().map_err(|_| {
+                    eyre!("There are no safenode releases for this platform").suggestion(
+                        "Use the --url argument to supply a safenode archive built for it",
+                    )
+                })?,
                 &ArchiveType::TarGz,
                 &temp_dir_path,
                 &callback,
@@ -61,12 +66,386 @@ pub async fn download_and_extract_release(
         (archive_path, version)
     };
 
+    if let Some(checksum) = checksum {
+        verify_checksum(&archive_path, &checksum)?;
+    }
     let safenode_download_path =
         release_repo.extract_release_archive(&archive_path, &temp_dir_path)?;
 
     Ok((safenode_download_path, version))
 }
 
+/// Get a release binary from a file on this machine, for hosts that can't reach the release
+/// repository.
+///
+/// The file is either the binary itself or a zip or gzipped tar archive with the binary inside,
+/// like a release. If a checksum is given, the file must match it. It's copied to a temporary
+/// location, as a download would be, and run with `--version` to check it works; the version it
+/// reports is returned with its path.
+pub fn extract_local_release(
+    path: &Path,
+    checksum: Option<&str>,
+    release_repo: &dyn SafeReleaseRepositoryInterface,
+) -> Result<(PathBuf, String)> {
+    if !path.is_file() {
+        return Err(eyre!("There is no file at {}", path.to_string_lossy()));
+    }
+    if let Some(checksum) = checksum {
+        verify_checksum(path, checksum)?;
+    }
+    let temp_dir_path = create_temp_dir()?;
+    let is_archive = path
+        .extension()
+        .is_some_and(|extension| extension == "gz" || extension == "zip");
+    let bin_path = if is_archive {
+        report!("Extracting {}...", path.to_string_lossy());
+        release_repo.extract_release_archive(path, &temp_dir_path)?
+    } else {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| eyre!("Could not get the file name of {}", path.to_string_lossy()))?;
+        let bin_path = temp_dir_path.join(file_name);
+        std::fs::copy(path, &bin_path)?;
+        bin_path
+    };
+
+    let version = get_bin_version(&bin_path).map_err(|e| {
+        eyre!(
+            "The binary from {} could not be run: {e}",
+            path.to_string_lossy()
+        )
+    })?;
+    report!("Using version {version} from {}", path.to_string_lossy());
+    Ok((bin_path, version))
+}
+
+/// Downloads the auditor release archive for a version and extracts its binary to a temporary
+/// location.
+///
+/// The releases crate doesn't know about the auditor, so the archive is downloaded from its bucket
+/// by URL, in the same way as a node archive supplied with `--url`.
+pub async fn download_and_extract_auditor(
+    version: &str,
+    checksum: Option<String>,
+    release_repo: &dyn SafeReleaseRepositoryInterface,
+) -> Result<PathBuf> {
+    let (pb, callback) = make_download_progress()?;
+    let temp_dir_path = create_temp_dir()?;
+    let url = get_auditor_archive_url(version)?;
+    report!("Downloading sn_auditor version {version} from {url}...");
+    let archive_path = release_repo
+        .download_release(&url, &temp_dir_path, &callback)
+        .await?;
+    pb.finish_with_message("Download complete");
+
+    if let Some(checksum) = checksum {
+        verify_checksum(&archive_path, &checksum)?;
+    }
+    Ok(release_repo.extract_release_archive(&archive_path, &temp_dir_path)?)
+}
+
+/// Get the URL of the archive for a version of the auditor, for this host.
+pub fn get_auditor_archive_url(version: &str) -> Result<String> {
+    let platform = get_running_platform()
+        .map_err(|_| eyre!("There are no sn_auditor releases for this platform"))?;
+    Ok(format!(
+        "https://sn-auditor.s3.eu-west-2.amazonaws.com/sn_auditor-{version}-{platform}.{}",
+        ArchiveType::TarGz
+    ))
+}
+
+type DownloadCallback = Box<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Make a progress bar for a download, with the callback that moves it along.
+fn make_download_progress() -> Result<(Arc<ProgressBar>, DownloadCallback)> {
+    let pb = Arc::new(ProgressBar::new(0));
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
+        .progress_chars("#>-"));
+    let pb_clone = pb.clone();
+    let callback: DownloadCallback = Box::new(move |downloaded, total| {
+        pb_clone.set_length(total);
+        pb_clone.set_position(downloaded);
+    });
+    Ok((pb, callback))
+}
+
+/// Get the URL of the archive the release repository downloads a release from, for this host.
+pub fn get_release_archive_url(release_type: &ReleaseType, version: &str) -> Result<String> {
+    // These are the buckets the releases crate downloads from.
+    let base_url = match release_type {
+        ReleaseType::Faucet => "https://sn-faucet.s3.eu-west-2.amazonaws.com",
+        ReleaseType::Safe => "https://sn-cli.s3.eu-west-2.amazonaws.com",
+        ReleaseType::Safenode => "https://sn-node.s3.eu-west-2.amazonaws.com",
+        ReleaseType::SafenodeManager | ReleaseType::SafenodeManagerDaemon => {
+            "https://sn-node-manager.s3.eu-west-2.amazonaws.com"
+        }
+        ReleaseType::SafenodeRpcClient => "https://sn-node-rpc-client.s3.eu-west-2.amazonaws.com",
+    };
+    let platform = get_running_platform()
+        .map_err(|_| eyre!("There are no {release_type} releases for this platform"))?;
+    Ok(format!(
+        "{base_url}/{}-{version}-{platform}.{}",
+        release_type.to_string().to_lowercase(),
+        ArchiveType::TarGz
+    ))
+}
+
+/// Get the checksum published for a release archive.
+///
+/// The checksum is in a file alongside the archive, with `.sha256` added to its name, either by
+/// itself or in the format written by `sha256sum`. `None` is returned if there is no such file.
+pub async fn get_published_checksum(archive_url: &str) -> Result<Option<String>> {
+    let checksum_url = format!("{archive_url}.sha256");
+    let response = reqwest::get(&checksum_url)
+        .await
+        .map_err(|e| eyre!("Could not retrieve the checksum from {checksum_url}: {e}"))?;
+    // S3 responds with 403 rather than 404 for a file that doesn't exist.
+    if matches!(
+        response.status(),
+        reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::FORBIDDEN
+    ) {
+        return Ok(None);
+    }
+    let contents = response.error_for_status()?.text().await?;
+    let checksum = parse_checksum(contents.split_whitespace().next().unwrap_or_default())
+        .map_err(|_| eyre!("The checksum published at {checksum_url} is not valid"))?;
+    Ok(Some(checksum))
+}
+
+/// Look up the checksum published for a release archive, which the download must then have.
+///
+/// It's an error if there isn't one, since the download would be installed without being
+/// verified. With `skip_checksum`, that's allowed, and there's a warning instead.
+pub async fn get_release_checksum(
+    archive_url: &str,
+    skip_checksum: bool,
+) -> Result<Option<String>> {
+    let checksum = get_published_checksum(archive_url).await?;
+    if checksum.is_none() {
+        if !skip_checksum {
+            return Err(eyre!(
+                "No checksum is published for {archive_url}, so the download can't be verified"
+            )
+            .suggestion(
+                "Supply the checksum with --checksum, or use --skip-checksum to install the \
+                 download without verifying it",
+            ));
+        }
+        report!(
+            "[!] Warning: no checksum is published for {archive_url}, so the download can't be \
+             verified"
+        );
+    }
+    Ok(checksum)
+}
+
+/// Parse a hex-encoded SHA-256 checksum, which is returned in lowercase.
+pub fn parse_checksum(value: &str) -> Result<String> {
+    if value.len() != 64 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(eyre!("'{value}' is not a hex-encoded SHA-256 checksum"));
+    }
+    Ok(value.to_lowercase())
+}
+
+/// Check a file has the expected checksum.
+pub fn verify_checksum(path: &Path, expected: &str) -> Result<()> {
+    let actual = get_file_checksum(path)?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(eyre!(
+            "The checksum of {} is {actual}, but it should be {expected}",
+            path.to_string_lossy()
+        )
+        .suggestion("The file may be corrupt or may have been tampered with, so it was not used"));
+    }
+    report!(
+        "{} Verified the checksum of {}",
+        "✓".green(),
+        path.to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Get the hex-encoded SHA-256 checksum of a file.
+pub fn get_file_checksum(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Get the version of a binary by running it with `--version`.
+pub fn get_bin_version(path: &Path) -> Result<String> {
+    let output = std::process::Command::new(path).arg("--version").output()?;
+    if !output.status.success() {
+        return Err(eyre!("Failed to run {} --version", path.to_string_lossy()));
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    parse_version_output(&output).ok_or_else(|| {
+        eyre!(
+            "Could not find a version in the output of {} --version",
+            path.to_string_lossy()
+        )
+    })
+}
+
+/// Find the process running an executable, if there is one.
+pub fn find_process_by_exe(exe_path: &Path) -> Option<u32> {
+    use sysinfo::{PidExt, ProcessExt, System, SystemExt};
+
+    let mut system = System::new();
+    system.refresh_processes();
+    system
+        .processes()
+        .values()
+        .find(|process| process.exe() == exe_path)
+        .map(|process| process.pid().as_u32())
+}
+
+/// Get the path of the executable a running process was started from.
+pub fn get_process_exe(pid: u32) -> Option<PathBuf> {
+    use sysinfo::{Pid, ProcessExt, System, SystemExt};
+
+    let mut system = System::new();
+    system.refresh_process(Pid::from(pid as usize));
+    system
+        .process(Pid::from(pid as usize))
+        .map(|process| process.exe().to_path_buf())
+}
+
+/// Get the total size of the files in a directory and the directories beneath it.
+///
+/// Links are not followed, so the `safenode` link in a node's data directory only counts once.
+pub fn get_dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += get_dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Find the version in the output of a binary's `--version` argument, e.g., `0.98.1` in
+/// `safenode cli 0.98.1`.
+fn parse_version_output(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .find(|word| semver::Version::parse(word.trim_start_matches('v')).is_ok())
+        .map(|word| word.trim_start_matches('v').to_string())
+}
+
+/// Parse an environment variable in the form `KEY=VALUE`.
+///
+/// The value can be empty, or contain `=`, but the name has to be one a shell would accept.
+pub fn parse_env_variable(value: &str) -> Result<(String, String)> {
+    let (name, value) = value
+        .split_once('=')
+        .ok_or_else(|| eyre!("The environment variable '{value}' must be in the form KEY=VALUE"))?;
+    let is_valid_name = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_valid_name {
+        return Err(eyre!("'{name}' is not a valid environment variable name"));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Set environment variables, replacing the values of any that are already set.
+///
+/// The variables keep the order they were first set in.
+pub fn merge_env_variables(variables: &mut Vec<(String, String)>, updates: Vec<(String, String)>) {
+    for (name, value) in updates {
+        match variables.iter_mut().find(|(n, _)| *n == name) {
+            Some(variable) => variable.1 = value,
+            None => variables.push((name, value)),
+        }
+    }
+}
+
+/// Parse a duration such as `30s`, `10m`, `1h`, `7d` or `2w`.
+///
+/// This is used for arguments that specify a period of time relative to now.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| eyre!("The duration '{value}' has no unit (s, m, h, d or w)"))?,
+    );
+    let number: u64 = number
+        .parse()
+        .map_err(|_| eyre!("The duration '{value}' must start with a number"))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(eyre!(
+                "The duration unit '{unit}' is not one of s, m, h, d or w"
+            ))
+        }
+    };
+    let secs = number
+        .checked_mul(multiplier)
+        .ok_or_else(|| eyre!("The duration '{value}' is out of range"))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Format a duration using the largest unit from `parse_duration` that represents it exactly.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    for (unit, multiplier) in [
+        ("w", 7 * 24 * 60 * 60),
+        ("d", 24 * 60 * 60),
+        ("h", 60 * 60),
+        ("m", 60),
+    ] {
+        if secs > 0 && secs.checked_rem(multiplier) == Some(0) {
+            return format!("{}{unit}", secs / multiplier);
+        }
+    }
+    format!("{secs}s")
+}
+
+/// Check that a rewards address is a valid public key for a wallet.
+///
+/// The address is the hex encoding of the wallet's main public key. Validating it up front means a
+/// typo is caught before any services are defined, rather than nodes being paid to nowhere.
+pub fn validate_rewards_address(address: &str) -> Result<()> {
+    MainPubkey::from_hex(address).map_err(|_| {
+        eyre!("The rewards address '{address}' is not valid")
+            .suggestion("The address should be the 96-character hex public key of a wallet")
+    })?;
+    Ok(())
+}
+
+/// Read rewards addresses from a file, with one address per line.
+///
+/// Blank lines and lines starting with `#` are ignored, so the file can be annotated.
+pub fn read_rewards_addresses(path: &Path) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        eyre!(
+            "Could not read rewards addresses from {}: {e}",
+            path.display()
+        )
+    })?;
+    Ok(contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
 /// There is a `tempdir` crate that provides the same kind of functionality, but it was flagged for
 /// a security vulnerability.
 fn create_temp_dir() -> Result<PathBuf> {
@@ -76,3 +455,182 @@ fn create_temp_dir() -> Result<PathBuf> {
     std::fs::create_dir_all(&new_temp_dir)?;
     Ok(new_temp_dir)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn parse_version_output_should_find_the_version() {
+        assert_eq!(
+            parse_version_output("safenode cli 0.98.1\n"),
+            Some("0.98.1".to_string())
+        );
+        assert_eq!(
+            parse_version_output("faucet v0.3.7"),
+            Some("0.3.7".to_string())
+        );
+        assert_eq!(parse_version_output("safenode cli"), None);
+    }
+
+    #[test]
+    fn get_file_checksum_should_return_the_sha256_of_the_file() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let file = temp_dir.child("safenode");
+        file.write_binary(b"abc")?;
+        assert_eq!(
+            get_file_checksum(file.path())?,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_release_checksum_should_fail_if_none_is_published_unless_it_is_skipped(
+    ) -> Result<()> {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().take(2).flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+        let archive_url =
+            format!("http://{address}/safenode-0.98.1-x86_64-unknown-linux-musl.tar.gz");
+
+        assert!(get_release_checksum(&archive_url, false).await.is_err());
+        assert_eq!(get_release_checksum(&archive_url, true).await?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn verify_checksum_should_reject_a_file_that_does_not_match() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let file = temp_dir.child("safenode-0.98.1-x86_64-unknown-linux-musl.tar.gz");
+        file.write_binary(b"abc")?;
+        let checksum =
+            parse_checksum("BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD")?;
+        verify_checksum(file.path(), &checksum)?;
+
+        let result = verify_checksum(file.path(), &"0".repeat(64));
+        assert!(result.is_err_and(|e| e.to_string().contains("but it should be 0000")));
+        assert!(parse_checksum("ba7816bf").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn get_dir_size_should_add_up_the_files_in_nested_directories() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        temp_dir.child("secret-key").write_binary(&[0; 32])?;
+        temp_dir
+            .child("record_store/record1")
+            .write_binary(&[0; 100])?;
+        temp_dir
+            .child("record_store/nested/record2")
+            .write_binary(&[0; 50])?;
+        assert_eq!(get_dir_size(temp_dir.path())?, 182);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_env_variable_should_split_on_the_first_equals_sign() -> Result<()> {
+        assert_eq!(
+            parse_env_variable("SN_LOG=all")?,
+            ("SN_LOG".to_string(), "all".to_string())
+        );
+        assert_eq!(
+            parse_env_variable("OPTS=a=b")?,
+            ("OPTS".to_string(), "a=b".to_string())
+        );
+        assert_eq!(
+            parse_env_variable("EMPTY=")?,
+            ("EMPTY".to_string(), String::new())
+        );
+        assert!(parse_env_variable("SN_LOG").is_err());
+        assert!(parse_env_variable("=all").is_err());
+        assert!(parse_env_variable("1ST=all").is_err());
+        assert!(parse_env_variable("HTTP-PROXY=x").is_err());
+
+        let mut variables = vec![
+            ("SN_LOG".to_string(), "all".to_string()),
+            ("HTTP_PROXY".to_string(), "http://proxy:3128".to_string()),
+        ];
+        merge_env_variables(
+            &mut variables,
+            vec![
+                ("HTTP_PROXY".to_string(), "http://proxy:8080".to_string()),
+                ("RUST_BACKTRACE".to_string(), "1".to_string()),
+            ],
+        );
+        assert_eq!(
+            variables,
+            vec![
+                ("SN_LOG".to_string(), "all".to_string()),
+                ("HTTP_PROXY".to_string(), "http://proxy:8080".to_string()),
+                ("RUST_BACKTRACE".to_string(), "1".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_duration_should_parse_each_unit() -> Result<()> {
+        assert_eq!(parse_duration("30s")?, Duration::from_secs(30));
+        assert_eq!(parse_duration("10m")?, Duration::from_secs(600));
+        assert_eq!(parse_duration("1h")?, Duration::from_secs(3600));
+        assert_eq!(parse_duration("7d")?, Duration::from_secs(7 * 86400));
+        assert_eq!(parse_duration("2w")?, Duration::from_secs(14 * 86400));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_duration_should_reject_invalid_values() {
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
+
+    #[test]
+    fn format_duration_should_use_the_largest_exact_unit() {
+        assert_eq!(format_duration(Duration::from_secs(14 * 86400)), "2w");
+        assert_eq!(format_duration(Duration::from_secs(86400)), "1d");
+        assert_eq!(format_duration(Duration::from_secs(36 * 3600)), "36h");
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+    }
+
+    #[test]
+    fn read_rewards_addresses_should_skip_blank_lines_and_comments() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let file = temp_dir.child("rewards_addresses.txt");
+        file.write_str("# Wallet A\naddress-a\n\n  address-b  \n# Wallet C\naddress-c\n")?;
+
+        let addresses = read_rewards_addresses(file.path())?;
+        assert_eq!(addresses, vec!["address-a", "address-b", "address-c"]);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_rewards_address_should_accept_a_wallet_public_key() -> Result<()> {
+        let address = MainPubkey::new(sn_transfers::bls::SecretKey::random().public_key()).to_hex();
+        validate_rewards_address(&address)?;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_rewards_address_should_reject_invalid_addresses() {
+        assert!(validate_rewards_address("").is_err());
+        assert!(validate_rewards_address("not-an-address").is_err());
+        assert!(validate_rewards_address(&"a".repeat(96)).is_err());
+        let address = MainPubkey::new(sn_transfers::bls::SecretKey::random().public_key()).to_hex();
+        assert!(validate_rewards_address(&address[..94]).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_releases::Platform;
+use std::process::Command;
+use sysinfo::{System, SystemExt};
+
+/// ARM hosts with this much memory or less, in megabytes, are treated as resource constrained.
+const LOW_MEMORY_THRESHOLD: u64 = 4096;
+/// The memory limit applied to each node on a resource-constrained host, in megabytes.
+const LOW_MEMORY_NODE_LIMIT: u64 = 512;
+/// The memory to allow for each node when recommending how many to run, in megabytes, which
+/// leaves some room for the rest of the system.
+const LOW_MEMORY_PER_NODE: u64 = 1024;
+/// The `SN_LOG` value used on a resource-constrained host, which only logs at the info level.
+///
+/// By default, safenode logs at the debug and trace levels, which is a lot of writing for an SD
+/// card.
+const REDUCED_LOG_TARGETS: &str = "safenode=info,sn_networking=info,sn_node=info";
+
+/// Defaults for new services that depend on the resources of the host.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostDefaults {
+    pub log_targets: Option<String>,
+    pub max_memory: Option<u64>,
+    /// The most nodes the host should run, if it is resource constrained.
+    pub recommended_max_count: Option<u16>,
+}
+
+impl HostDefaults {
+    pub fn is_constrained(&self) -> bool {
+        self.recommended_max_count.is_some()
+    }
+}
+
+/// Get the defaults for a host with the given machine hardware name and amount of memory, in
+/// megabytes.
+pub fn get_defaults_for_host(machine: &str, total_memory: u64) -> HostDefaults {
+    let is_arm = machine.starts_with("arm") || machine == "aarch64";
+    if !is_arm || total_memory > LOW_MEMORY_THRESHOLD {
+        return HostDefaults {
+            log_targets: None,
+            max_memory: None,
+            recommended_max_count: None,
+        };
+    }
+    HostDefaults {
+        log_targets: Some(REDUCED_LOG_TARGETS.to_string()),
+        max_memory: Some(LOW_MEMORY_NODE_LIMIT),
+        recommended_max_count: Some(std::cmp::max(1, total_memory / LOW_MEMORY_PER_NODE) as u16),
+    }
+}
+
+/// Get the defaults for the machine the node manager is running on.
+pub fn get_host_defaults() -> HostDefaults {
+    let mut system = System::new();
+    system.refresh_memory();
+    get_defaults_for_host(&get_machine(), system.total_memory() / (1024 * 1024))
+}
+
+/// Get the platform of the safenode release that should be downloaded for this machine.
+///
+/// The releases crate uses the architecture the node manager was built for, which is `arm` for
+/// both ARMv6 and ARMv7, so an ARMv7 board like a Raspberry Pi 2 or 3 would get the ARMv6 build.
+/// Using the hardware name of the machine instead picks the best binary for the host.
+pub fn get_running_platform() -> sn_releases::Result<Platform> {
+    if cfg!(target_os = "linux") {
+        if let Some(platform) = get_linux_platform_for_machine(&get_machine()) {
+            return Ok(platform);
+        }
+    }
+    sn_releases::get_running_platform()
+}
+
+/// There is no need to detect the C library, because the Linux releases are all statically linked
+/// against musl, so the same binary runs on glibc distributions and musl distributions like Alpine.
+fn get_linux_platform_for_machine(machine: &str) -> Option<Platform> {
+    match machine {
+        "x86_64" => Some(Platform::LinuxMusl),
+        "aarch64" | "arm64" => Some(Platform::LinuxMuslAarch64),
+        m if m.starts_with("armv7") || m.starts_with("armv8") => Some(Platform::LinuxMuslArmV7),
+        m if m.starts_with("arm") => Some(Platform::LinuxMuslArm),
+        _ => None,
+    }
+}
+
+/// Get the machine hardware name, as reported by `uname -m`, falling back to the architecture the
+/// node manager was built for.
+fn get_machine() -> String {
+    Command::new("uname")
+        .arg("-m")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|machine| !machine.is_empty())
+        .unwrap_or_else(|| std::env::consts::ARCH.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_defaults_for_host_should_constrain_a_low_memory_arm_host() {
+        let defaults = get_defaults_for_host("aarch64", 1906);
+        assert_eq!(
+            defaults,
+            HostDefaults {
+                log_targets: Some(REDUCED_LOG_TARGETS.to_string()),
+                max_memory: Some(512),
+                recommended_max_count: Some(1),
+            }
+        );
+        assert!(defaults.is_constrained());
+
+        let defaults = get_defaults_for_host("armv7l", 4096);
+        assert_eq!(defaults.recommended_max_count, Some(4));
+    }
+
+    #[test]
+    fn get_defaults_for_host_should_not_constrain_other_hosts() {
+        assert!(!get_defaults_for_host("aarch64", 8192).is_constrained());
+        assert!(!get_defaults_for_host("x86_64", 1024).is_constrained());
+    }
+
+    #[test]
+    fn get_linux_platform_for_machine_should_pick_the_arm_build_for_the_hardware() {
+        assert_eq!(
+            get_linux_platform_for_machine("aarch64").map(|p| p.to_string()),
+            Some(Platform::LinuxMuslAarch64.to_string())
+        );
+        assert_eq!(
+            get_linux_platform_for_machine("armv7l").map(|p| p.to_string()),
+            Some(Platform::LinuxMuslArmV7.to_string())
+        );
+        assert_eq!(
+            get_linux_platform_for_machine("armv6l").map(|p| p.to_string()),
+            Some(Platform::LinuxMuslArm.to_string())
+        );
+        assert_eq!(
+            get_linux_platform_for_machine("x86_64").map(|p| p.to_string()),
+            Some(Platform::LinuxMusl.to_string())
+        );
+        assert!(get_linux_platform_for_machine("riscv64").is_none());
+    }
+}
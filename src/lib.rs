@@ -0,0 +1,64 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Installs, manages and operates `safenode` as a service.
+//!
+//! This is the library behind the `safenode-manager` binary, which is a command-line wrapper
+//! around it, so node management can be embedded in other tools. The main parts of the API are:
+//!
+//! - [`node_registry::NodeRegistry`], the record of the services on the machine, which is loaded
+//!   from and saved to a file, by default the one at [`config::get_node_registry_path`].
+//! - [`add_service::add`], which obtains the `safenode` binary and installs a service for each
+//!   node, as described by [`add_service::AddServiceOptions`].
+//! - [`control::start`], [`control::stop`], [`control::upgrade`] and [`control::remove`], which
+//!   operate on a node from the registry.
+//! - [`service::ServiceControl`], the interface to the service manager. The
+//!   [`service::get_service_control`] function gets the one for the registry's backend, and a
+//!   different implementation can be supplied to the functions above.
+//!
+//! - [`cmd`], a function for each of the binary's commands, which loads the registry from its
+//!   default location, carries out the command and saves the registry. The binary only parses the
+//!   arguments and calls the one for the command.
+//!
+//! The functions print their progress as they go, unless the output is set to JSON with
+//! [`output::set_json_output`], in which case it goes to stderr.
+
+pub mod add_service;
+pub mod alerts;
+pub mod auditor;
+pub mod autoscale;
+pub mod chaos;
+pub mod cmd;
+pub mod config;
+pub mod config_file;
+pub mod container;
+pub mod control;
+pub mod daemon;
+pub mod diagnostics;
+pub mod doctor;
+pub mod dry_run;
+pub mod earnings;
+pub mod event_log;
+pub mod helpers;
+pub mod host;
+pub mod local;
+pub mod log_shipping;
+pub mod logs;
+pub mod metrics;
+pub mod migration;
+pub mod node_registry;
+pub mod notify;
+pub mod output;
+pub mod ports;
+pub mod schedule;
+pub mod selector;
+pub mod service;
+pub mod setup;
+pub mod systemd_template;
+pub mod telemetry;
+pub mod timeout;
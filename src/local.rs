@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::node_registry::{Node, NodeRegistry, NodeStatus, NodeType, RegistryGuard};
 use crate::service::ServiceControl;
 use color_eyre::{eyre::eyre, Result};
 use colored::Colorize;
@@ -13,7 +14,6 @@ use libp2p::{Multiaddr, PeerId};
 #[cfg(test)]
 use mockall::automock;
 use sn_node_rpc_client::{RpcActions, RpcClient};
-use sn_protocol::node_registry::{Node, NodeRegistry, NodeStatus};
 use std::io::Read;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
@@ -182,6 +182,7 @@ pub async fn run_faucet(
 
 pub async fn run_network(
     node_registry: &mut NodeRegistry,
+    registry_lock: &RegistryGuard,
     service_control: &dyn ServiceControl,
     network_options: LocalNetworkOptions,
 ) -> Result<()> {
@@ -234,7 +235,7 @@ pub async fn run_network(
         // launch, or maybe the validation will fail. In the error case, we will want to use the
         // `kill` command for the nodes that we did spin up. The `kill` command works on the basis
         // of what's in the node registry.
-        node_registry.save()?;
+        node_registry.save_locked(registry_lock)?;
     }
 
     if !network_options.skip_validation {
@@ -274,12 +275,18 @@ pub async fn run_node(
 
     Ok(Node {
         connected_peers,
+        records_stored: None,
+        resource_usage: None,
+        listen_addr: None,
         genesis,
+        node_type: NodeType::Safenode,
         service_name: format!("safenode-local{number}"),
         user: get_username()?,
         number,
         port,
         rpc_port,
+        rpc_address: None,
+        node_ip: None,
         version: version.clone(),
         status: NodeStatus::Running,
         pid: Some(node_info.pid),
@@ -287,6 +294,29 @@ pub async fn run_node(
         log_dir_path: Some(node_info.log_path),
         data_dir_path: Some(node_info.data_path),
         safenode_path: Some(launcher.get_safenode_path()),
+        local: true,
+        peers: peer,
+        home_network: false,
+        upnp: false,
+        extra_args: vec![],
+        env_variables: vec![],
+        rewards_address: None,
+        owner: None,
+        firewall_rule_added: false,
+        keep_alive: true,
+        throttle_interval: None,
+        log_targets: None,
+        max_memory: None,
+        auto_restart: None,
+        auto_restart_reset_period: 86400,
+        safenode_checksum: None,
+        previous_version: None,
+        maintenance: false,
+        restart_schedule: None,
+        last_stop_method: None,
+        exit_info: None,
+        watchdog_restarts: 0,
+        last_failure: None,
     })
 }
 
@@ -325,7 +355,7 @@ async fn validate_network(node_registry: &mut NodeRegistry, peers: Vec<Multiaddr
     all_peers.extend(additional_peers);
 
     for node in node_registry.nodes.iter() {
-        let rpc_client = RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
+        let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
         let net_info = rpc_client.network_info().await?;
         let peers = net_info.connected_peers;
         println!("Node {} has {} peers", node.peer_id.unwrap(), peers.len());
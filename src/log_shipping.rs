@@ -0,0 +1,226 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::logs::{LogLevel, LogLine};
+use crate::node_registry::Node;
+use chrono::Utc;
+use color_eyre::{eyre::eyre, Result};
+use serde_json::json;
+use std::collections::BTreeMap;
+use sysinfo::{System, SystemExt};
+
+/// The most lines that are held while the endpoint can't be reached. The oldest lines are dropped
+/// beyond this.
+pub const BUFFER_LIMIT: usize = 10_000;
+
+/// Where the logs are sent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogDestination {
+    /// The base URL of a Loki server, e.g., `http://loki:3100`.
+    Loki { url: String },
+    /// The base URL of an Elasticsearch cluster, and the index the lines are added to.
+    Elasticsearch { url: String, index: String },
+}
+
+/// The labels that identify where a line came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLabels {
+    pub host: String,
+    pub service_name: String,
+    pub peer_id: Option<String>,
+    pub version: String,
+}
+
+impl LogLabels {
+    pub fn new(host: &str, node: &Node) -> Self {
+        LogLabels {
+            host: host.to_string(),
+            service_name: node.service_name.clone(),
+            peer_id: node.peer_id.map(|p| p.to_string()),
+            version: node.version.clone(),
+        }
+    }
+}
+
+pub fn get_host_name() -> String {
+    System::new()
+        .host_name()
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Send a batch of lines, where each line's source is the index of its labels.
+pub async fn ship_lines(
+    client: &reqwest::Client,
+    destination: &LogDestination,
+    labels: &[LogLabels],
+    lines: &[LogLine],
+) -> Result<()> {
+    let request = match destination {
+        LogDestination::Loki { url } => client
+            .post(format!("{}/loki/api/v1/push", url.trim_end_matches('/')))
+            .json(&make_loki_push(labels, lines)),
+        LogDestination::Elasticsearch { url, index } => client
+            .post(format!("{}/_bulk", url.trim_end_matches('/')))
+            .header("Content-Type", "application/x-ndjson")
+            .body(make_elasticsearch_bulk(index, labels, lines)),
+    };
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(eyre!(
+            "The logs were rejected with status {}",
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+fn level_name(level: Option<LogLevel>) -> &'static str {
+    match level {
+        Some(LogLevel::Trace) => "trace",
+        Some(LogLevel::Debug) => "debug",
+        Some(LogLevel::Info) => "info",
+        Some(LogLevel::Warn) => "warn",
+        Some(LogLevel::Error) => "error",
+        None => "unknown",
+    }
+}
+
+/// Build the body of a Loki push, with a stream for each node and level.
+fn make_loki_push(labels: &[LogLabels], lines: &[LogLine]) -> serde_json::Value {
+    let mut streams: BTreeMap<(usize, &str), Vec<serde_json::Value>> = BTreeMap::new();
+    for line in lines {
+        let timestamp = line.timestamp.unwrap_or_else(Utc::now);
+        streams
+            .entry((line.source, level_name(line.level)))
+            .or_default()
+            .push(json!([
+                timestamp
+                    .timestamp_nanos_opt()
+                    .unwrap_or_default()
+                    .to_string(),
+                line.text
+            ]));
+    }
+    let streams = streams
+        .into_iter()
+        .map(|((source, level), values)| {
+            let labels = &labels[source];
+            let mut stream = json!({
+                "host": labels.host,
+                "service_name": labels.service_name,
+                "version": labels.version,
+                "level": level,
+            });
+            if let Some(peer_id) = &labels.peer_id {
+                stream["peer_id"] = json!(peer_id);
+            }
+            json!({ "stream": stream, "values": values })
+        })
+        .collect::<Vec<_>>();
+    json!({ "streams": streams })
+}
+
+/// Build the body of an Elasticsearch bulk request, with a document for each line.
+fn make_elasticsearch_bulk(index: &str, labels: &[LogLabels], lines: &[LogLine]) -> String {
+    let mut body = String::new();
+    for line in lines {
+        let labels = &labels[line.source];
+        body.push_str(&json!({ "index": { "_index": index } }).to_string());
+        body.push('\n');
+        body.push_str(
+            &json!({
+                "@timestamp": line.timestamp.unwrap_or_else(Utc::now).to_rfc3339(),
+                "host": labels.host,
+                "service_name": labels.service_name,
+                "peer_id": labels.peer_id,
+                "version": labels.version,
+                "level": level_name(line.level),
+                "message": line.text,
+            })
+            .to_string(),
+        );
+        body.push('\n');
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{parse_level, parse_timestamp};
+
+    fn make_labels() -> Vec<LogLabels> {
+        vec![LogLabels {
+            host: "host1".to_string(),
+            service_name: "safenode1".to_string(),
+            peer_id: None,
+            version: "0.98.1".to_string(),
+        }]
+    }
+
+    fn make_line(text: &str) -> LogLine {
+        LogLine {
+            source: 0,
+            timestamp: parse_timestamp(text),
+            level: parse_level(text),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn make_loki_push_should_group_lines_into_streams_by_node_and_level() {
+        let push = make_loki_push(
+            &make_labels(),
+            &[
+                make_line("[2024-02-05T04:00:00.000000Z INFO sn_node] one"),
+                make_line("[2024-02-05T04:00:01.000000Z WARN sn_node] two"),
+                make_line("[2024-02-05T04:00:02.000000Z INFO sn_node] three"),
+            ],
+        );
+        let streams = push["streams"].as_array().unwrap();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0]["stream"]["level"], "info");
+        assert_eq!(streams[0]["stream"]["service_name"], "safenode1");
+        assert_eq!(
+            streams[0]["values"],
+            json!([
+                [
+                    "1707105600000000000",
+                    "[2024-02-05T04:00:00.000000Z INFO sn_node] one"
+                ],
+                [
+                    "1707105602000000000",
+                    "[2024-02-05T04:00:02.000000Z INFO sn_node] three"
+                ]
+            ])
+        );
+        assert_eq!(streams[1]["stream"]["level"], "warn");
+    }
+
+    #[test]
+    fn make_elasticsearch_bulk_should_add_a_document_for_each_line() {
+        let bulk = make_elasticsearch_bulk(
+            "safenode-logs",
+            &make_labels(),
+            &[make_line(
+                "[2024-02-05T04:00:00.000000Z ERROR sn_node] failed",
+            )],
+        );
+        let lines = bulk.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], r#"{"index":{"_index":"safenode-logs"}}"#);
+        let document: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(document["@timestamp"], "2024-02-05T04:00:00+00:00");
+        assert_eq!(document["level"], "error");
+        assert_eq!(document["service_name"], "safenode1");
+        assert_eq!(
+            document["message"],
+            "[2024-02-05T04:00:00.000000Z ERROR sn_node] failed"
+        );
+    }
+}
@@ -0,0 +1,428 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::Node;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use color_eyre::{eyre::eyre, Result};
+use colored::{Color, Colorize};
+use regex::Regex;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The colours used for the prefixes of merged logs, in turn for each node.
+const PREFIX_COLOURS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+/// The most that's read from the end of a log file to find its last lines, in bytes.
+const TAIL_LIMIT: u64 = 1024 * 1024;
+
+/// How often the log files are checked for new lines when following them.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A line from a node's log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    /// The index of the node the line came from.
+    pub source: usize,
+    /// The time the line was written, which is taken from the previous line for lines without
+    /// one, like those that continue a multi-line message.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// The level the line was logged at, which is also taken from the previous line for lines
+    /// without one.
+    pub level: Option<LogLevel>,
+    pub text: String,
+}
+
+/// The levels the node logs at, from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "TRACE" => Ok(LogLevel::Trace),
+            "DEBUG" => Ok(LogLevel::Debug),
+            "INFO" => Ok(LogLevel::Info),
+            "WARN" => Ok(LogLevel::Warn),
+            "ERROR" => Ok(LogLevel::Error),
+            _ => Err(eyre!("'{s}' is not a log level")),
+        }
+    }
+}
+
+/// Which log lines should be displayed.
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    /// Only lines that match this pattern.
+    pub pattern: Option<Regex>,
+    /// Only lines logged at this level or a more severe one.
+    pub level: Option<LogLevel>,
+    /// Only lines written at or after this time.
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl LogFilter {
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none() && self.level.is_none() && self.since.is_none()
+    }
+
+    pub fn matches(&self, line: &LogLine) -> bool {
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(&line.text) {
+                return false;
+            }
+        }
+        if let Some(level) = self.level {
+            match line.level {
+                Some(line_level) if line_level >= level => {}
+                _ => return false,
+            }
+        }
+        if let Some(since) = self.since {
+            match line.timestamp {
+                Some(timestamp) if timestamp >= since => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Follows the most recent log file of a node, across log rotations.
+pub struct LogTail {
+    log_dir_path: PathBuf,
+    path: Option<PathBuf>,
+    offset: u64,
+    partial: String,
+    last_level: Option<LogLevel>,
+    /// Set when reading starts part of the way through a line.
+    skip_partial_line: bool,
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl LogTail {
+    pub fn new(log_dir_path: &Path) -> Self {
+        LogTail {
+            log_dir_path: log_dir_path.to_path_buf(),
+            path: None,
+            offset: 0,
+            partial: String::new(),
+            last_level: None,
+            skip_partial_line: false,
+            last_timestamp: None,
+        }
+    }
+
+    /// Read the last `count` lines of the current log file that pass the filter, and move to its
+    /// end.
+    ///
+    /// Without a filter, only the end of the file is read. With one, the whole file is searched.
+    pub fn read_last(
+        &mut self,
+        source: usize,
+        count: usize,
+        filter: &LogFilter,
+    ) -> Result<Vec<LogLine>> {
+        self.path = get_latest_log_file(&self.log_dir_path)?;
+        if let Some(path) = self.path.as_ref().filter(|_| filter.is_empty()) {
+            let len = std::fs::metadata(path)?.len();
+            if len > TAIL_LIMIT {
+                self.offset = len - TAIL_LIMIT;
+                self.skip_partial_line = true;
+            }
+        }
+        let mut lines = self.read_new(source)?;
+        lines.retain(|l| filter.matches(l));
+        Ok(lines.split_off(lines.len().saturating_sub(count)))
+    }
+
+    /// Read the complete lines that have been written since the last read.
+    ///
+    /// If the node has started a new log file, the rest of the old one is read before moving on to
+    /// the new one.
+    pub fn read_new(&mut self, source: usize) -> Result<Vec<LogLine>> {
+        let latest = get_latest_log_file(&self.log_dir_path)?;
+        let mut lines = Vec::new();
+        if let Some(path) = self.path.clone() {
+            if latest.as_ref() != Some(&path) {
+                lines.extend(self.read_from_offset(source, &path)?);
+                self.path = None;
+                self.offset = 0;
+                self.partial.clear();
+            }
+        }
+        if self.path.is_none() {
+            self.path = latest;
+        }
+        if let Some(path) = self.path.clone() {
+            lines.extend(self.read_from_offset(source, &path)?);
+        }
+        Ok(lines)
+    }
+
+    fn read_from_offset(&mut self, source: usize, path: &Path) -> Result<Vec<LogLine>> {
+        let mut file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let len = file.metadata()?.len();
+        // The file was truncated, so start again from the beginning.
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        self.offset += data.len() as u64;
+
+        self.partial.push_str(&String::from_utf8_lossy(&data));
+        let mut lines = Vec::new();
+        while let Some(index) = self.partial.find('\n') {
+            let text = self.partial[..index].trim_end_matches('\r').to_string();
+            self.partial.drain(..=index);
+            if self.skip_partial_line {
+                self.skip_partial_line = false;
+                continue;
+            }
+            if let Some(timestamp) = parse_timestamp(&text) {
+                self.last_timestamp = Some(timestamp);
+                self.last_level = parse_level(&text);
+            }
+            lines.push(LogLine {
+                source,
+                timestamp: self.last_timestamp,
+                level: self.last_level,
+                text,
+            });
+        }
+        Ok(lines)
+    }
+}
+
+/// Get the most recently written log file in a node's log directory.
+///
+/// The files that capture the node's standard output and error are not included.
+pub fn get_latest_log_file(log_dir_path: &Path) -> Result<Option<PathBuf>> {
+    if !log_dir_path.exists() {
+        return Ok(None);
+    }
+
+    let mut latest: Option<(std::time::SystemTime, PathBuf)> = None;
+    for entry in std::fs::read_dir(log_dir_path)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !path.is_file() || file_name.ends_with(".stdout") || file_name.ends_with(".stderr") {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        let is_latest = match &latest {
+            Some((time, _)) => modified > *time,
+            None => true,
+        };
+        if is_latest {
+            latest = Some((modified, path));
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
+
+/// Get the time at the start of a node's log line, e.g.,
+/// `[2024-02-05T04:00:00.123456Z INFO sn_node] Node started`.
+pub fn parse_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let token = line.trim_start_matches('[').split_whitespace().next()?;
+    DateTime::parse_from_rfc3339(token.trim_end_matches(']'))
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Get the level that follows the time at the start of a node's log line.
+pub fn parse_level(line: &str) -> Option<LogLevel> {
+    line.trim_start_matches('[')
+        .split_whitespace()
+        .nth(1)
+        .and_then(|token| token.trim_end_matches(']').parse().ok())
+}
+
+/// Order the lines from several nodes by their timestamps.
+///
+/// The sort is stable, so lines from the same node keep their order, and lines without a
+/// timestamp of their own stay with the line before them.
+pub fn merge_lines(mut lines: Vec<LogLine>) -> Vec<LogLine> {
+    lines.sort_by_key(|l| l.timestamp);
+    lines
+}
+
+/// Print the logs of the given nodes, optionally merging them into a single stream ordered by
+/// time, and optionally following them as they are written.
+pub async fn print_logs(
+    nodes: &[&Node],
+    count: usize,
+    merge: bool,
+    follow: bool,
+    filter: &LogFilter,
+) -> Result<()> {
+    let mut tails = Vec::new();
+    for node in nodes.iter() {
+        let log_dir_path = node.log_dir_path.as_ref().ok_or_else(|| {
+            eyre!(
+                "The log directory of {} is not in the registry",
+                node.service_name
+            )
+        })?;
+        tails.push(LogTail::new(log_dir_path));
+    }
+    let prefixes = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            node.service_name
+                .color(PREFIX_COLOURS[i % PREFIX_COLOURS.len()])
+                .to_string()
+        })
+        .collect::<Vec<String>>();
+    let show_prefix = merge || nodes.len() > 1;
+
+    let mut lines = Vec::new();
+    for (source, tail) in tails.iter_mut().enumerate() {
+        lines.extend(tail.read_last(source, count, filter)?);
+    }
+    if merge {
+        let merged = merge_lines(lines);
+        lines = merged[merged.len().saturating_sub(count)..].to_vec();
+    }
+    print_lines(&lines, &prefixes, show_prefix);
+
+    if !follow {
+        return Ok(());
+    }
+    loop {
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+        let mut lines = Vec::new();
+        for (source, tail) in tails.iter_mut().enumerate() {
+            lines.extend(tail.read_new(source)?);
+        }
+        lines.retain(|l| filter.matches(l));
+        if merge {
+            lines = merge_lines(lines);
+        }
+        print_lines(&lines, &prefixes, show_prefix);
+    }
+}
+
+fn print_lines(lines: &[LogLine], prefixes: &[String], show_prefix: bool) {
+    for line in lines {
+        if show_prefix {
+            println!("{} | {}", prefixes[line.source], line.text);
+        } else {
+            println!("{}", line.text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn merge_lines_should_interleave_the_nodes_by_time() {
+        let line = |source: usize, text: &str| LogLine {
+            source,
+            timestamp: parse_timestamp(text),
+            level: parse_level(text),
+            text: text.to_string(),
+        };
+        let merged = merge_lines(vec![
+            line(0, "[2024-02-05T04:00:00.000000Z INFO sn_node] a"),
+            line(0, "[2024-02-05T04:00:02.000000Z INFO sn_node] c"),
+            line(1, "[2024-02-05T04:00:01.000000Z INFO sn_node] b"),
+            line(1, "[2024-02-05T04:00:03.000000Z INFO sn_node] d"),
+        ]);
+        assert_eq!(
+            merged
+                .iter()
+                .map(|l| l.text.chars().last().unwrap())
+                .collect::<String>(),
+            "abcd"
+        );
+    }
+
+    #[test]
+    fn log_filter_should_apply_the_pattern_level_and_time() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        temp_dir.child("safenode.log").write_str(
+            "[2024-02-05T04:00:00.000000Z ERROR sn_node] old failure\n\
+             [2024-02-05T05:00:00.000000Z INFO sn_node] connected\n\
+             [2024-02-05T05:00:01.000000Z WARN sn_networking] dial failure\n\
+             caused by timeout\n\
+             [2024-02-05T05:00:02.000000Z ERROR sn_node] storage failure\n",
+        )?;
+
+        let filter = LogFilter {
+            pattern: Some(Regex::new("fail|cause")?),
+            level: Some(LogLevel::Warn),
+            since: Some(DateTime::parse_from_rfc3339("2024-02-05T04:30:00Z")?.with_timezone(&Utc)),
+        };
+        let lines = LogTail::new(temp_dir.path()).read_last(0, 10, &filter)?;
+        assert_eq!(
+            lines.iter().map(|l| l.text.as_str()).collect::<Vec<&str>>(),
+            vec![
+                "[2024-02-05T05:00:01.000000Z WARN sn_networking] dial failure",
+                "caused by timeout",
+                "[2024-02-05T05:00:02.000000Z ERROR sn_node] storage failure",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn log_tail_should_only_read_complete_new_lines() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let log_file = temp_dir.child("safenode.log");
+        log_file.write_str(
+            "[2024-02-05T04:00:00.000000Z INFO sn_node] one\ncontinued\n[2024-02-05T04:00:01",
+        )?;
+
+        let mut tail = LogTail::new(temp_dir.path());
+        let lines = tail.read_last(0, 10, &LogFilter::default())?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].text, "continued");
+        assert_eq!(lines[1].timestamp, lines[0].timestamp);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(log_file.path())?;
+        std::io::Write::write_all(&mut file, b".000000Z INFO sn_node] two\n")?;
+        let lines = tail.read_new(0)?;
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines[0].text,
+            "[2024-02-05T04:00:01.000000Z INFO sn_node] two"
+        );
+        Ok(())
+    }
+}
@@ -6,30 +6,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-mod add_service;
-mod config;
-mod control;
-mod helpers;
-mod local;
-mod service;
-
-use crate::add_service::{add, AddServiceOptions};
-use crate::config::*;
-use crate::control::{remove, start, status, stop, upgrade, UpgradeResult};
-use crate::helpers::download_and_extract_release;
-use crate::local::{kill_network, run_faucet, run_network, LocalNetworkOptions};
-use crate::service::{NodeServiceManager, ServiceControl};
-use clap::{Parser, Subcommand};
-use color_eyre::{eyre::eyre, Help, Result};
-use colored::Colorize;
-use libp2p_identity::PeerId;
-use semver::Version;
-use sn_node_rpc_client::RpcClient;
-use sn_peers_acquisition::{get_peers_from_args, PeersArgs};
-use sn_protocol::node_registry::{get_local_node_registry_path, NodeRegistry};
-use sn_releases::{ReleaseType, SafeReleaseRepositoryInterface};
+use clap::{CommandFactory, Parser, Subcommand};
+use color_eyre::{eyre::eyre, Result};
+use regex::Regex;
+use sn_node_manager::add_service::PortRange;
+use sn_node_manager::chaos::{parse_fault_rate, FaultRate};
+use sn_node_manager::cmd;
+use sn_node_manager::container::ServiceBackend;
+use sn_node_manager::control::{DEFAULT_STARTUP_TIMEOUT, DEFAULT_STOP_GRACE_PERIOD};
+use sn_node_manager::dry_run::set_dry_run;
+use sn_node_manager::earnings::ExportFormat;
+use sn_node_manager::helpers::{parse_checksum, parse_duration, parse_env_variable};
+use sn_node_manager::logs::LogLevel;
+use sn_node_manager::output::set_json_output;
+use sn_node_manager::selector::NodeSelector;
+use sn_node_manager::timeout::set_timeout;
+use sn_peers_acquisition::PeersArgs;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
-use std::str::FromStr;
+use std::time::Duration;
 
 const DEFAULT_NODE_COUNT: u16 = 25;
 
@@ -39,8 +34,32 @@ pub(crate) struct Cmd {
     /// Available sub commands.
     #[clap(subcommand)]
     pub cmd: SubCmd,
+    /// The longest the command can run for, e.g., "10m" or "90s".
+    ///
+    /// When the time is up, the nodes that haven't been processed are skipped, the registry is
+    /// saved with the changes to those that were, and the command reports which nodes completed.
+    ///
+    /// The daemon runs until it's stopped, so it can't be given a timeout.
+    #[clap(long, global = true, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+    /// Output a JSON document rather than text.
+    ///
+    /// Commands that change services output the services they changed. Any other messages are
+    /// written to stderr, so stdout only has the JSON.
+    #[clap(long, global = true)]
+    pub json: bool,
+    /// Report what the add, upgrade, remove, reset and autoscale run commands would do, without
+    /// changing anything.
+    ///
+    /// The releases that would be downloaded, the service definitions and users that would be
+    /// created, and the directories that would be deleted are all listed. The registry isn't
+    /// saved.
+    #[clap(long, global = true)]
+    pub dry_run: bool,
 }
 
+// The enum is only created once, when the arguments are parsed, so its size doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum SubCmd {
     /// Add one or more new safenode services.
@@ -48,6 +67,41 @@ pub enum SubCmd {
     /// This command must run as the root/administrative user.
     #[clap(name = "add")]
     Add {
+        /// Restart the node if it fails, after waiting this many seconds.
+        ///
+        /// This sets the recovery actions for the service, so the Service Control Manager will
+        /// restart the node after its first, second and subsequent failures.
+        ///
+        /// This only applies on Windows.
+        #[clap(long)]
+        auto_restart: Option<u64>,
+        /// The number of seconds without a failure after which the failure count for automatic
+        /// restarts is reset.
+        ///
+        /// This only applies on Windows.
+        #[clap(long, default_value_t = 86400, requires = "auto_restart")]
+        auto_restart_reset_period: u64,
+        /// How the services are run: by the service manager, as Docker or Podman containers, or as
+        /// instances of a templated systemd unit.
+        ///
+        /// With Docker or Podman, each node gets its own container, which uses the host's network
+        /// and mounts the node's data and log directories, so the other commands work in the same
+        /// way as they do for native services. Containers are only supported on Linux.
+        ///
+        /// With systemd, the systemd-template backend installs a single templated unit,
+        /// safenode@.service, with a small drop-in for each node rather than a whole unit, so that
+        /// large numbers of nodes can be managed with, e.g., `systemctl start safenode@{1..100}`.
+        ///
+        /// The backend is chosen when the first services are added, and all the services in the
+        /// registry use it.
+        #[clap(long, value_enum)]
+        backend: Option<ServiceBackend>,
+        /// The SHA-256 checksum the safenode archive, or the file supplied with --path, must have.
+        ///
+        /// Without this, a download is checked against the checksum published alongside the
+        /// release.
+        #[clap(long, value_parser = parse_checksum)]
+        checksum: Option<String>,
         /// The number of service instances.
         ///
         /// If the --first argument is used, the count has to be one, so --count and --first are
@@ -61,9 +115,43 @@ pub enum SubCmd {
         /// If not provided, the default location is platform specific:
         ///  - Linux: /var/safenode-manager/services
         ///  - macOS: /var/safenode-manager/services
-        ///  - Windows: C:\ProgramData\safenode\services
+        ///  - Windows: C:\ProgramData\safenode\data
         #[clap(long, verbatim_doc_comment)]
         data_dir_path: Option<PathBuf>,
+        /// An environment variable to set for safenode, in the form KEY=VALUE, e.g., SN_LOG=all.
+        ///
+        /// This argument can be used multiple times to set more than one variable. The variables
+        /// are written into the service definition, and can be changed later with 'node edit'.
+        #[clap(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_variable)]
+        env_variables: Vec<(String, String)>,
+        /// Arguments to pass to safenode as they are, supplied after `--`.
+        ///
+        /// These are added after the arguments the node manager sets, and are recorded for each
+        /// node, so they're kept when the service is reinstalled.
+        #[clap(last = true, allow_hyphen_values = true)]
+        extra_args: Vec<String>,
+        /// Set this flag to launch safenode with the --home-network flag.
+        ///
+        /// This is for nodes behind a NAT that can't be reached directly, whose traffic is then
+        /// relayed through other nodes.
+        #[clap(long)]
+        home_network: bool,
+        /// An interval applied between adding each service, in milliseconds.
+        #[clap(long, default_value_t = 0)]
+        interval: u64,
+        /// The image the containers are created from, when the backend is Docker or Podman.
+        ///
+        /// The safenode binary is mounted into the container, so the image only needs to provide
+        /// a root filesystem. The default is debian:bookworm-slim.
+        #[clap(long, requires = "backend")]
+        image: Option<String>,
+        /// Copy the settings of an existing service.
+        ///
+        /// The new services get the same version, peers, owner, rewards address, data and log
+        /// directory prefixes, limits and restart settings as the existing one, but with their own
+        /// ports and peer IDs. Any of these can still be overridden with the other arguments.
+        #[clap(long, conflicts_with = "first")]
+        like: Option<String>,
         /// Set this flag to launch safenode with the --local flag.
         ///
         /// This is useful for building a service-based local network.
@@ -79,22 +167,97 @@ pub enum SubCmd {
         ///  - Windows: C:\ProgramData\safenode\logs
         #[clap(long, verbatim_doc_comment)]
         log_dir_path: Option<PathBuf>,
+        /// The memory limit for each node, in megabytes.
+        ///
+        /// If the node uses more memory than this, systemd will stop it, and it will be
+        /// restarted. On ARM hosts with 4GB of memory or less, such as a Raspberry Pi, the
+        /// default is 512; otherwise there is no limit.
+        ///
+        /// This only applies on Linux.
+        #[clap(long)]
+        max_memory: Option<u64>,
+        /// Set this flag to prevent the service from being restarted if the node exits.
+        ///
+        /// This only applies on macOS; by default, launchd will restart the node.
+        #[clap(long)]
+        no_keep_alive: bool,
+        /// The IP address for the node(s) to listen on.
+        ///
+        /// By default, nodes listen on every interface.
+        #[clap(long)]
+        node_ip: Option<IpAddr>,
+        /// Set this flag to add an inbound Windows Firewall rule for each node's port.
+        ///
+        /// The rule will be deleted when the service is removed. This only applies on Windows.
+        #[clap(long)]
+        open_firewall: bool,
+        /// The owner of the node(s), for participation in the rewards programme.
+        ///
+        /// This can be changed later using the `node set-owner` command.
+        #[clap(long)]
+        owner: Option<String>,
+        /// Provide a safenode binary from a file, rather than downloading it.
+        ///
+        /// The file can be the binary itself, or a zip or gzipped tar archive with the binary
+        /// inside. The binary is run with --version to check it works, and the nodes are recorded
+        /// with the version it reports. This is for hosts that can't reach the release repository.
+        #[clap(long, conflicts_with_all = ["url", "version"])]
+        path: Option<PathBuf>,
         #[command(flatten)]
         peers: PeersArgs,
         /// Specify a port for the node to run on.
         ///
         /// If not used, a port will be selected at random.
         ///
-        /// This option only applies when a single service is being added.
+        /// A range of ports, e.g., 12000-12009, can be supplied when more than one service is
+        /// being added, and the ports will be assigned to the new services in turn. The count
+        /// defaults to the number of ports in the range.
         #[clap(long)]
-        port: Option<u16>,
+        port: Option<PortRange>,
+        /// The address that rewards earned by the node(s) should be paid to.
+        ///
+        /// A comma-separated list of addresses can be supplied, or the argument can be used more
+        /// than once, in which case the addresses will be assigned to the new services in turn.
+        ///
+        /// This can be changed later using the `node set-rewards-address` command.
+        #[clap(long, value_delimiter = ',')]
+        rewards_address: Vec<String>,
+        /// Provide a file with the addresses that rewards should be paid to, one per line.
+        ///
+        /// The addresses are assigned to the new services in turn, along with any supplied using
+        /// the --rewards-address argument.
+        #[clap(long)]
+        rewards_address_file: Option<PathBuf>,
+        /// The address for the node's RPC service to listen on, e.g., 0.0.0.0 to listen on every
+        /// interface.
+        ///
+        /// By default it only listens on the loopback address. The RPC service allows the node to
+        /// be stopped and restarted, so it should only be exposed on a trusted network.
+        #[clap(long)]
+        rpc_address: Option<IpAddr>,
         /// Specify a port for the node's RPC service to run on.
         ///
         /// If not used, a port will be selected at random.
         ///
-        /// This option only applies when a single service is being added.
+        /// As with --port, a range of ports can be supplied for more than one service.
         #[clap(long)]
-        rpc_port: Option<u16>,
+        rpc_port: Option<PortRange>,
+        /// Install the download even if no checksum is published for it.
+        ///
+        /// Without this, a download that can't be verified is an error, unless --checksum is used.
+        #[clap(long)]
+        skip_checksum: bool,
+        /// The minimum number of seconds launchd will wait before restarting the node.
+        ///
+        /// This only applies on macOS; if not used, the launchd default of 10 seconds applies.
+        #[clap(long)]
+        throttle_interval: Option<u64>,
+        /// Set this flag to launch safenode with the --upnp flag.
+        ///
+        /// The node will ask the router to forward its port, which makes it reachable behind a NAT
+        /// without setting up port forwarding by hand.
+        #[clap(long)]
+        upnp: bool,
         /// Provide a safenode binary using a URL.
         ///
         /// The binary must be inside a zip or gzipped tar archive.
@@ -111,10 +274,163 @@ pub enum SubCmd {
         /// On Windows this argument will have no effect.
         #[clap(long)]
         user: Option<String>,
+        /// Create a user for each service, named after it, rather than running them all as one.
+        ///
+        /// Each node's data and log directories are owned by its user and can't be read by the
+        /// others, so a compromised node can't get at the data or keys of the rest.
+        ///
+        /// On Windows this argument will have no effect.
+        #[clap(long, conflicts_with = "user")]
+        user_per_node: bool,
         /// The version of safenode
         #[clap(long)]
         version: Option<String>,
     },
+    /// Check the safenode services against their alert thresholds.
+    #[clap(name = "alerts", subcommand)]
+    Alerts(AlertsSubCmd),
+    /// Manage the auditor service, which follows the spends made on the network.
+    #[clap(name = "auditor", subcommand)]
+    Auditor(AuditorSubCmd),
+    /// Scale the number of safenode services with the resources of the host.
+    #[clap(name = "autoscale", subcommand)]
+    Autoscale(AutoscaleSubCmd),
+    /// Show the current balance of each safenode service's wallet, and the total.
+    ///
+    /// If no peer ID(s) or service name(s) are supplied, the balances of all services are shown.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "balance")]
+    Balance {
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+    /// Inject faults into the running safenode services at random, to check how the network copes
+    /// with churn.
+    ///
+    /// Each fault is picked at random from killing a node's process, which the service manager
+    /// should treat as a crash, restarting its service, and stopping its service. Nodes that were
+    /// stopped are started again at the end of the run. Every fault is logged as it's injected.
+    ///
+    /// Nodes in maintenance mode are left alone.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "chaos")]
+    Chaos {
+        /// How long to inject faults for, e.g., 2h.
+        #[clap(long, value_parser = parse_duration)]
+        duration: Duration,
+        /// How often to inject a fault, as a number of faults per period, e.g., 1/10m.
+        ///
+        /// The time between faults is random, but they happen at this rate on average.
+        #[clap(long, value_parser = parse_fault_rate)]
+        kill_rate: FaultRate,
+        /// The number of nodes that must be left running. No faults are injected while only this
+        /// many nodes are running.
+        #[clap(long, default_value_t = 1)]
+        min_running: usize,
+    },
+    /// Output the completion script for a shell.
+    ///
+    /// For example, for bash, run `safenode-manager completions bash >
+    /// /usr/share/bash-completion/completions/safenode-manager`.
+    #[clap(name = "completions")]
+    Completions {
+        /// The shell to output the script for.
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Run a daemon that serves an HTTP API for managing the safenode services on this machine.
+    ///
+    /// The API lets a central orchestrator get the status of the services, and start, stop,
+    /// restart and upgrade them, without having to log in to the machine. The operations run the
+    /// same commands as the command line.
+    ///
+    /// Requests must have an 'Authorization: Bearer <token>' header. The token is generated the
+    /// first time the daemon runs, and is written to a file that only the owner can read.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "daemon")]
+    Daemon {
+        /// The address to listen on.
+        ///
+        /// It's only reachable from this machine by default. If it's made reachable from
+        /// elsewhere, it should be put behind a proxy that provides TLS.
+        #[clap(long, default_value = "127.0.0.1:12600")]
+        address: SocketAddr,
+        /// Keep the services up to date by checking for a new version of safenode this often,
+        /// e.g., 6h.
+        ///
+        /// When there's a new version, the services are upgraded one at a time with a delay in
+        /// between, so they aren't all down at once. Nodes in maintenance mode are left alone.
+        #[clap(long, value_parser = parse_duration)]
+        auto_upgrade_interval: Option<Duration>,
+        /// How long to wait between upgrading each service during an automatic upgrade, e.g., 5m.
+        #[clap(long, default_value = "5m", value_parser = parse_duration)]
+        auto_upgrade_delay: Duration,
+        /// Record the balance of each service this often, e.g., 1h, as `earnings record` does.
+        ///
+        /// This builds the history that `earnings report` uses, without a separate scheduled job.
+        #[clap(long, value_parser = parse_duration)]
+        earnings_interval: Option<Duration>,
+        /// Check the services respond this often, e.g., 5m, and restart those that don't.
+        ///
+        /// A service that should be running is restarted if its process has gone, or its RPC
+        /// service doesn't answer. Nodes in maintenance mode are left alone.
+        #[clap(long, value_parser = parse_duration)]
+        watchdog_interval: Option<Duration>,
+        /// Have the watchdog recycle a service that looks shunned over this period, e.g., 1d, by
+        /// restarting it with a new peer ID.
+        ///
+        /// A service looks shunned if it has no connected peers, or it hasn't earned over the
+        /// period, as `status --details` reports. The earnings check needs the history from
+        /// --earnings-interval or `earnings record`. A service must have been running for at least
+        /// the period before it's recycled. Its secret key and records are removed, but its wallet
+        /// is kept.
+        #[clap(long, requires = "watchdog_interval", value_parser = parse_duration)]
+        recycle_shunned_after: Option<Duration>,
+        /// Serve metrics for each service on this port, in the format Prometheus scrapes.
+        ///
+        /// The metrics are at /metrics, on the same IP address as the API. They don't need the
+        /// token.
+        #[clap(long)]
+        metrics_port: Option<u16>,
+        /// Post a notification to this webhook URL when a service receives a payment, or the
+        /// watchdog restarts one.
+        ///
+        /// Payments are only noticed when the balances are recorded, so those notifications need
+        /// --earnings-interval.
+        #[clap(long)]
+        notify_webhook: Option<String>,
+    },
+    /// Write a bundle of information for debugging problems with the safenode services.
+    ///
+    /// The bundle contains a summary of the system, the node registry and the most recent logs of
+    /// each service. It can be shared with support to help them find the problem.
+    #[clap(name = "diagnostics")]
+    Diagnostics {
+        /// The file to write the bundle to.
+        ///
+        /// If not provided, it will be written to the current directory.
+        #[clap(long)]
+        out: Option<PathBuf>,
+        /// Upload the bundle to this URL, which will be provided by support.
+        ///
+        /// The bundle is only ever uploaded when this is supplied.
+        #[clap(long)]
+        upload: Option<String>,
+    },
+    /// Check the host can run and manage safenode services.
+    ///
+    /// Each check is reported as passing, as a warning or as failing, along with a suggested fix.
+    #[clap(name = "doctor")]
+    Doctor {},
+    /// Track the earnings of safenode services.
+    #[clap(name = "earnings", subcommand)]
+    Earnings(EarningsSubCmd),
+    /// Export the safenode services, for use with other tools or on another host.
+    #[clap(name = "export", subcommand)]
+    Export(ExportSubCmd),
     /// Add one or more new safenode services.
     ///
     /// This command must run as the root/administrative user.
@@ -133,6 +449,40 @@ pub enum SubCmd {
         #[clap(long)]
         version: Option<String>,
     },
+    /// Import the safenode services from an export of another host's registry.
+    ///
+    /// A service definition is created on this host for each node. The nodes whose data was
+    /// exported have it restored, so they keep their peer IDs. The rest use the version they
+    /// were running, which is downloaded, and will have new peer IDs when they're started. The
+    /// nodes are numbered after any services already on this host, so they may have new names.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "import")]
+    Import {
+        /// The file written by the export registry command.
+        path: PathBuf,
+        /// The directory the nodes' data directories are created under.
+        ///
+        /// If not provided, the default location is used.
+        #[clap(long)]
+        data_dir_path: Option<PathBuf>,
+        /// The directory the nodes' log directories are created under.
+        ///
+        /// If not provided, the default location is used.
+        #[clap(long)]
+        log_dir_path: Option<PathBuf>,
+        /// Install the downloads even if no checksum is published for them.
+        ///
+        /// A download is only needed for a node whose data wasn't exported. Without this, one that
+        /// can't be verified is an error.
+        #[clap(long)]
+        skip_checksum: bool,
+        /// The user the services should run as, rather than the users they had on the other host.
+        ///
+        /// If the account does not exist, it will be created.
+        #[clap(long)]
+        user: Option<String>,
+    },
     /// Kill the running local network.
     #[clap(name = "kill")]
     Kill {
@@ -178,23 +528,142 @@ pub enum SubCmd {
         #[clap(long)]
         skip_validation: bool,
     },
+    /// View the logs of the safenode services, or the events written by the node manager.
+    #[clap(name = "logs")]
+    Logs {
+        /// View the logs of all the services.
+        #[clap(long, conflicts_with = "service_name")]
+        all: bool,
+        /// The number of lines or events to display
+        #[clap(long, default_value_t = 20)]
+        count: usize,
+        /// Set this flag to read the events written by the node manager to the Windows Event Log.
+        ///
+        /// This only applies on Windows.
+        #[clap(long, conflicts_with_all = ["all", "service_name"])]
+        events: bool,
+        /// Keep displaying lines as they are written to the logs.
+        #[clap(long)]
+        follow: bool,
+        /// Only display lines that match this regular expression.
+        #[clap(long)]
+        grep: Option<Regex>,
+        /// Only display lines logged at this level or a more severe one.
+        ///
+        /// Lines that continue a message, like the rest of a backtrace, have the level of the line
+        /// they continue.
+        #[clap(long, value_enum)]
+        level: Option<LogLevel>,
+        /// Interleave the logs of all the services by time, in a single stream.
+        ///
+        /// Each line is prefixed with the name of its service, in a different colour for each
+        /// service.
+        #[clap(long, requires = "all")]
+        merge: bool,
+        /// The name of the service whose logs should be displayed.
+        #[clap(long)]
+        service_name: Option<String>,
+        /// Only display lines written within this period, e.g., 1h.
+        #[clap(long, value_parser = parse_duration)]
+        since: Option<Duration>,
+    },
+    /// Put safenode services in or out of maintenance mode.
+    ///
+    /// Services in maintenance mode are skipped when upgrading all services, so they can be worked
+    /// on by hand.
+    #[clap(name = "maintenance", subcommand)]
+    Maintenance(MaintenanceSubCmd),
+    /// Generate the man pages for the node manager and its commands.
+    ///
+    /// Without an output directory, the page for the top-level command is written to stdout.
+    #[clap(name = "man")]
+    Man {
+        /// The directory to write a page for each command to, e.g., /usr/share/man/man1.
+        #[clap(long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Change the configuration of existing safenode services.
+    #[clap(name = "node", subcommand)]
+    Node(NodeSubCmd),
+    /// Display the paths the node manager uses on this machine.
+    ///
+    /// This includes the data, log and binary paths of each service.
+    #[clap(name = "paths")]
+    Paths {},
+    /// List every port allocated to the safenode services, and whether anything is bound to it.
+    ///
+    /// Ports allocated to more than one service, ports of running services that nothing is
+    /// listening on, and ports of stopped services that something else is bound to are
+    /// highlighted.
+    #[clap(name = "ports")]
+    Ports {},
     /// Remove a safenode service.
     ///
-    /// Either a peer ID or the service name must be supplied.
+    /// Either a peer ID or the service name must be supplied, or the --all flag used.
     ///
     /// This command must run as the root/administrative user.
     #[clap(name = "remove")]
     Remove {
-        /// The peer ID of the service to remove.
-        #[clap(long)]
-        peer_id: Option<String>,
-        /// The name of the service to remove.
-        #[clap(long, conflicts_with = "peer_id")]
-        service_name: Option<String>,
+        /// Set this flag to remove every service.
+        #[clap(long, conflicts_with_all = ["peer_ids", "peer_ids_file", "service_name"])]
+        all: bool,
+        #[clap(flatten)]
+        selector: NodeSelector,
         /// Set this flag to keep the node's data and log directories.
         #[clap(long)]
         keep_directories: bool,
     },
+    /// Stop and remove every safenode service, delete all their data and log directories, and
+    /// clear the node registry.
+    ///
+    /// This is useful when a network has been restarted and the data held by the nodes is no
+    /// longer valid. Confirmation is asked for unless the --force flag is used.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "reset")]
+    Reset {
+        /// Set this flag to reset without asking for confirmation.
+        #[clap(long)]
+        force: bool,
+    },
+    /// Restart safenode services.
+    ///
+    /// Running services are stopped and then started again, and any others are just started.
+    ///
+    /// If no peer ID(s) or service name(s) are supplied, all services will be restarted, apart
+    /// from those in maintenance mode.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "restart")]
+    Restart {
+        /// Whether the nodes keep their peer IDs.
+        ///
+        /// They do by default. Use '--retain-peer-id false' to give them new peer IDs: their data
+        /// directories are cleared before they are started again, which removes their secret
+        /// keys and the records they store. Their wallets are kept.
+        #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+        retain_peer_id: bool,
+        #[clap(flatten)]
+        selector: NodeSelector,
+        /// The number of seconds to wait for each node to report its peer ID and the addresses
+        /// it's listening on.
+        #[clap(long, default_value_t = DEFAULT_STARTUP_TIMEOUT)]
+        startup_timeout: u64,
+    },
+    /// Manage the schedules for restarting safenode services.
+    #[clap(name = "restart-schedule", subcommand)]
+    RestartSchedule(RestartScheduleSubCmd),
+    /// Roll a safenode service back to the version it ran before it was last upgraded.
+    ///
+    /// If no peer ID or service name is supplied, all services that have a previous version will
+    /// be rolled back.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "rollback")]
+    Rollback {
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
     /// Run a local network.
     ///
     /// This will run safenode processes on the current machine to form a local network. A faucet
@@ -231,6 +700,39 @@ pub enum SubCmd {
         #[clap(long)]
         skip_validation: bool,
     },
+    /// Set up nodes by answering a few questions.
+    ///
+    /// This is a guided alternative to the add command for first-time users. It asks how many
+    /// nodes to run, the rewards address, and whether ports have been forwarded to the host, then
+    /// adds the services and optionally starts them.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "setup")]
+    Setup {},
+    /// Send the logs of the safenode services to Loki or Elasticsearch as they are written.
+    ///
+    /// Each line is labelled with the host, service name, peer ID, version and level, so the
+    /// logs of every node in a fleet can be searched in one place. The command keeps running
+    /// until it's stopped, so it's intended to be run as a service of its own. Lines are held and
+    /// sent again if the endpoint can't be reached.
+    ///
+    /// The services are read from the registry when the command starts, so it should be
+    /// restarted after services are added or removed.
+    #[clap(name = "ship-logs")]
+    ShipLogs {
+        /// The base URL of the Elasticsearch cluster, e.g., http://elasticsearch:9200.
+        #[clap(long, required_unless_present = "loki", conflicts_with = "loki")]
+        elasticsearch: Option<String>,
+        /// The Elasticsearch index the lines are added to.
+        #[clap(long, default_value = "safenode-logs")]
+        index: String,
+        /// The number of seconds between each batch of lines that's sent.
+        #[clap(long, default_value_t = 5)]
+        interval: u64,
+        /// The base URL of the Loki server, e.g., http://loki:3100.
+        #[clap(long)]
+        loki: Option<String>,
+    },
     /// Start a safenode service.
     ///
     /// If no peer ID(s) or service name(s) are supplied, all services will be started.
@@ -238,25 +740,45 @@ pub enum SubCmd {
     /// This command must run as the root/administrative user.
     #[clap(name = "start")]
     Start {
-        /// The peer ID of the service to start
-        #[clap(long)]
-        peer_id: Option<String>,
-        /// The name of the service to start
-        #[clap(long, conflicts_with = "peer_id")]
-        service_name: Option<String>,
+        /// The number of services to start at the same time.
+        ///
+        /// This can't be used with an interval.
+        #[clap(
+            long,
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u16).range(1..),
+            conflicts_with = "interval"
+        )]
+        concurrency: u16,
+        /// An interval applied between starting each service, in milliseconds.
+        ///
+        /// The wait is only between the services that are started, so the services that are
+        /// already running don't hold it up.
+        #[clap(long, default_value_t = 0)]
+        interval: u64,
+        #[clap(flatten)]
+        selector: NodeSelector,
+        /// The number of seconds to wait for each node to report its peer ID and the addresses
+        /// it's listening on.
+        #[clap(long, default_value_t = DEFAULT_STARTUP_TIMEOUT)]
+        startup_timeout: u64,
     },
     /// Get the status of services.
     #[clap(name = "status")]
     Status {
         /// Set this flag to display more details
-        #[clap(long)]
+        #[clap(long, conflicts_with = "json")]
         details: bool,
         /// Set this flag to return an error if any nodes are not running
         #[clap(long)]
         fail: bool,
-        /// Set this flag to output the status as a JSON document
-        #[clap(long, conflicts_with = "details")]
-        json: bool,
+        /// The period over which a running node with no earnings, payments or new records will be
+        /// flagged as possibly shunned, e.g., 12h, 1d or 1w.
+        ///
+        /// This relies on the history from the `earnings record` command, so nodes will only be
+        /// flagged if that has been recording for at least this long.
+        #[clap(long, default_value = "1d", value_parser = parse_duration)]
+        health_period: Duration,
     },
     /// Stop a safenode service.
     ///
@@ -265,13 +787,42 @@ pub enum SubCmd {
     /// This command must run as the root/administrative user.
     #[clap(name = "stop")]
     Stop {
-        /// The peer ID of the service to stop
-        #[clap(long)]
-        peer_id: Option<String>,
-        /// The name of the service to stop
-        #[clap(long, conflicts_with = "peer_id")]
-        service_name: Option<String>,
+        /// The number of services to stop at the same time.
+        ///
+        /// This can't be used with an interval.
+        #[clap(
+            long,
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u16).range(1..),
+            conflicts_with = "interval"
+        )]
+        concurrency: u16,
+        /// The number of seconds to wait for a node to exit after its service is stopped.
+        ///
+        /// If the node is still running at the end of this period, its process will be killed.
+        #[clap(long, default_value_t = DEFAULT_STOP_GRACE_PERIOD)]
+        grace_period: u64,
+        /// An interval applied between stopping each service, in milliseconds.
+        ///
+        /// This only applies when stopping all services.
+        #[clap(long, default_value_t = 0)]
+        interval: u64,
+        #[clap(flatten)]
+        selector: NodeSelector,
     },
+    /// Manage the sending of anonymous usage telemetry.
+    ///
+    /// Telemetry is off unless it is turned on.
+    #[clap(name = "telemetry", subcommand)]
+    Telemetry(TelemetrySubCmd),
+    /// Undo the most recent operation that changed the services.
+    ///
+    /// The registry is restored to the snapshot taken before the operation, and where it is safe,
+    /// the changes to the services are reversed too. Only the most recent operation can be undone.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "undo")]
+    Undo {},
     /// Upgrade a safenode service.
     ///
     /// If no peer ID(s) or service name(s) are supplied, all services will be upgraded.
@@ -279,111 +830,657 @@ pub enum SubCmd {
     /// This command must run as the root/administrative user.
     #[clap(name = "upgrade")]
     Upgrade {
-        /// The peer ID of the service to upgrade
+        /// The SHA-256 checksum the safenode archive, or the file supplied with --path, must have.
+        ///
+        /// Without this, the download is checked against the checksum published alongside the
+        /// release.
+        #[clap(long, value_parser = parse_checksum)]
+        checksum: Option<String>,
+        /// Set this flag to allow nodes to be downgraded to the version supplied, or the version of
+        /// the binary supplied with --path.
         #[clap(long)]
-        peer_id: Option<String>,
-        /// The name of the service to upgrade
-        #[clap(long, conflicts_with = "peer_id")]
-        service_name: Option<String>,
+        force: bool,
+        /// An interval applied between upgrading each service, in milliseconds.
+        ///
+        /// The wait is only between the services that are upgraded, so it's possible to upgrade
+        /// them one after another without all of them being down at once.
+        #[clap(long, default_value_t = 0)]
+        interval: u64,
+        /// Upgrade to a safenode binary from a file, rather than downloading a release.
+        ///
+        /// The file can be the binary itself, or a zip or gzipped tar archive with the binary
+        /// inside. The nodes are upgraded to the version the binary reports.
+        #[clap(long, conflicts_with = "version")]
+        path: Option<PathBuf>,
+        #[clap(flatten)]
+        selector: NodeSelector,
+        /// Install the download even if no checksum is published for it.
+        ///
+        /// Without this, a download that can't be verified is an error, unless --checksum is used.
+        #[clap(long)]
+        skip_checksum: bool,
+        /// Upgrade to a specific version of safenode, rather than the latest.
+        ///
+        /// A node at a later version is only downgraded if the --force flag is also used, which
+        /// is useful for moving off a new release that has problems.
+        #[clap(long)]
+        version: Option<String>,
+    },
+    /// Verify the safenode binaries of the services have not been modified.
+    ///
+    /// The checksum of each binary is compared against the checksum recorded when it was
+    /// installed.
+    ///
+    /// If no peer ID or service name is supplied, all services will be verified.
+    #[clap(name = "verify")]
+    Verify {
+        /// Download the release of each version in use and compare against its binary instead.
+        ///
+        /// This also detects a binary that was modified before it was installed.
+        #[clap(long)]
+        release: bool,
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+    /// Display the version of the node manager.
+    #[clap(name = "version")]
+    Version {
+        /// Set this flag to also display the version of every installed binary.
+        ///
+        /// The version of each node in the registry is compared against the version reported by
+        /// its binary, and any differences are flagged.
+        #[clap(long)]
+        details: bool,
     },
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
-    color_eyre::install()?;
-    let args = Cmd::parse();
-    match args.cmd {
-        SubCmd::Add {
-            count,
-            data_dir_path,
-            local,
-            log_dir_path,
-            peers,
-            port,
-            rpc_port,
-            url,
-            user,
-            version,
-        } => {
-            if !is_running_as_root() {
-                return Err(eyre!("The add command must run as the root user"));
-            }
-
-            if url.is_some() && version.is_some() {
-                return Err(
-                    eyre!("The url and version arguments are mutually exclusive").suggestion(
-                        "Please try again specifying either url or version, but not both.",
-                    ),
-                );
-            }
-
-            println!("=================================================");
-            println!("              Add Safenode Services              ");
-            println!("=================================================");
-            println!("{} service(s) to be added", count.unwrap_or(1));
-
-            let service_user = user.unwrap_or("safe".to_string());
-            let service_manager = NodeServiceManager {};
-            service_manager.create_service_user(&service_user)?;
-
-            let service_data_dir_path = get_service_data_dir_path(data_dir_path, &service_user)?;
-            let service_log_dir_path = get_service_log_dir_path(log_dir_path, &service_user)?;
-
-            let mut node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
-            let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
-
-            add(
-                AddServiceOptions {
-                    local,
-                    genesis: peers.first,
-                    count,
-                    peers: get_peers_from_args(peers).await?,
-                    port,
-                    rpc_port,
-                    safenode_dir_path: service_data_dir_path.clone(),
-                    service_data_dir_path,
-                    service_log_dir_path,
-                    url,
-                    user: service_user,
-                    version,
-                },
-                &mut node_registry,
-                &service_manager,
-                release_repo,
-            )
-            .await?;
+#[derive(Subcommand, Debug)]
+pub enum AlertsSubCmd {
+    /// Check each running service against its alert thresholds.
+    ///
+    /// The connected peers, memory use, disk use and restarts in the last hour are measured for
+    /// each service, and the webhook is notified of any service that breaches a threshold. This is
+    /// intended to be run periodically, e.g., from a timer.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "check")]
+    Check {},
+    /// Set the alert thresholds, and the webhook that is notified when they are breached.
+    ///
+    /// If no service name is supplied, the thresholds apply to every service, unless a service
+    /// has its own. Only the thresholds that are supplied are changed.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "set")]
+    Set {
+        /// The fullest the disk with the service's data directory can be, as a percentage
+        #[clap(long)]
+        max_disk_percent: Option<f64>,
+        /// The most memory the service's process can use, in megabytes
+        #[clap(long)]
+        max_memory: Option<u64>,
+        /// The most times the service can be restarted in an hour
+        #[clap(long)]
+        max_restarts_per_hour: Option<u32>,
+        /// The fewest peers the service can be connected to
+        #[clap(long)]
+        min_connected_peers: Option<usize>,
+        /// The name of the service the thresholds are for
+        #[clap(long)]
+        service_name: Option<String>,
+        /// The URL of the webhook that is notified when a threshold is breached
+        #[clap(long, conflicts_with = "service_name")]
+        webhook: Option<String>,
+    },
+    /// Display the alert thresholds and webhook.
+    #[clap(name = "status")]
+    Status {},
+}
 
-            node_registry.save()?;
+#[derive(Subcommand, Debug)]
+pub enum AuditorSubCmd {
+    /// Install the auditor as a service.
+    ///
+    /// It's run by the same service manager as the nodes, but it's kept apart from them in the
+    /// registry, so the other commands don't apply to it.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "install")]
+    Install {
+        /// Check the downloaded archive, or the file or archive supplied with --path, has this
+        /// SHA-256 checksum.
+        #[clap(long)]
+        checksum: Option<String>,
+        /// The directory the auditor's data directory is created under.
+        ///
+        /// If not provided, the default location is used.
+        #[clap(long)]
+        data_dir_path: Option<PathBuf>,
+        /// Arguments to pass to sn_auditor as they are, after those set by the manager.
+        #[clap(last = true)]
+        extra_args: Vec<String>,
+        /// The directory the auditor's log directory is created under.
+        ///
+        /// If not provided, the default location is used.
+        #[clap(long)]
+        log_dir_path: Option<PathBuf>,
+        /// Use an sn_auditor binary or release archive on this machine, rather than downloading
+        /// one.
+        #[clap(long, conflicts_with = "version")]
+        path: Option<PathBuf>,
+        #[command(flatten)]
+        peers: PeersArgs,
+        /// Install the download even if no checksum is published for it.
+        ///
+        /// Without this, a download that can't be verified is an error, unless --checksum is used.
+        #[clap(long)]
+        skip_checksum: bool,
+        /// The user the service should run as.
+        ///
+        /// If the account does not exist, it will be created.
+        ///
+        /// On Windows this argument will have no effect.
+        #[clap(long)]
+        user: Option<String>,
+        /// The version of sn_auditor to download.
+        ///
+        /// There's no way to find the latest version of the auditor, so either this or the path
+        /// must be supplied.
+        #[clap(long, required_unless_present = "path")]
+        version: Option<String>,
+    },
+    /// Remove the auditor service.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "remove")]
+    Remove {
+        /// Set this flag to keep the auditor's data and log directories.
+        #[clap(long)]
+        keep_directories: bool,
+    },
+    /// Start the auditor service.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "start")]
+    Start {},
+    /// Stop the auditor service.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "stop")]
+    Stop {},
+}
 
-            Ok(())
-        }
-        SubCmd::Faucet {
-            path,
-            peers,
-            version,
-        } => {
-            println!("=================================================");
-            println!("                 Running Faucet                  ");
-            println!("=================================================");
+#[derive(Subcommand, Debug)]
+pub enum AutoscaleSubCmd {
+    /// Remove the autoscale policy.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "clear")]
+    Clear {},
+    /// Add or remove services to keep the host within the autoscale policy.
+    ///
+    /// The bounds of the policy are applied straight away. Otherwise, a service is added if there
+    /// is room for another with the average footprint of the existing services, or removed if the
+    /// host is over its targets. Only one service is added or removed each time, so this is
+    /// intended to be run periodically, e.g., from a timer.
+    ///
+    /// New services use the settings of the most recently added service and are started. The
+    /// most recently added services are the ones removed.
+    ///
+    /// With the global --dry-run flag, the decision is displayed without adding or removing any
+    /// services.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "run")]
+    Run {},
+    /// Set the autoscale policy.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "set")]
+    Set {
+        /// The most services the host can run
+        #[clap(long)]
+        max_nodes: usize,
+        /// The memory that must be left available on the host, in megabytes
+        #[clap(long)]
+        memory_headroom: Option<u64>,
+        /// The fewest services the host can run
+        #[clap(long, default_value_t = 0)]
+        min_nodes: usize,
+        /// The fullest the disk with the services' data can be, as a percentage
+        #[clap(long)]
+        target_disk_percent: Option<f64>,
+    },
+}
 
-            let local_node_reg_path = &get_local_node_registry_path()?;
-            let mut local_node_registry = NodeRegistry::load(local_node_reg_path)?;
-            if !local_node_registry.nodes.is_empty() {
-                return Err(eyre!("A local network is already running")
-                    .suggestion("Use the kill command to destroy the network then try again"));
-            }
+#[derive(Subcommand, Debug)]
+pub enum EarningsSubCmd {
+    /// Export the earnings history with a row per service, per day.
+    ///
+    /// This is suitable for tax or accounting purposes.
+    #[clap(name = "export")]
+    Export {
+        /// The format of the exported data.
+        #[clap(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// The file to write the exported data to.
+        ///
+        /// If not provided, the data will be written to stdout.
+        #[clap(long)]
+        out: Option<PathBuf>,
+    },
+    /// Record the current balance of each safenode service.
+    ///
+    /// The change in balance since the last record is stored in the earnings history, which the
+    /// report is built from. This is intended to be run periodically, e.g., from a timer.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "record")]
+    Record {
+        /// Send a notification to this webhook URL for each node whose balance increased.
+        ///
+        /// The notification is a JSON document with the node's name, the amount it received and
+        /// its new balance, along with a `text` field for chat services that display it.
+        #[clap(long)]
+        notify_webhook: Option<String>,
+    },
+    /// Report the earnings of each safenode service over a period of time.
+    #[clap(name = "report")]
+    Report {
+        /// Set this flag to output the report as CSV
+        #[clap(long, conflicts_with = "json")]
+        csv: bool,
+        /// The period to report on, relative to now, e.g., 12h, 7d or 4w.
+        #[clap(long, default_value = "7d", value_parser = parse_duration)]
+        since: Duration,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportSubCmd {
+    /// Write a Compose file with a container for each safenode service.
+    ///
+    /// The containers have the same ports, directories, logging targets, memory limits and
+    /// versions as the services, so the setup can be moved to, or reproduced with, a container
+    /// orchestrator. The directories and binaries are mounted from the host, so they need to be
+    /// present wherever the file is used.
+    #[clap(name = "compose")]
+    Compose {
+        /// The file to write the Compose file to.
+        ///
+        /// If not provided, the file will be written to stdout.
+        #[clap(long)]
+        out: Option<PathBuf>,
+    },
+    /// Write the node registry to a file, which can be imported on another host.
+    ///
+    /// This is for moving nodes from one host to another. The removed nodes are left out.
+    #[clap(name = "registry")]
+    Registry {
+        /// Include the data directory of each node, which has its secret key, so it keeps its
+        /// peer ID on the new host.
+        ///
+        /// The nodes must be stopped first. The file should be kept safe, since anyone with it
+        /// could run the nodes.
+        #[clap(long)]
+        include_data: bool,
+        /// The file to write the export to, as a gzipped tarball.
+        #[clap(long)]
+        out: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MaintenanceSubCmd {
+    /// Take safenode services out of maintenance mode.
+    ///
+    /// If no peer ID or service name is supplied, all services will be taken out of maintenance
+    /// mode.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "off")]
+    Off {
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+    /// Put safenode services in maintenance mode.
+    ///
+    /// If no peer ID or service name is supplied, all services on the machine will be put in
+    /// maintenance mode.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "on")]
+    On {
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RestartScheduleSubCmd {
+    /// Remove a restart schedule.
+    ///
+    /// If no peer ID or service name is supplied, the schedule for all services is removed.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "clear")]
+    Clear {
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+    /// Restart the services that are scheduled to restart in the current minute.
+    ///
+    /// This is intended to be run every minute, e.g., from a timer.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "run")]
+    Run {
+        /// Wait a random number of seconds, up to this many, before restarting each service.
+        #[clap(long, default_value_t = 60)]
+        jitter: u64,
+        /// Skip restarting a service if it would leave fewer than this many services running.
+        #[clap(long, default_value_t = 0)]
+        min_running: usize,
+    },
+    /// Set when safenode services should be restarted, as a cron expression.
+    ///
+    /// The expression has the five fields used by cron: minute, hour, day of the month, month and
+    /// day of the week, in local time. For example, '0 4 * * *' restarts at 04:00 every day.
+    ///
+    /// If no peer ID or service name is supplied, the schedule applies to all services that don't
+    /// have their own.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "set")]
+    Set {
+        /// The cron expression
+        expression: String,
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+}
 
-            let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
-            let faucet_path =
-                get_bin_path(path, ReleaseType::Faucet, version, &*release_repo).await?;
+#[derive(Subcommand, Debug)]
+pub enum TelemetrySubCmd {
+    /// Stop sending anonymous usage telemetry.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "off")]
+    Off {},
+    /// Send anonymous usage telemetry after each command.
+    ///
+    /// The telemetry is the command used, the operating system and architecture, the version of
+    /// the node manager, the number of nodes and the class of any error. Nothing that identifies
+    /// the machine or its nodes is sent.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "on")]
+    On {
+        /// The URL the telemetry will be sent to
+        #[clap(long)]
+        endpoint: String,
+    },
+    /// Display whether telemetry is being sent.
+    #[clap(name = "status")]
+    Status {},
+}
 
-            let peers = get_peers_from_args(peers).await?;
-            run_faucet(&mut local_node_registry, faucet_path, peers[0].clone()).await?;
+#[derive(Subcommand, Debug)]
+pub enum NodeSubCmd {
+    /// Change the settings of a safenode service.
+    ///
+    /// The service definition will be regenerated with the new settings. If the service was
+    /// running, it will be restarted, retaining its data and peer ID.
+    ///
+    /// Either a peer ID or a service name must be supplied.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "edit")]
+    Edit {
+        /// Restart the node if it fails, after waiting this many seconds.
+        ///
+        /// This only applies on Windows.
+        #[clap(long)]
+        auto_restart: Option<u64>,
+        /// The number of seconds without a failure after which the failure count for automatic
+        /// restarts is reset.
+        ///
+        /// This only applies on Windows.
+        #[clap(long)]
+        auto_restart_reset_period: Option<u64>,
+        /// An environment variable to set for safenode, in the form KEY=VALUE.
+        ///
+        /// This argument can be used multiple times. A variable the node already has is given the
+        /// new value.
+        #[clap(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_variable)]
+        env_variables: Vec<(String, String)>,
+        /// Whether the node should be restarted if it exits.
+        ///
+        /// This only applies on macOS.
+        #[clap(long)]
+        keep_alive: Option<bool>,
+        /// The logging targets and levels for the node, in the format of the SN_LOG variable.
+        ///
+        /// This only applies on Linux.
+        #[clap(long)]
+        log_targets: Option<String>,
+        /// The memory limit for the node, in megabytes.
+        ///
+        /// This only applies on Linux.
+        #[clap(long)]
+        max_memory: Option<u64>,
+        /// The new port for the node to run on.
+        #[clap(long)]
+        port: Option<u16>,
+        /// The new address for the node's RPC service to listen on, e.g., 0.0.0.0 to listen on
+        /// every interface.
+        #[clap(long)]
+        rpc_address: Option<IpAddr>,
+        /// The new port for the node's RPC service to run on.
+        #[clap(long)]
+        rpc_port: Option<u16>,
+        #[clap(flatten)]
+        selector: NodeSelector,
+        /// The minimum number of seconds launchd will wait before restarting the node.
+        ///
+        /// This only applies on macOS.
+        #[clap(long)]
+        throttle_interval: Option<u64>,
+        /// The name of an environment variable to remove from the node.
+        ///
+        /// This argument can be used multiple times.
+        #[clap(long = "unset-env", value_name = "KEY")]
+        unset_env_variables: Vec<String>,
+    },
+    /// Rename a safenode service.
+    ///
+    /// The service will be recreated under the new name. If it was running, it will be
+    /// restarted, retaining its data and peer ID.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "rename")]
+    Rename {
+        /// The name of the service to rename.
+        service_name: String,
+        /// The new name for the service.
+        #[clap(long)]
+        to: String,
+    },
+    /// Change the owner of safenode services.
+    ///
+    /// The service definition will be regenerated with the new owner. Any services that were
+    /// running will be restarted, retaining their data and peer ID.
+    ///
+    /// If no peer ID(s) or service name(s) are supplied, the owner will be changed for all
+    /// services.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "set-owner")]
+    SetOwner {
+        /// The new owner.
+        #[clap(long)]
+        owner: String,
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+    /// Change the rewards address used by safenode services.
+    ///
+    /// The service definition will be regenerated with the new address. Any services that were
+    /// running will be restarted, retaining their data and peer ID.
+    ///
+    /// If no peer ID(s) or service name(s) are supplied, the address will be changed for all
+    /// services.
+    ///
+    /// This command must run as the root/administrative user.
+    #[clap(name = "set-rewards-address")]
+    SetRewardsAddress {
+        /// The new rewards address.
+        address: String,
+        #[clap(flatten)]
+        selector: NodeSelector,
+    },
+}
 
-            local_node_registry.save()?;
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    let args = Cmd::parse();
+    let command = get_command_name();
+    if let Some(timeout) = args.timeout {
+        // The deadline is for the whole process, so it would cut short every operation the
+        // daemon ran once it had passed.
+        if matches!(args.cmd, SubCmd::Daemon { .. }) {
+            return Err(eyre!(
+                "The timeout argument can't be used with the daemon command"
+            ));
+        }
+        set_timeout(timeout);
+    }
+    if args.json {
+        set_json_output();
+    }
+    if args.dry_run {
+        if !matches!(
+            args.cmd,
+            SubCmd::Add { .. }
+                | SubCmd::Autoscale(AutoscaleSubCmd::Run {})
+                | SubCmd::Remove { .. }
+                | SubCmd::Reset { .. }
+                | SubCmd::Upgrade { .. }
+        ) {
+            return Err(eyre!(
+                "The dry-run flag can only be used with the add, upgrade, remove, reset and \
+                 autoscale run commands"
+            ));
+        }
+        set_dry_run();
+    }
+    let result = run_command(args.cmd).await;
+    if !command.starts_with("telemetry") {
+        cmd::telemetry::send(&command, &result).await;
+    }
+    result
+}
 
-            Ok(())
+async fn run_command(cmd: SubCmd) -> Result<()> {
+    match cmd {
+        SubCmd::Add {
+            auto_restart,
+            auto_restart_reset_period,
+            backend,
+            checksum,
+            count,
+            data_dir_path,
+            env_variables,
+            extra_args,
+            home_network,
+            interval,
+            image,
+            like,
+            local,
+            log_dir_path,
+            max_memory,
+            no_keep_alive,
+            node_ip,
+            open_firewall,
+            owner,
+            path,
+            peers,
+            port,
+            rewards_address,
+            rewards_address_file,
+            rpc_address,
+            rpc_port,
+            skip_checksum,
+            throttle_interval,
+            upnp,
+            url,
+            user,
+            user_per_node,
+            version,
+        } => {
+            cmd::node::add(
+                auto_restart,
+                auto_restart_reset_period,
+                backend,
+                checksum,
+                count,
+                data_dir_path,
+                env_variables,
+                extra_args,
+                home_network,
+                interval,
+                image,
+                like,
+                local,
+                log_dir_path,
+                max_memory,
+                no_keep_alive,
+                node_ip,
+                open_firewall,
+                owner,
+                path,
+                peers,
+                port,
+                rewards_address,
+                rewards_address_file,
+                rpc_address,
+                rpc_port,
+                skip_checksum,
+                throttle_interval,
+                upnp,
+                url,
+                user,
+                user_per_node,
+                version,
+            )
+            .await
+        }
+        SubCmd::Earnings(EarningsSubCmd::Export { format, out }) => {
+            cmd::earnings::export(format, out)
+        }
+        SubCmd::Export(ExportSubCmd::Compose { out }) => cmd::migration::export_compose(out),
+        SubCmd::Export(ExportSubCmd::Registry { include_data, out }) => {
+            cmd::migration::export_registry(include_data, out)
+        }
+        SubCmd::Import {
+            path,
+            data_dir_path,
+            log_dir_path,
+            skip_checksum,
+            user,
+        } => cmd::migration::import(path, data_dir_path, log_dir_path, skip_checksum, user).await,
+        SubCmd::Earnings(EarningsSubCmd::Record { notify_webhook }) => {
+            cmd::earnings::record(notify_webhook).await
         }
+        SubCmd::Earnings(EarningsSubCmd::Report { csv, since }) => {
+            cmd::earnings::report(csv, since)
+        }
+        SubCmd::Faucet {
+            path,
+            peers,
+            version,
+        } => cmd::local::faucet(path, peers, version).await,
         SubCmd::Join {
             count,
             faucet_path,
@@ -393,106 +1490,184 @@ async fn main() -> Result<()> {
             peers,
             skip_validation: _,
         } => {
-            println!("=================================================");
-            println!("             Joining Local Network               ");
-            println!("=================================================");
-
-            let local_node_reg_path = &get_local_node_registry_path()?;
-            let mut local_node_registry = NodeRegistry::load(local_node_reg_path)?;
-
-            let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
-            let faucet_path = get_bin_path(
+            cmd::local::join(
+                count,
                 faucet_path,
-                ReleaseType::Faucet,
                 faucet_version,
-                &*release_repo,
-            )
-            .await?;
-            let node_path = get_bin_path(
                 node_path,
-                ReleaseType::Safenode,
                 node_version,
-                &*release_repo,
+                peers,
             )
-            .await?;
-
-            // If no peers are obtained we will attempt to join the existing local network, if one
-            // is running.
-            let peers = match get_peers_from_args(peers).await {
-                Ok(peers) => Some(peers),
-                Err(e) => match e {
-                    sn_peers_acquisition::error::Error::PeersNotObtained => None,
-                    _ => return Err(e.into()),
-                },
-            };
-            let options = LocalNetworkOptions {
-                faucet_bin_path: faucet_path,
-                join: true,
-                node_count: count,
+            .await
+        }
+        SubCmd::Kill { keep_directories } => cmd::local::kill(keep_directories),
+        SubCmd::Alerts(AlertsSubCmd::Check {}) => cmd::alerts::check().await,
+        SubCmd::Alerts(AlertsSubCmd::Set {
+            max_disk_percent,
+            max_memory,
+            max_restarts_per_hour,
+            min_connected_peers,
+            service_name,
+            webhook,
+        }) => cmd::alerts::set(
+            max_disk_percent,
+            max_memory,
+            max_restarts_per_hour,
+            min_connected_peers,
+            service_name,
+            webhook,
+        ),
+        SubCmd::Alerts(AlertsSubCmd::Status {}) => cmd::alerts::status(),
+        SubCmd::Auditor(AuditorSubCmd::Install {
+            checksum,
+            data_dir_path,
+            extra_args,
+            log_dir_path,
+            path,
+            peers,
+            skip_checksum,
+            user,
+            version,
+        }) => {
+            cmd::auditor::install(
+                checksum,
+                data_dir_path,
+                extra_args,
+                log_dir_path,
+                path,
                 peers,
-                safenode_bin_path: node_path,
-                skip_validation: true,
-            };
-            run_network(&mut local_node_registry, &NodeServiceManager {}, options).await?;
-            Ok(())
+                skip_checksum,
+                user,
+                version,
+            )
+            .await
         }
-        SubCmd::Kill { keep_directories } => {
-            let local_reg_path = &get_local_node_registry_path()?;
-            let local_node_registry = NodeRegistry::load(local_reg_path)?;
-            if local_node_registry.nodes.is_empty() {
-                println!("No local network is currently running");
-            } else {
-                println!("=================================================");
-                println!("             Killing Local Network               ");
-                println!("=================================================");
-                kill_network(&local_node_registry, keep_directories)?;
-                std::fs::remove_file(local_reg_path)?;
-            }
-            Ok(())
+        SubCmd::Auditor(AuditorSubCmd::Remove { keep_directories }) => {
+            cmd::auditor::remove(keep_directories).await
         }
-        SubCmd::Remove {
-            peer_id,
+        SubCmd::Auditor(AuditorSubCmd::Start {}) => cmd::auditor::start().await,
+        SubCmd::Auditor(AuditorSubCmd::Stop {}) => cmd::auditor::stop().await,
+        SubCmd::Autoscale(AutoscaleSubCmd::Clear {}) => cmd::autoscale::clear(),
+        SubCmd::Autoscale(AutoscaleSubCmd::Run {}) => cmd::autoscale::run(run_args).await,
+        SubCmd::Autoscale(AutoscaleSubCmd::Set {
+            max_nodes,
+            memory_headroom,
+            min_nodes,
+            target_disk_percent,
+        }) => cmd::autoscale::set(max_nodes, memory_headroom, min_nodes, target_disk_percent),
+        SubCmd::Balance { selector } => cmd::node::balance(selector),
+        SubCmd::Chaos {
+            duration,
+            kill_rate,
+            min_running,
+        } => cmd::chaos::run(duration, kill_rate, min_running).await,
+        SubCmd::Daemon {
+            address,
+            auto_upgrade_interval,
+            auto_upgrade_delay,
+            earnings_interval,
+            watchdog_interval,
+            recycle_shunned_after,
+            metrics_port,
+            notify_webhook,
+        } => {
+            cmd::daemon::run(
+                address,
+                auto_upgrade_interval,
+                auto_upgrade_delay,
+                earnings_interval,
+                watchdog_interval,
+                recycle_shunned_after,
+                metrics_port,
+                notify_webhook,
+                run_args,
+            )
+            .await
+        }
+        SubCmd::Diagnostics { out, upload } => cmd::diagnostics::collect(out, upload).await,
+        SubCmd::Doctor {} => cmd::diagnostics::doctor().await,
+        SubCmd::Logs {
+            all,
+            count,
+            events,
+            follow,
+            grep,
+            level,
+            merge,
             service_name,
-            keep_directories,
+            since,
         } => {
-            if !is_running_as_root() {
-                return Err(eyre!("The remove command must run as the root user"));
-            }
-            if peer_id.is_none() && service_name.is_none() {
-                return Err(eyre!("Either a peer ID or a service name must be supplied"));
-            }
-
-            println!("=================================================");
-            println!("           Remove Safenode Services              ");
-            println!("=================================================");
-
-            let mut node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
-            if let Some(ref name) = service_name {
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.service_name == *name)
-                    .ok_or_else(|| eyre!("No service named '{name}'"))?;
-                remove(node, &NodeServiceManager {}, keep_directories).await?;
-            } else if let Some(ref peer_id) = peer_id {
-                let peer_id = PeerId::from_str(peer_id)?;
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.peer_id == Some(peer_id))
-                    .ok_or_else(|| {
-                        eyre!(format!(
-                            "Could not find node with peer ID '{}'",
-                            peer_id.to_string()
-                        ))
-                    })?;
-                remove(node, &NodeServiceManager {}, keep_directories).await?;
-            }
-
-            node_registry.save()?;
-
-            Ok(())
+            cmd::logs::show(
+                all,
+                count,
+                events,
+                follow,
+                grep,
+                level,
+                merge,
+                service_name,
+                since,
+            )
+            .await
+        }
+        SubCmd::Maintenance(MaintenanceSubCmd::On { selector }) => {
+            cmd::node::maintenance(true, selector)
+        }
+        SubCmd::Maintenance(MaintenanceSubCmd::Off { selector }) => {
+            cmd::node::maintenance(false, selector)
+        }
+        SubCmd::Node(NodeSubCmd::Edit {
+            auto_restart,
+            auto_restart_reset_period,
+            env_variables,
+            keep_alive,
+            log_targets,
+            max_memory,
+            port,
+            rpc_address,
+            rpc_port,
+            selector,
+            throttle_interval,
+            unset_env_variables,
+        }) => {
+            cmd::node::edit(
+                auto_restart,
+                auto_restart_reset_period,
+                env_variables,
+                keep_alive,
+                log_targets,
+                max_memory,
+                port,
+                rpc_address,
+                rpc_port,
+                selector,
+                throttle_interval,
+                unset_env_variables,
+            )
+            .await
+        }
+        SubCmd::Node(NodeSubCmd::Rename { service_name, to }) => {
+            cmd::node::rename(service_name, to).await
         }
+        SubCmd::Node(NodeSubCmd::SetOwner { owner, selector }) => {
+            cmd::node::set_owner(owner, selector).await
+        }
+        SubCmd::Node(NodeSubCmd::SetRewardsAddress { address, selector }) => {
+            cmd::node::set_rewards_address(address, selector).await
+        }
+        SubCmd::Paths {} => cmd::node::paths(),
+        SubCmd::Ports {} => cmd::node::ports(),
+        SubCmd::Remove {
+            all,
+            selector,
+            keep_directories,
+        } => cmd::node::remove(all, selector, keep_directories).await,
+        SubCmd::Reset { force } => cmd::node::reset(force).await,
+        SubCmd::Restart {
+            retain_peer_id,
+            selector,
+            startup_timeout,
+        } => cmd::node::restart(retain_peer_id, selector, startup_timeout).await,
         SubCmd::Run {
             count,
             faucet_path,
@@ -500,360 +1675,113 @@ async fn main() -> Result<()> {
             node_path,
             node_version,
             skip_validation: _,
-        } => {
-            let local_node_reg_path = &get_local_node_registry_path()?;
-            let mut local_node_registry = NodeRegistry::load(local_node_reg_path)?;
-            if !local_node_registry.nodes.is_empty() {
-                return Err(eyre!("A local network is already running")
-                    .suggestion("Use the kill command to destroy the network then try again"));
-            }
-
-            println!("=================================================");
-            println!("             Launching Local Network             ");
-            println!("=================================================");
-
-            let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
-            let faucet_path = get_bin_path(
-                faucet_path,
-                ReleaseType::Faucet,
-                faucet_version,
-                &*release_repo,
-            )
-            .await?;
-            let node_path = get_bin_path(
-                node_path,
-                ReleaseType::Safenode,
-                node_version,
-                &*release_repo,
-            )
-            .await?;
-
-            let options = LocalNetworkOptions {
-                faucet_bin_path: faucet_path,
-                join: false,
-                node_count: count,
-                peers: None,
-                safenode_bin_path: node_path,
-                skip_validation: true,
-            };
-            run_network(&mut local_node_registry, &NodeServiceManager {}, options).await?;
-
-            local_node_registry.save()?;
-
-            Ok(())
-        }
+        } => cmd::local::run(count, faucet_path, faucet_version, node_path, node_version).await,
+        SubCmd::Setup {} => cmd::setup::run(run_args).await,
+        SubCmd::ShipLogs {
+            elasticsearch,
+            index,
+            interval,
+            loki,
+        } => cmd::logs::ship(elasticsearch, index, interval, loki).await,
         SubCmd::Start {
-            peer_id,
-            service_name,
-        } => {
-            if !is_running_as_root() {
-                return Err(eyre!("The start command must run as the root user"));
-            }
-
-            println!("=================================================");
-            println!("             Start Safenode Services             ");
-            println!("=================================================");
-
-            let mut node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
-            if let Some(ref name) = service_name {
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.service_name == *name)
-                    .ok_or_else(|| eyre!("No service named '{name}'"))?;
-
-                let rpc_client = RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
-                start(node, &NodeServiceManager {}, &rpc_client).await?;
-            } else if let Some(ref peer_id) = peer_id {
-                let peer_id = PeerId::from_str(peer_id)?;
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.peer_id == Some(peer_id))
-                    .ok_or_else(|| {
-                        eyre!(format!(
-                            "Could not find node with peer ID '{}'",
-                            peer_id.to_string()
-                        ))
-                    })?;
-
-                let rpc_client = RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
-                start(node, &NodeServiceManager {}, &rpc_client).await?;
-            } else {
-                for node in node_registry.nodes.iter_mut() {
-                    let rpc_client =
-                        RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
-                    start(node, &NodeServiceManager {}, &rpc_client).await?;
-                }
-            }
-
-            node_registry.save()?;
-
-            Ok(())
-        }
+            concurrency,
+            interval,
+            selector,
+            startup_timeout,
+        } => cmd::node::start(concurrency, interval, selector, startup_timeout).await,
         SubCmd::Status {
             details,
             fail,
-            json,
-        } => {
-            let mut node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
-            if !node_registry.nodes.is_empty() {
-                if !json {
-                    println!("=================================================");
-                    println!("                Safenode Services                ");
-                    println!("=================================================");
-                }
-                status(
-                    &mut node_registry,
-                    &NodeServiceManager {},
-                    details,
-                    json,
-                    fail,
-                )
-                .await?;
-                node_registry.save()?;
-            }
-
-            let mut local_node_registry = NodeRegistry::load(&get_local_node_registry_path()?)?;
-            if !local_node_registry.nodes.is_empty() {
-                if !json {
-                    println!("=================================================");
-                    println!("                Local Network                    ");
-                    println!("=================================================");
-                }
-                status(
-                    &mut local_node_registry,
-                    &NodeServiceManager {},
-                    details,
-                    json,
-                    fail,
-                )
-                .await?;
-                local_node_registry.save()?;
-            }
-
-            Ok(())
-        }
+            health_period,
+        } => cmd::node::status(details, fail, health_period).await,
         SubCmd::Stop {
-            peer_id,
-            service_name,
-        } => {
-            if !is_running_as_root() {
-                return Err(eyre!("The stop command must run as the root user"));
-            }
-
-            println!("=================================================");
-            println!("              Stop Safenode Services             ");
-            println!("=================================================");
-
-            let mut node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
-            if let Some(ref name) = service_name {
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.service_name == *name)
-                    .ok_or_else(|| eyre!("No service named '{name}'"))?;
-                stop(node, &NodeServiceManager {}).await?;
-            } else if let Some(ref peer_id) = peer_id {
-                let peer_id = PeerId::from_str(peer_id)?;
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.peer_id == Some(peer_id))
-                    .ok_or_else(|| {
-                        eyre!(format!(
-                            "Could not find node with peer ID '{}'",
-                            peer_id.to_string()
-                        ))
-                    })?;
-                stop(node, &NodeServiceManager {}).await?;
-            } else {
-                for node in node_registry.nodes.iter_mut() {
-                    stop(node, &NodeServiceManager {}).await?;
-                }
-            }
-
-            node_registry.save()?;
-
-            Ok(())
+            concurrency,
+            grace_period,
+            interval,
+            selector,
+        } => cmd::node::stop(concurrency, grace_period, interval, selector).await,
+        SubCmd::RestartSchedule(RestartScheduleSubCmd::Run {
+            jitter,
+            min_running,
+        }) => cmd::restart_schedule::run(jitter, min_running).await,
+        SubCmd::RestartSchedule(RestartScheduleSubCmd::Clear { selector }) => {
+            cmd::restart_schedule::clear(selector)
         }
+        SubCmd::RestartSchedule(RestartScheduleSubCmd::Set {
+            expression,
+            selector,
+        }) => cmd::restart_schedule::set(expression, selector),
+        SubCmd::Rollback { selector } => cmd::node::rollback(selector).await,
+        SubCmd::Telemetry(TelemetrySubCmd::Off {}) => cmd::telemetry::off(),
+        SubCmd::Telemetry(TelemetrySubCmd::On { endpoint }) => cmd::telemetry::on(endpoint),
+        SubCmd::Telemetry(TelemetrySubCmd::Status {}) => cmd::telemetry::status(),
+        SubCmd::Undo {} => cmd::node::undo(),
         SubCmd::Upgrade {
-            peer_id,
-            service_name,
+            checksum,
+            force,
+            interval,
+            path,
+            selector,
+            skip_checksum,
+            version,
         } => {
-            if !is_running_as_root() {
-                return Err(eyre!("The upgrade command must run as the root user"));
-            }
-
-            println!("=================================================");
-            println!("           Upgrade Safenode Services             ");
-            println!("=================================================");
-
-            println!("Retrieving latest version of safenode...");
-            let release_repo = <dyn SafeReleaseRepositoryInterface>::default_config();
-            let latest_version = release_repo
-                .get_latest_version(&ReleaseType::Safenode)
-                .await
-                .map(|v| Version::parse(&v).unwrap())?;
-            println!("Latest version is {latest_version}");
-
-            let mut node_registry = NodeRegistry::load(&get_node_registry_path()?)?;
-            let any_nodes_need_upgraded = node_registry.nodes.iter().any(|n| {
-                let current_version = Version::parse(&n.version).unwrap();
-                current_version < latest_version
-            });
-
-            if !any_nodes_need_upgraded {
-                println!("{} All nodes are at the latest version", "✓".green());
-                return Ok(());
-            }
-
-            let (safenode_download_path, _) = download_and_extract_release(
-                ReleaseType::Safenode,
-                None,
-                Some(latest_version.to_string()),
-                &*release_repo,
+            cmd::node::upgrade(
+                checksum,
+                force,
+                interval,
+                path,
+                selector,
+                skip_checksum,
+                version,
             )
-            .await?;
-
-            let mut upgrade_summary = Vec::new();
-
-            if let Some(ref name) = service_name {
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.service_name == *name)
-                    .ok_or_else(|| eyre!("No service named '{name}'"))?;
-
-                let rpc_client = RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
-                let result = upgrade(
-                    node,
-                    &safenode_download_path,
-                    &latest_version,
-                    &NodeServiceManager {},
-                    &rpc_client,
-                )
-                .await;
-
-                match result {
-                    Ok(upgrade_result) => {
-                        upgrade_summary.push((node.service_name.clone(), upgrade_result));
-                    }
-                    Err(e) => {
-                        upgrade_summary.push((
-                            node.service_name.clone(),
-                            UpgradeResult::Error(format!("Error: {}", e)),
-                        ));
-                    }
-                }
-            } else if let Some(ref peer_id) = peer_id {
-                let peer_id = PeerId::from_str(peer_id)?;
-                let node = node_registry
-                    .nodes
-                    .iter_mut()
-                    .find(|x| x.peer_id == Some(peer_id))
-                    .ok_or_else(|| {
-                        eyre!(format!(
-                            "Could not find node with peer ID '{}'",
-                            peer_id.to_string()
-                        ))
-                    })?;
-
-                let rpc_client = RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
-                let result = upgrade(
-                    node,
-                    &safenode_download_path,
-                    &latest_version,
-                    &NodeServiceManager {},
-                    &rpc_client,
-                )
-                .await;
-
-                match result {
-                    Ok(upgrade_result) => {
-                        upgrade_summary.push((node.service_name.clone(), upgrade_result));
-                    }
-                    Err(e) => {
-                        upgrade_summary.push((
-                            node.service_name.clone(),
-                            UpgradeResult::Error(format!("Error: {}", e)),
-                        ));
-                    }
-                }
-            } else {
-                for node in node_registry.nodes.iter_mut() {
-                    let rpc_client =
-                        RpcClient::new(&format!("https://127.0.0.1:{}", node.rpc_port));
-                    let result = upgrade(
-                        node,
-                        &safenode_download_path,
-                        &latest_version,
-                        &NodeServiceManager {},
-                        &rpc_client,
-                    )
-                    .await;
-
-                    match result {
-                        Ok(upgrade_result) => {
-                            upgrade_summary.push((node.service_name.clone(), upgrade_result));
-                        }
-                        Err(e) => {
-                            upgrade_summary.push((
-                                node.service_name.clone(),
-                                UpgradeResult::Error(format!("Error: {}", e)),
-                            ));
-                        }
-                    }
-                }
-            }
-
-            node_registry.save()?;
-
-            println!("Upgrade summary:");
-            for (service_name, upgrade_result) in upgrade_summary {
-                match upgrade_result {
-                    UpgradeResult::NotRequired => {
-                        println!("- {service_name} was at the latest version");
-                    }
-                    UpgradeResult::Upgraded(previous_version, new_version) => {
-                        println!(
-                            "{} {service_name} upgraded from {previous_version} to {new_version}",
-                            "✓".green()
-                        );
-                    }
-                    UpgradeResult::Error(msg) => {
-                        println!("{} {service_name} was not upgraded: {}", "✕".red(), msg);
-                    }
+            .await
+        }
+        SubCmd::Verify { release, selector } => cmd::node::verify(release, selector).await,
+        SubCmd::Completions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Cmd::command(),
+                "safenode-manager",
+                &mut std::io::stdout(),
+            );
+            Ok(())
+        }
+        SubCmd::Man { output_dir } => {
+            // The pages are named after the command, which defaults to the name of the package.
+            let command = Cmd::command().name("safenode-manager");
+            match output_dir {
+                Some(output_dir) => {
+                    std::fs::create_dir_all(&output_dir)?;
+                    clap_mangen::generate_to(command, &output_dir)?;
+                    println!("Wrote the man pages to {}", output_dir.to_string_lossy());
                 }
+                None => clap_mangen::Man::new(command).render(&mut std::io::stdout())?,
             }
-
             Ok(())
         }
+        SubCmd::Version { details } => cmd::node::version(details),
     }
 }
 
-#[cfg(unix)]
-fn is_running_as_root() -> bool {
-    users::get_effective_uid() == 0
-}
-
-#[cfg(windows)]
-fn is_running_as_root() -> bool {
-    // The Windows implementation for this will be much more complex.
-    true
+/// Run a command from its arguments, as they would be given on the command line.
+///
+/// This is for the commands that carry out their work by running others, like the daemon.
+async fn run_args(args: Vec<String>) -> Result<()> {
+    let cmd = Cmd::try_parse_from(args)?;
+    Box::pin(run_command(cmd.cmd)).await
 }
 
-async fn get_bin_path(
-    path_option: Option<PathBuf>,
-    release_type: ReleaseType,
-    version: Option<String>,
-    release_repo: &dyn SafeReleaseRepositoryInterface,
-) -> Result<PathBuf> {
-    if let Some(path) = path_option {
-        Ok(path)
-    } else {
-        let (download_path, _) =
-            download_and_extract_release(release_type, None, version, release_repo).await?;
-        Ok(download_path)
+/// Get the name of the command being run, including its subcommand, e.g., `node edit`.
+///
+/// Only the names are used, not any of the arguments.
+fn get_command_name() -> String {
+    let mut names = Vec::new();
+    if let Ok(matches) = Cmd::command().try_get_matches_from(std::env::args_os()) {
+        let mut matches = &matches;
+        while let Some((name, sub_matches)) = matches.subcommand() {
+            names.push(name.to_string());
+            matches = sub_matches;
+        }
     }
+    names.join(" ")
 }
@@ -0,0 +1,165 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::control::refresh_node_registry;
+use crate::earnings::get_node_balance;
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use crate::service::ServiceControl;
+use sn_node_rpc_client::{RpcActions, RpcClient};
+use std::fmt::Write;
+
+/// What is exported for a node. Anything that couldn't be measured is empty, and is left out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeMetrics {
+    pub service_name: String,
+    pub version: String,
+    pub running: bool,
+    pub rpc_reachable: bool,
+    pub uptime_secs: Option<u64>,
+    pub restart_count: Option<u32>,
+    pub balance: Option<u64>,
+}
+
+/// Measure each of the nodes that haven't been removed.
+///
+/// The registry is refreshed first, so the running state is current, but the changes are not
+/// saved: a scrape shouldn't change the registry.
+pub async fn collect_metrics(
+    node_registry: &mut NodeRegistry,
+    service_control: &dyn ServiceControl,
+) -> Vec<NodeMetrics> {
+    refresh_node_registry(node_registry, service_control, false).await;
+
+    let mut metrics = Vec::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        let mut node_metrics = NodeMetrics {
+            service_name: node.service_name.clone(),
+            version: node.version.clone(),
+            running: node.status == NodeStatus::Running,
+            ..Default::default()
+        };
+        if node_metrics.running {
+            let rpc_client = RpcClient::new(&node.get_rpc_endpoint());
+            if let Ok(info) = rpc_client.node_info().await {
+                node_metrics.rpc_reachable = true;
+                node_metrics.uptime_secs = Some(info.uptime.as_secs());
+            }
+        }
+        if !node.local && node.status != NodeStatus::Added {
+            node_metrics.restart_count = service_control
+                .get_exit_info(&node.service_name)
+                .ok()
+                .and_then(|exit_info| exit_info.restart_count);
+        }
+        node_metrics.balance = get_node_balance(node).ok();
+        metrics.push(node_metrics);
+    }
+    metrics
+}
+
+/// Gets the value of a metric from what was measured for a node.
+type MetricValue = fn(&NodeMetrics) -> Option<u64>;
+
+/// Render the metrics in the Prometheus text format.
+///
+/// Every metric is labelled with the service name. The version is exported as the label of an
+/// info metric, which is how Prometheus represents values that aren't numbers.
+pub fn render_metrics(metrics: &[NodeMetrics]) -> String {
+    let mut output = String::new();
+    let families: [(&str, &str, MetricValue); 5] = [
+        ("safenode_running", "Whether the node is running", |m| {
+            Some(m.running as u64)
+        }),
+        (
+            "safenode_rpc_reachable",
+            "Whether the node's RPC service responded",
+            |m| Some(m.rpc_reachable as u64),
+        ),
+        (
+            "safenode_uptime_seconds",
+            "The time since the node last started",
+            |m| m.uptime_secs,
+        ),
+        (
+            "safenode_restart_count",
+            "The number of times the service manager has restarted the node",
+            |m| m.restart_count.map(u64::from),
+        ),
+        (
+            "safenode_wallet_balance_nanos",
+            "The balance of the node's wallet, in nanos",
+            |m| m.balance,
+        ),
+    ];
+
+    // Writing to a string can't fail.
+    let _ = writeln!(
+        output,
+        "# HELP safenode_info The version of safenode the node runs\n# TYPE safenode_info gauge"
+    );
+    for node_metrics in metrics {
+        let _ = writeln!(
+            output,
+            "safenode_info{{service_name=\"{}\",version=\"{}\"}} 1",
+            node_metrics.service_name, node_metrics.version
+        );
+    }
+    for (name, help, value) in families {
+        let _ = writeln!(output, "# HELP {name} {help}\n# TYPE {name} gauge");
+        for node_metrics in metrics {
+            if let Some(value) = value(node_metrics) {
+                let _ = writeln!(
+                    output,
+                    "{name}{{service_name=\"{}\"}} {value}",
+                    node_metrics.service_name
+                );
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_metrics_should_leave_out_the_values_that_were_not_measured() {
+        let metrics = vec![
+            NodeMetrics {
+                service_name: "safenode1".to_string(),
+                version: "0.98.1".to_string(),
+                running: true,
+                rpc_reachable: true,
+                uptime_secs: Some(3600),
+                restart_count: Some(2),
+                balance: Some(500),
+            },
+            NodeMetrics {
+                service_name: "safenode2".to_string(),
+                version: "0.98.0".to_string(),
+                ..Default::default()
+            },
+        ];
+        let output = render_metrics(&metrics);
+
+        assert!(output.contains("# TYPE safenode_uptime_seconds gauge\n"));
+        assert!(output.contains("safenode_info{service_name=\"safenode2\",version=\"0.98.0\"} 1\n"));
+        assert!(output.contains("safenode_running{service_name=\"safenode1\"} 1\n"));
+        assert!(output.contains("safenode_running{service_name=\"safenode2\"} 0\n"));
+        assert!(output.contains("safenode_uptime_seconds{service_name=\"safenode1\"} 3600\n"));
+        assert!(output.contains("safenode_restart_count{service_name=\"safenode1\"} 2\n"));
+        assert!(output.contains("safenode_wallet_balance_nanos{service_name=\"safenode1\"} 500\n"));
+        assert!(!output.contains("safenode_uptime_seconds{service_name=\"safenode2\"}"));
+        assert!(!output.contains("safenode_wallet_balance_nanos{service_name=\"safenode2\"}"));
+    }
+}
@@ -0,0 +1,473 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config::{create_owned_dir, create_private_dir, create_private_file};
+use crate::control::get_service_config;
+use crate::helpers::{
+    download_and_extract_release, get_file_checksum, get_release_archive_url, get_release_checksum,
+};
+use crate::node_registry::{Node, NodeRegistry, NodeStatus};
+use crate::service::ServiceControl;
+use color_eyre::{eyre::eyre, Help, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sn_releases::{ReleaseType, SafeReleaseRepositoryInterface};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+const REGISTRY_ENTRY: &str = "node_registry.json";
+const DATA_DIR: &str = "data";
+
+/// Write the nodes in the registry to a gzipped tarball, which can be imported on another host.
+///
+/// The removed nodes are left out. With `include_data`, the data directory of each node is
+/// included too, which has its secret key, so it keeps its peer ID on the new host. The nodes
+/// must be stopped for their data to be exported, since a running node would be changing it.
+/// Only the owner can read the export, since it has the secret keys and wallets of the nodes.
+/// The number of nodes exported is returned.
+pub fn export_registry(
+    node_registry: &NodeRegistry,
+    out_path: &Path,
+    include_data: bool,
+) -> Result<usize> {
+    let mut exported = node_registry.clone();
+    exported.nodes.retain(|n| n.status != NodeStatus::Removed);
+    if include_data {
+        if let Some(node) = exported
+            .nodes
+            .iter()
+            .find(|n| n.status == NodeStatus::Running)
+        {
+            return Err(eyre!(
+                "{} is running, so its data can't be exported",
+                node.service_name
+            )
+            .suggestion("Stop the nodes then try again"));
+        }
+    }
+
+    if out_path.exists() {
+        std::fs::remove_file(out_path)?;
+    }
+    let file = create_private_file(out_path)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let json = serde_json::to_string_pretty(&exported)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    builder.append_data(&mut header, REGISTRY_ENTRY, json.as_bytes())?;
+    if include_data {
+        for node in exported.nodes.iter() {
+            if let Some(data_dir_path) = &node.data_dir_path {
+                if data_dir_path.exists() {
+                    builder.append_dir_all(
+                        format!("{DATA_DIR}/{}", node.service_name),
+                        data_dir_path,
+                    )?;
+                }
+            }
+        }
+    }
+    builder.into_inner()?.finish()?;
+    Ok(exported.nodes.len())
+}
+
+/// Read the nodes from an export.
+pub fn read_export(path: &Path) -> Result<Vec<Node>> {
+    let mut archive = open_export(path)?;
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.path()? == Path::new(REGISTRY_ENTRY) {
+            let registry: NodeRegistry = serde_json::from_reader(entry)?;
+            return Ok(registry.nodes);
+        }
+    }
+    Err(eyre!(
+        "{} is not an export of a node registry",
+        path.to_string_lossy()
+    ))
+}
+
+/// The options for importing nodes, which are the same on the new host for all of them.
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    pub service_data_dir_path: PathBuf,
+    pub service_log_dir_path: PathBuf,
+    /// Install a download even if no checksum is published for it.
+    pub skip_checksum: bool,
+    /// The user the services run as, rather than the users they had on the other host.
+    pub user: Option<String>,
+}
+
+/// Add the nodes from an export to the registry, with new service definitions for this host.
+///
+/// The nodes are renumbered after the nodes already in the registry, so they may be given new
+/// service names, but they keep their ports and settings. A node whose data was exported has it
+/// restored, so it keeps its peer ID, and it uses the `safenode` binary from its data. Otherwise
+/// the version the node was running is downloaded, and the node will have a new peer ID when it
+/// is started. The service users are expected to exist already.
+///
+/// The names each node was exported and imported with are returned.
+pub async fn import_nodes(
+    export_path: &Path,
+    nodes: Vec<Node>,
+    options: &ImportOptions,
+    node_registry: &mut NodeRegistry,
+    service_control: &dyn ServiceControl,
+    release_repo: &dyn SafeReleaseRepositoryInterface,
+) -> Result<Vec<(String, String)>> {
+    let mut allocated_ports = node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+        .flat_map(|n| [n.port, n.rpc_port])
+        .collect::<HashSet<u16>>();
+    for port in nodes.iter().flat_map(|n| [n.port, n.rpc_port]) {
+        if !allocated_ports.insert(port) || !service_control.is_port_free(port) {
+            return Err(eyre!("Port {port} of an imported node is already in use")
+                .suggestion("Remove the service using the port, then try again"));
+        }
+    }
+    if nodes.iter().any(|n| n.genesis) && node_registry.nodes.iter().any(|n| n.genesis) {
+        return Err(eyre!(
+            "The export has a genesis node, and one already exists"
+        ));
+    }
+
+    let mut downloads: HashMap<String, PathBuf> = HashMap::new();
+    let mut imported = Vec::new();
+    for mut node in nodes {
+        let exported_name = node.service_name.clone();
+        node.number = node_registry.nodes.len() as u16 + 1;
+        node.service_name = format!("safenode{}", node.number);
+        // Nodes with a user of their own have a user named after the service.
+        let own_user = node.user == exported_name;
+        if let Some(user) = &options.user {
+            node.user = user.clone();
+        } else if own_user {
+            node.user = node.service_name.clone();
+        }
+
+        let safenode_file_name = node
+            .safenode_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|f| f.to_owned())
+            .ok_or_else(|| eyre!("The safenode path of {exported_name} was not exported"))?;
+        let data_dir_path = options.service_data_dir_path.join(&node.service_name);
+        let log_dir_path = options.service_log_dir_path.join(&node.service_name);
+        let safenode_path = data_dir_path.join(safenode_file_name);
+        if data_dir_path.exists() {
+            return Err(eyre!(
+                "The data directory for {}, {}, already exists",
+                node.service_name,
+                data_dir_path.to_string_lossy()
+            ));
+        }
+
+        let create_dir = if own_user {
+            create_private_dir
+        } else {
+            create_owned_dir
+        };
+        create_dir(data_dir_path.clone(), &node.user)?;
+        create_dir(log_dir_path.clone(), &node.user)?;
+        let has_data = extract_node_data(export_path, &exported_name, &data_dir_path)?;
+        if has_data {
+            set_owner(&data_dir_path, &node.user)?;
+        }
+        if !safenode_path.exists() {
+            let download_path = match downloads.get(&node.version) {
+                Some(path) => path.clone(),
+                None => {
+                    // The download is checked in the same way as it is for add and upgrade.
+                    let archive_url =
+                        get_release_archive_url(&ReleaseType::Safenode, &node.version)?;
+                    let checksum =
+                        get_release_checksum(&archive_url, options.skip_checksum).await?;
+                    let (path, _) = download_and_extract_release(
+                        ReleaseType::Safenode,
+                        None,
+                        Some(node.version.clone()),
+                        checksum,
+                        release_repo,
+                    )
+                    .await?;
+                    downloads.insert(node.version.clone(), path.clone());
+                    path
+                }
+            };
+            std::fs::copy(download_path, &safenode_path)?;
+        }
+
+        if !has_data {
+            node.peer_id = None;
+            node.previous_version = None;
+        }
+        node.status = if node.peer_id.is_some() {
+            NodeStatus::Stopped
+        } else {
+            NodeStatus::Added
+        };
+        node.pid = None;
+        node.connected_peers = None;
+        node.records_stored = None;
+        node.resource_usage = None;
+        node.listen_addr = None;
+        node.last_stop_method = None;
+        node.exit_info = None;
+        node.watchdog_restarts = 0;
+        node.last_failure = None;
+        node.data_dir_path = Some(data_dir_path);
+        node.log_dir_path = Some(log_dir_path);
+        node.safenode_checksum = Some(get_file_checksum(&safenode_path)?);
+        node.safenode_path = Some(safenode_path);
+
+        service_control.install(get_service_config(&node)?)?;
+        if node.firewall_rule_added {
+            service_control.add_firewall_rule(&node.service_name, node.port)?;
+        }
+        imported.push((exported_name, node.service_name.clone()));
+        node_registry.nodes.push(node);
+    }
+
+    for path in downloads.into_values() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(imported)
+}
+
+fn open_export(path: &Path) -> Result<tar::Archive<GzDecoder<std::fs::File>>> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| eyre!("Could not open {}: {e}", path.to_string_lossy()))?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+/// Extract the data directory of a node from an export, returning whether it had any.
+///
+/// Entries that would be written outside the directory are rejected, as are links and any other
+/// entry that isn't a file or a directory, since a link could lead a later entry outside it.
+fn extract_node_data(export_path: &Path, service_name: &str, dir_path: &Path) -> Result<bool> {
+    let prefix = Path::new(DATA_DIR).join(service_name);
+    let mut archive = open_export(export_path)?;
+    let mut has_data = false;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let Ok(relative_path) = entry_path.strip_prefix(&prefix) else {
+            continue;
+        };
+        if !relative_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(eyre!(
+                "The export has an entry outside the data directory: {}",
+                entry_path.to_string_lossy()
+            ));
+        }
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            return Err(eyre!(
+                "The export has an entry that isn't a file or a directory: {}",
+                entry_path.to_string_lossy()
+            ));
+        }
+        has_data = true;
+        if relative_path.as_os_str().is_empty() {
+            continue;
+        }
+        let path = dir_path.join(relative_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&path)?;
+    }
+    Ok(has_data)
+}
+
+/// Give the user everything in a directory that was extracted by the user running the command.
+#[cfg(unix)]
+fn set_owner(dir_path: &Path, owner: &str) -> Result<()> {
+    use nix::unistd::{chown, Gid, Uid};
+    use users::get_user_by_name;
+
+    if crate::config::is_user_mode() {
+        return Ok(());
+    }
+    let user = get_user_by_name(owner).ok_or_else(|| eyre!("User '{owner}' does not exist"))?;
+    let uid = Uid::from_raw(user.uid());
+    let gid = Gid::from_raw(user.primary_group_id());
+    let mut dir_paths = vec![dir_path.to_path_buf()];
+    while let Some(dir_path) = dir_paths.pop() {
+        for entry in std::fs::read_dir(&dir_path)? {
+            let entry = entry?;
+            chown(&entry.path(), Some(uid), Some(gid))?;
+            if entry.file_type()?.is_dir() {
+                dir_paths.push(entry.path());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The permissions of the extracted files are inherited from the data directory.
+#[cfg(windows)]
+fn set_owner(_dir_path: &Path, _owner: &str) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::NodeType;
+    use assert_fs::prelude::*;
+    use predicates::prelude::*;
+
+    fn make_node(service_name: &str) -> Node {
+        Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: service_name.to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Stopped,
+            pid: None,
+            peer_id: None,
+            data_dir_path: None,
+            log_dir_path: None,
+            safenode_path: None,
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }
+    }
+
+    #[test]
+    fn extract_node_data_should_restore_the_data_of_the_exported_node() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let data_dir = temp_dir.child("safenode1");
+        data_dir.child("secret-key").write_str("key1")?;
+        data_dir.child("record_store/record").write_str("record")?;
+        let other_data_dir = temp_dir.child("safenode2");
+        other_data_dir.child("secret-key").write_str("key2")?;
+
+        let mut nodes = Vec::new();
+        for (service_name, data_dir_path, status) in [
+            ("safenode1", data_dir.to_path_buf(), NodeStatus::Stopped),
+            ("safenode2", other_data_dir.to_path_buf(), NodeStatus::Added),
+            (
+                "safenode3",
+                temp_dir.child("safenode3").to_path_buf(),
+                NodeStatus::Removed,
+            ),
+        ] {
+            let mut node = make_node(service_name);
+            node.status = status;
+            node.data_dir_path = Some(data_dir_path);
+            nodes.push(node);
+        }
+        let node_registry = NodeRegistry {
+            save_path: temp_dir.child("node_registry.json").to_path_buf(),
+            nodes,
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let export_path = temp_dir.child("export.tar.gz");
+        assert_eq!(export_registry(&node_registry, &export_path, true)?, 2);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&export_path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let nodes = read_export(&export_path)?;
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|n| n.service_name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["safenode1", "safenode2"]
+        );
+
+        let restored_dir = temp_dir.child("restored");
+        assert!(extract_node_data(&export_path, "safenode1", &restored_dir)?);
+        restored_dir.child("secret-key").assert("key1");
+        restored_dir.child("record_store/record").assert("record");
+        assert!(!extract_node_data(
+            &export_path,
+            "safenode3",
+            &restored_dir
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn extract_node_data_should_reject_a_link() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let outside_dir = temp_dir.child("outside");
+        outside_dir.create_dir_all()?;
+
+        let export_path = temp_dir.child("export.tar.gz");
+        let file = std::fs::File::create(&export_path)?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        builder.append_link(&mut header, "data/safenode1/x", outside_dir.path())?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, "data/safenode1/x/passwd", "evil".as_bytes())?;
+        builder.into_inner()?.finish()?;
+
+        let restored_dir = temp_dir.child("restored");
+        assert!(extract_node_data(&export_path, "safenode1", &restored_dir).is_err());
+        outside_dir
+            .child("passwd")
+            .assert(predicate::path::missing());
+        Ok(())
+    }
+}
@@ -0,0 +1,883 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::dry_run::is_dry_run;
+use crate::output::report;
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use libp2p::{Multiaddr, PeerId};
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// The number of registry snapshots to keep.
+const SNAPSHOT_LIMIT: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NodeStatus {
+    /// The node service has been added but not started for the first time
+    Added,
+    /// Last time we checked the service was running
+    Running,
+    /// The node service has been stopped
+    Stopped,
+    /// The node service has been removed
+    Removed,
+}
+
+/// The Safe Network binaries that can be run as services.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum NodeType {
+    #[default]
+    Safenode,
+    /// The auditor, which follows the spends made on the network.
+    Auditor,
+}
+
+impl NodeType {
+    /// The name of the binary in the release archives.
+    pub fn bin_name(&self) -> &'static str {
+        match self {
+            NodeType::Safenode => "safenode",
+            NodeType::Auditor => "sn_auditor",
+        }
+    }
+}
+
+impl std::fmt::Display for NodeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.bin_name())
+    }
+}
+
+/// How a node was stopped the last time it was stopped.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StopMethod {
+    /// The node exited within the grace period after its service was stopped
+    Graceful,
+    /// The node was still running at the end of the grace period and its process was killed
+    Killed,
+}
+
+/// The resources a node was using when it was last measured.
+///
+/// The memory and CPU are those of the node's process, so they're only measured while it runs.
+/// The CPU usage is a percentage of one core.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub disk_bytes: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    pub cpu_percent: Option<f32>,
+}
+
+/// How the node's process last exited, and how many times it has been restarted, as reported by
+/// the service manager.
+///
+/// Not every service manager provides all of these. The Service Control Manager on Windows doesn't
+/// count restarts, for example.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExitInfo {
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    /// The reason the service manager gives for the exit, e.g., `oom-kill` from systemd.
+    pub reason: Option<String>,
+    pub restart_count: Option<u32>,
+}
+
+/// Why the watchdog last had to restart a node, and when.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeFailure {
+    pub reason: String,
+    pub time: DateTime<Utc>,
+}
+
+impl std::fmt::Display for ExitInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.signal, self.exit_code) {
+            (Some(signal), _) => write!(f, "killed by signal {signal}")?,
+            (None, Some(exit_code)) => write!(f, "exited with code {exit_code}")?,
+            (None, None) => write!(f, "-")?,
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, " ({reason})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The container runtime used to run the node services instead of the service manager.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ContainerRuntime {
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    /// The name of the runtime's command line tool.
+    pub fn command(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+}
+
+/// The runtime and image used when the node services run as containers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContainerBackend {
+    pub runtime: ContainerRuntime,
+    pub image: String,
+}
+
+fn serialize_peer_id<S>(value: &Option<PeerId>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if let Some(peer_id) = value {
+        return serializer.serialize_str(&peer_id.to_string());
+    }
+    serializer.serialize_none()
+}
+
+fn deserialize_peer_id<'de, D>(deserializer: D) -> Result<Option<PeerId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    if let Some(peer_id_str) = s {
+        PeerId::from_str(&peer_id_str)
+            .map(Some)
+            .map_err(DeError::custom)
+    } else {
+        Ok(None)
+    }
+}
+
+fn serialize_connected_peers<S>(
+    connected_peers: &Option<Vec<PeerId>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match connected_peers {
+        Some(peers) => {
+            let peer_strs: Vec<String> = peers.iter().map(|p| p.to_string()).collect();
+            serializer.serialize_some(&peer_strs)
+        }
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_connected_peers<'de, D>(deserializer: D) -> Result<Option<Vec<PeerId>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let vec: Option<Vec<String>> = Option::deserialize(deserializer)?;
+    match vec {
+        Some(peer_strs) => {
+            let peers: Result<Vec<PeerId>, _> = peer_strs
+                .into_iter()
+                .map(|s| PeerId::from_str(&s).map_err(DeError::custom))
+                .collect();
+            peers.map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// A node managed by the node manager.
+///
+/// This was originally defined in `sn_protocol`, but it was moved here because the node manager
+/// now needs to retain information about a node that no other component has any interest in, such
+/// as the arguments required to regenerate its service definition.
+///
+/// Fields added after the move use `serde(default)` so that existing registry files can still be
+/// loaded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Node {
+    pub genesis: bool,
+    /// The binary the service runs, which is `safenode` for every node in the list of nodes.
+    #[serde(default)]
+    pub node_type: NodeType,
+    pub version: String,
+    pub service_name: String,
+    pub user: String,
+    pub number: u16,
+    pub port: u16,
+    pub rpc_port: u16,
+    /// The address the RPC service listens on. Without one, it's the loopback address.
+    ///
+    /// This can be the address of another host, for a node whose RPC service is exposed to the
+    /// network.
+    #[serde(default)]
+    pub rpc_address: Option<IpAddr>,
+    /// The IP address the node listens on, if not every interface.
+    #[serde(default)]
+    pub node_ip: Option<IpAddr>,
+    pub status: NodeStatus,
+    pub pid: Option<u32>,
+    #[serde(
+        serialize_with = "serialize_peer_id",
+        deserialize_with = "deserialize_peer_id"
+    )]
+    pub peer_id: Option<PeerId>,
+    pub data_dir_path: Option<PathBuf>,
+    pub log_dir_path: Option<PathBuf>,
+    pub safenode_path: Option<PathBuf>,
+    #[serde(
+        serialize_with = "serialize_connected_peers",
+        deserialize_with = "deserialize_connected_peers"
+    )]
+    pub connected_peers: Option<Vec<PeerId>>,
+    /// The number of records the node held when it was last queried.
+    #[serde(default)]
+    pub records_stored: Option<u64>,
+    #[serde(default)]
+    pub resource_usage: Option<ResourceUsage>,
+    /// The addresses the node reported it was listening on after it was started.
+    #[serde(default)]
+    pub listen_addr: Option<Vec<Multiaddr>>,
+    #[serde(default)]
+    pub local: bool,
+    #[serde(default)]
+    pub peers: Vec<Multiaddr>,
+    #[serde(default)]
+    pub home_network: bool,
+    #[serde(default)]
+    pub upnp: bool,
+    /// Arguments passed to `safenode` as they are, after those set by the manager.
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Environment variables set for `safenode` by the service definition, e.g. `SN_LOG`.
+    #[serde(default)]
+    pub env_variables: Vec<(String, String)>,
+    #[serde(default)]
+    pub rewards_address: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub firewall_rule_added: bool,
+    #[serde(default = "default_keep_alive")]
+    pub keep_alive: bool,
+    #[serde(default)]
+    pub throttle_interval: Option<u64>,
+    #[serde(default)]
+    pub log_targets: Option<String>,
+    #[serde(default)]
+    pub max_memory: Option<u64>,
+    #[serde(default)]
+    pub auto_restart: Option<u64>,
+    #[serde(default = "default_auto_restart_reset_period")]
+    pub auto_restart_reset_period: u64,
+    /// The SHA-256 checksum of the safenode binary, taken when it was installed.
+    #[serde(default)]
+    pub safenode_checksum: Option<String>,
+    /// The version the node was running before it was last upgraded, which it can be rolled back
+    /// to.
+    #[serde(default)]
+    pub previous_version: Option<String>,
+    /// Set while the node is in maintenance mode, in which case it will be skipped by operations
+    /// that apply to all nodes, like upgrades.
+    #[serde(default)]
+    pub maintenance: bool,
+    /// When the node should be restarted, as a cron expression.
+    #[serde(default)]
+    pub restart_schedule: Option<String>,
+    #[serde(default)]
+    pub last_stop_method: Option<StopMethod>,
+    #[serde(default)]
+    pub exit_info: Option<ExitInfo>,
+    /// The number of times the watchdog has restarted the node because it wasn't responding.
+    #[serde(default)]
+    pub watchdog_restarts: u32,
+    #[serde(default)]
+    pub last_failure: Option<NodeFailure>,
+}
+
+fn default_keep_alive() -> bool {
+    true
+}
+
+fn default_auto_restart_reset_period() -> u64 {
+    86400
+}
+
+impl Node {
+    /// The address the node's RPC service is reached at.
+    ///
+    /// A node listening on every interface is reached at the loopback address.
+    pub fn get_rpc_socket_addr(&self) -> SocketAddr {
+        let ip = match self.rpc_address {
+            Some(ip) if !ip.is_unspecified() => ip,
+            _ => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        };
+        SocketAddr::new(ip, self.rpc_port)
+    }
+
+    /// The endpoint for an RPC client to connect to the node.
+    pub fn get_rpc_endpoint(&self) -> String {
+        format!("https://{}", self.get_rpc_socket_addr())
+    }
+
+    pub fn get_multiaddr(&self) -> Option<Multiaddr> {
+        if let Some(peer_id) = self.peer_id {
+            let addr = Multiaddr::from(std::net::Ipv4Addr::LOCALHOST);
+
+            #[cfg(feature = "tcp")]
+            let addr = addr.with(libp2p::multiaddr::Protocol::Tcp(self.port));
+            #[cfg(feature = "quic")]
+            let addr = addr
+                .with(libp2p::multiaddr::Protocol::Udp(self.port))
+                .with(libp2p::multiaddr::Protocol::QuicV1);
+
+            let peer = addr.with(libp2p::multiaddr::Protocol::P2p(peer_id));
+
+            return Some(peer);
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeRegistry {
+    pub save_path: PathBuf,
+    pub nodes: Vec<Node>,
+    pub faucet_pid: Option<u32>,
+    /// When nodes without their own schedule should be restarted, as a cron expression.
+    #[serde(default)]
+    pub restart_schedule: Option<String>,
+    /// Set when the node services run as containers rather than under the service manager.
+    #[serde(default)]
+    pub container: Option<ContainerBackend>,
+    /// Set when the node services are instances of a single templated systemd unit.
+    #[serde(default)]
+    pub systemd_template: bool,
+    /// The auditor service, if it has been installed.
+    ///
+    /// It's kept apart from the nodes, so the commands for the nodes don't apply to it.
+    #[serde(default)]
+    pub auditor: Option<Node>,
+}
+
+impl NodeRegistry {
+    /// Save the registry and release the lock on it.
+    ///
+    /// The registry is written to a temporary file that then replaces it, so a crash part way
+    /// through can't leave it half written. The previous version is kept as a backup, which is
+    /// used if the registry can't be read.
+    ///
+    /// Nothing is written for a dry run.
+    pub fn save(&self, lock: RegistryGuard) -> Result<()> {
+        self.save_locked(&lock)
+    }
+
+    /// Save the registry, keeping the lock on it, for an operation that records its progress as
+    /// it goes.
+    pub fn save_locked(&self, lock: &RegistryGuard) -> Result<()> {
+        if is_dry_run() {
+            return Ok(());
+        }
+        let _writing = lock.writing.lock().unwrap();
+        self.write(&lock.path)
+    }
+
+    /// Save the changes to one node to the locked registry, leaving the others as they are there.
+    ///
+    /// An operation on many nodes uses this to record each node as it finishes with it, while the
+    /// others are still being worked on. The nodes can be saved from more than one thread, and
+    /// each save waits for the others. Nothing is written for a dry run.
+    pub fn save_node(lock: &RegistryGuard, node: &Node) -> Result<()> {
+        Self::merge_nodes(lock, std::slice::from_ref(node))
+    }
+
+    /// Save the nodes into the registry, replacing those with the same names and leaving anything
+    /// else as it is there, then release the lock on it.
+    pub fn save_nodes(&self, lock: RegistryGuard) -> Result<()> {
+        Self::merge_nodes(&lock, &self.nodes)
+    }
+
+    fn merge_nodes(lock: &RegistryGuard, nodes: &[Node]) -> Result<()> {
+        if is_dry_run() {
+            return Ok(());
+        }
+        let _writing = lock.writing.lock().unwrap();
+        let mut registry = if lock.path.exists() {
+            Self::read(&lock.path)?
+        } else {
+            Self::new(&lock.path)
+        };
+        for node in nodes {
+            match registry
+                .nodes
+                .iter_mut()
+                .find(|n| n.service_name == node.service_name)
+            {
+                Some(saved_node) => *saved_node = node.clone(),
+                None => registry.nodes.push(node.clone()),
+            }
+        }
+        registry.write(&lock.path)
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        let temp_path = get_sibling_path(path, "tmp");
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        if path.exists() {
+            std::fs::copy(path, get_sibling_path(path, "bak"))?;
+        }
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Load the registry to read it.
+    ///
+    /// The registry isn't locked, so reading it doesn't wait for a command that's changing it,
+    /// like a long upgrade. It's only ever replaced whole when it's saved, so a read sees it as it
+    /// was either before or after a save. A command that changes the registry should use
+    /// [`NodeRegistry::load_locked`] instead.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(path));
+        }
+        Self::read(path)
+    }
+
+    /// Load the registry and lock it until it's saved.
+    ///
+    /// The lock is held for the whole of a command that changes the registry, so two commands
+    /// run at the same time, e.g., an upgrade from cron and a start by hand, can't each load the
+    /// registry and then overwrite the other's changes when they save it. A command that finds
+    /// the registry locked waits for the other to finish.
+    pub fn load_locked(path: &Path) -> Result<(Self, RegistryGuard)> {
+        if is_held(path) {
+            return Err(eyre!("The node registry is already locked by this command"));
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let lock = RegistryGuard::new(path, RegistryLock::acquire(path)?);
+        let registry = if path.exists() {
+            Self::read(path)?
+        } else {
+            Self::new(path)
+        };
+        Ok((registry, lock))
+    }
+
+    /// Load the registry and lock it until it's saved, unless another command has it locked.
+    ///
+    /// This is for the daemon's scheduled tasks, which would stop the daemon responding if they
+    /// waited for the lock. The task can instead be tried again the next time it runs.
+    pub fn try_load_locked(path: &Path) -> Result<Option<(Self, RegistryGuard)>> {
+        if is_held(path) {
+            return Err(eyre!("The node registry is already locked by this command"));
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let Some(lock) = RegistryLock::try_acquire(path)? else {
+            return Ok(None);
+        };
+        let lock = RegistryGuard::new(path, lock);
+        let registry = if path.exists() {
+            Self::read(path)?
+        } else {
+            Self::new(path)
+        };
+        Ok(Some((registry, lock)))
+    }
+
+    fn new(path: &Path) -> Self {
+        NodeRegistry {
+            save_path: path.to_path_buf(),
+            nodes: vec![],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        match serde_json::from_str(&contents) {
+            Ok(registry) => Ok(registry),
+            Err(e) => {
+                let backup_path = get_sibling_path(path, "bak");
+                if !backup_path.exists() {
+                    return Err(e.into());
+                }
+                report!(
+                    "The node registry at {} could not be read ({e}), so its backup is being used",
+                    path.display()
+                );
+                Ok(serde_json::from_str(&std::fs::read_to_string(
+                    backup_path,
+                )?)?)
+            }
+        }
+    }
+
+    /// Save a snapshot of the registry, as it is now, before an operation changes it.
+    ///
+    /// The snapshots are written to a directory alongside the registry, and only the most recent
+    /// ones are kept.
+    pub fn snapshot(&self, operation: &str) -> Result<()> {
+        if is_dry_run() {
+            return Ok(());
+        }
+        let snapshots_dir = get_snapshots_dir(&self.save_path);
+        std::fs::create_dir_all(&snapshots_dir)?;
+
+        let timestamp = Utc::now();
+        let snapshot = RegistrySnapshot {
+            operation: operation.to_string(),
+            timestamp,
+            undone: false,
+            registry: self.clone(),
+        };
+        let path = snapshots_dir.join(format!("{}.json", timestamp.format("%Y%m%dT%H%M%S%.6fZ")));
+        std::fs::write(path, serde_json::to_string(&snapshot)?)?;
+
+        let snapshots = get_snapshot_paths(&snapshots_dir)?;
+        if snapshots.len() > SNAPSHOT_LIMIT {
+            for path in &snapshots[..snapshots.len() - SNAPSHOT_LIMIT] {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The registries this process holds the lock on.
+static HELD_LOCKS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+fn is_held(path: &Path) -> bool {
+    HELD_LOCKS.lock().unwrap().iter().any(|p| p == path)
+}
+
+/// The lock on a registry that was loaded to be changed, which is released when the registry is
+/// saved or this is dropped.
+pub struct RegistryGuard {
+    path: PathBuf,
+    /// The nodes of an operation can be saved from more than one thread, which take turns.
+    writing: Mutex<()>,
+    _lock: RegistryLock,
+}
+
+impl RegistryGuard {
+    fn new(path: &Path, lock: RegistryLock) -> Self {
+        HELD_LOCKS.lock().unwrap().push(path.to_path_buf());
+        Self {
+            path: path.to_path_buf(),
+            writing: Mutex::new(()),
+            _lock: lock,
+        }
+    }
+}
+
+impl Drop for RegistryGuard {
+    fn drop(&mut self) {
+        HELD_LOCKS.lock().unwrap().retain(|p| *p != self.path);
+    }
+}
+
+/// An advisory lock on a registry file, so another instance of the node manager can't change it
+/// at the same time.
+///
+/// The lock is on a file alongside the registry, since the registry itself is replaced when it's
+/// saved. It's released when this is dropped.
+struct RegistryLock {
+    _file: std::fs::File,
+}
+
+impl RegistryLock {
+    #[cfg(unix)]
+    fn acquire(registry_path: &Path) -> Result<Self> {
+        use nix::fcntl::{flock, FlockArg};
+        use std::os::unix::io::AsRawFd;
+
+        let file = Self::open(registry_path)?;
+        if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
+            report!("Waiting for another node manager command to finish with the registry...");
+            flock(file.as_raw_fd(), FlockArg::LockExclusive)
+                .map_err(|e| eyre!("Could not lock the node registry: {e}"))?;
+        }
+        Ok(Self { _file: file })
+    }
+
+    #[cfg(unix)]
+    fn try_acquire(registry_path: &Path) -> Result<Option<Self>> {
+        use nix::fcntl::{flock, FlockArg};
+        use std::os::unix::io::AsRawFd;
+
+        let file = Self::open(registry_path)?;
+        if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
+            return Ok(None);
+        }
+        Ok(Some(Self { _file: file }))
+    }
+
+    #[cfg(unix)]
+    fn open(registry_path: &Path) -> Result<std::fs::File> {
+        Ok(std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(get_sibling_path(registry_path, "lock"))?)
+    }
+
+    /// Windows has no advisory locks, but a file opened without sharing can't be opened again
+    /// until it's closed, so opening the lock file is retried until it's free.
+    #[cfg(windows)]
+    fn acquire(registry_path: &Path) -> Result<Self> {
+        loop {
+            if let Some(lock) = Self::try_acquire(registry_path)? {
+                return Ok(lock);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+
+    #[cfg(windows)]
+    fn try_acquire(registry_path: &Path) -> Result<Option<Self>> {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+        match std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .share_mode(0)
+            .open(get_sibling_path(registry_path, "lock"))
+        {
+            Ok(file) => Ok(Some(Self { _file: file })),
+            Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Get the path of a file alongside the registry, named after it with an extra extension.
+fn get_sibling_path(registry_path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = registry_path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    file_name.push(format!(".{extension}"));
+    registry_path.with_file_name(file_name)
+}
+
+/// A copy of the registry taken before an operation changed it, which `undo` can restore.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    pub operation: String,
+    pub timestamp: DateTime<Utc>,
+    /// Set once the snapshot has been restored, so the same operation isn't undone twice.
+    pub undone: bool,
+    pub registry: NodeRegistry,
+}
+
+impl RegistrySnapshot {
+    /// Load the most recent snapshot of the registry at the given path, if there is one.
+    pub fn load_latest(registry_path: &Path) -> Result<Option<(PathBuf, Self)>> {
+        let snapshots_dir = get_snapshots_dir(registry_path);
+        if !snapshots_dir.exists() {
+            return Ok(None);
+        }
+        match get_snapshot_paths(&snapshots_dir)?.pop() {
+            Some(path) => {
+                let snapshot = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+                Ok(Some((path, snapshot)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+pub fn get_snapshots_dir(registry_path: &Path) -> PathBuf {
+    registry_path
+        .parent()
+        .map_or_else(|| PathBuf::from("."), |p| p.to_path_buf())
+        .join("registry_snapshots")
+}
+
+/// Get the paths of the snapshots in the directory, oldest first.
+fn get_snapshot_paths(snapshots_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = std::fs::read_dir(snapshots_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<PathBuf>>();
+    // The file names are timestamps, so they sort in the order they were written.
+    paths.sort();
+    Ok(paths)
+}
+
+pub fn get_local_node_registry_path() -> Result<PathBuf> {
+    let path = dirs_next::data_dir()
+        .ok_or_else(|| eyre!("Could not obtain user data directory"))?
+        .join("safe")
+        .join("local_node_registry.json");
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn load_should_use_the_backup_if_the_registry_cannot_be_read() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let registry_path = temp_dir.child("node_registry.json");
+
+        let (mut node_registry, lock) = NodeRegistry::load_locked(registry_path.path())?;
+        node_registry.faucet_pid = Some(1000);
+        node_registry.save_locked(&lock)?;
+        node_registry.faucet_pid = Some(2000);
+        node_registry.save(lock)?;
+        temp_dir
+            .child("node_registry.json.tmp")
+            .assert(predicates::path::missing());
+
+        registry_path.write_str("{\"save_path\":")?;
+        let node_registry = NodeRegistry::load(registry_path.path())?;
+        assert_eq!(node_registry.faucet_pid, Some(1000));
+        Ok(())
+    }
+
+    #[test]
+    fn load_locked_should_hold_the_lock_until_the_registry_is_saved() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let registry_path = temp_dir.child("node_registry.json");
+
+        let (mut node_registry, lock) = NodeRegistry::load_locked(registry_path.path())?;
+        assert!(NodeRegistry::load_locked(registry_path.path()).is_err());
+        // The registry can still be read while it's locked.
+        NodeRegistry::load(registry_path.path())?;
+
+        node_registry.faucet_pid = Some(1000);
+        node_registry.save(lock)?;
+        let (node_registry, _lock) = NodeRegistry::load_locked(registry_path.path())?;
+        assert_eq!(node_registry.faucet_pid, Some(1000));
+        Ok(())
+    }
+
+    #[test]
+    fn try_load_locked_should_not_wait_for_another_command() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let registry_path = temp_dir.child("node_registry.json");
+
+        // A lock taken directly is like one held by another instance of the node manager.
+        let other_lock = RegistryLock::acquire(registry_path.path())?;
+        assert!(NodeRegistry::try_load_locked(registry_path.path())?.is_none());
+        // Reading doesn't need the lock.
+        NodeRegistry::load(registry_path.path())?;
+
+        drop(other_lock);
+        assert!(NodeRegistry::try_load_locked(registry_path.path())?.is_some());
+        Ok(())
+    }
+
+    fn make_node(service_name: &str) -> Node {
+        Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: service_name.to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Stopped,
+            pid: None,
+            peer_id: None,
+            data_dir_path: None,
+            log_dir_path: None,
+            safenode_path: None,
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }
+    }
+
+    #[test]
+    fn save_node_should_only_change_that_node_in_the_saved_registry() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let registry_path = temp_dir.child("node_registry.json");
+
+        let (mut node_registry, lock) = NodeRegistry::load_locked(registry_path.path())?;
+        node_registry.nodes = vec![make_node("safenode1"), make_node("safenode2")];
+        node_registry.save_locked(&lock)?;
+
+        // The other node has changed in memory, but only the first one is saved.
+        node_registry.nodes[0].status = NodeStatus::Running;
+        node_registry.nodes[0].pid = Some(1000);
+        node_registry.nodes[1].status = NodeStatus::Running;
+        NodeRegistry::save_node(&lock, &node_registry.nodes[0])?;
+        drop(lock);
+
+        let saved_registry = NodeRegistry::load(registry_path.path())?;
+        assert_eq!(saved_registry.nodes[0].status, NodeStatus::Running);
+        assert_eq!(saved_registry.nodes[0].pid, Some(1000));
+        assert_eq!(saved_registry.nodes[1].status, NodeStatus::Stopped);
+        Ok(())
+    }
+
+    #[test]
+    fn save_node_should_write_a_new_registry_if_there_is_none() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let registry_path = temp_dir.child("node_registry.json");
+
+        let (_, lock) = NodeRegistry::load_locked(registry_path.path())?;
+        NodeRegistry::save_node(&lock, &make_node("safenode1"))?;
+        drop(lock);
+
+        let saved_registry = NodeRegistry::load(registry_path.path())?;
+        assert_eq!(saved_registry.nodes.len(), 1);
+        assert_eq!(saved_registry.nodes[0].service_name, "safenode1");
+        Ok(())
+    }
+}
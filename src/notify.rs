@@ -0,0 +1,163 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::earnings::BalanceRecord;
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use serde::Serialize;
+use sn_transfers::NanoTokens;
+
+/// The body of the request sent to a webhook when a node's balance increases.
+///
+/// The `text` field is a readable summary, which is what chat services like Slack or Mattermost
+/// will display if the webhook URL is one of theirs. The other fields are there for anything that
+/// wants to process the notification.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EarningsNotification {
+    pub service_name: String,
+    pub amount: u64,
+    pub balance: u64,
+    pub text: String,
+}
+
+impl EarningsNotification {
+    /// Build a notification for a record, if its balance increased.
+    pub fn from_record(record: &BalanceRecord) -> Option<Self> {
+        if record.delta <= 0 {
+            return None;
+        }
+        let amount = record.delta as u64;
+        Some(Self {
+            service_name: record.service_name.clone(),
+            amount,
+            balance: record.balance,
+            text: format!(
+                "{} received {} (balance: {})",
+                record.service_name,
+                NanoTokens::from(amount),
+                NanoTokens::from(record.balance)
+            ),
+        })
+    }
+}
+
+/// The body of the request sent to a webhook when a node breaches any of its alert thresholds.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AlertNotification {
+    pub service_name: String,
+    pub breaches: Vec<String>,
+    pub text: String,
+}
+
+impl AlertNotification {
+    pub fn new(service_name: &str, breaches: Vec<String>) -> Self {
+        Self {
+            service_name: service_name.to_string(),
+            text: format!("{service_name}: {}", breaches.join("; ")),
+            breaches,
+        }
+    }
+}
+
+/// Send a notification to a webhook for each record where the node's balance increased.
+///
+/// A failure to notify is reported but does not cause an error, since the records have already
+/// been taken and we don't want a webhook that is down to interfere with that.
+pub async fn notify_earnings(webhook_url: &str, records: &[BalanceRecord]) {
+    let client = reqwest::Client::new();
+    for notification in records.iter().filter_map(EarningsNotification::from_record) {
+        match send(&client, webhook_url, &notification).await {
+            Ok(()) => println!(
+                "{} Sent earnings notification for {}",
+                "✓".green(),
+                notification.service_name
+            ),
+            Err(e) => println!(
+                "{} Failed to send earnings notification for {}: {e}",
+                "✕".red(),
+                notification.service_name
+            ),
+        }
+    }
+}
+
+/// Send a notification to a webhook for each node that breached a threshold.
+///
+/// As with earnings, a failure to notify is reported but does not cause an error.
+pub async fn notify_alerts(webhook_url: &str, notifications: &[AlertNotification]) {
+    let client = reqwest::Client::new();
+    for notification in notifications {
+        match send(&client, webhook_url, notification).await {
+            Ok(()) => println!(
+                "{} Sent alert for {}",
+                "✓".green(),
+                notification.service_name
+            ),
+            Err(e) => println!(
+                "{} Failed to send alert for {}: {e}",
+                "✕".red(),
+                notification.service_name
+            ),
+        }
+    }
+}
+
+async fn send(
+    client: &reqwest::Client,
+    webhook_url: &str,
+    notification: &impl Serialize,
+) -> Result<()> {
+    let response = client.post(webhook_url).json(notification).send().await?;
+    if !response.status().is_success() {
+        return Err(eyre!("The webhook returned status {}", response.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn balance_record(delta: i64, balance: u64) -> BalanceRecord {
+        BalanceRecord {
+            service_name: "safenode1".to_string(),
+            timestamp: Utc::now(),
+            balance,
+            delta,
+            payments_received: 1,
+            records_stored: None,
+        }
+    }
+
+    #[test]
+    fn from_record_should_build_a_notification_for_an_increase_in_balance() {
+        let notification = EarningsNotification::from_record(&balance_record(10, 110));
+        assert_eq!(
+            notification,
+            Some(EarningsNotification {
+                service_name: "safenode1".to_string(),
+                amount: 10,
+                balance: 110,
+                text: "safenode1 received 0.000000010 (balance: 0.000000110)".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn from_record_should_not_build_a_notification_if_the_balance_did_not_increase() {
+        assert_eq!(
+            EarningsNotification::from_record(&balance_record(0, 100)),
+            None
+        );
+        assert_eq!(
+            EarningsNotification::from_record(&balance_record(-5, 95)),
+            None
+        );
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{Node, NodeRegistry};
+use color_eyre::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json_output() {
+    JSON_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+pub fn is_json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Print a line of output meant for a person.
+///
+/// When JSON is being output, the line goes to stderr, so that stdout only has the JSON document.
+#[macro_export]
+macro_rules! report {
+    ($($arg:tt)*) => {
+        if $crate::output::is_json_output() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+pub use crate::report;
+
+/// Print the named services from the registry as a JSON document, in registry order.
+pub fn print_nodes_json(node_registry: &NodeRegistry, service_names: &[String]) -> Result<()> {
+    let nodes = node_registry
+        .nodes
+        .iter()
+        .filter(|n| service_names.contains(&n.service_name))
+        .collect::<Vec<&Node>>();
+    println!("{}", serde_json::to_string(&nodes)?);
+    Ok(())
+}
@@ -0,0 +1,211 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{TcpListener, UdpSocket};
+
+/// What a port is used for.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortUse {
+    Node,
+    Rpc,
+}
+
+impl std::fmt::Display for PortUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PortUse::Node => write!(f, "node"),
+            PortUse::Rpc => write!(f, "rpc"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+/// The protocol nodes listen on, which depends on the transport the node manager was built for.
+#[cfg(feature = "tcp")]
+const NODE_PROTOCOL: Protocol = Protocol::Tcp;
+#[cfg(not(feature = "tcp"))]
+const NODE_PROTOCOL: Protocol = Protocol::Udp;
+
+/// A port allocated to a service, and whether anything is bound to it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PortEntry {
+    pub service_name: String,
+    #[serde(rename = "use")]
+    pub port_use: PortUse,
+    pub port: u16,
+    pub protocol: Protocol,
+    pub service_running: bool,
+    pub in_use: bool,
+    pub issues: Vec<String>,
+}
+
+/// Get every port allocated to the services in the registry that haven't been removed.
+///
+/// The entries come back without their bind state or issues, which are filled in by
+/// `check_ports`.
+pub fn get_port_entries(node_registry: &NodeRegistry) -> Vec<PortEntry> {
+    let mut entries = Vec::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Removed)
+    {
+        for (port_use, port, protocol) in [
+            (PortUse::Node, node.port, NODE_PROTOCOL),
+            (PortUse::Rpc, node.rpc_port, Protocol::Tcp),
+        ] {
+            entries.push(PortEntry {
+                service_name: node.service_name.clone(),
+                port_use,
+                port,
+                protocol,
+                service_running: node.status == NodeStatus::Running,
+                in_use: false,
+                issues: Vec::new(),
+            });
+        }
+    }
+    entries
+}
+
+pub fn is_port_in_use(port: u16, protocol: Protocol) -> bool {
+    match protocol {
+        Protocol::Tcp => TcpListener::bind(("127.0.0.1", port)).is_err(),
+        Protocol::Udp => UdpSocket::bind(("127.0.0.1", port)).is_err(),
+    }
+}
+
+/// Fill in the bind state of each port and the issues with it.
+///
+/// A port is reported if it's allocated to more than one service, if its service is running but
+/// nothing is bound to it, or if its service isn't running but something else is bound to it.
+pub fn check_ports<F>(entries: &mut [PortEntry], is_in_use: F)
+where
+    F: Fn(u16, Protocol) -> bool,
+{
+    let mut allocations: HashMap<(u16, Protocol), Vec<String>> = HashMap::new();
+    for entry in entries.iter() {
+        allocations
+            .entry((entry.port, entry.protocol))
+            .or_default()
+            .push(format!("{} ({})", entry.service_name, entry.port_use));
+    }
+
+    for entry in entries.iter_mut() {
+        entry.in_use = is_in_use(entry.port, entry.protocol);
+        let owners = &allocations[&(entry.port, entry.protocol)];
+        if owners.len() > 1 {
+            entry
+                .issues
+                .push(format!("allocated more than once: {}", owners.join(", ")));
+        }
+        if entry.service_running && !entry.in_use {
+            entry
+                .issues
+                .push("the service is running but nothing is listening".to_string());
+        }
+        if !entry.service_running && entry.in_use {
+            entry
+                .issues
+                .push("the service is not running but the port is in use".to_string());
+        }
+    }
+}
+
+pub fn print_ports(entries: &[PortEntry]) {
+    println!(
+        "{:<18} {:<5} {:>6} {:<8} {:<9} Issues",
+        "Service Name", "Use", "Port", "Protocol", "State"
+    );
+    for entry in entries {
+        let state = if entry.in_use { "LISTEN" } else { "FREE" };
+        let issues = if entry.issues.is_empty() {
+            "-".to_string()
+        } else {
+            entry.issues.join("; ").yellow().to_string()
+        };
+        println!(
+            "{:<18} {:<5} {:>6} {:<8} {:<9} {issues}",
+            entry.service_name,
+            entry.port_use.to_string(),
+            entry.port,
+            entry.protocol.to_string(),
+            state,
+        );
+    }
+    let issue_count = entries.iter().filter(|e| !e.issues.is_empty()).count();
+    if issue_count > 0 {
+        println!("{} {issue_count} port(s) have issues", "⚠".yellow());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_entry(service_name: &str, port_use: PortUse, port: u16, running: bool) -> PortEntry {
+        PortEntry {
+            service_name: service_name.to_string(),
+            port_use,
+            port,
+            protocol: Protocol::Tcp,
+            service_running: running,
+            in_use: false,
+            issues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn check_ports_should_report_conflicts_and_mismatched_bind_states() {
+        let mut entries = vec![
+            make_entry("safenode1", PortUse::Node, 8080, true),
+            make_entry("safenode1", PortUse::Rpc, 8081, true),
+            make_entry("safenode2", PortUse::Node, 8082, false),
+            make_entry("safenode2", PortUse::Rpc, 8081, false),
+        ];
+        check_ports(&mut entries, |port, _| port == 8080 || port == 8082);
+
+        assert!(entries[0].in_use);
+        assert!(entries[0].issues.is_empty());
+        assert_eq!(
+            entries[1].issues,
+            vec![
+                "allocated more than once: safenode1 (rpc), safenode2 (rpc)".to_string(),
+                "the service is running but nothing is listening".to_string(),
+            ]
+        );
+        assert_eq!(
+            entries[2].issues,
+            vec!["the service is not running but the port is in use".to_string()]
+        );
+        assert_eq!(
+            entries[3].issues,
+            vec!["allocated more than once: safenode1 (rpc), safenode2 (rpc)".to_string()]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{NodeRegistry, NodeStatus};
+use chrono::{DateTime, Datelike, TimeZone, Timelike};
+use color_eyre::{eyre::eyre, Help, Result};
+use std::str::FromStr;
+
+/// A schedule in the five field format used by cron: minute, hour, day of the month, month and
+/// day of the week.
+///
+/// Each field can be `*`, a number, a range like `1-5`, a step like `*/15` or `0-30/10`, or a
+/// comma-separated list of those. The `@hourly`, `@daily`, `@weekly` and `@monthly` shorthands are
+/// also supported. As with cron, if both the day of the month and the day of the week are
+/// restricted, a time matches if either of them does.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Vec<u32>,
+    months: Vec<u32>,
+    days_of_week: Vec<u32>,
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            expression => expression,
+        };
+        let fields = expression.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(eyre!("The schedule '{s}' does not have five fields").suggestion(
+                "Use the cron format of minute, hour, day of the month, month and day of the week, \
+                e.g., '0 4 * * *' to restart at 04:00 every day",
+            ));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 are Sunday.
+        if days_of_week.contains(&7) {
+            days_of_week.retain(|d| *d != 7);
+            if !days_of_week.contains(&0) {
+                days_of_week.insert(0, 0);
+            }
+        }
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            days_of_month_restricted: !fields[2].starts_with('*'),
+            days_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl CronSchedule {
+    /// Whether the minute the given time falls in is one of the scheduled minutes.
+    pub fn matches<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        if !self.minutes.contains(&time.minute())
+            || !self.hours.contains(&time.hour())
+            || !self.months.contains(&time.month())
+        {
+            return false;
+        }
+        let day_of_month = self.days_of_month.contains(&time.day());
+        let day_of_week = self
+            .days_of_week
+            .contains(&time.weekday().num_days_from_sunday());
+        if self.days_of_month_restricted && self.days_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_value(step, 1, max)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            // A step from a single value runs to the end of the range, as it does with cron.
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start > end {
+            return Err(eyre!("The range '{range}' in the schedule is backwards"));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort();
+    values.dedup();
+    Ok(values)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32> {
+    let parsed = value
+        .parse::<u32>()
+        .map_err(|_| eyre!("'{value}' is not a valid value in the schedule"))?;
+    if parsed < min || parsed > max {
+        return Err(eyre!(
+            "The value {parsed} in the schedule is outside the range {min}-{max}"
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Get the names of the running nodes whose restart schedule includes the given time.
+///
+/// A node's own schedule takes precedence over the schedule in the registry, which applies to
+/// every node. Nodes in maintenance mode are never restarted.
+pub fn get_nodes_due_for_restart<Tz: TimeZone>(
+    node_registry: &NodeRegistry,
+    time: &DateTime<Tz>,
+) -> Result<Vec<String>> {
+    let mut due = Vec::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|n| n.status == NodeStatus::Running && !n.maintenance)
+    {
+        let schedule = match node
+            .restart_schedule
+            .as_ref()
+            .or(node_registry.restart_schedule.as_ref())
+        {
+            Some(schedule) => CronSchedule::from_str(schedule)?,
+            None => continue,
+        };
+        if schedule.matches(time) {
+            due.push(node.service_name.clone());
+        }
+    }
+    Ok(due)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::{Node, NodeType};
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn make_node(service_name: &str, status: NodeStatus) -> Node {
+        Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: service_name.to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status,
+            pid: None,
+            peer_id: None,
+            data_dir_path: None,
+            log_dir_path: None,
+            safenode_path: None,
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }
+    }
+
+    #[test]
+    fn cron_schedule_should_match_the_scheduled_minutes() -> Result<()> {
+        let schedule = CronSchedule::from_str("*/15 4 * * *")?;
+        assert!(schedule.matches(&at("2024-02-05T04:00:00Z")));
+        assert!(schedule.matches(&at("2024-02-05T04:45:30Z")));
+        assert!(!schedule.matches(&at("2024-02-05T04:10:00Z")));
+        assert!(!schedule.matches(&at("2024-02-05T05:00:00Z")));
+
+        // 2024-02-05 was a Monday.
+        let schedule = CronSchedule::from_str("30 2 * * 1-5")?;
+        assert!(schedule.matches(&at("2024-02-05T02:30:00Z")));
+        assert!(!schedule.matches(&at("2024-02-04T02:30:00Z")));
+
+        let schedule = CronSchedule::from_str("@weekly")?;
+        assert!(schedule.matches(&at("2024-02-04T00:00:00Z")));
+        assert!(!schedule.matches(&at("2024-02-05T00:00:00Z")));
+
+        // When both days are restricted, either can match.
+        let schedule = CronSchedule::from_str("0 0 1 * 7")?;
+        assert!(schedule.matches(&at("2024-02-01T00:00:00Z")));
+        assert!(schedule.matches(&at("2024-02-04T00:00:00Z")));
+        assert!(!schedule.matches(&at("2024-02-05T00:00:00Z")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cron_schedule_should_reject_invalid_expressions() {
+        assert!(CronSchedule::from_str("0 4 * *").is_err());
+        assert!(CronSchedule::from_str("60 4 * * *").is_err());
+        assert!(CronSchedule::from_str("0 4 0 * *").is_err());
+        assert!(CronSchedule::from_str("0 5-4 * * *").is_err());
+        assert!(CronSchedule::from_str("0 four * * *").is_err());
+    }
+
+    #[test]
+    fn get_nodes_due_for_restart_should_use_the_node_schedule_before_the_global_one() -> Result<()>
+    {
+        let mut node1 = make_node("safenode1", NodeStatus::Running);
+        node1.restart_schedule = Some("0 3 * * *".to_string());
+        let node2 = make_node("safenode2", NodeStatus::Running);
+        let mut node3 = make_node("safenode3", NodeStatus::Running);
+        node3.maintenance = true;
+        let node4 = make_node("safenode4", NodeStatus::Stopped);
+        let node_registry = NodeRegistry {
+            save_path: PathBuf::from("/tmp/node_registry.json"),
+            nodes: vec![node1, node2, node3, node4],
+            faucet_pid: None,
+            restart_schedule: Some("0 4 * * *".to_string()),
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        assert_eq!(
+            get_nodes_due_for_restart(&node_registry, &at("2024-02-05T03:00:00Z"))?,
+            vec!["safenode1".to_string()]
+        );
+        assert_eq!(
+            get_nodes_due_for_restart(&node_registry, &at("2024-02-05T04:00:00Z"))?,
+            vec!["safenode2".to_string()]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::node_registry::{Node, NodeRegistry};
+use clap::Args;
+use color_eyre::{eyre::eyre, Result};
+use libp2p_identity::PeerId;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The arguments used to pick which services a command applies to.
+#[derive(Args, Clone, Debug, Default)]
+pub struct NodeSelector {
+    /// The peer ID of a service.
+    ///
+    /// This argument can be used multiple times to select more than one service.
+    #[clap(long = "peer-id", value_name = "PEER_ID")]
+    pub peer_ids: Vec<String>,
+    /// A file of peer IDs, with one per line.
+    ///
+    /// Blank lines and lines starting with '#' are ignored.
+    #[clap(long)]
+    pub peer_ids_file: Option<PathBuf>,
+    /// The name of a service
+    #[clap(long, conflicts_with_all = ["peer_ids", "peer_ids_file"])]
+    pub service_name: Option<String>,
+}
+
+impl NodeSelector {
+    pub fn is_empty(&self) -> bool {
+        self.peer_ids.is_empty() && self.peer_ids_file.is_none() && self.service_name.is_none()
+    }
+
+    /// Get the indexes of the selected services in the registry, in the order they were given.
+    ///
+    /// If no services were selected, `None` is returned, which most commands take to mean every
+    /// service. It's an error for any of the peer IDs or the name to not match a service.
+    pub fn select(&self, node_registry: &NodeRegistry) -> Result<Option<Vec<usize>>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(ref name) = self.service_name {
+            let index = node_registry
+                .nodes
+                .iter()
+                .position(|n| n.service_name == *name)
+                .ok_or_else(|| eyre!("No service named '{name}'"))?;
+            return Ok(Some(vec![index]));
+        }
+
+        let mut peer_ids = self.peer_ids.clone();
+        if let Some(ref path) = self.peer_ids_file {
+            peer_ids.extend(read_peer_ids(path)?);
+        }
+        let mut indexes = Vec::new();
+        for peer_id in peer_ids {
+            let peer_id = PeerId::from_str(&peer_id)?;
+            let index = node_registry
+                .nodes
+                .iter()
+                .position(|n| n.peer_id == Some(peer_id))
+                .ok_or_else(|| eyre!("Could not find node with peer ID '{peer_id}'"))?;
+            if !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+        Ok(Some(indexes))
+    }
+
+    /// Get the selected services, in the order they are in the registry, or `None` if no services
+    /// were selected.
+    pub fn select_mut<'a>(
+        &self,
+        node_registry: &'a mut NodeRegistry,
+    ) -> Result<Option<Vec<&'a mut Node>>> {
+        let indexes = match self.select(node_registry)? {
+            Some(indexes) => indexes,
+            None => return Ok(None),
+        };
+        Ok(Some(
+            node_registry
+                .nodes
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, _)| indexes.contains(i))
+                .map(|(_, n)| n)
+                .collect(),
+        ))
+    }
+}
+
+/// Read peer IDs from a file, with one peer ID per line.
+///
+/// Blank lines and lines starting with `#` are ignored, so the file can be annotated.
+pub fn read_peer_ids(path: &Path) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| eyre!("Could not read peer IDs from {}: {e}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_registry::{NodeStatus, NodeType};
+    use assert_fs::prelude::*;
+
+    fn make_node(service_name: &str, peer_id: PeerId) -> Node {
+        Node {
+            genesis: false,
+            node_type: NodeType::Safenode,
+            version: "0.98.1".to_string(),
+            service_name: service_name.to_string(),
+            user: "safe".to_string(),
+            number: 1,
+            port: 8080,
+            rpc_port: 8081,
+            rpc_address: None,
+            node_ip: None,
+            status: NodeStatus::Running,
+            pid: None,
+            peer_id: Some(peer_id),
+            data_dir_path: None,
+            log_dir_path: None,
+            safenode_path: None,
+            connected_peers: None,
+            records_stored: None,
+            resource_usage: None,
+            listen_addr: None,
+            local: false,
+            peers: vec![],
+            home_network: false,
+            upnp: false,
+            extra_args: vec![],
+            env_variables: vec![],
+            rewards_address: None,
+            owner: None,
+            firewall_rule_added: false,
+            keep_alive: true,
+            throttle_interval: None,
+            log_targets: None,
+            max_memory: None,
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            safenode_checksum: None,
+            previous_version: None,
+            maintenance: false,
+            restart_schedule: None,
+            last_stop_method: None,
+            exit_info: None,
+            watchdog_restarts: 0,
+            last_failure: None,
+        }
+    }
+
+    #[test]
+    fn select_should_combine_repeated_peer_ids_with_a_file_of_them() -> Result<()> {
+        let peer_ids = (0..3).map(|_| PeerId::random()).collect::<Vec<PeerId>>();
+        let node_registry = NodeRegistry {
+            save_path: PathBuf::from("/tmp/node_registry.json"),
+            nodes: vec![
+                make_node("safenode1", peer_ids[0]),
+                make_node("safenode2", peer_ids[1]),
+                make_node("safenode3", peer_ids[2]),
+            ],
+            faucet_pid: None,
+            restart_schedule: None,
+            container: None,
+            systemd_template: false,
+            auditor: None,
+        };
+
+        let temp_dir = assert_fs::TempDir::new()?;
+        let peer_ids_file = temp_dir.child("peer_ids.txt");
+        peer_ids_file.write_str(&format!(
+            "# Nodes to upgrade\n{}\n\n{}\n",
+            peer_ids[2], peer_ids[0]
+        ))?;
+
+        let selector = NodeSelector {
+            peer_ids: vec![peer_ids[0].to_string()],
+            peer_ids_file: Some(peer_ids_file.to_path_buf()),
+            service_name: None,
+        };
+        assert_eq!(selector.select(&node_registry)?, Some(vec![0, 2]));
+
+        assert_eq!(NodeSelector::default().select(&node_registry)?, None);
+
+        let selector = NodeSelector {
+            peer_ids: vec![PeerId::random().to_string()],
+            ..Default::default()
+        };
+        assert!(selector.select(&node_registry).is_err());
+        Ok(())
+    }
+}
@@ -6,37 +6,74 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::config::is_user_mode;
+use crate::container::ContainerServiceManager;
+use crate::dry_run::{is_dry_run, DryRunServiceControl};
+use crate::node_registry::{ExitInfo, NodeRegistry, NodeType};
+use crate::systemd_template::SystemdTemplateManager;
+use async_trait::async_trait;
 use color_eyre::Result;
 use libp2p::Multiaddr;
 #[cfg(test)]
 use mockall::automock;
 use service_manager::{
-    ServiceInstallCtx, ServiceLabel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
+    ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceManager, ServiceManagerKind,
+    ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
 };
-use std::net::SocketAddr;
 #[cfg(feature = "tcp")]
 use std::net::TcpListener as SocketBinder;
 #[cfg(not(feature = "tcp"))]
 use std::net::UdpSocket as SocketBinder;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::Duration;
 use std::{ffi::OsString, thread::sleep};
 
-use std::path::PathBuf;
-use sysinfo::{Pid, System, SystemExt};
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, ProcessExt, System, SystemExt};
 
 #[derive(Debug, PartialEq)]
 pub struct ServiceConfig {
+    /// The delay, in seconds, before the node is restarted after a failure. This only applies on
+    /// Windows.
+    pub auto_restart: Option<u64>,
+    /// The period, in seconds, after which the failure count is reset. This only applies on
+    /// Windows.
+    pub auto_restart_reset_period: u64,
     pub data_dir_path: PathBuf,
+    /// Environment variables for the node, which are set in the service definition.
+    pub env_variables: Vec<(String, String)>,
+    /// Arguments passed to `safenode` as they are, after those set by the manager.
+    pub extra_args: Vec<String>,
     pub genesis: bool,
+    /// Run the node as if it's behind a NAT, relaying its traffic through other nodes.
+    pub home_network: bool,
+    /// Restart the node if it exits. This only applies on macOS.
+    pub keep_alive: bool,
     pub local: bool,
     pub log_dir_path: PathBuf,
+    /// The value of `SN_LOG` for the node, which sets its logging targets and levels. This only
+    /// applies with systemd.
+    pub log_targets: Option<String>,
+    /// The memory limit for the node, in megabytes. This only applies with systemd.
+    pub max_memory: Option<u64>,
     pub name: String,
+    /// The IP address the node listens on. It listens on every interface if this isn't set.
+    pub node_ip: Option<IpAddr>,
     pub node_port: u16,
+    /// The binary the service runs, which decides the arguments it's given.
+    pub node_type: NodeType,
+    pub owner: Option<String>,
     pub peers: Vec<Multiaddr>,
+    pub rewards_address: Option<String>,
+    /// The address the RPC service listens on. It's the loopback address if this isn't set.
+    pub rpc_address: Option<IpAddr>,
     pub rpc_port: u16,
     pub safenode_path: PathBuf,
     pub service_user: String,
+    /// The minimum number of seconds between restarts. This only applies on macOS.
+    pub throttle_interval: Option<u64>,
+    /// Ask the router to forward the node's port with UPnP.
+    pub upnp: bool,
 }
 
 /// A thin wrapper around the `service_manager::ServiceManager`, which makes our own testing
@@ -46,21 +83,65 @@ pub struct ServiceConfig {
 /// need assert that the service manager is used. Testing code that used the real service manager
 /// would result in real services on the machines we are testing on; that can leave a bit of a mess
 /// to clean up, especially if the tests fail.
+///
+/// The waits are asynchronous, so nodes being started or stopped at the same time don't hold each
+/// other up while they wait.
 #[cfg_attr(test, automock)]
-pub trait ServiceControl {
+#[async_trait]
+pub trait ServiceControl: Sync {
+    fn add_firewall_rule(&self, service_name: &str, port: u16) -> Result<()>;
     fn create_service_user(&self, username: &str) -> Result<()>;
     fn get_available_port(&self) -> Result<u16>;
+    fn get_exit_info(&self, service_name: &str) -> Result<ExitInfo>;
     fn install(&self, config: ServiceConfig) -> Result<()>;
     fn is_port_free(&self, port: u16) -> bool;
     fn is_service_process_running(&self, pid: u32) -> bool;
+    fn kill_process(&self, pid: u32) -> Result<()>;
+    fn remove_firewall_rule(&self, service_name: &str) -> Result<()>;
     fn start(&self, service_name: &str) -> Result<()>;
     fn stop(&self, service_name: &str) -> Result<()>;
     fn uninstall(&self, service_name: &str) -> Result<()>;
-    fn wait(&self, delay: u64);
+    async fn wait(&self, delay: u64);
 }
 
 pub struct NodeServiceManager {}
 
+/// Get the service control for the registry's nodes, which depends on the backend the registry
+/// records.
+///
+/// For a dry run, the changes to the services are reported rather than made.
+pub fn get_service_control(node_registry: &NodeRegistry) -> Box<dyn ServiceControl> {
+    if is_dry_run() {
+        return Box::new(DryRunServiceControl {
+            inner: get_backend_service_control(node_registry),
+        });
+    }
+    get_backend_service_control(node_registry)
+}
+
+fn get_backend_service_control(node_registry: &NodeRegistry) -> Box<dyn ServiceControl> {
+    if let Some(backend) = &node_registry.container {
+        return Box::new(ContainerServiceManager {
+            backend: backend.clone(),
+        });
+    }
+    if node_registry.systemd_template {
+        return Box::new(SystemdTemplateManager {});
+    }
+    Box::new(NodeServiceManager {})
+}
+
+/// Get the platform's service manager, at the user level if the services belong to the current
+/// user rather than the system.
+fn get_native_manager() -> Result<Box<dyn ServiceManager>> {
+    let mut manager = <dyn ServiceManager>::native()?;
+    if is_user_mode() {
+        manager.set_level(ServiceLevel::User)?;
+    }
+    Ok(manager)
+}
+
+#[async_trait]
 impl ServiceControl for NodeServiceManager {
     #[cfg(target_os = "linux")]
     fn create_service_user(&self, username: &str) -> Result<()> {
@@ -73,12 +154,22 @@ impl ServiceControl for NodeServiceManager {
             return Ok(());
         }
 
-        let output = Command::new("useradd")
+        // Alpine and other BusyBox based systems don't have `useradd`, only `adduser`.
+        let output = match Command::new("useradd")
             .arg("-m")
             .arg("-s")
             .arg("/bin/bash")
             .arg(username)
-            .output()?;
+            .output()
+        {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Command::new("adduser")
+                .arg("-D")
+                .arg("-s")
+                .arg("/bin/sh")
+                .arg(username)
+                .output()?,
+            result => result?,
+        };
         if !output.status.success() {
             return Err(eyre!("Failed to create user account"));
         }
@@ -146,11 +237,94 @@ impl ServiceControl for NodeServiceManager {
         Ok(())
     }
 
+    #[cfg(target_os = "freebsd")]
+    fn create_service_user(&self, username: &str) -> Result<()> {
+        use color_eyre::eyre::eyre;
+        use std::process::Command;
+
+        let output = Command::new("id").arg("-u").arg(username).output()?;
+        if output.status.success() {
+            println!("The {username} user already exists");
+            return Ok(());
+        }
+
+        let output = Command::new("pw")
+            .arg("useradd")
+            .arg("-n")
+            .arg(username)
+            .arg("-m")
+            .arg("-s")
+            .arg("/usr/sbin/nologin")
+            .output()?;
+        if !output.status.success() {
+            return Err(eyre!("Failed to create user account"));
+        }
+        println!("Created {username} user account for running the service");
+        Ok(())
+    }
+
     #[cfg(target_os = "windows")]
     fn create_service_user(&self, _username: &str) -> Result<()> {
         Ok(())
     }
 
+    /// Allow inbound traffic to the node's port through the Windows Firewall.
+    ///
+    /// The rule is named after the service, so it can be found again when the service is removed.
+    #[cfg(target_os = "windows")]
+    fn add_firewall_rule(&self, service_name: &str, port: u16) -> Result<()> {
+        use color_eyre::eyre::eyre;
+        use std::process::Command;
+
+        let protocol = if cfg!(feature = "tcp") { "TCP" } else { "UDP" };
+        let output = Command::new("netsh")
+            .arg("advfirewall")
+            .arg("firewall")
+            .arg("add")
+            .arg("rule")
+            .arg(format!("name={service_name}"))
+            .arg("dir=in")
+            .arg("action=allow")
+            .arg(format!("protocol={protocol}"))
+            .arg(format!("localport={port}"))
+            .output()?;
+        if !output.status.success() {
+            return Err(eyre!("Failed to add firewall rule for {service_name}"));
+        }
+        println!("Added firewall rule for {service_name} on port {port}");
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn add_firewall_rule(&self, _service_name: &str, _port: u16) -> Result<()> {
+        use color_eyre::eyre::eyre;
+        Err(eyre!("Firewall rules can only be added on Windows"))
+    }
+
+    #[cfg(target_os = "windows")]
+    fn remove_firewall_rule(&self, service_name: &str) -> Result<()> {
+        use color_eyre::eyre::eyre;
+        use std::process::Command;
+
+        let output = Command::new("netsh")
+            .arg("advfirewall")
+            .arg("firewall")
+            .arg("delete")
+            .arg("rule")
+            .arg(format!("name={service_name}"))
+            .output()?;
+        if !output.status.success() {
+            return Err(eyre!("Failed to remove firewall rule for {service_name}"));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn remove_firewall_rule(&self, _service_name: &str) -> Result<()> {
+        use color_eyre::eyre::eyre;
+        Err(eyre!("Firewall rules can only be removed on Windows"))
+    }
+
     fn is_port_free(&self, port: u16) -> bool {
         SocketBinder::bind(("127.0.0.1", port)).is_ok()
     }
@@ -161,6 +335,72 @@ impl ServiceControl for NodeServiceManager {
         system.process(Pid::from(pid as usize)).is_some()
     }
 
+    fn kill_process(&self, pid: u32) -> Result<()> {
+        use color_eyre::eyre::eyre;
+
+        let mut system = System::new();
+        system.refresh_process(Pid::from(pid as usize));
+        match system.process(Pid::from(pid as usize)) {
+            Some(process) => {
+                if !process.kill() {
+                    return Err(eyre!("Failed to kill the process with PID {pid}"));
+                }
+                Ok(())
+            }
+            // The process exited in the meantime.
+            None => Ok(()),
+        }
+    }
+
+    /// Ask the service manager how the service's process last exited.
+    ///
+    /// This is only supported with systemd, launchd and the Service Control Manager. For other
+    /// service managers, the information will be empty.
+    fn get_exit_info(&self, service_name: &str) -> Result<ExitInfo> {
+        use std::process::Command;
+
+        let label: ServiceLabel = service_name.parse()?;
+        match ServiceManagerKind::native()? {
+            ServiceManagerKind::Systemd => {
+                let mut command = Command::new("systemctl");
+                if is_user_mode() {
+                    command.arg("--user");
+                }
+                let output = command
+                    .arg("show")
+                    .arg(format!("{}.service", label.to_script_name()))
+                    .arg("-p")
+                    .arg("Result,ExecMainCode,ExecMainStatus,NRestarts")
+                    .output()?;
+                Ok(parse_systemctl_exit_info(&String::from_utf8_lossy(
+                    &output.stdout,
+                )))
+            }
+            ServiceManagerKind::Launchd => {
+                let domain = if is_user_mode() {
+                    format!("gui/{}", get_current_uid()?)
+                } else {
+                    "system".to_string()
+                };
+                let output = Command::new("launchctl")
+                    .arg("print")
+                    .arg(format!("{domain}/{}", label.to_qualified_name()))
+                    .output()?;
+                Ok(parse_launchctl_exit_info(&String::from_utf8_lossy(
+                    &output.stdout,
+                )))
+            }
+            ServiceManagerKind::Sc => {
+                let output = Command::new("sc.exe")
+                    .arg("queryex")
+                    .arg(label.to_qualified_name())
+                    .output()?;
+                Ok(parse_sc_exit_info(&String::from_utf8_lossy(&output.stdout)))
+            }
+            _ => Ok(ExitInfo::default()),
+        }
+    }
+
     fn get_available_port(&self) -> Result<u16> {
         let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
 
@@ -176,66 +416,106 @@ impl ServiceControl for NodeServiceManager {
 
     fn install(&self, config: ServiceConfig) -> Result<()> {
         let label: ServiceLabel = config.name.parse()?;
-        let manager = <dyn ServiceManager>::native()?;
-        let mut args = vec![
-            OsString::from("--port"),
-            OsString::from(config.node_port.to_string()),
-            OsString::from("--rpc"),
-            OsString::from(format!("127.0.0.1:{}", config.rpc_port)),
-            OsString::from("--root-dir"),
-            OsString::from(config.data_dir_path.to_string_lossy().to_string()),
-            OsString::from("--log-output-dest"),
-            OsString::from(config.log_dir_path.to_string_lossy().to_string()),
-        ];
+        let manager = get_native_manager()?;
+        let args = make_node_args(&config);
 
-        if config.genesis {
-            args.push(OsString::from("--first"));
-        }
-        if config.local {
-            args.push(OsString::from("--local"));
-        }
-
-        if !config.peers.is_empty() {
-            let peers_str = config
-                .peers
-                .iter()
-                .map(|peer| peer.to_string())
-                .collect::<Vec<_>>()
-                .join(",");
-            args.push(OsString::from("--peer"));
-            args.push(OsString::from(peers_str));
-        }
+        // The plist generated by the service manager doesn't provide the throttle interval or the
+        // output paths, so on macOS we generate our own. Likewise, the rc.d script would run the
+        // node as root, and wouldn't restart it, and the systemd unit has no way to set a memory
+        // limit or the environment.
+        let contents = if (config.log_targets.is_some()
+            || config.max_memory.is_some()
+            || !config.env_variables.is_empty())
+            && matches!(
+                ServiceManagerKind::native(),
+                Ok(ServiceManagerKind::Systemd)
+            ) {
+            Some(make_systemd_unit(
+                &label.to_script_name(),
+                &config.safenode_path,
+                &args,
+                &config.service_user,
+                config.log_targets.as_deref(),
+                config.max_memory,
+                &config.env_variables,
+            ))
+        } else if cfg!(target_os = "freebsd") {
+            Some(make_rcd_script(
+                &label.to_script_name(),
+                &config.safenode_path,
+                &args,
+                &config.service_user,
+                config.keep_alive,
+                &config.log_dir_path,
+                &config.env_variables,
+            ))
+        } else if cfg!(target_os = "macos") {
+            Some(make_launchd_plist(
+                &label.to_qualified_name(),
+                &config.safenode_path,
+                &args,
+                if is_user_mode() {
+                    None
+                } else {
+                    Some(&config.service_user)
+                },
+                config.keep_alive,
+                config.throttle_interval,
+                &config.log_dir_path,
+                &config.env_variables,
+            ))
+        } else {
+            None
+        };
 
         manager.install(ServiceInstallCtx {
             label: label.clone(),
             program: config.safenode_path.to_path_buf(),
             args,
-            contents: None,
-            username: Some(config.service_user.to_string()),
+            contents,
+            // A user agent always runs as the user it belongs to, and it can't have a user name.
+            username: if is_user_mode() {
+                None
+            } else {
+                Some(config.service_user.to_string())
+            },
             working_directory: None,
             environment: None,
         })?;
 
+        if cfg!(windows) {
+            if let Some(auto_restart) = config.auto_restart {
+                set_failure_actions(
+                    &label.to_qualified_name(),
+                    auto_restart,
+                    config.auto_restart_reset_period,
+                )?;
+            }
+            if !config.env_variables.is_empty() {
+                set_environment(&label.to_qualified_name(), &config.env_variables)?;
+            }
+        }
+
         Ok(())
     }
 
     fn start(&self, service_name: &str) -> Result<()> {
         let label: ServiceLabel = service_name.parse()?;
-        let manager = <dyn ServiceManager>::native()?;
+        let manager = get_native_manager()?;
         manager.start(ServiceStartCtx { label })?;
         Ok(())
     }
 
     fn stop(&self, service_name: &str) -> Result<()> {
         let label: ServiceLabel = service_name.parse()?;
-        let manager = <dyn ServiceManager>::native()?;
+        let manager = get_native_manager()?;
         manager.stop(ServiceStopCtx { label })?;
         Ok(())
     }
 
     fn uninstall(&self, service_name: &str) -> Result<()> {
         let label: ServiceLabel = service_name.parse()?;
-        let manager = <dyn ServiceManager>::native()?;
+        let manager = get_native_manager()?;
         manager.uninstall(ServiceUninstallCtx { label })?;
         Ok(())
     }
@@ -243,7 +523,753 @@ impl ServiceControl for NodeServiceManager {
     /// Provide a delay for the service to start or stop.
     ///
     /// This is wrapped mainly just for unit testing.
-    fn wait(&self, delay: u64) {
-        std::thread::sleep(std::time::Duration::from_secs(delay));
+    async fn wait(&self, delay: u64) {
+        tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+    }
+}
+
+/// Get the arguments the node is started with.
+pub fn make_node_args(config: &ServiceConfig) -> Vec<OsString> {
+    if config.node_type == NodeType::Auditor {
+        return make_auditor_args(config);
+    }
+    let mut args = vec![
+        OsString::from("--port"),
+        OsString::from(config.node_port.to_string()),
+        OsString::from("--rpc"),
+        OsString::from(
+            SocketAddr::new(
+                config
+                    .rpc_address
+                    .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                config.rpc_port,
+            )
+            .to_string(),
+        ),
+        OsString::from("--root-dir"),
+        OsString::from(config.data_dir_path.to_string_lossy().to_string()),
+        OsString::from("--log-output-dest"),
+        OsString::from(config.log_dir_path.to_string_lossy().to_string()),
+    ];
+
+    if let Some(node_ip) = config.node_ip {
+        args.push(OsString::from("--ip"));
+        args.push(OsString::from(node_ip.to_string()));
+    }
+    if config.genesis {
+        args.push(OsString::from("--first"));
+    }
+    if config.local {
+        args.push(OsString::from("--local"));
+    }
+    if config.home_network {
+        args.push(OsString::from("--home-network"));
+    }
+    if config.upnp {
+        args.push(OsString::from("--upnp"));
+    }
+    if let Some(rewards_address) = &config.rewards_address {
+        args.push(OsString::from("--rewards-address"));
+        args.push(OsString::from(rewards_address));
+    }
+    if let Some(owner) = &config.owner {
+        args.push(OsString::from("--owner"));
+        args.push(OsString::from(owner));
+    }
+
+    if !config.peers.is_empty() {
+        let peers_str = config
+            .peers
+            .iter()
+            .map(|peer| peer.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        args.push(OsString::from("--peer"));
+        args.push(OsString::from(peers_str));
+    }
+    args.extend(config.extra_args.iter().map(OsString::from));
+    args
+}
+
+/// Get the arguments the auditor is started with.
+///
+/// The auditor has no ports of its own. It only needs the peers to join the network with.
+fn make_auditor_args(config: &ServiceConfig) -> Vec<OsString> {
+    let mut args = vec![
+        OsString::from("--log-output-dest"),
+        OsString::from(config.log_dir_path.to_string_lossy().to_string()),
+    ];
+    for peer in &config.peers {
+        args.push(OsString::from("--peer"));
+        args.push(OsString::from(peer.to_string()));
+    }
+    args.extend(config.extra_args.iter().map(OsString::from));
+    args
+}
+
+#[cfg(unix)]
+fn get_current_uid() -> Result<u32> {
+    Ok(users::get_current_uid())
+}
+
+#[cfg(windows)]
+fn get_current_uid() -> Result<u32> {
+    use color_eyre::eyre::eyre;
+    Err(eyre!("There is no user ID on Windows"))
+}
+
+/// Parse the output of `systemctl show` for the `Result`, `ExecMainCode`, `ExecMainStatus` and
+/// `NRestarts` properties.
+///
+/// The main code says whether the process exited or was killed, and the status is the exit code or
+/// the signal accordingly. A code of zero means the process hasn't exited yet.
+pub fn parse_systemctl_exit_info(output: &str) -> ExitInfo {
+    let mut info = ExitInfo::default();
+    let mut code = None;
+    let mut status = None;
+    for line in output.lines() {
+        match line.split_once('=') {
+            Some(("Result", value)) if value != "success" => info.reason = Some(value.to_string()),
+            Some(("ExecMainCode", value)) => code = value.parse::<i32>().ok(),
+            Some(("ExecMainStatus", value)) => status = value.parse::<i32>().ok(),
+            Some(("NRestarts", value)) => info.restart_count = value.parse::<u32>().ok(),
+            _ => {}
+        }
+    }
+    match code {
+        // CLD_EXITED
+        Some(1) => info.exit_code = status,
+        // CLD_KILLED or CLD_DUMPED
+        Some(2) | Some(3) => info.signal = status,
+        _ => {}
+    }
+    info
+}
+
+/// Parse the output of `launchctl print` for the service.
+fn parse_launchctl_exit_info(output: &str) -> ExitInfo {
+    let mut info = ExitInfo::default();
+    for line in output.lines() {
+        match line.trim().split_once(" = ") {
+            Some(("runs", value)) => {
+                // The first run isn't a restart.
+                info.restart_count = value.parse::<u32>().ok().map(|r| r.saturating_sub(1))
+            }
+            Some(("last exit code", value)) => info.exit_code = value.parse::<i32>().ok(),
+            Some(("last terminating signal", value)) => {
+                // e.g., "Killed: 9"
+                if let Some((name, number)) = value.rsplit_once(": ") {
+                    info.signal = number.parse::<i32>().ok();
+                    info.reason = Some(name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Parse the output of `sc.exe queryex` for the service.
+///
+/// If the service returned its own error code, the Win32 exit code will be 1066
+/// (`ERROR_SERVICE_SPECIFIC_ERROR`), so the service exit code is used instead.
+fn parse_sc_exit_info(output: &str) -> ExitInfo {
+    let mut win32_exit_code = None;
+    let mut service_exit_code = None;
+    for line in output.lines() {
+        if let Some((key, value)) = line.trim().split_once(':') {
+            let value = value
+                .split_whitespace()
+                .next()
+                .and_then(|v| v.parse::<i32>().ok());
+            match key.trim() {
+                "WIN32_EXIT_CODE" => win32_exit_code = value,
+                "SERVICE_EXIT_CODE" => service_exit_code = value,
+                _ => {}
+            }
+        }
+    }
+    ExitInfo {
+        exit_code: if win32_exit_code == Some(1066) {
+            service_exit_code
+        } else {
+            win32_exit_code
+        },
+        ..Default::default()
+    }
+}
+
+/// Configure the Service Control Manager to restart the node when it fails.
+///
+/// The `failureflag` setting is also enabled, so the actions apply when the node exits with an
+/// error, not only when it crashes.
+fn set_failure_actions(service_name: &str, delay: u64, reset_period: u64) -> Result<()> {
+    use color_eyre::eyre::eyre;
+    use std::process::Command;
+
+    let output = Command::new("sc.exe")
+        .args(make_failure_actions_args(service_name, delay, reset_period))
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Failed to set the recovery actions for {service_name}"
+        ));
+    }
+    let output = Command::new("sc.exe")
+        .arg("failureflag")
+        .arg(service_name)
+        .arg("1")
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!("Failed to set the failure flag for {service_name}"));
+    }
+    Ok(())
+}
+
+/// Set the environment of a service, which the Service Control Manager reads from the service's
+/// registry key when it starts the node.
+fn set_environment(service_name: &str, env_variables: &[(String, String)]) -> Result<()> {
+    use color_eyre::eyre::eyre;
+    use std::process::Command;
+
+    let output = Command::new("reg.exe")
+        .args(make_environment_args(service_name, env_variables))
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Failed to set the environment variables for {service_name}"
+        ));
+    }
+    Ok(())
+}
+
+fn make_environment_args(service_name: &str, env_variables: &[(String, String)]) -> Vec<String> {
+    vec![
+        "add".to_string(),
+        format!("HKLM\\SYSTEM\\CurrentControlSet\\Services\\{service_name}"),
+        "/v".to_string(),
+        "Environment".to_string(),
+        "/t".to_string(),
+        "REG_MULTI_SZ".to_string(),
+        "/d".to_string(),
+        // The strings of a multi-string value are separated by `\0`.
+        env_variables
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<String>>()
+            .join("\\0"),
+        "/f".to_string(),
+    ]
+}
+
+/// Format an environment variable as a systemd `Environment` setting.
+///
+/// The assignment is quoted, so the value can contain spaces. The characters systemd would
+/// otherwise interpret are escaped: quotes and backslashes, `%`, which starts a specifier, and
+/// line breaks, which would end the setting.
+pub fn make_systemd_environment(name: &str, value: &str) -> String {
+    let mut escaped = String::new();
+    for c in format!("{name}={value}").chars() {
+        match c {
+            '\\' | '"' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '%' => escaped.push_str("%%"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    format!("Environment=\"{escaped}\"\n")
+}
+
+fn make_failure_actions_args(service_name: &str, delay: u64, reset_period: u64) -> Vec<String> {
+    let restart = format!("restart/{}", delay * 1000);
+    vec![
+        "failure".to_string(),
+        service_name.to_string(),
+        "reset=".to_string(),
+        reset_period.to_string(),
+        "actions=".to_string(),
+        [restart.as_str(); 3].join("/"),
+    ]
+}
+
+/// Generate the launchd property list for a node service.
+///
+/// Standard output and error are written to files in the node's log directory, which is where
+/// anything written by safenode before its own logging is initialised, e.g., a panic, will end up.
+#[allow(clippy::too_many_arguments)]
+fn make_launchd_plist(
+    label: &str,
+    program: &Path,
+    args: &[OsString],
+    username: Option<&str>,
+    keep_alive: bool,
+    throttle_interval: Option<u64>,
+    log_dir_path: &Path,
+    env_variables: &[(String, String)],
+) -> String {
+    fn escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    let mut plist = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<dict>\n",
+    );
+    plist.push_str(&format!(
+        "  <key>Label</key>\n  <string>{}</string>\n",
+        escape(label)
+    ));
+    plist.push_str("  <key>ProgramArguments</key>\n  <array>\n");
+    plist.push_str(&format!(
+        "    <string>{}</string>\n",
+        escape(&program.to_string_lossy())
+    ));
+    for arg in args {
+        plist.push_str(&format!(
+            "    <string>{}</string>\n",
+            escape(&arg.to_string_lossy())
+        ));
+    }
+    plist.push_str("  </array>\n");
+    plist.push_str(&format!(
+        "  <key>KeepAlive</key>\n  <{}/>\n",
+        if keep_alive { "true" } else { "false" }
+    ));
+    if let Some(throttle_interval) = throttle_interval {
+        plist.push_str(&format!(
+            "  <key>ThrottleInterval</key>\n  <integer>{throttle_interval}</integer>\n"
+        ));
+    }
+    if let Some(username) = username {
+        plist.push_str(&format!(
+            "  <key>UserName</key>\n  <string>{}</string>\n",
+            escape(username)
+        ));
+    }
+    if !env_variables.is_empty() {
+        plist.push_str("  <key>EnvironmentVariables</key>\n  <dict>\n");
+        for (name, value) in env_variables {
+            plist.push_str(&format!(
+                "    <key>{}</key>\n    <string>{}</string>\n",
+                escape(name),
+                escape(value)
+            ));
+        }
+        plist.push_str("  </dict>\n");
+    }
+    plist.push_str(&format!(
+        "  <key>StandardOutPath</key>\n  <string>{}</string>\n",
+        escape(&log_dir_path.join("safenode.stdout").to_string_lossy())
+    ));
+    plist.push_str(&format!(
+        "  <key>StandardErrorPath</key>\n  <string>{}</string>\n",
+        escape(&log_dir_path.join("safenode.stderr").to_string_lossy())
+    ));
+    plist.push_str("</dict>\n</plist>\n");
+    plist
+}
+
+/// Generate the systemd unit for a node service that has a memory limit, logging targets or
+/// environment variables.
+///
+/// Apart from those, the unit is the same as the one the service manager would generate.
+fn make_systemd_unit(
+    name: &str,
+    program: &Path,
+    args: &[OsString],
+    username: &str,
+    log_targets: Option<&str>,
+    max_memory: Option<u64>,
+    env_variables: &[(String, String)],
+) -> String {
+    let args = args
+        .iter()
+        .map(|a| a.to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+    let mut unit = format!(
+        "[Unit]\nDescription={name}\n[Service]\nExecStart={} {args}\nRestart=on-failure\n\
+         User={username}\n",
+        program.to_string_lossy()
+    );
+    if let Some(log_targets) = log_targets {
+        unit.push_str(&make_systemd_environment("SN_LOG", log_targets));
+    }
+    for (name, value) in env_variables {
+        unit.push_str(&make_systemd_environment(name, value));
+    }
+    if let Some(max_memory) = max_memory {
+        unit.push_str(&format!("MemoryMax={max_memory}M\n"));
+    }
+    unit.push_str("[Install]\nWantedBy=multi-user.target\n");
+    unit
+}
+
+/// Generate the rc.d script for a node service on FreeBSD.
+///
+/// The node is run through `daemon`, which drops privileges to the service user, restarts the node
+/// if it exits and captures its output in the node's log directory.
+fn make_rcd_script(
+    name: &str,
+    program: &Path,
+    args: &[OsString],
+    username: &str,
+    keep_alive: bool,
+    log_dir_path: &Path,
+    env_variables: &[(String, String)],
+) -> String {
+    let rc_name = name.replace('-', "_");
+    // rc.subr runs the command with the variables in `${name}_env` set, by passing them to env(1)
+    // in the command it evaluates, so each assignment is quoted in the same way as the options
+    // below.
+    let env = if env_variables.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "{rc_name}_env=\"{}\"\n",
+            env_variables
+                .iter()
+                .map(|(name, value)| quote_rcd_arg(&format!("{name}={value}")))
+                .collect::<Vec<String>>()
+                .join(" ")
+        )
+    };
+    // The options are expanded unquoted in the command arguments, which rc.subr evaluates, so each
+    // argument is quoted to have it passed to the node intact.
+    let args = args
+        .iter()
+        .map(|a| quote_rcd_arg(&a.to_string_lossy()))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let restart = if keep_alive { "-r " } else { "" };
+    format!(
+        r#"#!/bin/sh
+#
+# PROVIDE: {rc_name}
+# REQUIRE: LOGIN FILESYSTEMS NETWORKING
+# KEYWORD: shutdown
+
+. /etc/rc.subr
+
+name="{rc_name}"
+desc="Safe Network node ({name})"
+rcvar="{rc_name}_enable"
+
+load_rc_config ${{name}}
+
+: ${{{rc_name}_options="{args}"}}
+
+{env}pidfile="/var/run/{rc_name}.pid"
+procname="/usr/sbin/daemon"
+command="/usr/sbin/daemon"
+command_args="{restart}-u {username} -o {output} -P ${{pidfile}} {program} ${{{rc_name}_options}}"
+
+run_rc_command "$1"
+"#,
+        program = program.to_string_lossy(),
+        output = log_dir_path.join("safenode.stdout").to_string_lossy(),
+    )
+}
+
+/// Quote an argument for the options of an rc.d script.
+///
+/// The options are set in double quotes, and the argument is single quoted within them, so the
+/// characters that are special in either are escaped.
+fn quote_rcd_arg(arg: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in arg.chars() {
+        match c {
+            '\'' => quoted.push_str("'\\''"),
+            '"' | '$' | '`' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_failure_actions_args_should_restart_after_every_failure() {
+        assert_eq!(
+            make_failure_actions_args("safenode1", 10, 86400),
+            vec![
+                "failure",
+                "safenode1",
+                "reset=",
+                "86400",
+                "actions=",
+                "restart/10000/restart/10000/restart/10000",
+            ]
+        );
+    }
+
+    #[test]
+    fn make_environment_args_should_set_a_multi_string_value_on_the_service_key() {
+        assert_eq!(
+            make_environment_args(
+                "safenode1",
+                &[
+                    ("SN_LOG".to_string(), "all".to_string()),
+                    ("RUST_BACKTRACE".to_string(), "1".to_string()),
+                ]
+            ),
+            vec![
+                "add",
+                "HKLM\\SYSTEM\\CurrentControlSet\\Services\\safenode1",
+                "/v",
+                "Environment",
+                "/t",
+                "REG_MULTI_SZ",
+                "/d",
+                "SN_LOG=all\\0RUST_BACKTRACE=1",
+                "/f",
+            ]
+        );
+    }
+
+    #[test]
+    fn make_node_args_should_use_the_rpc_address_and_add_the_connectivity_flags_last() {
+        let config = ServiceConfig {
+            auto_restart: None,
+            auto_restart_reset_period: 86400,
+            data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            env_variables: vec![],
+            extra_args: vec!["--max-log-files".to_string(), "5".to_string()],
+            genesis: false,
+            home_network: true,
+            keep_alive: true,
+            local: false,
+            log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            log_targets: None,
+            max_memory: None,
+            name: "safenode1".to_string(),
+            node_ip: None,
+            node_port: 8080,
+            node_type: NodeType::Safenode,
+            owner: None,
+            peers: vec![],
+            rewards_address: None,
+            rpc_address: Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            rpc_port: 8081,
+            safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+            service_user: "safe".to_string(),
+            throttle_interval: None,
+            upnp: true,
+        };
+
+        let args = make_node_args(&config);
+        let rpc_index = args.iter().position(|a| a == "--rpc").unwrap();
+        assert_eq!(args[rpc_index + 1], "0.0.0.0:8081");
+        assert_eq!(
+            args[args.len() - 4..],
+            ["--home-network", "--upnp", "--max-log-files", "5"]
+        );
+    }
+
+    #[test]
+    fn make_systemd_unit_should_include_the_memory_limit_logging_targets_and_environment() {
+        let unit = make_systemd_unit(
+            "safenode1",
+            Path::new("/var/safenode-manager/services/safenode1/safenode"),
+            &[OsString::from("--port"), OsString::from("8080")],
+            "safe",
+            Some("safenode=info"),
+            Some(512),
+            &[
+                ("HTTPS_PROXY".to_string(), "http://proxy:3128".to_string()),
+                ("GREETING".to_string(), "say \"hi\" 100%".to_string()),
+            ],
+        );
+
+        assert_eq!(
+            unit,
+            "[Unit]\n\
+             Description=safenode1\n\
+             [Service]\n\
+             ExecStart=/var/safenode-manager/services/safenode1/safenode --port 8080\n\
+             Restart=on-failure\n\
+             User=safe\n\
+             Environment=\"SN_LOG=safenode=info\"\n\
+             Environment=\"HTTPS_PROXY=http://proxy:3128\"\n\
+             Environment=\"GREETING=say \\\"hi\\\" 100%%\"\n\
+             MemoryMax=512M\n\
+             [Install]\n\
+             WantedBy=multi-user.target\n"
+        );
+    }
+
+    #[test]
+    fn make_rcd_script_should_run_the_node_as_the_service_user_through_daemon() {
+        let script = make_rcd_script(
+            "safenode1",
+            Path::new("/var/safenode-manager/services/safenode1/safenode"),
+            &[
+                OsString::from("--port"),
+                OsString::from("8080"),
+                OsString::from("--owner"),
+                OsString::from("chris's node"),
+            ],
+            "safe",
+            true,
+            Path::new("/var/log/safenode/safenode1"),
+            &[],
+        );
+
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("rcvar=\"safenode1_enable\""));
+        assert!(script
+            .contains(": ${safenode1_options=\"'--port' '8080' '--owner' 'chris'\\''s node'\"}"));
+        // The pidfile has the PID of daemon, which supervises the node, so stopping the service
+        // stops daemon rather than having it restart the node.
+        assert!(script.contains("procname=\"/usr/sbin/daemon\""));
+        assert!(script.contains(
+            "command_args=\"-r -u safe -o /var/log/safenode/safenode1/safenode.stdout \
+             -P ${pidfile} /var/safenode-manager/services/safenode1/safenode \
+             ${safenode1_options}\""
+        ));
+    }
+
+    #[test]
+    fn make_rcd_script_should_quote_the_environment_variables() {
+        let script = make_rcd_script(
+            "safenode1",
+            Path::new("/var/safenode-manager/services/safenode1/safenode"),
+            &[],
+            "safe",
+            true,
+            Path::new("/var/log/safenode/safenode1"),
+            &[
+                ("SN_LOG".to_string(), "all".to_string()),
+                (
+                    "GREETING".to_string(),
+                    "say \"hi\" to $USER's `node`".to_string(),
+                ),
+            ],
+        );
+
+        assert!(script.contains(
+            "safenode1_env=\"'SN_LOG=all' \
+             'GREETING=say \\\"hi\\\" to \\$USER'\\''s \\`node\\`'\"\n"
+        ));
+    }
+
+    #[test]
+    fn make_launchd_plist_should_include_keep_alive_throttle_interval_and_output_paths() {
+        let plist = make_launchd_plist(
+            "safenode1",
+            Path::new("/var/safenode-manager/services/safenode1/safenode"),
+            &[OsString::from("--port"), OsString::from("8080")],
+            Some("safe"),
+            true,
+            Some(30),
+            Path::new("/var/log/safenode/safenode1"),
+            &[("SN_LOG".to_string(), "all".to_string())],
+        );
+
+        assert!(plist.contains("<key>Label</key>\n  <string>safenode1</string>"));
+        assert!(plist.contains(
+            "<array>\n    <string>/var/safenode-manager/services/safenode1/safenode</string>\n    \
+             <string>--port</string>\n    <string>8080</string>\n  </array>"
+        ));
+        assert!(plist.contains("<key>KeepAlive</key>\n  <true/>"));
+        assert!(plist.contains("<key>ThrottleInterval</key>\n  <integer>30</integer>"));
+        assert!(plist.contains("<key>UserName</key>\n  <string>safe</string>"));
+        assert!(plist.contains(
+            "<key>EnvironmentVariables</key>\n  <dict>\n    <key>SN_LOG</key>\n    \
+             <string>all</string>\n  </dict>"
+        ));
+        assert!(plist.contains(
+            "<key>StandardOutPath</key>\n  <string>/var/log/safenode/safenode1/safenode.stdout</string>"
+        ));
+        assert!(plist.contains(
+            "<key>StandardErrorPath</key>\n  <string>/var/log/safenode/safenode1/safenode.stderr</string>"
+        ));
+    }
+
+    #[test]
+    fn make_launchd_plist_should_omit_optional_keys() {
+        let plist = make_launchd_plist(
+            "safenode1",
+            Path::new("/Users/alice/safenode"),
+            &[],
+            None,
+            false,
+            None,
+            Path::new("/Users/alice/Library/Logs/safenode/safenode1"),
+            &[],
+        );
+
+        assert!(plist.contains("<key>KeepAlive</key>\n  <false/>"));
+        assert!(!plist.contains("ThrottleInterval"));
+        assert!(!plist.contains("UserName"));
+        assert!(!plist.contains("EnvironmentVariables"));
+    }
+
+    #[test]
+    fn parse_systemctl_exit_info_should_report_an_oom_kill() {
+        let output = "Result=oom-kill\nNRestarts=3\nExecMainCode=2\nExecMainStatus=9\n";
+        assert_eq!(
+            parse_systemctl_exit_info(output),
+            ExitInfo {
+                exit_code: None,
+                signal: Some(9),
+                reason: Some("oom-kill".to_string()),
+                restart_count: Some(3),
+            }
+        );
+
+        let output = "Result=success\nNRestarts=0\nExecMainCode=1\nExecMainStatus=0\n";
+        assert_eq!(
+            parse_systemctl_exit_info(output),
+            ExitInfo {
+                exit_code: Some(0),
+                signal: None,
+                reason: None,
+                restart_count: Some(0),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_launchctl_exit_info_should_read_the_last_exit() {
+        let output = "system/safenode1 = {\n\tactive count = 1\n\tstate = running\n\truns = 4\n\tlast terminating signal = Killed: 9\n}\n";
+        assert_eq!(
+            parse_launchctl_exit_info(output),
+            ExitInfo {
+                exit_code: None,
+                signal: Some(9),
+                reason: Some("Killed".to_string()),
+                restart_count: Some(3),
+            }
+        );
+
+        let output = "\truns = 1\n\tlast exit code = 101\n";
+        assert_eq!(parse_launchctl_exit_info(output).exit_code, Some(101));
+    }
+
+    #[test]
+    fn parse_sc_exit_info_should_prefer_the_service_exit_code() {
+        let output = "SERVICE_NAME: safenode1\r\n        TYPE               : 10  WIN32_OWN_PROCESS\r\n        STATE              : 1  STOPPED\r\n        WIN32_EXIT_CODE    : 1066  (0x42a)\r\n        SERVICE_EXIT_CODE  : 101  (0x65)\r\n";
+        assert_eq!(parse_sc_exit_info(output).exit_code, Some(101));
+
+        let output = "        WIN32_EXIT_CODE    : 1067  (0x42b)\r\n        SERVICE_EXIT_CODE  : 0  (0x0)\r\n";
+        assert_eq!(parse_sc_exit_info(output).exit_code, Some(1067));
     }
 }
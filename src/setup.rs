@@ -0,0 +1,208 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::helpers::validate_rewards_address;
+use color_eyre::{eyre::eyre, Result};
+use std::io::{BufRead, Write};
+
+/// The answers to the questions asked by the setup command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetupAnswers {
+    pub count: u16,
+    pub rewards_address: Option<String>,
+    /// The first of the ports that have been forwarded to the host, if the user has set up port
+    /// forwarding. The nodes are given consecutive ports from this one.
+    pub forwarded_port: Option<u16>,
+    pub start: bool,
+}
+
+/// Ask the setup questions.
+///
+/// A question is asked again if the answer isn't valid. An empty answer takes the default.
+pub fn ask_setup_questions<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    default_count: u16,
+) -> Result<SetupAnswers> {
+    let count = loop {
+        let answer = ask(
+            input,
+            output,
+            "How many nodes would you like to run?",
+            &default_count.to_string(),
+        )?;
+        match answer.parse::<u16>() {
+            Ok(count) if count > 0 => break count,
+            _ => writeln!(output, "Please enter a number greater than zero")?,
+        }
+    };
+
+    let rewards_address = loop {
+        let answer = ask(
+            input,
+            output,
+            "What is the rewards address for the nodes? Leave it blank to skip",
+            "",
+        )?;
+        if answer.is_empty() {
+            break None;
+        }
+        match validate_rewards_address(&answer) {
+            Ok(()) => break Some(answer),
+            Err(e) => writeln!(output, "{e}")?,
+        }
+    };
+
+    let forwarded_port = if ask_yes_no(
+        input,
+        output,
+        "Have you forwarded ports on your router to this machine?",
+        false,
+    )? {
+        loop {
+            let answer = ask(input, output, "What is the first forwarded port?", "")?;
+            match answer.parse::<u16>() {
+                Ok(port) if port > 0 && port.checked_add(count - 1).is_some() => break Some(port),
+                _ => writeln!(
+                    output,
+                    "Please enter a port that leaves room for {count} nodes"
+                )?,
+            }
+        }
+    } else {
+        None
+    };
+
+    let start = ask_yes_no(input, output, "Start the nodes once they are added?", true)?;
+
+    Ok(SetupAnswers {
+        count,
+        rewards_address,
+        forwarded_port,
+        start,
+    })
+}
+
+/// Get the arguments for each of the add commands that perform the setup.
+///
+/// Without port forwarding, a single command adds all the nodes. With it, each node has to be
+/// added by itself, since a port can only be given when adding one service. The firewall is
+/// opened for the forwarded ports.
+pub fn get_add_commands(answers: &SetupAnswers) -> Vec<Vec<String>> {
+    let mut base = vec!["safenode-manager".to_string(), "add".to_string()];
+    if let Some(rewards_address) = &answers.rewards_address {
+        base.push("--rewards-address".to_string());
+        base.push(rewards_address.clone());
+    }
+
+    match answers.forwarded_port {
+        Some(first_port) => (0..answers.count)
+            .map(|i| {
+                let mut args = base.clone();
+                args.push("--port".to_string());
+                args.push((first_port + i).to_string());
+                args.push("--open-firewall".to_string());
+                args
+            })
+            .collect(),
+        None => {
+            let mut args = base;
+            args.push("--count".to_string());
+            args.push(answers.count.to_string());
+            vec![args]
+        }
+    }
+}
+
+fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: &str,
+) -> Result<String> {
+    if default.is_empty() {
+        write!(output, "{question} ")?;
+    } else {
+        write!(output, "{question} [{default}] ")?;
+    }
+    output.flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(eyre!("The question was not answered"));
+    }
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(default.to_string());
+    }
+    Ok(answer.to_string())
+}
+
+/// Ask a question with a yes or no answer, which is asked again until it's answered.
+pub fn ask_yes_no<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: bool,
+) -> Result<bool> {
+    let default = if default { "y" } else { "n" };
+    loop {
+        let answer = ask(input, output, &format!("{question} (y/n)"), default)?;
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => writeln!(output, "Please answer 'y' or 'n'")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn setup_should_add_each_node_on_its_own_forwarded_port() -> Result<()> {
+        // The first answer for the port doesn't leave room for the nodes, so it's asked again.
+        let mut input = Cursor::new("3\n\nnot sure\ny\n65535\n12000\n\n");
+        let mut output = Vec::new();
+        let answers = ask_setup_questions(&mut input, &mut output, 1)?;
+        assert_eq!(
+            answers,
+            SetupAnswers {
+                count: 3,
+                rewards_address: None,
+                forwarded_port: Some(12000),
+                start: true,
+            }
+        );
+
+        let commands = get_add_commands(&answers);
+        assert_eq!(commands.len(), 3);
+        assert_eq!(
+            commands[2],
+            vec![
+                "safenode-manager",
+                "add",
+                "--port",
+                "12002",
+                "--open-firewall"
+            ]
+        );
+
+        let commands = get_add_commands(&SetupAnswers {
+            forwarded_port: None,
+            ..answers
+        });
+        assert_eq!(
+            commands,
+            vec![vec!["safenode-manager", "add", "--count", "3"]]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config::is_user_mode;
+use crate::node_registry::ExitInfo;
+use crate::service::{
+    make_node_args, make_systemd_environment, parse_systemctl_exit_info, NodeServiceManager,
+    ServiceConfig, ServiceControl,
+};
+use async_trait::async_trait;
+use color_eyre::{eyre::eyre, Result};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The name of the templated unit every node is an instance of.
+const TEMPLATE_UNIT_NAME: &str = "safenode@.service";
+
+/// Runs each node service as an instance of a single templated systemd unit.
+///
+/// The template only has the settings shared by every node. Each instance has a drop-in with its
+/// own command line, user and limits, which is much less to write out than a whole unit per node
+/// for a large number of nodes. The instance is named after the node's number, so the
+/// `safenode5` service is `safenode@5.service`, and ranges of nodes can be managed with
+/// `systemctl` directly, e.g., `systemctl start safenode@{1..100}`.
+pub struct SystemdTemplateManager {}
+
+#[async_trait]
+impl ServiceControl for SystemdTemplateManager {
+    fn add_firewall_rule(&self, service_name: &str, port: u16) -> Result<()> {
+        NodeServiceManager {}.add_firewall_rule(service_name, port)
+    }
+
+    fn create_service_user(&self, username: &str) -> Result<()> {
+        NodeServiceManager {}.create_service_user(username)
+    }
+
+    fn get_available_port(&self) -> Result<u16> {
+        NodeServiceManager {}.get_available_port()
+    }
+
+    fn get_exit_info(&self, service_name: &str) -> Result<ExitInfo> {
+        let output = systemctl(&[
+            "show",
+            &get_unit_name(service_name),
+            "-p",
+            "Result,ExecMainCode,ExecMainStatus,NRestarts",
+        ])?;
+        Ok(parse_systemctl_exit_info(&output))
+    }
+
+    fn install(&self, config: ServiceConfig) -> Result<()> {
+        let unit_dir_path = get_unit_dir_path()?;
+        std::fs::create_dir_all(&unit_dir_path)?;
+        std::fs::write(unit_dir_path.join(TEMPLATE_UNIT_NAME), make_template_unit())?;
+
+        let unit_name = get_unit_name(&config.name);
+        let drop_in_dir_path = unit_dir_path.join(format!("{unit_name}.d"));
+        std::fs::create_dir_all(&drop_in_dir_path)?;
+        std::fs::write(
+            drop_in_dir_path.join("safenode.conf"),
+            make_instance_drop_in(
+                &config.safenode_path,
+                &make_node_args(&config),
+                if is_user_mode() {
+                    None
+                } else {
+                    Some(&config.service_user)
+                },
+                config.log_targets.as_deref(),
+                &config.env_variables,
+                config.max_memory,
+            ),
+        )?;
+
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", &unit_name])?;
+        Ok(())
+    }
+
+    fn is_port_free(&self, port: u16) -> bool {
+        NodeServiceManager {}.is_port_free(port)
+    }
+
+    fn is_service_process_running(&self, pid: u32) -> bool {
+        NodeServiceManager {}.is_service_process_running(pid)
+    }
+
+    fn kill_process(&self, pid: u32) -> Result<()> {
+        NodeServiceManager {}.kill_process(pid)
+    }
+
+    fn remove_firewall_rule(&self, service_name: &str) -> Result<()> {
+        NodeServiceManager {}.remove_firewall_rule(service_name)
+    }
+
+    fn start(&self, service_name: &str) -> Result<()> {
+        systemctl(&["start", &get_unit_name(service_name)])?;
+        Ok(())
+    }
+
+    fn stop(&self, service_name: &str) -> Result<()> {
+        systemctl(&["stop", &get_unit_name(service_name)])?;
+        Ok(())
+    }
+
+    /// Remove the instance's drop-in. The template is left in place for the other instances.
+    fn uninstall(&self, service_name: &str) -> Result<()> {
+        let unit_name = get_unit_name(service_name);
+        systemctl(&["disable", &unit_name])?;
+        let drop_in_dir_path = get_unit_dir_path()?.join(format!("{unit_name}.d"));
+        if drop_in_dir_path.exists() {
+            std::fs::remove_dir_all(drop_in_dir_path)?;
+        }
+        systemctl(&["daemon-reload"])?;
+        Ok(())
+    }
+
+    async fn wait(&self, delay: u64) {
+        NodeServiceManager {}.wait(delay).await
+    }
+}
+
+fn get_unit_dir_path() -> Result<PathBuf> {
+    if is_user_mode() {
+        let config_dir_path = dirs_next::config_dir()
+            .ok_or_else(|| eyre!("Could not obtain the user's config directory"))?;
+        return Ok(config_dir_path.join("systemd").join("user"));
+    }
+    Ok(PathBuf::from("/etc/systemd/system"))
+}
+
+/// Get the name of the instance unit for a service.
+///
+/// Services are named after their number, which is used as the instance name. A service that has
+/// been renamed to something else uses its whole name.
+fn get_unit_name(service_name: &str) -> String {
+    let instance = match service_name.strip_prefix("safenode") {
+        Some(number) if !number.is_empty() => number,
+        _ => service_name,
+    };
+    format!("safenode@{instance}.service")
+}
+
+fn systemctl(args: &[&str]) -> Result<String> {
+    let mut command = Command::new("systemctl");
+    if is_user_mode() {
+        command.arg("--user");
+    }
+    let output = command.args(args).output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "systemctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn make_template_unit() -> String {
+    "[Unit]\nDescription=safenode %i\n[Service]\nRestart=on-failure\n\
+     [Install]\nWantedBy=multi-user.target\n"
+        .to_string()
+}
+
+/// Generate the drop-in that turns the template into a particular node.
+fn make_instance_drop_in(
+    program: &Path,
+    args: &[OsString],
+    username: Option<&str>,
+    log_targets: Option<&str>,
+    env_variables: &[(String, String)],
+    max_memory: Option<u64>,
+) -> String {
+    let args = args
+        .iter()
+        .map(|a| a.to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+    let mut drop_in = format!(
+        "[Service]\nExecStart={} {args}\n",
+        program.to_string_lossy()
+    );
+    if let Some(username) = username {
+        drop_in.push_str(&format!("User={username}\n"));
+    }
+    if let Some(log_targets) = log_targets {
+        drop_in.push_str(&make_systemd_environment("SN_LOG", log_targets));
+    }
+    for (name, value) in env_variables {
+        drop_in.push_str(&make_systemd_environment(name, value));
+    }
+    if let Some(max_memory) = max_memory {
+        drop_in.push_str(&format!("MemoryMax={max_memory}M\n"));
+    }
+    drop_in
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_unit_name_should_use_the_node_number_as_the_instance() {
+        assert_eq!(get_unit_name("safenode5"), "safenode@5.service");
+        assert_eq!(get_unit_name("archive"), "safenode@archive.service");
+    }
+
+    #[test]
+    fn make_instance_drop_in_should_include_the_command_line_user_and_limits() {
+        let drop_in = make_instance_drop_in(
+            Path::new("/var/safenode-manager/services/safenode1/safenode"),
+            &[OsString::from("--port"), OsString::from("8080")],
+            Some("safe"),
+            Some("sn_networking=debug"),
+            &[("RUST_BACKTRACE".to_string(), "1".to_string())],
+            Some(512),
+        );
+        assert_eq!(
+            drop_in,
+            "[Service]\n\
+             ExecStart=/var/safenode-manager/services/safenode1/safenode --port 8080\n\
+             User=safe\n\
+             Environment=\"SN_LOG=sn_networking=debug\"\n\
+             Environment=\"RUST_BACKTRACE=1\"\n\
+             MemoryMax=512M\n"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// How long to wait for the telemetry endpoint, so it can't hold up a command.
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether anonymous usage telemetry is sent, and where to.
+///
+/// Telemetry is off unless it has been turned on with the `telemetry on` command.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+}
+
+impl TelemetrySettings {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// The anonymous event sent after a command has run.
+///
+/// Nothing that identifies the machine or its nodes is included: no peer IDs, addresses, paths,
+/// service names or error messages. Errors are only reported by their broad class.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TelemetryEvent {
+    pub command: String,
+    pub os: String,
+    pub arch: String,
+    pub manager_version: String,
+    pub node_count: usize,
+    pub error_class: Option<String>,
+}
+
+impl TelemetryEvent {
+    pub fn new(command: &str, node_count: usize, result: &Result<()>) -> Self {
+        Self {
+            command: command.to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            manager_version: env!("CARGO_PKG_VERSION").to_string(),
+            node_count,
+            error_class: result.as_ref().err().map(get_error_class),
+        }
+    }
+}
+
+/// Get the broad class of an error, e.g., `io:PermissionDenied` or `network`, without any of the
+/// details in its message.
+pub fn get_error_class(error: &color_eyre::eyre::Report) -> String {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            return format!("io:{:?}", e.kind());
+        }
+        if cause.downcast_ref::<reqwest::Error>().is_some() {
+            return "network".to_string();
+        }
+        if cause.downcast_ref::<serde_json::Error>().is_some() {
+            return "json".to_string();
+        }
+    }
+    "other".to_string()
+}
+
+pub async fn send_event(endpoint: &str, event: &TelemetryEvent) -> Result<()> {
+    let response = reqwest::Client::new()
+        .post(endpoint)
+        .timeout(SEND_TIMEOUT)
+        .json(event)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(eyre!(
+            "The telemetry endpoint returned status {}",
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn telemetry_event_should_only_contain_the_error_class() {
+        let result: Result<()> = Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "/var/safenode-manager/services/safenode1",
+        )
+        .into());
+        let event = TelemetryEvent::new("add", 3, &result);
+        assert_eq!(event.command, "add");
+        assert_eq!(event.node_count, 3);
+        assert_eq!(event.error_class, Some("io:PermissionDenied".to_string()));
+        assert!(!serde_json::to_string(&event).unwrap().contains("safenode1"));
+
+        let result: Result<()> = Err(eyre!("No service named 'safenode1'"));
+        assert_eq!(
+            TelemetryEvent::new("start", 3, &result).error_class,
+            Some("other".to_string())
+        );
+        assert_eq!(TelemetryEvent::new("status", 3, &Ok(())).error_class, None);
+    }
+
+    #[test]
+    fn telemetry_settings_should_be_off_by_default() -> Result<()> {
+        let temp_dir = assert_fs::TempDir::new()?;
+        let path = temp_dir.path().join("telemetry.json");
+        assert!(!TelemetrySettings::load(&path)?.enabled);
+
+        let settings = TelemetrySettings {
+            enabled: true,
+            endpoint: Some("http://localhost:8080/events".to_string()),
+        };
+        settings.save(&path)?;
+        assert_eq!(TelemetrySettings::load(&path)?, settings);
+        Ok(())
+    }
+}
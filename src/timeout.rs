@@ -0,0 +1,238 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::output::report;
+use color_eyre::{eyre::Report, Result};
+use futures::StreamExt;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// When the command has to finish by, if it was given a timeout.
+static DEADLINE: OnceLock<Instant> = OnceLock::new();
+
+pub fn set_timeout(timeout: Duration) {
+    let _ = DEADLINE.set(Instant::now() + timeout);
+}
+
+pub fn has_timed_out() -> bool {
+    DEADLINE
+        .get()
+        .is_some_and(|deadline| Instant::now() >= *deadline)
+}
+
+/// The error for a command that ran out of time, with the nodes it had and hadn't finished with.
+#[derive(Debug, Default, PartialEq)]
+pub struct TimedOut {
+    pub completed: Vec<String>,
+    pub incomplete: Vec<String>,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "The command timed out")?;
+        if !self.completed.is_empty() {
+            write!(f, ". Completed: {}", self.completed.join(", "))?;
+        }
+        if !self.incomplete.is_empty() {
+            write!(f, ". Not completed: {}", self.incomplete.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+pub fn is_timeout(error: &Report) -> bool {
+    error.downcast_ref::<TimedOut>().is_some()
+}
+
+/// Run a step of a command in the time left before the deadline.
+///
+/// Without a timeout, the step is run to completion.
+pub async fn within_timeout<F: Future>(step: F) -> Result<F::Output> {
+    match DEADLINE.get() {
+        Some(deadline) => tokio::time::timeout_at(tokio::time::Instant::from_std(*deadline), step)
+            .await
+            .map_err(|_| TimedOut::default().into()),
+        None => Ok(step.await),
+    }
+}
+
+/// Keeps track of the nodes an operation has finished with, so they can be reported if it times
+/// out.
+///
+/// Once the deadline has passed, the remaining nodes are skipped rather than failing straight
+/// away, which gives the command the chance to save the registry with the changes to the nodes
+/// that did complete.
+#[derive(Debug, Default)]
+pub struct Progress {
+    timed_out: TimedOut,
+    total: usize,
+}
+
+impl Progress {
+    /// Keep track of an operation on a number of nodes, reporting a count as each one finishes.
+    pub fn with_total(total: usize) -> Self {
+        Self {
+            total,
+            ..Default::default()
+        }
+    }
+
+    /// Run the step for a node, unless the command has already timed out.
+    ///
+    /// The output of the step is returned if it finished. A node that was skipped, or whose step
+    /// was cut short by the deadline, is reported as not completed.
+    pub async fn run<F: Future>(&mut self, service_name: &str, step: F) -> Option<F::Output> {
+        if has_timed_out() {
+            self.timed_out.incomplete.push(service_name.to_string());
+            return None;
+        }
+        let output = match within_timeout(step).await {
+            Ok(output) => {
+                self.timed_out.completed.push(service_name.to_string());
+                Some(output)
+            }
+            Err(_) => {
+                self.timed_out.incomplete.push(service_name.to_string());
+                None
+            }
+        };
+        let finished = self.timed_out.completed.len() + self.timed_out.incomplete.len();
+        report_progress(finished, self.total, service_name);
+        output
+    }
+
+    /// Run the steps for many nodes, with up to `concurrency` of them underway at once.
+    ///
+    /// The steps are futures on the runtime the command is already using, so they take turns
+    /// while they wait, e.g., for a node to start. The outputs are returned in the order the
+    /// steps were given, with `None` for the nodes that were skipped or cut short.
+    pub async fn run_concurrently<S, F>(
+        &mut self,
+        steps: Vec<(String, S)>,
+        concurrency: usize,
+    ) -> Vec<Option<F::Output>>
+    where
+        S: FnOnce() -> F,
+        F: Future,
+    {
+        let total = steps.len();
+        let mut running = futures::stream::iter(steps.into_iter().enumerate())
+            .map(|(index, (service_name, step))| async move {
+                let mut progress = Progress::default();
+                let output = progress.run(&service_name, step()).await;
+                (index, service_name, progress, output)
+            })
+            .buffer_unordered(concurrency.max(1));
+        let mut finished = Vec::with_capacity(total);
+        while let Some((index, service_name, progress, output)) = running.next().await {
+            finished.push((index, progress, output));
+            report_progress(finished.len(), total, &service_name);
+        }
+
+        finished.sort_by_key(|(index, _, _)| *index);
+        finished
+            .into_iter()
+            .map(|(_, progress, output)| {
+                self.timed_out
+                    .completed
+                    .extend(progress.timed_out.completed);
+                self.timed_out
+                    .incomplete
+                    .extend(progress.timed_out.incomplete);
+                output
+            })
+            .collect()
+    }
+
+    /// The nodes whose steps finished, in the order they were run.
+    pub fn completed(&self) -> &[String] {
+        &self.timed_out.completed
+    }
+
+    /// Get the timeout error if any of the nodes were not completed.
+    pub fn finish(self) -> Result<()> {
+        if self.timed_out.incomplete.is_empty() {
+            return Ok(());
+        }
+        Err(self.timed_out.into())
+    }
+}
+
+/// Report how many of the nodes an operation has finished with, when there's more than one.
+fn report_progress(finished: usize, total: usize, service_name: &str) {
+    if total > 1 {
+        report!("[{finished}/{total}] Finished with {service_name}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn run_concurrently_should_return_the_outputs_in_the_order_of_the_steps() -> Result<()> {
+        let steps = (1..=5)
+            .map(|i| {
+                let step = move || async move {
+                    // The earlier steps take longer, so they finish after the later ones.
+                    tokio::time::sleep(Duration::from_millis(50 - i * 10)).await;
+                    i
+                };
+                (format!("safenode{i}"), step)
+            })
+            .collect::<Vec<_>>();
+
+        let mut progress = Progress::default();
+        let outputs = progress.run_concurrently(steps, 3).await;
+
+        assert_eq!(outputs, vec![Some(1), Some(2), Some(3), Some(4), Some(5)]);
+        assert_eq!(
+            progress.completed(),
+            [
+                "safenode1",
+                "safenode2",
+                "safenode3",
+                "safenode4",
+                "safenode5"
+            ]
+        );
+        progress.finish()
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn run_concurrently_should_run_the_steps_at_the_same_time_on_one_thread() {
+        let steps = (1..=4)
+            .map(|i| {
+                let step = || async { tokio::time::sleep(Duration::from_millis(200)).await };
+                (format!("safenode{i}"), step)
+            })
+            .collect::<Vec<_>>();
+
+        let started = std::time::Instant::now();
+        let outputs = Progress::default().run_concurrently(steps, 4).await;
+
+        assert_eq!(outputs.len(), 4);
+        assert!(started.elapsed() < Duration::from_millis(600));
+    }
+
+    #[test]
+    fn timed_out_should_list_the_completed_and_incomplete_nodes() {
+        let error = TimedOut {
+            completed: vec!["safenode1".to_string(), "safenode2".to_string()],
+            incomplete: vec!["safenode3".to_string()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "The command timed out. Completed: safenode1, safenode2. Not completed: safenode3"
+        );
+        assert!(is_timeout(&Report::new(error)));
+    }
+}